@@ -0,0 +1,162 @@
+use crate::config::Config;
+use crate::editor::export::{export_source, ExportFormat};
+use crate::editor::languages::Language;
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use crate::models::backend::{local_ollama, Backend};
+use crate::models::{Predictor, DEFAULT_MODEL};
+use anyhow::{anyhow, Result};
+use std::fs;
+
+/// Headless subcommands that run the predictor without opening the editor
+pub enum Command {
+    /// `nars complete file.rs:120` prints a completion for the given line
+    Complete {
+        path: String,
+        line: usize,
+        model: Option<String>,
+    },
+    /// `nars edit file.rs --prompt "..."` rewrites the file from an instruction
+    Edit {
+        path: String,
+        prompt: String,
+        model: Option<String>,
+        write: bool,
+    },
+    /// `nars highlight file.rs [--html]` prints the file with syntax highlighting
+    Highlight { path: String, format: ExportFormat },
+    /// `nars doctor [--model name]` checks the setup and prints what to fix
+    Doctor { model: String },
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let Some(name) = args.get(1) else {
+            return Ok(None);
+        };
+        // the config's model.name when not given
+        let mut model = None;
+        let mut prompt = None;
+        let mut write = false;
+        let mut format = ExportFormat::Ansi;
+        let mut positional = Vec::new();
+        let mut rest = args[2..].iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--model" => {
+                    model = Some(rest.next().ok_or(anyhow!("--model needs a value"))?.clone())
+                }
+                "--prompt" => {
+                    prompt = Some(
+                        rest.next()
+                            .ok_or(anyhow!("--prompt needs a value"))?
+                            .clone(),
+                    )
+                }
+                "--write" => write = true,
+                "--html" => format = ExportFormat::Html,
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        match name.as_str() {
+            "complete" => {
+                let target = positional
+                    .first()
+                    .ok_or(anyhow!("usage: nars complete <file>:<line>"))?;
+                let (path, line) = target
+                    .rsplit_once(':')
+                    .ok_or(anyhow!("usage: nars complete <file>:<line>"))?;
+                Ok(Some(Command::Complete {
+                    path: path.to_string(),
+                    line: line.parse()?,
+                    model,
+                }))
+            }
+            "edit" => Ok(Some(Command::Edit {
+                path: positional.first().cloned().ok_or(anyhow!(
+                    "usage: nars edit <file> --prompt <instruction> [--write]"
+                ))?,
+                prompt: prompt.ok_or(anyhow!("nars edit needs --prompt"))?,
+                model,
+                write,
+            })),
+            "highlight" => Ok(Some(Command::Highlight {
+                path: positional
+                    .first()
+                    .cloned()
+                    .ok_or(anyhow!("usage: nars highlight <file> [--html]"))?,
+                format,
+            })),
+            "doctor" => Ok(Some(Command::Doctor {
+                model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            })),
+            _ => Ok(None),
+        }
+    }
+}
+
+pub async fn run(command: Command, config: Config) -> Result<()> {
+    let excluded = match &command {
+        Command::Complete { path, .. } | Command::Edit { path, .. } => {
+            config.model.excludes(path).then_some(path)
+        }
+        Command::Highlight { .. } | Command::Doctor { .. } => None,
+    };
+    if let Some(path) = excluded {
+        return Err(anyhow!(
+            "{} matches model.exclude and is never sent to the model",
+            path
+        ));
+    }
+    match command {
+        Command::Complete { path, line, model } => {
+            let content = fs::read_to_string(&path)?;
+            if line == 0 || content.lines().nth(line - 1).is_none() {
+                return Err(anyhow!("{} has no line {}", path, line));
+            }
+            let model = model.unwrap_or_else(|| config.model.name.clone());
+            let predictor =
+                Predictor::headless(Backend::ollama(local_ollama(&config.model)?), model)
+                    .with_backends(Backend::all_from_config(&config.model.backends)?)
+                    .with_options(config.model.options.clone());
+            // the same context as a prediction at the end of the line in the editor
+            let (mut editor, _) = Editor::new(path.clone(), config);
+            let request = editor.line_request(&path, content, line);
+            println!("{}", predictor.predict(&request, 0).await?);
+        }
+        Command::Edit {
+            path,
+            prompt,
+            model,
+            write,
+        } => {
+            let content = fs::read_to_string(&path)?;
+            let model = model.unwrap_or_else(|| config.model.name.clone());
+            let predictor =
+                Predictor::headless(Backend::ollama(local_ollama(&config.model)?), model)
+                    .with_backends(Backend::all_from_config(&config.model.backends)?)
+                    .with_options(config.model.options.clone());
+            let updated = predictor.edit(&content, &prompt).await?;
+            if write {
+                fs::write(&path, format!("{}\n", updated.trim_end()))?;
+            } else {
+                println!("{}", updated);
+            }
+        }
+        Command::Doctor { .. } => unreachable!("main runs the doctor before loading the config"),
+        Command::Highlight { path, format } => {
+            let content = fs::read_to_string(&path)?;
+            print!(
+                "{}",
+                export_source(
+                    &content,
+                    Language::detect(&path, &config.file_types),
+                    format,
+                    &Theme::named(&config.render.theme),
+                )?
+            );
+        }
+    }
+    Ok(())
+}
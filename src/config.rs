@@ -0,0 +1,368 @@
+use crate::models::DEFAULT_MODEL;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub bell: BellStyle,
+    pub render: RenderConfig,
+    pub languages: HashMap<String, LanguageConfig>,
+    /// Globs mapped to language names, e.g. `"*.zon" = "zig"`
+    pub file_types: HashMap<String, String>,
+    pub encryption: EncryptionConfig,
+    pub model: ModelConfig,
+    pub gutter: GutterConfig,
+}
+
+/// How invalid operations (moving past the buffer edge, accepting without a
+/// prediction) are signalled
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BellStyle {
+    #[default]
+    Visual,
+    Audible,
+    None,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Insert the closing `}` when Enter is pressed after an unclosed `{`
+    pub auto_close_blocks: bool,
+    /// Indent new lines to match the syntax tree on Enter
+    pub auto_indent: bool,
+    /// Insert the closing bracket or quote when typing an opening one
+    pub auto_pairs: bool,
+    /// Spaces per indentation level
+    pub indent_width: usize,
+    /// Indent with tabs instead of spaces
+    pub indent_with_tabs: bool,
+    /// Follow the indentation a file already uses when it is opened
+    pub detect_indent: bool,
+    /// Column comments are wrapped at by alt+q
+    pub text_width: usize,
+    /// Columns marked with a vertical guide, counting from 1
+    pub rulers: Vec<usize>,
+    /// Prompts replacing the built-in ones, by scope (`line`, `block`,
+    /// `function`, `file`, `multiline`) with `default` for the others
+    pub prompts: HashMap<String, String>,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            auto_close_blocks: true,
+            auto_indent: true,
+            auto_pairs: true,
+            indent_width: 4,
+            indent_with_tabs: false,
+            detect_indent: true,
+            text_width: 80,
+            rulers: Vec::new(),
+            prompts: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EncryptionBackend {
+    #[default]
+    Gpg,
+    Age,
+}
+
+/// Files matching `patterns` are decrypted on load and encrypted again on save
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub backend: EncryptionBackend,
+    pub patterns: Vec<String>,
+    pub recipients: Vec<String>,
+    /// age identity file used for decryption, gpg uses its agent instead
+    pub identity: Option<String>,
+}
+
+impl EncryptionConfig {
+    pub fn matches(&self, path: &str) -> bool {
+        self.patterns.iter().any(|pattern| {
+            glob::Pattern::new(pattern)
+                .map(|p| p.matches(path))
+                .unwrap_or(false)
+        })
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// Model used when none is given on the command line
+    pub name: String,
+    /// Tokens of surrounding code sent with block, function and file predictions
+    pub context_budget: usize,
+    /// Backends tried in order, the local ollama when empty
+    pub backends: Vec<BackendConfig>,
+    /// Files never sent to the model or indexed, e.g. `*.pem` or `**/secrets/**`
+    pub exclude: Vec<String>,
+    /// Predictions sampled for the completion menu
+    pub candidates: usize,
+    /// Send the code around the cursor with fill-in-the-middle tokens to
+    /// models that have them, instead of an instruction
+    pub fim: bool,
+    /// Predict the rest of the line by itself once typing stops
+    pub auto_predict: bool,
+    /// How long typing has to stop for before an automatic prediction
+    pub auto_predict_ms: u64,
+    /// Where the local ollama runs, e.g. `gpu-box:11434`. `OLLAMA_HOST` and
+    /// `--ollama-host` take precedence.
+    pub ollama_host: String,
+    /// Environment variable holding a bearer token for an ollama behind a proxy
+    pub ollama_api_key_env: Option<String>,
+    /// Sampling options sent with every request, the model's own when unset
+    pub options: GenerationOptions,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_MODEL.to_string(),
+            context_budget: 2048,
+            backends: Vec::new(),
+            exclude: Vec::new(),
+            candidates: 3,
+            fim: true,
+            auto_predict: false,
+            auto_predict_ms: 300,
+            ollama_host: "localhost:11434".to_string(),
+            ollama_api_key_env: None,
+            options: GenerationOptions::default(),
+        }
+    }
+}
+
+// room for the built-in instructions around the code in a prompt
+const INSTRUCTION_TOKENS: u32 = 128;
+
+impl ModelConfig {
+    /// Takes the ollama host from `--ollama-host` if given, then from
+    /// `OLLAMA_HOST`, keeping the config's otherwise
+    pub fn resolve_ollama_host(&mut self, flag: Option<&str>) {
+        let host = flag
+            .map(str::to_string)
+            .or_else(|| env::var("OLLAMA_HOST").ok().filter(|host| !host.is_empty()));
+        if let Some(host) = host {
+            self.ollama_host = host;
+        }
+    }
+
+    /// Tokens of code a prompt may carry: `context_budget`, or less when the
+    /// `num_ctx` window couldn't also hold the instruction and the answer
+    pub fn context_tokens(&self) -> usize {
+        let Some(window) = self.options.num_ctx else {
+            return self.context_budget;
+        };
+        let answer = self.options.num_predict.unwrap_or(window / 4);
+        let room = window.saturating_sub(answer + INSTRUCTION_TOKENS);
+        self.context_budget.min(room as usize)
+    }
+
+    /// Whether `path` matches an `exclude` glob, checked against both the
+    /// whole path and the file name so `.env*` also catches `config/.env.local`
+    pub fn excludes(&self, path: &str) -> bool {
+        let file_name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+        let path = path.strip_prefix("./").unwrap_or(path);
+        self.exclude.iter().any(|pattern| {
+            glob::Pattern::new(pattern)
+                .map(|p| p.matches(path) || p.matches(file_name))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Ollama's sampling `options`, also mapped onto the other backends' fields
+/// where they have one
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct GenerationOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Most tokens to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+    /// Makes generation repeatable, with the same prompt and temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    /// Tokens of context the model runs with, only ollama takes it. Prompts
+    /// are cut down to fit it with room left for the answer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+}
+
+impl GenerationOptions {
+    pub const NAMES: [&'static str; 6] = [
+        "temperature",
+        "top_p",
+        "num_predict",
+        "seed",
+        "repeat_penalty",
+        "num_ctx",
+    ];
+
+    /// Sets the option called `name` from text, an empty value unsets it
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<Option<T>> {
+            if value.is_empty() {
+                return Ok(None);
+            }
+            let parsed = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid value for {}: {}", name, value))?;
+            Ok(Some(parsed))
+        }
+        match name {
+            "temperature" => self.temperature = parse(name, value)?,
+            "top_p" => self.top_p = parse(name, value)?,
+            "num_predict" => self.num_predict = parse(name, value)?,
+            "seed" => self.seed = parse(name, value)?,
+            "repeat_penalty" => self.repeat_penalty = parse(name, value)?,
+            "num_ctx" => self.num_ctx = parse(name, value)?,
+            _ => anyhow::bail!("Unknown generation option: {}", name),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Ollama,
+    /// Any server with an OpenAI style `/chat/completions` endpoint
+    OpenAi,
+    /// Anthropic's messages API, for Claude models
+    Anthropic,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BackendConfig {
+    pub kind: BackendKind,
+    pub url: String,
+    /// Overrides the model passed on the command line
+    pub model: Option<String>,
+    /// Environment variable holding the API key
+    pub api_key_env: Option<String>,
+    /// The API key itself, for when it can't come from the environment
+    pub api_key: Option<String>,
+    /// How long to wait for the first token before trying the next backend
+    pub timeout_ms: u64,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self {
+            kind: BackendKind::Ollama,
+            url: "http://localhost:11434".to_string(),
+            model: None,
+            api_key_env: None,
+            api_key: None,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Markers next to the line numbers for lines changed since the last commit
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GutterConfig {
+    pub enabled: bool,
+    /// Lines that only differ in whitespace count as unchanged
+    pub ignore_whitespace: bool,
+    /// Lines that only differ in `\r\n` vs `\n` count as unchanged
+    pub ignore_line_endings: bool,
+}
+
+impl Default for GutterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ignore_whitespace: false,
+            ignore_line_endings: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// How long to wait for input before checking for new predictions
+    pub poll_interval_ms: u64,
+    /// Upper bound on redraws per second
+    pub max_fps: u32,
+    /// Wrap long lines at the pane width instead of clipping them
+    pub soft_wrap: bool,
+    /// Built in colour scheme (`auto`, `dark`, `light` or `high-contrast`) or
+    /// a theme file
+    pub theme: String,
+    /// Don't blink the cursor or stream predictions in as they are generated
+    pub reduced_motion: bool,
+    /// Files bigger than this many megabytes aren't parsed, diffed or predicted on
+    pub large_file_mb: u64,
+    /// Take the mouse over from the terminal to click, drag and scroll
+    pub mouse: bool,
+    /// Number lines by their distance from the cursor line
+    pub relative_line_numbers: bool,
+    /// Draw spaces, tabs and trailing whitespace as dim glyphs
+    pub show_whitespace: bool,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 10,
+            max_fps: 60,
+            soft_wrap: false,
+            theme: "auto".to_string(),
+            reduced_motion: false,
+            large_file_mb: 16,
+            mouse: true,
+            relative_line_numbers: false,
+            show_whitespace: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let path = config_dir().join("config.toml");
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let raw = fs::read_to_string(&path)?;
+        toml::from_str(&raw)
+            .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    pub fn language(&self, name: &str) -> LanguageConfig {
+        self.languages.get(name).cloned().unwrap_or_default()
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    let base = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config"));
+    base.join("nars")
+}
@@ -0,0 +1,311 @@
+use crate::config::{config_dir, Config};
+use crate::editor::languages::Language;
+use crate::editor::theme::Theme;
+use crate::models::anthropic;
+use crate::models::backend::{local_ollama, Backend, ModelBackend};
+use anyhow::{anyhow, Result};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+use tokio::time::timeout;
+use tree_sitter::Parser;
+
+// how long a backend gets to answer before it counts as unreachable
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+// prints each check as it runs and keeps its result, counting the failures
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    results: Vec<(&'static str, String)>,
+}
+
+impl Report {
+    fn section(&self, name: &str) {
+        println!("\n{}", name);
+    }
+
+    fn ok(&mut self, message: &str) {
+        self.result("ok", message);
+    }
+
+    fn warn(&mut self, message: &str) {
+        self.result("warn", message);
+    }
+
+    fn fail(&mut self, message: &str) {
+        self.result("FAIL", message);
+        self.failures += 1;
+    }
+
+    fn result(&mut self, status: &'static str, message: &str) {
+        println!("  {:<5} {}", status, message);
+        self.results.push((status, message.to_string()));
+    }
+}
+
+fn has_model(models: &[String], model: &str) -> bool {
+    models
+        .iter()
+        .any(|name| name == model || name.strip_suffix(":latest") == Some(model))
+}
+
+// the terminal can't be asked directly without taking it over, so this goes
+// by what the environment says about it
+fn check_terminal(report: &mut Report) {
+    report.section("Terminal");
+    if io::stdout().is_terminal() {
+        report.ok("stdout is a terminal");
+    } else {
+        report.warn("stdout is not a terminal, run `nars doctor` in the terminal you edit in");
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+        report.ok(&format!("true color (COLORTERM={})", colorterm));
+    } else {
+        report.warn(
+            "COLORTERM isn't truecolor or 24bit, so the selection colour may be approximated. \
+             Set COLORTERM=truecolor if your terminal supports it",
+        );
+    }
+    match term.as_str() {
+        "" | "dumb" => report.fail(&format!(
+            "TERM is {:?}, so colours and bracketed paste won't work. Set it to your terminal, \
+             e.g. xterm-256color",
+            term
+        )),
+        "linux" => report.warn(
+            "the Linux console has no bracketed paste, so pasted text arrives as keys and \
+             gets auto indented and paired",
+        ),
+        _ => report.ok(&format!(
+            "bracketed paste is likely supported (TERM={})",
+            term
+        )),
+    }
+}
+
+// checks the config file and everything in it that is only looked at later,
+// returning the config to use for the remaining checks
+fn check_config(report: &mut Report) -> Config {
+    report.section("Config");
+    let path = config_dir().join("config.toml");
+    if !path.exists() {
+        report.ok(&format!(
+            "no config at {}, using the defaults",
+            path.display()
+        ));
+    }
+    let config = match Config::load() {
+        Ok(config) => {
+            if path.exists() {
+                report.ok(&format!("{} is valid", path.display()));
+            }
+            config
+        }
+        Err(e) => {
+            report.fail(&e.to_string());
+            Config::default()
+        }
+    };
+    if let Err(e) = Theme::load(&config.render.theme) {
+        report.fail(&e.to_string());
+    }
+    for (glob, name) in &config.file_types {
+        if Language::from_name(name).is_none() {
+            report.fail(&format!(
+                "file_types maps {} to unknown language {}",
+                glob, name
+            ));
+        }
+    }
+    let globs = config
+        .file_types
+        .keys()
+        .chain(&config.encryption.patterns)
+        .chain(&config.model.exclude);
+    for glob in globs {
+        if let Err(e) = glob::Pattern::new(glob) {
+            report.fail(&format!("{:?} is not a valid glob: {}", glob, e));
+        }
+    }
+    config
+}
+
+fn check_grammars(report: &mut Report) {
+    report.section("Grammars");
+    for language in Language::ALL {
+        let mut parser = Parser::new();
+        if let Err(e) = parser.set_language(language.grammar()) {
+            report.fail(&format!(
+                "the {} grammar can't be loaded: {}",
+                language.name(),
+                e
+            ));
+            continue;
+        }
+        let sample = match language {
+            Language::Rust => "fn main() {}\n",
+            Language::Zig => "pub fn main() void {}\n",
+        };
+        match parser.parse(sample, None) {
+            Some(tree) if !tree.root_node().has_error() => {
+                report.ok(&format!("{} grammar", language.name()))
+            }
+            _ => report.warn(&format!(
+                "the {} grammar loads but can't parse a trivial file, highlighting falls back \
+                 to keywords. Rebuild nars with the grammar sources in tree-sitter-{}/",
+                language.name(),
+                language.name()
+            )),
+        }
+    }
+}
+
+async fn check_backends(report: &mut Report, config: &Config, model: &str) {
+    report.section("Models");
+    let backends = if config.model.backends.is_empty() {
+        match local_ollama(&config.model) {
+            Ok(client) => vec![Backend::ollama(client)],
+            Err(e) => {
+                report.fail(&e.to_string());
+                Vec::new()
+            }
+        }
+    } else {
+        let mut backends = Vec::new();
+        for backend in &config.model.backends {
+            match Backend::from_config(backend) {
+                Ok(backend) => backends.push(backend),
+                Err(e) => report.fail(&e.to_string()),
+            }
+        }
+        backends
+    };
+    for backend in backends {
+        let model = backend.model.as_deref().unwrap_or(model);
+        let ollama = backend.name.contains("ollama");
+        match timeout(CONNECT_TIMEOUT, backend.health_check()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if ollama => {
+                report.fail(&format!(
+                    "can't reach {} ({}). Is ollama running? Start it with `ollama serve`",
+                    backend.name, e
+                ));
+                continue;
+            }
+            Ok(Err(e)) => {
+                report.fail(&format!("can't reach {}: {}", backend.name, e));
+                continue;
+            }
+            Err(_) => {
+                report.fail(&format!(
+                    "{} didn't answer within {}s",
+                    backend.name,
+                    CONNECT_TIMEOUT.as_secs()
+                ));
+                continue;
+            }
+        };
+        report.ok(&format!("{} is reachable", backend.name));
+        if backend.name.starts_with("anthropic") {
+            // aliases such as claude-sonnet-4-0 aren't in the model list
+            report.ok(&format!(
+                "{} is asked for as {}",
+                model,
+                anthropic::model_id(model)
+            ));
+            continue;
+        }
+        let models = match timeout(CONNECT_TIMEOUT, backend.list_models()).await {
+            Ok(Ok(models)) => models,
+            Ok(Err(e)) => {
+                report.fail(&format!("can't list the models on {}: {}", backend.name, e));
+                continue;
+            }
+            Err(_) => {
+                report.fail(&format!("{} didn't list its models in time", backend.name));
+                continue;
+            }
+        };
+        if has_model(&models, model) {
+            report.ok(&format!("{} is available", model));
+        } else if ollama {
+            report.fail(&format!(
+                "{} isn't pulled on {}, get it with `ollama pull {}`",
+                model, backend.name, model
+            ));
+        } else {
+            report.fail(&format!(
+                "{} doesn't offer {}, it has: {}",
+                backend.name,
+                model,
+                models.join(", ")
+            ));
+        }
+    }
+}
+
+/// `nars doctor`: checks the terminal, config, grammars and model backends and
+/// prints what to fix. Fails if any check failed.
+pub async fn run(model: &str, ollama_host: Option<&str>) -> Result<()> {
+    let mut report = Report::default();
+    check_terminal(&mut report);
+    let mut config = check_config(&mut report);
+    config.model.resolve_ollama_host(ollama_host);
+    check_grammars(&mut report);
+    check_backends(&mut report, &config, model).await;
+    println!();
+    match report.failures {
+        0 => {
+            println!("Everything looks fine");
+            Ok(())
+        }
+        1 => Err(anyhow!("1 check failed")),
+        n => Err(anyhow!("{} checks failed", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BackendConfig, BackendKind};
+
+    #[test]
+    fn test_latest_tag_is_optional() {
+        let models = ["qwen2.5-coder:latest".to_string(), "llama3:8b".to_string()];
+        assert!(has_model(&models, "qwen2.5-coder"));
+        assert!(has_model(&models, "qwen2.5-coder:latest"));
+        assert!(has_model(&models, "llama3:8b"));
+        assert!(!has_model(&models, "llama3"));
+    }
+
+    #[test]
+    fn test_grammars_load_and_parse() {
+        let mut report = Report::default();
+        check_grammars(&mut report);
+        assert_eq!(report.results.len(), Language::ALL.len());
+        assert_eq!(report.results[0], ("ok", "rust grammar".to_string()));
+        // a grammar built without its sources only warns
+        assert_eq!(report.failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_backends_fail_with_a_hint() {
+        let mut config = Config::default();
+        for kind in [BackendKind::Ollama, BackendKind::OpenAi] {
+            config.model.backends.push(BackendConfig {
+                kind,
+                // nothing listens on port 1
+                url: "http://127.0.0.1:1".to_string(),
+                ..BackendConfig::default()
+            });
+        }
+        let mut report = Report::default();
+        check_backends(&mut report, &config, "qwen").await;
+        assert_eq!(report.failures, 2);
+        assert!(report.results[0].1.contains("Is ollama running?"));
+        assert!(report.results[1].1.starts_with("can't reach openai"));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::editor::symbols::extract_functions;
+use crate::editor::Editor;
+use crate::models::Predictor;
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Model written summaries shown above each function in view
+pub struct Annotations {
+    pub enabled: bool,
+    // keyed by a hash of the function text, so scrolling back never re-queries
+    // and editing a function asks again
+    summaries: HashMap<u64, String>,
+    // one request at a time, the rest of the viewport waits its turn
+    pending: Option<u64>,
+    result_tx: mpsc::Sender<(u64, Result<String>)>,
+    result_rx: mpsc::Receiver<(u64, Result<String>)>,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        let (result_tx, result_rx) = mpsc::channel(8);
+        Self {
+            enabled: false,
+            summaries: HashMap::new(),
+            pending: None,
+            result_tx,
+            result_rx,
+        }
+    }
+}
+
+fn function_hash(code: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Editor {
+    pub(crate) fn toggle_annotations(&mut self) {
+        if self.ai_excluded() {
+            self.status_message = Some("Annotations are off for this file".to_string());
+            self.bell();
+            return;
+        }
+        self.annotations.enabled = !self.annotations.enabled;
+        self.status_message = Some(
+            if self.annotations.enabled {
+                "Annotations on"
+            } else {
+                "Annotations off"
+            }
+            .to_string(),
+        );
+    }
+
+    // functions starting inside the viewport
+    fn visible_functions(&self, window_height: usize) -> Vec<Range<usize>> {
+        let starts = self.visible_line_starts();
+        let Some(&view_start) = starts.first() else {
+            return Vec::new();
+        };
+        let view_end = starts
+            .get(window_height)
+            .copied()
+            .unwrap_or(self.content.len());
+        extract_functions(&self.content)
+            .into_iter()
+            .map(|(_, range)| range)
+            .filter(|range| range.start >= view_start && range.start < view_end)
+            .collect()
+    }
+
+    /// Asks for the summary of the next function in view that doesn't have one
+    pub(crate) fn request_annotations(&mut self, predictor: &Arc<Predictor>, window_height: usize) {
+        if !self.annotations.enabled || self.annotations.pending.is_some() {
+            return;
+        }
+        // the function being typed in changes with every key, so wait until the
+        // cursor leaves it
+        let cursor = self.cursor_position;
+        let next = self
+            .visible_functions(window_height)
+            .into_iter()
+            .filter(|range| !range.contains(&cursor))
+            .map(|range| (function_hash(&self.content[range.clone()]), range))
+            .find(|(key, _)| !self.annotations.summaries.contains_key(key));
+        if let Some((key, range)) = next {
+            self.annotations.pending = Some(key);
+            predictor.clone().summarize_background(
+                key,
+                self.content[range].to_string(),
+                self.annotations.result_tx.clone(),
+            );
+        }
+    }
+
+    /// Stores summaries as they arrive, returns true if any did
+    pub(crate) fn poll_annotations(&mut self) -> bool {
+        let mut received = false;
+        while let Ok((key, result)) = self.annotations.result_rx.try_recv() {
+            // failures are cached as empty so they aren't retried on every frame
+            let summary = result
+                .ok()
+                .and_then(|text| text.lines().next().map(str::to_string))
+                .map(|line| line.trim().trim_start_matches('/').trim().to_string())
+                .unwrap_or_default();
+            self.annotations.summaries.insert(key, summary);
+            if self.annotations.pending == Some(key) {
+                self.annotations.pending = None;
+            }
+            received = true;
+        }
+        received
+    }
+
+    /// Comment lines to draw above the functions in view, keyed by the start
+    /// of the line each belongs above
+    pub(crate) fn visible_annotations(&self, window_height: usize) -> Vec<(usize, String)> {
+        if !self.annotations.enabled || self.large_file() {
+            return Vec::new();
+        }
+        self.visible_functions(window_height)
+            .into_iter()
+            .filter_map(|range| {
+                let key = function_hash(&self.content[range.clone()]);
+                let summary = self.annotations.summaries.get(&key)?;
+                let line_start = self.line_start(range.start);
+                let indent: String = self.content[line_start..]
+                    .chars()
+                    .take_while(|&c| c == ' ' || c == '\t')
+                    .collect();
+                (!summary.is_empty()).then(|| (line_start, format!("{}// {}", indent, summary)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::test_editor;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_summaries_are_drawn_above_their_functions() {
+        let content = "fn a() {\n    1\n}\n\nimpl X {\n    fn b() {}\n}\n";
+        let mut editor = test_editor(content, 0);
+        editor.annotations.enabled = true;
+        let functions = extract_functions(content);
+        let key = |i: usize| function_hash(&content[functions[i].1.clone()]);
+        editor.annotations.pending = Some(key(1));
+        let results = &editor.annotations.result_tx;
+        results
+            .try_send((key(0), Ok("// Returns one.\nIt is a constant.".to_string())))
+            .unwrap();
+        results
+            .try_send((key(1), Err(anyhow!("timed out"))))
+            .unwrap();
+        assert!(editor.poll_annotations());
+        assert!(editor.annotations.pending.is_none());
+        // a failure is kept as an empty summary and not drawn
+        assert_eq!(editor.annotations.summaries[&key(1)], "");
+        assert_eq!(
+            editor.visible_annotations(20),
+            [(0, "// Returns one.".to_string())]
+        );
+        // an edited function is a different one
+        editor.apply_edit(13, 14, "2");
+        assert!(editor.visible_annotations(20).is_empty());
+    }
+}
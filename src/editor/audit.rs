@@ -0,0 +1,96 @@
+use crate::editor::popup::Popup;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use ratatui::text::Line;
+use std::fs;
+
+/// Something worth knowing about later, like a save or an accepted prediction
+pub struct AuditEntry {
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+impl AuditEntry {
+    fn line(&self) -> String {
+        format!("{} {}", self.time.format("%H:%M:%S"), self.message)
+    }
+}
+
+impl Editor {
+    /// Adds to the action log shown by alt+g
+    pub(crate) fn audit(&mut self, message: String) {
+        self.audit_log.push(AuditEntry {
+            time: Local::now(),
+            message,
+        });
+    }
+
+    pub(crate) fn open_audit_prompt(&mut self) {
+        self.prompt = Some(Prompt::new(
+            PromptKind::AuditFilter,
+            "Filter action log (ctrl+s exports): ",
+        ));
+    }
+
+    fn audit_matches(&self, filter: &str) -> Vec<&AuditEntry> {
+        let filter = filter.trim().to_lowercase();
+        self.audit_log
+            .iter()
+            .filter(|entry| entry.message.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    /// Shows the entries containing the prompt input, newest first
+    pub(crate) fn confirm_audit_filter(&mut self) {
+        let filter = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let matches = self.audit_matches(&filter);
+        if matches.is_empty() {
+            self.status_message = Some("No matching actions".to_string());
+            return;
+        }
+        let lines = matches
+            .iter()
+            .rev()
+            .map(|entry| Line::from(entry.line()))
+            .collect();
+        self.popup = Some(Popup::new("Action log", lines));
+    }
+
+    /// Writes the entries containing the prompt input next to the file, e.g.
+    /// `main.rs.actions.log`
+    pub(crate) fn export_audit_log(&mut self) {
+        let filter = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let path = format!("{}.actions.log", self.filename.as_deref().unwrap_or("nars"));
+        let write = || -> Result<usize> {
+            let matches = self.audit_matches(&filter);
+            let text: String = matches
+                .iter()
+                .map(|entry| format!("{}\n", entry.line()))
+                .collect();
+            fs::write(&path, text)?;
+            Ok(matches.len())
+        };
+        self.status_message = Some(match write() {
+            Ok(count) => format!("Exported {} actions to {}", count, path),
+            Err(e) => format!("Failed to export the action log: {}", e),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_filters_actions() {
+        let mut editor = test_editor("", 0);
+        editor.audit("Saved test.rs".to_string());
+        editor.audit("Accepted prediction at 1:1: \"fn main() {}\"".to_string());
+        assert_eq!(editor.audit_matches("").len(), 2);
+        let matches = editor.audit_matches("PREDICTION");
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].line().ends_with("\"fn main() {}\""));
+    }
+}
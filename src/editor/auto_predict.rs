@@ -0,0 +1,67 @@
+use crate::editor::Editor;
+use crate::models::scope::PredictionScope;
+use crate::models::Predictor;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+impl Editor {
+    /// Restarts the idle timer after a keystroke, when predictions start by
+    /// themselves
+    pub(crate) fn schedule_auto_prediction(&mut self) {
+        self.auto_predict_at = self
+            .auto_predict
+            .then(|| Instant::now() + Duration::from_millis(self.config.model.auto_predict_ms));
+    }
+
+    // worth predicting: something typed on the line, nothing after the
+    // cursor but closers, and no selection or prompt in the way
+    fn wants_auto_prediction(&self) -> bool {
+        let rest = &self.content[self.cursor_position..self.line_end(self.cursor_position)];
+        self.current_prediction.is_none()
+            && self.prompt.is_none()
+            && self.selection.is_none()
+            && self.carets.is_empty()
+            && self.completion.is_none()
+            && !self.get_current_line_content().trim().is_empty()
+            && (rest.is_empty() || self.closers_after(self.cursor_position).is_some())
+    }
+
+    /// Starts a line prediction once typing has stopped for
+    /// `model.auto_predict_ms`, returns true if it did
+    pub(crate) fn poll_auto_prediction(&mut self, predictor: &Arc<Predictor>) -> bool {
+        if self.auto_predict_at.is_none_or(|at| Instant::now() < at) {
+            return false;
+        }
+        self.auto_predict_at = None;
+        if predictor.is_predicting() || !self.wants_auto_prediction() {
+            return false;
+        }
+        self.request_prediction(predictor, PredictionScope::Line);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_typing_restarts_the_idle_timer() {
+        let mut editor = test_editor("", 0);
+        editor.schedule_auto_prediction();
+        assert!(editor.auto_predict_at.is_none());
+        editor.auto_predict = true;
+        editor.schedule_auto_prediction();
+        let first = editor.auto_predict_at.unwrap();
+        editor.schedule_auto_prediction();
+        assert!(editor.auto_predict_at.unwrap() >= first);
+
+        editor.content = "let x = ()\n".to_string();
+        editor.cursor_position = 9;
+        assert!(editor.wants_auto_prediction());
+        editor.cursor_position = 4;
+        assert!(!editor.wants_auto_prediction());
+        editor.cursor_position = 11;
+        assert!(!editor.wants_auto_prediction());
+    }
+}
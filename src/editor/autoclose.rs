@@ -0,0 +1,169 @@
+use crate::editor::Editor;
+
+// an odd number of quotes means the line ends inside a string, where the
+// parser's error recovery can still read a `{` as an unclosed block
+fn in_string(line: &str) -> bool {
+    let mut inside = false;
+    let mut escaped = false;
+    for c in line.replace("'\"'", "").chars() {
+        if c == '"' && !escaped {
+            inside = !inside;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    inside
+}
+
+impl Editor {
+    /// Inserts a newline, closing the block on its own line when the cursor sits
+    /// just after a `{` the syntax tree reports as unclosed.
+    pub(crate) fn insert_newline(&mut self) {
+        let language = self.config.language(self.language.name());
+        let auto_close = language.auto_close_blocks;
+        let line_start = self.line_start(self.cursor_position);
+        let indent: String = self.content[line_start..]
+            .chars()
+            .take_while(|&c| c == ' ' || c == '\t')
+            .collect();
+        let rest_of_line = &self.content[self.cursor_position..self.line_end(self.cursor_position)];
+
+        if auto_close && rest_of_line.trim().is_empty() {
+            if let Some(brace) = self.brace_before_cursor() {
+                if self.is_unclosed_block(brace, indent.len()) {
+                    self.current_prediction = None;
+                    self.prediction_start_position = None;
+                    let pos = self.cursor_position;
+                    let unit = self.indent_unit();
+                    self.apply_edit(pos, pos, &format!("\n{}{}\n{}}}", indent, unit, indent));
+                    self.cursor_position = pos + 1 + indent.len() + unit.len();
+                    self.update_syntax_tree();
+                    return;
+                }
+            }
+        }
+        if language.auto_indent {
+            self.current_prediction = None;
+            self.prediction_start_position = None;
+            self.insert_indented_newline();
+        } else {
+            self.insert_char('\n');
+        }
+    }
+
+    fn brace_before_cursor(&self) -> Option<usize> {
+        let line_start = self.line_start(self.cursor_position);
+        let before = self.content[line_start..self.cursor_position].trim_end();
+        (before.ends_with('{') && !in_string(before)).then(|| line_start + before.len() - 1)
+    }
+
+    fn is_unclosed_block(&self, brace: usize, indent: usize) -> bool {
+        let Some(tree) = &self.tree else {
+            return false;
+        };
+        if !tree.root_node().has_error() {
+            return false;
+        }
+        let Some(node) = tree.root_node().descendant_for_byte_range(brace, brace + 1) else {
+            return false;
+        };
+        if node.kind() != "{" {
+            return false;
+        }
+        let Some(parent) = node.parent() else {
+            return true;
+        };
+        if parent.is_error() {
+            return true;
+        }
+        match parent.child(parent.child_count().saturating_sub(1)) {
+            Some(last) if last.kind() == "}" && !last.is_missing() => {
+                // the parser happily borrows the closer of an enclosing block, so a
+                // `}` indented less than the opening line belongs to someone else
+                let closer_line = self.line_start(last.start_byte());
+                let closer_indent = self.content[closer_line..last.start_byte()]
+                    .chars()
+                    .take_while(|&c| c == ' ' || c == '\t')
+                    .count();
+                closer_indent < indent
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::LanguageConfig;
+    use crate::editor::test_editor;
+    use crate::editor::Editor;
+
+    fn editor_with(content: &str) -> Editor {
+        test_editor(content, content.len())
+    }
+
+    #[test]
+    fn test_closes_unclosed_block() {
+        let mut editor = editor_with("fn main() {");
+        editor.insert_newline();
+        assert_eq!(editor.content, "fn main() {\n    \n}");
+        assert_eq!(editor.cursor_position, "fn main() {\n    ".len());
+    }
+
+    #[test]
+    fn test_closes_nested_block_that_borrows_outer_closer() {
+        let content = "fn main() {\n    if x {\n}";
+        let mut editor = editor_with(content);
+        editor.cursor_position = "fn main() {\n    if x {".len();
+        editor.insert_newline();
+        assert_eq!(
+            editor.content,
+            "fn main() {\n    if x {\n        \n    }\n}"
+        );
+    }
+
+    #[test]
+    fn test_plain_newline_when_block_closed() {
+        let mut editor = editor_with("fn main() {}");
+        editor.cursor_position = "fn main() {".len();
+        editor.insert_newline();
+        assert_eq!(editor.content, "fn main() {\n    \n}");
+    }
+
+    #[test]
+    fn test_braces_in_comments_and_strings_are_left_alone() {
+        for content in [
+            "fn main() {}\n// {",
+            "fn main() {\n    let s = \"{",
+            "fn main() {\n    let s = \"\\\"{",
+        ] {
+            let mut editor = editor_with(content);
+            editor.insert_newline();
+            assert!(
+                !editor.content[content.len()..].contains('}'),
+                "{:?}",
+                editor.content
+            );
+        }
+    }
+
+    #[test]
+    fn test_closes_after_a_finished_string() {
+        let mut editor = editor_with("fn main() {\n    if s == \"\\\\\" && c == '\"' {");
+        editor.insert_newline();
+        assert!(editor.content.ends_with("{\n        \n    }"));
+    }
+
+    #[test]
+    fn test_off_for_languages_without_it() {
+        let mut editor = editor_with("fn main() {");
+        editor.config.languages.insert(
+            "rust".to_string(),
+            LanguageConfig {
+                auto_close_blocks: false,
+                ..LanguageConfig::default()
+            },
+        );
+        editor.insert_newline();
+        assert!(!editor.content.contains('}'));
+    }
+}
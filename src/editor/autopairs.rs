@@ -0,0 +1,168 @@
+use crate::editor::repeat::Action;
+use crate::editor::Editor;
+
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+fn is_closer(c: char) -> bool {
+    PAIRS.iter().any(|&(_, close)| close == c)
+}
+
+// a quote right after a word is an apostrophe or closes a string, and `'`
+// after `&` or `<` starts a lifetime
+fn opens_quote(quote: char, prev: Option<char>) -> bool {
+    prev.is_none_or(|p| {
+        !(p.is_alphanumeric() || p == '_' || (quote == '\'' && (p == '&' || p == '<')))
+    })
+}
+
+impl Editor {
+    /// Types `text` at the cursor. A single bracket or quote gets its closer
+    /// inserted after it, and typing a closer that is already under the cursor
+    /// steps over it.
+    pub(crate) fn type_text(&mut self, text: &str) {
+        let mut chars = text.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            self.insert_text(text);
+            return;
+        };
+        if !self.config.language(self.language.name()).auto_pairs {
+            self.insert_text(text);
+            return;
+        }
+        let pos = self.cursor_position;
+        let next = self.content[pos..].chars().next();
+        let prev = self.content[..pos].chars().next_back();
+        if is_closer(c) && next == Some(c) {
+            self.cursor_position += c.len_utf8();
+            return;
+        }
+        let Some(&(_, close)) = PAIRS.iter().find(|&&(open, _)| open == c) else {
+            self.insert_text(text);
+            return;
+        };
+        // only pair in front of whitespace or punctuation, never glued to a word
+        let before_gap = next.is_none_or(|n| n.is_whitespace() || is_closer(n) || ",;".contains(n));
+        if before_gap && (c != close || opens_quote(c, prev)) {
+            self.apply_edit(pos, pos, &format!("{}{}", c, close));
+            self.cursor_position = pos + c.len_utf8();
+            self.update_syntax_tree();
+        } else {
+            self.insert_text(text);
+        }
+    }
+
+    /// Backspace, removing both halves of an empty pair
+    pub(crate) fn backspace(&mut self) {
+        let pos = self.cursor_position;
+        let prev = self.content[..pos].chars().next_back();
+        let next = self.content[pos..].chars().next();
+        let empty_pair = PAIRS
+            .iter()
+            .any(|&(open, close)| prev == Some(open) && next == Some(close));
+        if empty_pair && self.config.language(self.language.name()).auto_pairs {
+            self.apply_edit(pos - 1, pos + 1, "");
+            self.cursor_position = pos - 1;
+            self.update_syntax_tree();
+        } else {
+            self.delete_char();
+        }
+    }
+
+    // closers the cursor sits in front of, e.g. the `)` auto-inserted after `foo(`
+    pub(crate) fn closers_after(&self, pos: usize) -> Option<&str> {
+        let rest = &self.content[pos..self.line_end(pos)];
+        (!rest.is_empty() && rest.chars().all(is_closer)).then_some(rest)
+    }
+
+    /// Ghost text to draw at the cursor column when the prediction has to go
+    /// in front of closers that are already typed, rather than after the line
+    pub(crate) fn ghost_before_closers(&self) -> Option<(usize, String)> {
+        let pred = self.current_prediction.as_ref()?;
+        let start = self.prediction_start_position?;
+        let closers = self.closers_after(start)?;
+        let line_start = self.line_start(start);
+        let first_line = pred.lines().next()?;
+        let completion = first_line.strip_prefix(&self.content[line_start..start])?;
+        let ghost = completion.strip_suffix(closers).unwrap_or(completion);
+        Some((start - line_start, ghost.to_string()))
+    }
+
+    /// Accepts the prediction in place of the closers after the cursor, since
+    /// the model writes its own. Returns false if the prediction doesn't fit.
+    pub(crate) fn accept_before_closers(&mut self, pred: &str, start: usize) -> bool {
+        let Some(closers) = self.closers_after(start) else {
+            return false;
+        };
+        let end = start + closers.len();
+        let Some(completion) = pred.strip_prefix(&self.content[self.line_start(start)..start])
+        else {
+            return false;
+        };
+        let completion = completion.to_string();
+        self.apply_edit(start, end, &completion);
+        self.cursor_position = start + completion.len();
+        self.update_syntax_tree();
+        self.record(Action::Insert(completion));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::LanguageConfig;
+    use crate::editor::test_editor;
+    use crate::editor::Editor;
+
+    fn type_chars(content: &str, cursor: usize, chars: &str) -> Editor {
+        let mut editor = test_editor(content, cursor);
+        for c in chars.chars() {
+            editor.type_text(&c.to_string());
+        }
+        editor
+    }
+
+    #[test]
+    fn test_pairs_and_steps_over_closers() {
+        let editor = type_chars("", 0, "foo(\"a\")");
+        assert_eq!(editor.content, "foo(\"a\")");
+        assert_eq!(editor.cursor_position, 8);
+        let editor = type_chars("", 0, "foo(");
+        assert_eq!(editor.content, "foo()");
+        assert_eq!(editor.cursor_position, 4);
+    }
+
+    #[test]
+    fn test_leaves_apostrophes_and_lifetimes_alone() {
+        let editor = type_chars("", 0, "&'a don't");
+        assert_eq!(editor.content, "&'a don't");
+        let editor = type_chars("x", 0, "(");
+        assert_eq!(editor.content, "(x");
+    }
+
+    #[test]
+    fn test_backspace_removes_only_an_empty_pair() {
+        let mut editor = type_chars("", 0, "(");
+        editor.backspace();
+        assert_eq!(editor.content, "");
+        let mut editor = test_editor("(a)", 2);
+        editor.backspace();
+        assert_eq!(editor.content, "()");
+        editor.config.languages.insert(
+            "rust".to_string(),
+            LanguageConfig {
+                auto_pairs: false,
+                ..LanguageConfig::default()
+            },
+        );
+        editor.backspace();
+        assert_eq!(editor.content, ")");
+    }
+
+    #[test]
+    fn test_accepts_prediction_over_closers() {
+        let mut editor = type_chars("", 0, "foo(");
+        assert!(editor.accept_before_closers("foo(bar);", 4));
+        assert_eq!(editor.content, "foo(bar);");
+        assert_eq!(editor.cursor_position, 9);
+    }
+}
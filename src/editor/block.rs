@@ -0,0 +1,123 @@
+use crate::editor::multicursor::Caret;
+use crate::editor::selection::{Selection, SelectionMode};
+use crate::editor::Editor;
+use ratatui::crossterm::event::KeyCode;
+use unicode_width::UnicodeWidthStr;
+
+/// Corners of a rectangular selection, as line numbers and display columns.
+/// Each of its lines is a caret, so edits apply to every line at once.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockSelection {
+    anchor_line: usize,
+    anchor_column: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Editor {
+    /// Start offset of buffer line `row`
+    pub(crate) fn line_offset(&self, row: usize) -> usize {
+        self.content
+            .split_inclusive('\n')
+            .take(row)
+            .map(str::len)
+            .sum()
+    }
+
+    /// Moves the free corner of the block selection, starting one at the
+    /// cursor if there is none
+    pub(crate) fn extend_block(&mut self, code: KeyCode) {
+        let mut block = self.block.take().unwrap_or_else(|| {
+            let line = self.point_at(self.cursor_position).row;
+            let column = self.column_of(self.cursor_position);
+            BlockSelection {
+                anchor_line: line,
+                anchor_column: column,
+                line,
+                column,
+            }
+        });
+        let last_line = self.content.matches('\n').count();
+        let (top, bottom) = (
+            block.anchor_line.min(block.line),
+            block.anchor_line.max(block.line),
+        );
+        let widest = (top..=bottom)
+            .map(|row| {
+                let start = self.line_offset(row);
+                self.content[start..self.line_end(start)].width()
+            })
+            .max()
+            .unwrap_or(0);
+        match code {
+            KeyCode::Up => block.line = block.line.saturating_sub(1),
+            KeyCode::Down => block.line = (block.line + 1).min(last_line),
+            KeyCode::Left => block.column = block.column.saturating_sub(1),
+            KeyCode::Right => block.column = (block.column + 1).min(widest.max(block.column)),
+            _ => {}
+        }
+        self.block = Some(block);
+        self.apply_block(block);
+    }
+
+    // one caret per line of the block, selecting its columns. Lines too short
+    // to reach the block get a caret at their end.
+    fn apply_block(&mut self, block: BlockSelection) {
+        let (top, bottom) = (
+            block.anchor_line.min(block.line),
+            block.anchor_line.max(block.line),
+        );
+        let (left, right) = (
+            block.anchor_column.min(block.column),
+            block.anchor_column.max(block.column),
+        );
+        self.carets.clear();
+        for row in top..=bottom {
+            let line_start = self.line_offset(row);
+            let start = self.offset_at_column(line_start, left);
+            let end = self.offset_at_column(line_start, right);
+            let (anchor, position) = if block.column >= block.anchor_column {
+                (start, end)
+            } else {
+                (end, start)
+            };
+            // a zero width block is plain carets, so backspace deletes before them
+            let selection = (right > left).then_some(Selection {
+                anchor,
+                mode: SelectionMode::Char,
+            });
+            if row == block.line {
+                self.cursor_position = position;
+                self.selection = selection;
+            } else {
+                self.carets.push(Caret {
+                    position,
+                    selection,
+                    goal_column: None,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    use ratatui::crossterm::event::KeyCode;
+
+    #[test]
+    fn test_types_over_every_line_of_the_block() {
+        let mut editor = test_editor("a: u8,\nb: u8,\nc\nd: u8,", 3);
+        for code in [KeyCode::Down, KeyCode::Down, KeyCode::Down] {
+            editor.extend_block(code);
+        }
+        editor.extend_block(KeyCode::Right);
+        editor.extend_block(KeyCode::Right);
+        editor.for_each_caret(|e| {
+            e.delete_selection();
+            e.insert_char('i');
+        });
+        assert_eq!(editor.content, "a: i,\nb: i,\nci\nd: i,");
+    }
+}
@@ -0,0 +1,62 @@
+use crate::editor::Editor;
+use std::ops::Range;
+
+impl Editor {
+    /// The modules, impls, types and functions around the cursor, outermost
+    /// first, e.g. `["mod tests", "fn test_parse"]`
+    pub(crate) fn breadcrumbs(&self) -> Vec<String> {
+        let Some(tree) = self.tree.as_ref().filter(|_| !self.syntax_degraded()) else {
+            return Vec::new();
+        };
+        let pos = self.cursor_position;
+        let mut node = tree.root_node().descendant_for_byte_range(pos, pos);
+        let mut crumbs = Vec::new();
+        while let Some(current) = node {
+            if let Some(label) = self.language.scope_label(&current, &self.content) {
+                crumbs.push(label);
+            }
+            node = current.parent();
+        }
+        crumbs.reverse();
+        crumbs
+    }
+
+    /// The label and extent of the innermost scope around the cursor whose
+    /// label `wanted` accepts, e.g. the function it is in
+    pub(crate) fn enclosing_scope(
+        &self,
+        wanted: impl Fn(&str) -> bool,
+    ) -> Option<(String, Range<usize>)> {
+        let tree = self.tree.as_ref().filter(|_| !self.syntax_degraded())?;
+        let pos = self.cursor_position;
+        let mut node = tree.root_node().descendant_for_byte_range(pos, pos);
+        while let Some(current) = node {
+            if let Some(label) = self.language.scope_label(&current, &self.content) {
+                if wanted(&label) {
+                    return Some((label, current.byte_range()));
+                }
+            }
+            node = current.parent();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_breadcrumbs_follow_the_cursor() {
+        let mut editor = test_editor("mod a {\n    impl Display for Foo {\n        fn fmt(&self) {\n            1;\n        }\n    }\n}\n\nfn b() {}\n", 0);
+        editor.cursor_position = editor.content.find("1;").unwrap();
+        assert_eq!(
+            editor.breadcrumbs(),
+            vec!["mod a", "impl Display for Foo", "fn fmt"]
+        );
+        editor.cursor_position = editor.content.find("b()").unwrap();
+        assert_eq!(editor.breadcrumbs(), vec!["fn b"]);
+        editor.cursor_position = editor.content.len();
+        assert!(editor.breadcrumbs().is_empty());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::editor::encoding::FileEncoding;
+use crate::editor::history::History;
+use crate::editor::indent::IndentStyle;
+use crate::editor::languages::Language;
+use crate::editor::line_endings::LineEnding;
+use crate::editor::split::PaneView;
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use crate::lockfile::FileLock;
+use crate::logger::log_to_file;
+use crate::remote::RemoteFile;
+use anyhow::{bail, Result};
+use ratatui::layout::Rect;
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+use std::time::SystemTime;
+use tokio::sync::oneshot;
+use tree_sitter::Tree;
+use unicode_width::UnicodeWidthStr;
+
+/// A file open in a tab other than the current one. Switching tabs swaps
+/// these with the editor's own fields.
+pub struct Buffer {
+    content: String,
+    cursor_position: usize,
+    scroll_offset: usize,
+    tree: Option<Tree>,
+    filename: Option<String>,
+    language: Language,
+    encrypted: bool,
+    remote: Option<RemoteFile>,
+    lock: Option<FileLock>,
+    disk_modified: Option<SystemTime>,
+    disk_dismissed: Option<SystemTime>,
+    line_ending: LineEnding,
+    file_encoding: FileEncoding,
+    modified: bool,
+    folds: Vec<Range<usize>>,
+    marks: BTreeMap<char, usize>,
+    indent_style: IndentStyle,
+    git_base: Option<Vec<String>>,
+    git_base_rx: Option<oneshot::Receiver<Option<Vec<String>>>>,
+    history: History,
+}
+
+// the label a tab shows, `*` marks unsaved changes
+fn tab_label(filename: Option<&str>, modified: bool) -> String {
+    let name = filename
+        .map(|f| {
+            Path::new(f)
+                .file_name()
+                .map_or(f.to_string(), |n| n.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "untitled".to_string());
+    format!(" {}{} ", name, if modified { "*" } else { "" })
+}
+
+impl Editor {
+    // an empty buffer like the one nars starts with when given no file
+    fn empty_buffer(&self) -> Buffer {
+        Buffer {
+            content: String::new(),
+            cursor_position: 0,
+            scroll_offset: 0,
+            tree: None,
+            filename: None,
+            language: self.language,
+            encrypted: false,
+            remote: None,
+            lock: None,
+            disk_modified: None,
+            disk_dismissed: None,
+            line_ending: LineEnding::Lf,
+            file_encoding: FileEncoding::default(),
+            modified: false,
+            folds: Vec::new(),
+            marks: BTreeMap::new(),
+            indent_style: self.indent_style,
+            git_base: None,
+            git_base_rx: None,
+            history: History::default(),
+        }
+    }
+
+    // trades the current buffer for `buffer`, leaving the current one in it
+    fn swap_buffer(&mut self, buffer: &mut Buffer) {
+        std::mem::swap(&mut self.content, &mut buffer.content);
+        std::mem::swap(&mut self.cursor_position, &mut buffer.cursor_position);
+        std::mem::swap(&mut self.scroll_offset, &mut buffer.scroll_offset);
+        std::mem::swap(&mut self.tree, &mut buffer.tree);
+        std::mem::swap(&mut self.filename, &mut buffer.filename);
+        std::mem::swap(&mut self.language, &mut buffer.language);
+        std::mem::swap(&mut self.encrypted, &mut buffer.encrypted);
+        std::mem::swap(&mut self.remote, &mut buffer.remote);
+        std::mem::swap(&mut self.lock, &mut buffer.lock);
+        std::mem::swap(&mut self.disk_modified, &mut buffer.disk_modified);
+        std::mem::swap(&mut self.disk_dismissed, &mut buffer.disk_dismissed);
+        std::mem::swap(&mut self.line_ending, &mut buffer.line_ending);
+        std::mem::swap(&mut self.file_encoding, &mut buffer.file_encoding);
+        std::mem::swap(&mut self.modified, &mut buffer.modified);
+        std::mem::swap(&mut self.folds, &mut buffer.folds);
+        std::mem::swap(&mut self.marks, &mut buffer.marks);
+        std::mem::swap(&mut self.indent_style, &mut buffer.indent_style);
+        std::mem::swap(&mut self.git_base, &mut buffer.git_base);
+        std::mem::swap(&mut self.git_base_rx, &mut buffer.git_base_rx);
+        std::mem::swap(&mut self.history, &mut buffer.history);
+        self.gutter_cache = None;
+        if let Err(e) = self.parser.set_language(self.language.grammar()) {
+            log_to_file(&format!(
+                "Error loading {} grammar: {}",
+                self.language.name(),
+                e
+            ));
+        }
+        // what was in progress belongs to the buffer being left
+        self.clear_selection();
+        self.clear_carets();
+        self.goal_column = None;
+        self.cancel_prediction();
+        self.current_prediction = None;
+        self.prediction_start_position = None;
+        self.completion = None;
+        self.completion_request = None;
+        self.search = None;
+        self.replace = None;
+        self.template_fill = None;
+        self.rewrite = None;
+        self.test_return = None;
+        self.confirm_overwrite = false;
+        // the chat and annotations were turned on for the other buffer
+        if self.ai_excluded() {
+            self.chat = None;
+            self.annotations.enabled = false;
+        }
+        if let Some(split) = self.split.as_mut() {
+            split.other = PaneView {
+                cursor: self.cursor_position,
+                scroll_offset: self.scroll_offset,
+            };
+        }
+    }
+
+    /// Number of open buffers, the current one included
+    pub(crate) fn buffer_count(&self) -> usize {
+        self.buffers.len() + 1
+    }
+
+    /// Makes the buffer in tab `tab` the current one
+    pub(crate) fn switch_buffer(&mut self, tab: usize) {
+        if tab == self.active_buffer || tab >= self.buffer_count() {
+            return;
+        }
+        // buffers holds every tab but the current one, in order
+        let index = if tab < self.active_buffer {
+            tab
+        } else {
+            tab - 1
+        };
+        let mut buffer = self.buffers.remove(index);
+        self.swap_buffer(&mut buffer);
+        let at = if tab < self.active_buffer {
+            self.active_buffer - 1
+        } else {
+            self.active_buffer
+        };
+        self.buffers.insert(at, buffer);
+        self.active_buffer = tab;
+    }
+
+    /// Moves to the next tab, or the previous one, wrapping around
+    pub(crate) fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffer_count();
+        if count == 1 {
+            self.bell();
+            return;
+        }
+        let tab = if forward {
+            (self.active_buffer + 1) % count
+        } else {
+            (self.active_buffer + count - 1) % count
+        };
+        self.switch_buffer(tab);
+    }
+
+    // opens an empty buffer in a new tab after the current one
+    fn new_buffer(&mut self) {
+        let mut buffer = self.empty_buffer();
+        self.swap_buffer(&mut buffer);
+        self.buffers.insert(self.active_buffer, buffer);
+        self.active_buffer += 1;
+    }
+
+    /// Opens `text` in a new tab as an unsaved buffer with no file, in the
+    /// current buffer's language
+    pub(crate) fn open_scratch_buffer(&mut self, text: &str) {
+        self.new_buffer();
+        self.apply_edit(0, 0, text);
+        self.history = History::default();
+        self.cursor_position = 0;
+        self.update_syntax_tree();
+    }
+
+    /// Opens `path` in a tab of its own, or switches to the tab it is already
+    /// open in. An untouched empty buffer is reused.
+    pub(crate) fn open_in_buffer(&mut self, path: String) -> Result<()> {
+        if let Some(tab) = self.tab_of(&path) {
+            self.switch_buffer(tab);
+            return Ok(());
+        }
+        if self.filename.is_some() || self.modified {
+            self.new_buffer();
+        }
+        self.open_file(path)
+    }
+
+    fn tab_of(&self, path: &str) -> Option<usize> {
+        self.tab_labels_with(|filename, _| filename == Some(path))
+            .into_iter()
+            .position(|open| open)
+    }
+
+    // `f` applied to each tab's file name and modified flag, in tab order
+    fn tab_labels_with<T>(&self, f: impl Fn(Option<&str>, bool) -> T) -> Vec<T> {
+        let mut tabs: Vec<T> = self
+            .buffers
+            .iter()
+            .map(|b| f(b.filename.as_deref(), b.modified))
+            .collect();
+        tabs.insert(
+            self.active_buffer,
+            f(self.filename.as_deref(), self.modified),
+        );
+        tabs
+    }
+
+    /// The first tab other than the current one with unsaved changes
+    pub(crate) fn modified_buffer(&self) -> Option<usize> {
+        let tabs = self.tab_labels_with(|_, modified| modified);
+        (0..tabs.len()).find(|&tab| tabs[tab] && tab != self.active_buffer)
+    }
+
+    /// Closes the current buffer, the next tab takes its place. Unsaved
+    /// changes are only thrown away when `force` is set.
+    pub(crate) fn close_buffer(&mut self, force: bool) -> Result<()> {
+        if self.modified && !force {
+            bail!("Unsaved changes, :w saves them and :bd! throws them away");
+        }
+        if self.buffers.is_empty() {
+            bail!("Only one buffer is open, esc exits");
+        }
+        let index = self.active_buffer.min(self.buffers.len() - 1);
+        let mut buffer = self.buffers.remove(index);
+        self.swap_buffer(&mut buffer);
+        self.active_buffer = index;
+        // dropping it releases its lock
+        drop(buffer);
+        Ok(())
+    }
+
+    /// The row the tab bar takes at the top of `area` while more than one
+    /// buffer is open, and the rest of `area`
+    pub(crate) fn split_tabs(&self, area: Rect) -> (Option<Rect>, Rect) {
+        if self.buffers.is_empty() || area.height < 2 {
+            return (None, area);
+        }
+        let bar = Rect::new(area.x, area.y, area.width, 1);
+        let rest = Rect::new(area.x, area.y + 1, area.width, area.height - 1);
+        (Some(bar), rest)
+    }
+
+    // the tabs that fit in `width` columns with their label and column,
+    // starting late enough that the current tab is shown
+    fn tab_layout(&self, width: u16) -> Vec<(usize, String, u16)> {
+        let labels = self.tab_labels_with(tab_label);
+        let widths: Vec<u16> = labels.iter().map(|l| l.width() as u16).collect();
+        let mut first = 0;
+        while first < self.active_buffer
+            && widths[first..=self.active_buffer].iter().sum::<u16>() > width
+        {
+            first += 1;
+        }
+        let mut x = 0;
+        let mut layout = Vec::new();
+        for (tab, label) in labels.into_iter().enumerate().skip(first) {
+            if x >= width {
+                break;
+            }
+            let w = widths[tab];
+            layout.push((tab, label, x));
+            x += w;
+        }
+        layout
+    }
+
+    /// The tab drawn at `column` of the tab bar
+    pub(crate) fn tab_at(&self, bar: Rect, column: u16) -> Option<usize> {
+        let column = column.checked_sub(bar.x)?;
+        self.tab_layout(bar.width)
+            .into_iter()
+            .find(|(_, label, x)| column >= *x && column < x + label.width() as u16)
+            .map(|(tab, _, _)| tab)
+    }
+
+    pub(crate) fn render_tabs(&self, f: &mut Frame, bar: Rect, theme: &Theme) {
+        let spans: Vec<Span> = self
+            .tab_layout(bar.width)
+            .into_iter()
+            .map(|(tab, label, _)| {
+                if tab == self.active_buffer {
+                    Span::styled(label, theme.base.add_modifier(Modifier::REVERSED))
+                } else {
+                    Span::styled(label, theme.dim)
+                }
+            })
+            .collect();
+        f.render_widget(Paragraph::new(Line::from(spans)).style(theme.base), bar);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::chat::Chat;
+    use crate::editor::Editor;
+    use ratatui::layout::Rect;
+
+    // opens a buffer on `text` named `name` without touching the disk
+    fn add_buffer(editor: &mut Editor, name: &str, text: &str) {
+        editor.new_buffer();
+        editor.filename = Some(name.to_string());
+        editor.apply_edit(0, 0, text);
+        editor.modified = false;
+    }
+
+    #[test]
+    fn test_tabs_keep_their_own_buffer() {
+        let (mut editor, _) = Editor::new("a.rs".to_string(), Config::default());
+        editor.filename = Some("src/a.rs".to_string());
+        editor.apply_edit(0, 0, "fn a() {}\n");
+        add_buffer(&mut editor, "b.rs", "fn b() {}\n");
+        add_buffer(&mut editor, "c.rs", "fn c() {}\n");
+        assert_eq!(editor.buffer_count(), 3);
+        assert_eq!(editor.active_buffer, 2);
+        editor.switch_buffer(0);
+        assert_eq!(editor.content, "fn a() {}\n");
+        assert!(editor.modified);
+        editor.cycle_buffer(false);
+        assert_eq!(editor.filename.as_deref(), Some("c.rs"));
+        assert_eq!(editor.modified_buffer(), Some(0));
+        // the tab bar takes the top row
+        let bar = editor.split_tabs(Rect::new(0, 0, 80, 20)).0.unwrap();
+        assert_eq!(bar, Rect::new(0, 0, 80, 1));
+        // " a.rs* " then " b.rs "
+        assert_eq!(editor.tab_at(bar, 8), Some(1));
+        assert!(editor.close_buffer(false).is_ok());
+        assert_eq!(editor.filename.as_deref(), Some("b.rs"));
+        assert!(editor.close_buffer(false).is_ok());
+        assert_eq!(editor.filename.as_deref(), Some("src/a.rs"));
+        assert!(editor.close_buffer(true).is_err());
+        assert!(editor.split_tabs(Rect::new(0, 0, 80, 20)).0.is_none());
+    }
+
+    #[test]
+    fn test_tab_bar_scrolls_to_the_current_tab() {
+        let (mut editor, _) = Editor::new("a.rs".to_string(), Config::default());
+        for i in 0..10 {
+            add_buffer(&mut editor, &format!("file{}.rs", i), "");
+        }
+        let bar = Rect::new(0, 0, 30, 1);
+        let layout = editor.tab_layout(bar.width);
+        assert_eq!(layout.last().map(|(tab, _, _)| *tab), Some(10));
+        assert_eq!(editor.tab_at(bar, 0), Some(layout[0].0));
+    }
+
+    #[test]
+    fn test_switching_to_an_excluded_buffer_turns_the_model_off() {
+        let (mut editor, _) = Editor::new("a.rs".to_string(), Config::default());
+        add_buffer(&mut editor, "b.rs", "const KEY: &str = \"hunter2\";\n");
+        editor.encrypted = true;
+        editor.switch_buffer(0);
+        editor.chat = Some(Chat::default());
+        editor.annotations.enabled = true;
+        editor.switch_buffer(1);
+        assert!(editor.chat.is_none());
+        assert!(!editor.annotations.enabled);
+    }
+}
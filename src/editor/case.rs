@@ -0,0 +1,186 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::repeat::Action;
+use crate::editor::selection::{Selection, SelectionMode};
+use crate::editor::Editor;
+
+/// A case an identifier or region can be converted to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    Upper,
+    Lower,
+    Snake,
+    Camel,
+    Pascal,
+}
+
+impl Case {
+    pub const ALL: [Case; 5] = [
+        Case::Upper,
+        Case::Lower,
+        Case::Snake,
+        Case::Camel,
+        Case::Pascal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::Upper => "upper",
+            Case::Lower => "lower",
+            Case::Snake => "snake",
+            Case::Camel => "camel",
+            Case::Pascal => "pascal",
+        }
+    }
+
+    /// Converts `text`, identifiers in it are converted one by one and
+    /// everything between them is kept
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Case::Upper => return text.to_uppercase(),
+            Case::Lower => return text.to_lowercase(),
+            _ => {}
+        }
+        let mut out = String::new();
+        let mut ident = String::new();
+        for c in text.chars() {
+            if c.is_alphanumeric() || c == '_' {
+                ident.push(c);
+                continue;
+            }
+            out.push_str(&self.convert_ident(&std::mem::take(&mut ident)));
+            out.push(c);
+        }
+        out.push_str(&self.convert_ident(&ident));
+        out
+    }
+
+    fn convert_ident(self, ident: &str) -> String {
+        // leading underscores mark private or unused names, keep them
+        let body = ident.trim_start_matches('_');
+        let mut out = ident[..ident.len() - body.len()].to_string();
+        for (idx, word) in split_words(body).iter().enumerate() {
+            match self {
+                Case::Snake if idx > 0 => out.push('_'),
+                Case::Camel if idx == 0 => {
+                    out.push_str(word);
+                    continue;
+                }
+                _ => {}
+            }
+            match self {
+                Case::Camel | Case::Pascal => out.push_str(&capitalize(word)),
+                _ => out.push_str(word),
+            }
+        }
+        out
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// lowercase words of an identifier, split at underscores and case changes so
+// `parseHTTPRequest` gives parse, http and request
+fn split_words(ident: &str) -> Vec<String> {
+    let chars: Vec<char> = ident.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (idx, &c) in chars.iter().enumerate() {
+        if c == '_' {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = idx.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(idx + 1);
+        let boundary = c.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_numeric()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+impl Editor {
+    pub(crate) fn open_case_prompt(&mut self) {
+        let names: Vec<&str> = Case::ALL.iter().map(|case| case.name()).collect();
+        self.prompt = Some(Prompt::new(
+            PromptKind::Case,
+            &format!("Case ({}): ", names.join(", ")),
+        ));
+    }
+
+    pub(crate) fn confirm_case(&mut self) {
+        let name = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        match Case::ALL
+            .into_iter()
+            .find(|case| case.name() == name.trim())
+        {
+            Some(case) => self.transform_case(case),
+            None => {
+                self.status_message = Some(format!("No case named {}", name.trim()));
+                self.bell();
+            }
+        }
+    }
+
+    /// Converts the selection, or the identifier under the cursor
+    pub(crate) fn transform_case(&mut self, case: Case) {
+        let selected = self.selection_range();
+        let Some((start, end)) = selected.or_else(|| self.word_range_at(self.cursor_position))
+        else {
+            self.bell();
+            return;
+        };
+        let text = case.apply(&self.content[start..end]);
+        let cursor = self.cursor_position;
+        self.apply_edit(start, end, &text);
+        let end = start + text.len();
+        if selected.is_some() {
+            self.selection = Some(Selection {
+                anchor: start,
+                mode: SelectionMode::Char,
+            });
+            self.cursor_position = end;
+        } else {
+            self.cursor_position = cursor.clamp(start, end);
+            while !self.content.is_char_boundary(self.cursor_position) {
+                self.cursor_position -= 1;
+            }
+        }
+        self.update_syntax_tree();
+        self.record(Action::TransformCase(case));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converts_between_cases() {
+        assert_eq!(Case::Snake.apply("parseHTTPRequest"), "parse_http_request");
+        assert_eq!(Case::Camel.apply("parse_http_request"), "parseHttpRequest");
+        assert_eq!(Case::Pascal.apply("_unused_value"), "_UnusedValue");
+        assert_eq!(
+            Case::Snake.apply("let fooBar = BazQux;"),
+            "let foo_bar = baz_qux;"
+        );
+        assert_eq!(Case::Upper.apply("max_len"), "MAX_LEN");
+    }
+}
@@ -0,0 +1,316 @@
+use crate::editor::prediction::BYTES_PER_TOKEN;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::repeat::Action;
+use crate::editor::theme::Theme;
+use crate::editor::toast::Severity;
+use crate::editor::Editor;
+use crate::models::parser::parse_code_output;
+use crate::models::Predictor;
+use anyhow::Result;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::Frame;
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+// share of the width the chat panel takes from the panes
+const PANEL_PERCENT: u16 = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    User,
+    Model,
+}
+
+/// A conversation with the model about the buffer, shown in a panel on the
+/// right
+#[derive(Default)]
+pub struct Chat {
+    messages: Vec<(Role, String)>,
+    // chunks of the answer being streamed in
+    reply_rx: Option<mpsc::Receiver<Result<String>>>,
+}
+
+/// The prompt for the next answer: the file, then the conversation so far
+pub fn chat_prompt(filename: &str, context: &str, messages: &[(Role, String)]) -> String {
+    let mut prompt = format!(
+        "You are helping someone edit {}. Answer their questions about it briefly, putting any code in a single fenced code block. Here is the file:\n```\n{}\n```\n",
+        filename, context
+    );
+    for (role, text) in messages {
+        let speaker = match role {
+            Role::User => "User",
+            Role::Model => "Assistant",
+        };
+        prompt.push_str(&format!("\n{}: {}\n", speaker, text.trim()));
+    }
+    prompt.push_str("\nAssistant: ");
+    prompt
+}
+
+impl Chat {
+    pub fn is_answering(&self) -> bool {
+        self.reply_rx.is_some()
+    }
+
+    // the lines of the conversation, code blocks set apart from the text
+    fn lines(&self, theme: &Theme) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        for (role, text) in &self.messages {
+            let (name, style) = match role {
+                Role::User => ("you", theme.keyword),
+                Role::Model => ("model", theme.function),
+            };
+            lines.push(Line::from(Span::styled(name, style)));
+            let mut in_code = false;
+            for line in text.lines() {
+                if line.trim_start().starts_with("```") {
+                    in_code = !in_code;
+                    lines.push(Line::from(Span::styled(line.to_string(), theme.dim)));
+                } else if in_code {
+                    lines.push(Line::from(Span::styled(line.to_string(), theme.string)));
+                } else {
+                    lines.push(Line::from(line.to_string()));
+                }
+            }
+            lines.push(Line::from(""));
+        }
+        lines
+    }
+
+    /// Draws the conversation scrolled to its end
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let lines = self.lines(theme);
+        let width = area.width.saturating_sub(2).max(1) as usize;
+        // rows the lines take once wrapped, to keep the newest in view
+        let rows: usize = lines
+            .iter()
+            .map(|line| line.width().max(1).div_ceil(width))
+            .sum();
+        let scroll = rows.saturating_sub(area.height.saturating_sub(2) as usize);
+        let title = if self.is_answering() {
+            "Chat (answering...)"
+        } else {
+            "Chat (alt+y inserts the code)"
+        };
+        f.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll as u16, 0))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(theme.base),
+                ),
+            area,
+        );
+    }
+}
+
+impl Editor {
+    /// The area left for the panes and the chat panel's, if it is open
+    pub(crate) fn split_chat(&self, area: Rect) -> (Rect, Option<Rect>) {
+        if self.chat.is_none() {
+            return (area, None);
+        }
+        let panels = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(1), Constraint::Percentage(PANEL_PERCENT)])
+            .split(area);
+        (panels[0], Some(panels[1]))
+    }
+
+    /// Opens the chat or moves to its input, from the input it closes the chat
+    pub(crate) fn toggle_chat(&mut self) {
+        if self.prompt.as_ref().map(|p| p.kind) == Some(PromptKind::Chat) {
+            self.prompt = None;
+            self.chat = None;
+            return;
+        }
+        if self.ai_excluded() {
+            self.status_message = Some("This file is never sent to the model".to_string());
+            return;
+        }
+        self.chat.get_or_insert_with(Chat::default);
+        self.prompt = Some(Prompt::new(PromptKind::Chat, "Ask: "));
+    }
+
+    // the buffer as sent with every question, cut down to the context budget
+    // around the cursor when it is too long
+    fn chat_context(&self) -> &str {
+        let budget = self.config.model.context_tokens() * BYTES_PER_TOKEN;
+        if self.content.len() <= budget {
+            return &self.content;
+        }
+        let mut start = self.cursor_position.saturating_sub(budget / 2);
+        while !self.content.is_char_boundary(start) {
+            start += 1;
+        }
+        let mut end = (start + budget).min(self.content.len());
+        while !self.content.is_char_boundary(end) {
+            end -= 1;
+        }
+        &self.content[start..end]
+    }
+
+    /// Sends the question in the chat input, the answer streams into the panel
+    pub(crate) fn send_chat_message(&mut self, predictor: &Arc<Predictor>) {
+        let question = self
+            .prompt
+            .as_mut()
+            .map(|p| std::mem::take(&mut p.input))
+            .unwrap_or_default();
+        if question.trim().is_empty() {
+            return;
+        }
+        self.ask_chat(predictor, question);
+    }
+
+    /// Adds `question` to the chat, opening it if needed, and streams the model's answer in under it
+    pub(crate) fn ask_chat(&mut self, predictor: &Arc<Predictor>, question: String) {
+        let filename = self
+            .filename
+            .clone()
+            .unwrap_or_else(|| "a new file".to_string());
+        let context = self.chat_context().to_string();
+        let chat = self.chat.get_or_insert_with(Chat::default);
+        chat.messages.push((Role::User, question));
+        let prompt = chat_prompt(&filename, &context, &chat.messages);
+        chat.messages.push((Role::Model, String::new()));
+        let (chunk_tx, chunk_rx) = mpsc::channel(64);
+        // an answer still streaming in is dropped for the new one
+        chat.reply_rx = Some(chunk_rx);
+        predictor.clone().chat_background(prompt, chunk_tx);
+    }
+
+    /// Adds the chunks of the answer that arrived, returns true if any did
+    pub(crate) fn poll_chat(&mut self) -> bool {
+        let Some(chat) = self.chat.as_mut() else {
+            return false;
+        };
+        let Some(reply_rx) = chat.reply_rx.as_mut() else {
+            return false;
+        };
+        let mut changed = false;
+        let mut failure = None;
+        loop {
+            match reply_rx.try_recv() {
+                Ok(Ok(chunk)) => {
+                    if let Some((Role::Model, answer)) = chat.messages.last_mut() {
+                        answer.push_str(&chunk);
+                    }
+                }
+                Ok(Err(e)) => failure = Some(e),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    chat.reply_rx = None;
+                    changed = true;
+                    break;
+                }
+            }
+            changed = true;
+        }
+        if let Some(e) = failure {
+            chat.reply_rx = None;
+            self.notify(Severity::Error, format!("Chat failed: {}", e));
+        }
+        changed
+    }
+
+    /// Inserts the code block of the latest answer at the cursor
+    pub(crate) fn insert_chat_code(&mut self) {
+        let answer = self.chat.as_ref().and_then(|chat| {
+            chat.messages
+                .iter()
+                .rev()
+                .find(|(role, _)| *role == Role::Model)
+                .map(|(_, text)| text.clone())
+        });
+        let Some(code) = answer
+            .filter(|text| text.contains("```"))
+            .and_then(|text| parse_code_output(&text).ok())
+            .map(|parsed| parsed.code)
+        else {
+            self.status_message = Some("The answer has no code to insert".to_string());
+            self.bell();
+            return;
+        };
+        let at = self.cursor_position;
+        self.apply_edit(at, at, &code);
+        self.update_syntax_tree();
+        self.cursor_position = at + code.len();
+        self.record(Action::Insert(code.clone()));
+        let line = self.point_at(at).row + 1;
+        self.audit(format!(
+            "Inserted code from the chat at line {}: {:?}",
+            line, code
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::editor::test_editor;
+    use crate::models::backend::{Backend, MockBackend};
+
+    #[test]
+    fn test_answer_streams_in_and_its_code_is_inserted() {
+        let mut editor = test_editor("fn main() {\n    \n}\n", 16);
+        let (chunk_tx, chunk_rx) = mpsc::channel(8);
+        editor.chat = Some(Chat {
+            messages: vec![
+                (Role::User, "print hello".to_string()),
+                (Role::Model, String::new()),
+            ],
+            reply_rx: Some(chunk_rx),
+        });
+        for chunk in ["Like this:\n```rust\n", "println!(\"hello\");\n```\n"] {
+            chunk_tx.try_send(Ok(chunk.to_string())).unwrap();
+        }
+        drop(chunk_tx);
+        assert!(editor.poll_chat());
+        assert!(!editor.chat.as_ref().unwrap().is_answering());
+        editor.insert_chat_code();
+        assert_eq!(editor.content, "fn main() {\n    println!(\"hello\");\n}\n");
+        assert_eq!(
+            editor.last_action,
+            Some(Action::Insert("println!(\"hello\");".to_string()))
+        );
+        assert!(editor.audit_log[0]
+            .message
+            .starts_with("Inserted code from the chat at line 2"));
+
+        let prompt = chat_prompt("test.rs", "fn main() {}", &editor.chat.unwrap().messages);
+        assert!(prompt.contains("```\nfn main() {}\n```"));
+        assert!(prompt.contains("User: print hello\n"));
+        assert!(prompt.ends_with("```\n\nAssistant: "));
+    }
+
+    #[tokio::test]
+    async fn test_excluded_buffer_is_not_sent_from_an_open_chat() {
+        let (mut editor, prediction_tx) = Editor::new("test.rs".to_string(), Config::default());
+        editor.content = "const KEY: &str = \"hunter2\";\n".to_string();
+        editor.chat = Some(Chat::default());
+        editor.encrypted = true;
+        let backend = MockBackend::default();
+        let prompts = backend.prompts.clone();
+        let predictor = Arc::new(Predictor::new(
+            Backend::new("mock", backend),
+            prediction_tx,
+            "m".into(),
+        ));
+        editor.sync_exclusion(&predictor);
+        editor.ask_chat(&predictor, "what is this".to_string());
+        let chat = editor.chat.as_mut().unwrap();
+        let reply = chat.reply_rx.as_mut().unwrap().recv().await.unwrap();
+        assert_eq!(
+            reply.unwrap_err().to_string(),
+            "this file is never sent to the model"
+        );
+        assert!(prompts.lock().unwrap().is_empty());
+    }
+}
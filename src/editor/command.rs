@@ -0,0 +1,142 @@
+use crate::config::GenerationOptions;
+use crate::editor::model_picker::ModelPicker;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use anyhow::{anyhow, bail, Result};
+
+impl Editor {
+    pub(crate) fn open_command_line(&mut self) {
+        self.prompt = Some(Prompt::new(PromptKind::Command, ":"));
+    }
+
+    /// Runs what was typed in the command line, returns true to exit
+    pub(crate) fn run_command_line(&mut self) -> bool {
+        let line = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        self.run_command(line.trim()).unwrap_or_else(|e| {
+            self.status_message = Some(e.to_string());
+            self.bell();
+            false
+        })
+    }
+
+    /// Runs one command: `w [path]`, `q`, `q!`, `wq` or `x`, `e[!] path`,
+    /// `bn`, `bp`, `bd[!]`, `set option`, `help`, `model` or a line number to go to.
+    /// Returns true to exit.
+    pub(crate) fn run_command(&mut self, line: &str) -> Result<bool> {
+        let (name, arg) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(name, arg)| (name, arg.trim()));
+        match name {
+            "" => {}
+            "w" | "write" if !arg.is_empty() => self.save_as(arg.to_string()),
+            "w" | "write" | "wq" | "x" if self.filename.is_none() => {
+                self.open_save_as_prompt();
+            }
+            "w" | "write" => self.save(),
+            "wq" | "x" => {
+                self.save();
+                // a failed save leaves the buffer open with its reason
+                return Ok(!self.modified);
+            }
+            "q" | "quit" => return Ok(self.request_quit()),
+            "q!" | "quit!" => return Ok(true),
+            "e" | "edit" | "e!" | "edit!" if arg.is_empty() => {
+                bail!("{} needs a file to open", name);
+            }
+            "e" | "edit" => self.open_in_buffer(arg.to_string())?,
+            // in place of the current buffer, throwing its changes away
+            "e!" | "edit!" => self.open_file(arg.to_string())?,
+            "bn" | "bnext" => self.cycle_buffer(true),
+            "bp" | "bprevious" => self.cycle_buffer(false),
+            "bd" | "bdelete" => self.close_buffer(false)?,
+            "bd!" | "bdelete!" => self.close_buffer(true)?,
+            "set" => self.set_option(arg)?,
+            "h" | "help" => self.show_help(),
+            "model" => self.model_picker = Some(ModelPicker::Wanted),
+            _ => {
+                let line: usize = name
+                    .parse()
+                    .map_err(|_| anyhow!("Unknown command: {}", name))?;
+                self.go_to_line(line);
+            }
+        }
+        Ok(false)
+    }
+
+    // `option`, `nooption` or `option=value`
+    fn set_option(&mut self, arg: &str) -> Result<()> {
+        let (name, value) = arg
+            .split_once('=')
+            .map_or((arg, None), |(n, v)| (n, Some(v)));
+        let (name, on) = match name.strip_prefix("no") {
+            Some(name) if value.is_none() => (name, false),
+            _ => (name, true),
+        };
+        match (name, value) {
+            ("wrap", None) => self.soft_wrap = on,
+            ("relativenumber" | "rnu", None) => self.relative_numbers = on,
+            ("whitespace" | "list", None) => self.show_whitespace = on,
+            ("autopredict", None) => {
+                self.auto_predict = on;
+                self.auto_predict_at = None;
+            }
+            (name, Some(value)) if GenerationOptions::NAMES.contains(&name) => {
+                self.config.model.options.set(name, value)?;
+                self.options_changed = true;
+            }
+            ("theme", Some(theme)) => {
+                self.theme = Theme::load(theme)?;
+                self.theme_name = theme.to_string();
+            }
+            ("", _) => bail!("set needs an option: wrap, relativenumber, whitespace, autopredict, theme= or a generation option like temperature="),
+            _ => bail!("Unknown option: {}", arg),
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor to the start of `line`, counting from 1, or the last
+    /// line past the end
+    pub(crate) fn go_to_line(&mut self, line: usize) {
+        let last = self.content.matches('\n').count();
+        self.clear_selection();
+        self.clear_carets();
+        self.goal_column = None;
+        self.cursor_position = self.line_offset(line.saturating_sub(1).min(last));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_commands_set_options_and_move() {
+        let mut editor = test_editor("one\ntwo\nthree\n", 0);
+        assert!(!editor.run_command("3").unwrap());
+        assert_eq!(editor.cursor_position, 8);
+        editor.run_command("99").unwrap();
+        assert_eq!(editor.cursor_position, 14);
+        editor.run_command("set wrap").unwrap();
+        editor.run_command("set  nornu").unwrap();
+        assert!(editor.soft_wrap && !editor.relative_numbers);
+        editor.run_command("set autopredict").unwrap();
+        assert!(editor.auto_predict);
+        editor.run_command("set temperature=0.2").unwrap();
+        editor.run_command("set seed=42").unwrap();
+        editor.run_command("set seed=").unwrap();
+        assert_eq!(editor.config.model.options.temperature, Some(0.2));
+        assert!(editor.options_changed && editor.config.model.options.seed.is_none());
+        assert!(editor.run_command("set top_p=high").is_err());
+        editor.run_command("set theme=light").unwrap();
+        assert_eq!(editor.theme_name, "light");
+        assert!(editor.run_command("set colour").is_err());
+        assert!(editor.run_command("frobnicate").is_err());
+        assert!(editor.run_command("e").is_err());
+        assert!(editor.run_command("bd").is_err());
+        editor.modified = true;
+        assert!(!editor.run_command("q").unwrap());
+        assert!(editor.prompt.is_some());
+        assert!(editor.run_command("q!").unwrap());
+    }
+}
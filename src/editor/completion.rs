@@ -0,0 +1,222 @@
+use crate::editor::theme::Theme;
+use crate::editor::{Editor, Placement};
+use crate::models::scope::PredictionScope;
+use crate::models::Predictor;
+use anyhow::{anyhow, Result};
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Position, Rect};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use unicode_width::UnicodeWidthStr;
+
+// widest the menu gets, longer candidates are cut off
+const MAX_WIDTH: usize = 60;
+
+/// Candidates being sampled for the menu, for the cursor position they were
+/// asked for at
+pub struct CompletionRequest {
+    start: usize,
+    result_rx: oneshot::Receiver<Result<Vec<String>>>,
+}
+
+/// The menu under the cursor, the selected candidate is shown as the
+/// prediction
+pub struct Completion {
+    candidates: Vec<String>,
+    selected: usize,
+}
+
+impl Completion {
+    // the first line of a candidate, with how many more there are
+    fn label(candidate: &str) -> String {
+        let mut lines = candidate.trim_start().lines();
+        let first = lines.next().unwrap_or_default().trim_end();
+        match lines.count() {
+            0 => first.to_string(),
+            more => format!("{}  (+{} more)", first, more),
+        }
+    }
+
+    /// Lists the candidates below the cell `at`, or above it when there is
+    /// more room there
+    pub fn render(&self, f: &mut Frame, area: Rect, at: Position, theme: &Theme) {
+        let labels: Vec<String> = self
+            .candidates
+            .iter()
+            .map(|candidate| Self::label(candidate))
+            .collect();
+        let widest = labels.iter().map(|label| label.width()).max().unwrap_or(0);
+        let width = (widest.min(MAX_WIDTH) as u16 + 2).min(area.width);
+        let below = area.bottom().saturating_sub(at.y + 1);
+        let above = at.y.saturating_sub(area.y);
+        let height = (labels.len() as u16 + 2).min(below.max(above));
+        let y = if height <= below {
+            at.y + 1
+        } else {
+            at.y - height
+        };
+        let x = at.x.min(area.right().saturating_sub(width));
+        let menu_area = Rect::new(x, y, width, height);
+        let lines: Vec<Line> = labels
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| {
+                let style = if i == self.selected {
+                    theme.selection
+                } else {
+                    theme.base
+                };
+                Line::from(Span::styled(label, style))
+            })
+            .collect();
+        f.render_widget(Clear, menu_area);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme.dim)
+                    .style(theme.base),
+            ),
+            menu_area,
+        );
+    }
+}
+
+impl Editor {
+    /// Samples several predictions for the rest of the line to pick one from
+    pub(crate) fn request_completions(&mut self, predictor: &Arc<Predictor>) {
+        if self.large_file() {
+            self.status_message = Some("Predictions are off for large files".to_string());
+            return;
+        }
+        self.load_symbol_index();
+        let (result_tx, result_rx) = oneshot::channel();
+        let count = self.config.model.candidates;
+        predictor.clone().candidates_background(
+            self.prediction_request(PredictionScope::Line),
+            count,
+            result_tx,
+        );
+        self.completion_request = Some(CompletionRequest {
+            start: self.cursor_position,
+            result_rx,
+        });
+        self.status_message = Some(format!("Sampling {} completions...", count));
+    }
+
+    /// Opens the menu once the candidates are in, returns true if it did
+    pub(crate) fn poll_completions(&mut self) -> bool {
+        let Some(request) = self.completion_request.as_mut() else {
+            return false;
+        };
+        let result = match request.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => Err(anyhow!("request was dropped")),
+        };
+        let Some(CompletionRequest { start, .. }) = self.completion_request.take() else {
+            return false;
+        };
+        self.status_message = None;
+        let candidates = match result {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                self.status_message = Some(format!("Completions failed: {}", e));
+                return true;
+            }
+        };
+        // they complete a line the cursor has left
+        if start != self.cursor_position {
+            return true;
+        }
+        match candidates.len() {
+            0 => self.status_message = Some("No completions".to_string()),
+            1 => {}
+            _ => {
+                self.completion = Some(Completion {
+                    candidates: candidates.clone(),
+                    selected: 0,
+                })
+            }
+        }
+        self.current_prediction = candidates.into_iter().next();
+        self.prediction_start_position = self.current_prediction.as_ref().map(|_| start);
+        true
+    }
+
+    /// Up and down pick a candidate, enter accepts it like tab and esc closes
+    /// the menu with the prediction. Other keys close the menu and go on as
+    /// usual, returns true if the key was used up.
+    pub(crate) fn handle_completion_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Up | KeyCode::Down if key.modifiers.is_empty() => {
+                self.move_completion(key.code == KeyCode::Up);
+                true
+            }
+            KeyCode::Enter => {
+                self.completion = None;
+                self.accept_prediction(Placement::Cursor);
+                true
+            }
+            KeyCode::Esc => {
+                self.completion = None;
+                self.current_prediction = None;
+                self.prediction_start_position = None;
+                true
+            }
+            _ => {
+                self.completion = None;
+                false
+            }
+        }
+    }
+
+    /// Selects the next or previous candidate and shows it as the prediction
+    pub(crate) fn move_completion(&mut self, up: bool) {
+        let Some(completion) = self.completion.as_mut() else {
+            return;
+        };
+        let count = completion.candidates.len();
+        completion.selected = if up {
+            (completion.selected + count - 1) % count
+        } else {
+            (completion.selected + 1) % count
+        };
+        self.current_prediction = Some(completion.candidates[completion.selected].clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_menu_previews_the_selected_candidate() {
+        let mut editor = test_editor("let x\n", 5);
+        let (result_tx, result_rx) = oneshot::channel();
+        editor.completion_request = Some(CompletionRequest {
+            start: 5,
+            result_rx,
+        });
+        assert!(!editor.poll_completions());
+        let candidates = vec![" = 1;".to_string(), ": u8 = 2;\nlet y = 3;".to_string()];
+        result_tx.send(Ok(candidates)).unwrap();
+        assert!(editor.poll_completions());
+        assert_eq!(editor.current_prediction.as_deref(), Some(" = 1;"));
+        editor.move_completion(true);
+        assert_eq!(
+            editor.current_prediction.as_deref(),
+            Some(": u8 = 2;\nlet y = 3;")
+        );
+        assert_eq!(
+            Completion::label(": u8 = 2;\nlet y = 3;"),
+            ": u8 = 2;  (+1 more)"
+        );
+        assert_eq!(editor.prediction_start_position, Some(5));
+    }
+}
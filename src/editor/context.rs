@@ -0,0 +1,122 @@
+use crate::editor::Editor;
+
+// share of the budget for the start of the file when the middle is left out
+const HEAD_SHARE: usize = 4;
+
+impl Editor {
+    // offsets the buffer can be cut at without splitting an item: where each
+    // top level node of the syntax tree starts, and the end of the buffer
+    fn item_starts(&self) -> Vec<usize> {
+        let Some(tree) = self.tree.as_ref().filter(|_| !self.syntax_degraded()) else {
+            return vec![self.content.len()];
+        };
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let mut starts: Vec<usize> = root
+            .children(&mut cursor)
+            .map(|node| self.line_start(node.start_byte()))
+            .collect();
+        starts.push(self.content.len());
+        starts
+    }
+
+    // the first cut in `from..=to`, going by items and then by lines
+    fn first_cut(&self, items: &[usize], from: usize, to: usize) -> usize {
+        if let Some(&start) = items.iter().find(|&&p| p >= from && p <= to) {
+            return start;
+        }
+        if from == 0 || self.content.as_bytes()[from - 1] == b'\n' {
+            return from;
+        }
+        self.content[from..to]
+            .find('\n')
+            .map_or(from, |p| from + p + 1)
+    }
+
+    // the last cut in `from..=to`, going by items and then by lines
+    fn last_cut(&self, items: &[usize], from: usize, to: usize) -> usize {
+        if let Some(&start) = items.iter().rev().find(|&&p| p >= from && p <= to) {
+            return start;
+        }
+        self.content[from..to]
+            .rfind('\n')
+            .map_or(to, |p| from + p + 1)
+    }
+
+    fn char_boundary(&self, mut pos: usize, forward: bool) -> usize {
+        while !self.content.is_char_boundary(pos) {
+            if forward {
+                pos += 1;
+            } else {
+                pos -= 1;
+            }
+        }
+        pos
+    }
+
+    /// The code before `at` in at most `budget` bytes. When the whole of it
+    /// doesn't fit, the start of the file, where the imports and types are,
+    /// is kept along with the code leading up to `at`, and the middle is left
+    /// out between top level items, or between lines inside a long one.
+    pub(crate) fn context_before(&self, at: usize, budget: usize) -> String {
+        if at <= budget {
+            return self.content[..at].to_string();
+        }
+        let marker = format!("{} ...\n", self.language.comment_prefix());
+        let items = self.item_starts();
+        let head_budget = budget.saturating_sub(marker.len()) / HEAD_SHARE;
+        let head_end = self.last_cut(&items, 0, self.char_boundary(head_budget, false));
+        let tail_budget = budget.saturating_sub(head_end + marker.len());
+        let tail_start = self.char_boundary(at - tail_budget, true);
+        let tail_start = self.first_cut(&items, tail_start, at);
+        if head_end == 0 {
+            return self.content[tail_start..at].to_string();
+        }
+        format!(
+            "{}{}{}",
+            &self.content[..head_end],
+            marker,
+            &self.content[tail_start..at]
+        )
+    }
+
+    /// The code after `at` in at most `budget` bytes, cut at the end of an
+    /// item or a line when it doesn't all fit
+    pub(crate) fn context_after(&self, at: usize, budget: usize) -> String {
+        if self.content.len() - at <= budget {
+            return self.content[at..].to_string();
+        }
+        let end = self.char_boundary(at + budget, false);
+        let end = self.last_cut(&self.item_starts(), at, end);
+        self.content[at..end].to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_middle_items_are_left_out_when_the_file_is_too_long() {
+        let head = "use std::fmt;\n\n";
+        let filler: String = (0..20)
+            .map(|i| format!("fn f{}() {{\n    let x = {};\n}}\n\n", i, i))
+            .collect();
+        let tail = "fn last() {\n    let y = ";
+        let editor = test_editor(&format!("{}{}{}", head, filler, tail), 0);
+        let at = editor.content.len();
+        assert_eq!(editor.context_before(at, 10_000), editor.content);
+
+        let context = editor.context_before(at, 120);
+        assert!(context.len() <= 120);
+        assert!(context.starts_with("use std::fmt;\n\n// ...\n"));
+        assert!(context.ends_with("fn f19() {\n    let x = 19;\n}\n\nfn last() {\n    let y = "));
+        assert!(!context.contains("f0"));
+
+        let after = editor.context_after(head.len(), 70);
+        assert_eq!(
+            after,
+            "fn f0() {\n    let x = 0;\n}\n\nfn f1() {\n    let x = 1;\n}\n\n"
+        );
+    }
+}
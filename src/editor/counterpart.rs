@@ -0,0 +1,158 @@
+use crate::editor::languages::Language;
+use crate::editor::tasks::project_root;
+use crate::editor::Editor;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+// end of the braced block opened after `from`, or the end of the source
+fn block_end(source: &str, from: usize) -> usize {
+    let mut depth = 0;
+    for (offset, c) in source[from..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    return from + offset + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    source.len()
+}
+
+// `tests/foo.rs` for `src/foo.rs`, and the other way around
+fn counterpart(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let root = project_root(path, "Cargo.toml");
+    let in_tests = path.components().any(|c| c.as_os_str() == "tests");
+    let dir = if in_tests { "src" } else { "tests" };
+    Some(root.join(dir).join(format!("{}.rs", stem)))
+}
+
+impl Editor {
+    /// Test modules and blocks in the buffer, from their marker line to the
+    /// end of their block
+    fn test_blocks(&self) -> Vec<Range<usize>> {
+        let marker = self.language.test_marker();
+        let mut blocks = Vec::new();
+        let mut line_start = 0;
+        for line in self.content.split_inclusive('\n') {
+            if line.trim_start().starts_with(marker) {
+                blocks.push(line_start..block_end(&self.content, line_start));
+            }
+            line_start += line.len();
+        }
+        blocks
+    }
+
+    /// Jumps between code and its tests. Inside a test the cursor goes back to
+    /// where it came from, otherwise to the next test in the file. Rust files
+    /// without unit tests switch to their `tests/` file, and a test module is
+    /// added when there is neither.
+    pub(crate) fn toggle_test(&mut self) {
+        let path = self.filename.as_ref().map(PathBuf::from);
+        let counterpart = path
+            .as_deref()
+            .filter(|_| self.language == Language::Rust)
+            .and_then(counterpart)
+            .filter(|p| p.exists());
+        let in_tests_dir = path
+            .as_deref()
+            .is_some_and(|p| p.components().any(|c| c.as_os_str() == "tests"));
+        if let Some(source) = counterpart.as_ref().filter(|_| in_tests_dir) {
+            self.switch_file(source);
+            return;
+        }
+
+        let pos = self.cursor_position;
+        let blocks = self.test_blocks();
+        if blocks.iter().any(|block| block.contains(&pos)) {
+            let mut back = self.test_return.take().unwrap_or(0).min(self.content.len());
+            while !self.content.is_char_boundary(back) {
+                back -= 1;
+            }
+            self.cursor_position = back;
+            return;
+        }
+        if let Some(block) = blocks.iter().find(|b| b.start > pos).or(blocks.first()) {
+            self.test_return = Some(pos);
+            self.cursor_position = block.start;
+            return;
+        }
+        match counterpart {
+            Some(tests) => self.switch_file(&tests),
+            None => self.append_test_template(),
+        }
+    }
+
+    fn append_test_template(&mut self) {
+        let name: String = self
+            .filename
+            .as_deref()
+            .and_then(|p| Path::new(p).file_stem()?.to_str())
+            .unwrap_or("it")
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let mut text = self.language.test_template().replace("{{name}}", &name);
+        if !self.content.is_empty() && !self.content.ends_with('\n') {
+            text.insert(0, '\n');
+        }
+        let cursor = text.find("{{cursor}}").unwrap_or(text.len());
+        let text = text.replace("{{cursor}}", "");
+        let end = self.content.len();
+        self.test_return = Some(self.cursor_position);
+        self.apply_edit(end, end, &text);
+        self.cursor_position = end + cursor;
+        self.update_syntax_tree();
+        self.status_message = Some("Added tests".to_string());
+    }
+
+    // replaces the buffer with another file, refusing while there are unsaved edits
+    fn switch_file(&mut self, path: &Path) {
+        if self.modified {
+            self.status_message = Some(format!("Save before switching to {}", path.display()));
+            self.bell();
+            return;
+        }
+        let path = path.to_string_lossy().to_string();
+        self.selection = None;
+        self.current_prediction = None;
+        self.prediction_start_position = None;
+        self.test_return = None;
+        if let Err(e) = self.open_file(path.clone()) {
+            self.status_message = Some(format!("Failed to open {}: {}", path, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::Editor;
+
+    #[test]
+    fn test_adds_tests_then_toggles_back() {
+        let (mut editor, _) = Editor::new("lib.rs".to_string(), Config::default());
+        editor.content = "fn add() {}".to_string();
+        editor.cursor_position = 3;
+        editor.update_syntax_tree();
+        editor.toggle_test();
+        assert!(editor
+            .content
+            .starts_with("fn add() {}\n\n#[cfg(test)]\nmod tests {"));
+        assert_eq!(
+            editor.content[editor.cursor_position..].lines().next(),
+            Some("")
+        );
+        editor.toggle_test();
+        assert_eq!(editor.cursor_position, 3);
+        editor.toggle_test();
+        assert_eq!(
+            editor.cursor_position,
+            editor.content.find("#[cfg").unwrap()
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::editor::popup::Popup;
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::ops::Range;
+
+// larger changed regions are replaced wholesale instead of diffed
+const MAX_DIFF_CELLS: usize = 4_000_000;
+// unchanged lines shown around each change in a unified diff
+const CONTEXT_LINES: usize = 3;
+
+/// A run of `old` replaced by a run of `new`, either of which can be empty
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// The changes that turn `old` into `new`, in order, using Myers' algorithm
+/// on what is left between their common prefix and suffix
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return Vec::new();
+    }
+    let whole = || {
+        vec![Hunk {
+            old: 0..old_mid.len(),
+            new: 0..new_mid.len(),
+        }]
+    };
+    let hunks = match matching_pairs(old_mid, new_mid) {
+        Some(pairs) => hunks_between(&pairs, old_mid.len(), new_mid.len()),
+        None => whole(),
+    };
+    hunks
+        .into_iter()
+        .map(|hunk| Hunk {
+            old: hunk.old.start + prefix..hunk.old.end + prefix,
+            new: hunk.new.start + prefix..hunk.new.end + prefix,
+        })
+        .collect()
+}
+
+// indices of the items kept from `old` to `new`, None if finding them would
+// take more than MAX_DIFF_CELLS steps
+fn matching_pairs<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m + 1;
+    let at = |k: isize| (k + offset) as usize;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    'search: for d in 0..=n + m {
+        if d as usize * (n + m) as usize > MAX_DIFF_CELLS {
+            return None;
+        }
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    // walk back from the end through the furthest points of each round
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let (d, k) = (d as isize, x - y);
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[at(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    pairs.reverse();
+    Some(pairs)
+}
+
+fn hunks_between(pairs: &[(usize, usize)], old_len: usize, new_len: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for &(x, y) in pairs.iter().chain([(old_len, new_len)].iter()) {
+        if x > i || y > j {
+            hunks.push(Hunk {
+                old: i..x,
+                new: j..y,
+            });
+        }
+        (i, j) = (x + 1, y + 1);
+    }
+    hunks
+}
+
+// words, runs of whitespace and single punctuation characters, so a changed
+// argument highlights the argument rather than the whole line
+fn words(text: &str) -> Vec<Range<usize>> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut prev = None;
+    for (idx, c) in text.char_indices() {
+        let end = idx + c.len_utf8();
+        match words.last_mut() {
+            Some(word) if prev == Some(class(c)) && class(c) != 2 => word.end = end,
+            _ => words.push(idx..end),
+        }
+        prev = Some(class(c));
+    }
+    words
+}
+
+// the bytes covered by a range of words
+fn byte_range(words: &[Range<usize>], range: Range<usize>, len: usize) -> Range<usize> {
+    match words.get(range.start) {
+        Some(first) if !range.is_empty() => first.start..words[range.end - 1].end,
+        Some(first) => first.start..first.start,
+        None => len..len,
+    }
+}
+
+/// Changes between two lines word by word, as byte ranges into each line
+pub fn word_diff(old: &str, new: &str) -> Vec<Hunk> {
+    let (old_words, new_words) = (words(old), words(new));
+    let old_text: Vec<&str> = old_words.iter().map(|w| &old[w.clone()]).collect();
+    let new_text: Vec<&str> = new_words.iter().map(|w| &new[w.clone()]).collect();
+    diff(&old_text, &new_text)
+        .into_iter()
+        .map(|hunk| Hunk {
+            old: byte_range(&old_words, hunk.old, old.len()),
+            new: byte_range(&new_words, hunk.new, new.len()),
+        })
+        .collect()
+}
+
+/// The text `new` adds to `old` with the byte offset in `old` it goes at, or
+/// None if `new` also drops or changes some of `old`
+pub fn insertions<'a>(old: &str, new: &'a str) -> Option<Vec<(usize, &'a str)>> {
+    let old_chars: Vec<(usize, char)> = old.char_indices().collect();
+    let new_chars: Vec<(usize, char)> = new.char_indices().collect();
+    let byte = |chars: &[(usize, char)], idx: usize, len: usize| {
+        chars.get(idx).map_or(len, |&(pos, _)| pos)
+    };
+    let old_text: Vec<char> = old_chars.iter().map(|&(_, c)| c).collect();
+    let new_text: Vec<char> = new_chars.iter().map(|&(_, c)| c).collect();
+    diff(&old_text, &new_text)
+        .into_iter()
+        .map(|hunk| {
+            let at = byte(&old_chars, hunk.old.start, old.len());
+            let start = byte(&new_chars, hunk.new.start, new.len());
+            let end = byte(&new_chars, hunk.new.end, new.len());
+            hunk.old.is_empty().then_some((at, &new[start..end]))
+        })
+        .collect()
+}
+
+// a changed line with the words that differ from its counterpart highlighted
+fn changed_line(sign: &str, line: &str, changed: &[Range<usize>], style: Style) -> Line<'static> {
+    let mut spans = vec![Span::styled(sign.to_string(), style)];
+    let mut pos = 0;
+    for range in changed.iter().filter(|range| !range.is_empty()) {
+        spans.push(Span::styled(line[pos..range.start].to_string(), style));
+        spans.push(Span::styled(
+            line[range.clone()].to_string(),
+            style.add_modifier(Modifier::REVERSED),
+        ));
+        pos = range.end;
+    }
+    spans.push(Span::styled(line[pos..].to_string(), style));
+    Line::from(spans)
+}
+
+/// A unified diff of two texts' lines. Lines that were edited rather than
+/// added or removed have the changed words highlighted.
+pub fn unified(old: &[&str], new: &[&str], theme: &Theme) -> Vec<Line<'static>> {
+    let hunks = diff(old, new);
+    let mut lines = Vec::new();
+    let mut idx = 0;
+    while idx < hunks.len() {
+        // hunks close enough to share their context are shown together
+        let mut last = idx;
+        while hunks
+            .get(last + 1)
+            .is_some_and(|next| next.old.start - hunks[last].old.end <= 2 * CONTEXT_LINES)
+        {
+            last += 1;
+        }
+        let old_start = hunks[idx].old.start.saturating_sub(CONTEXT_LINES);
+        let new_start = hunks[idx].new.start - (hunks[idx].old.start - old_start);
+        let old_end = (hunks[last].old.end + CONTEXT_LINES).min(old.len());
+        let new_end = hunks[last].new.end + (old_end - hunks[last].old.end);
+        lines.push(Line::styled(
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old_start + 1,
+                old_end - old_start,
+                new_start + 1,
+                new_end - new_start
+            ),
+            theme.dim,
+        ));
+        let mut pos = old_start;
+        for hunk in &hunks[idx..=last] {
+            for line in &old[pos..hunk.old.start] {
+                lines.push(Line::from(format!(" {}", line)));
+            }
+            let paired = hunk.old.len().min(hunk.new.len());
+            let word_hunks: Vec<Vec<Hunk>> = (0..paired)
+                .map(|n| word_diff(old[hunk.old.start + n], new[hunk.new.start + n]))
+                .collect();
+            for (n, line) in old[hunk.old.clone()].iter().enumerate() {
+                let changed: Vec<_> = word_hunks
+                    .get(n)
+                    .map(|hunks| hunks.iter().map(|h| h.old.clone()).collect())
+                    .unwrap_or_default();
+                lines.push(changed_line("-", line, &changed, theme.removed));
+            }
+            for (n, line) in new[hunk.new.clone()].iter().enumerate() {
+                let changed: Vec<_> = word_hunks
+                    .get(n)
+                    .map(|hunks| hunks.iter().map(|h| h.new.clone()).collect())
+                    .unwrap_or_default();
+                lines.push(changed_line("+", line, &changed, theme.added));
+            }
+            pos = hunk.old.end;
+        }
+        for line in &old[pos..old_end] {
+            lines.push(Line::from(format!(" {}", line)));
+        }
+        idx = last + 1;
+    }
+    lines
+}
+
+impl Editor {
+    /// Shows how the buffer differs from the last commit
+    pub(crate) fn show_git_diff(&mut self) {
+        let Some(base) = &self.git_base else {
+            self.status_message = Some("No committed version to compare with".to_string());
+            self.bell();
+            return;
+        };
+        let old: Vec<&str> = base
+            .iter()
+            .map(|line| line.trim_end_matches('\r'))
+            .collect();
+        let new: Vec<&str> = self.content.split('\n').collect();
+        let lines = unified(&old, &new, &self.theme);
+        if lines.is_empty() {
+            self.status_message = Some("No changes since the last commit".to_string());
+            return;
+        }
+        self.popup = Some(Popup::new("Changes since the last commit", lines));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diffs_lines_and_words() {
+        let old = ["a", "b", "c", "d", "e"];
+        let hunks = diff(&old, &["a", "c", "x", "y", "e"]);
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    old: 1..2,
+                    new: 1..1
+                },
+                Hunk {
+                    old: 3..4,
+                    new: 2..4
+                },
+            ]
+        );
+        let hunks = word_diff("foo(a, b)", "foo(a, c)");
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                old: 7..8,
+                new: 7..8
+            }]
+        );
+        assert_eq!(
+            insertions("foo(bar)", "foo(bar, baz)"),
+            Some(vec![(7, ", baz")])
+        );
+        assert_eq!(insertions("let x = 1", "let y = 1"), None);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::editor::prediction::BYTES_PER_TOKEN;
+use crate::editor::rewrite::RewriteKind;
+use crate::editor::Editor;
+use crate::models::Predictor;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+impl Editor {
+    /// Asks the model for a doc comment on the item the cursor is in, or on
+    /// the file outside of one, shown as a diff to add or drop
+    pub(crate) fn generate_doc_comment(&mut self, predictor: &Arc<Predictor>) {
+        let item = self.enclosing_scope(|label| !label.starts_with("test"));
+        let (what, code, range, module) = match item {
+            Some((label, range)) => {
+                let start = self.line_start(range.start);
+                let code = self.content[start..range.end].to_string();
+                (format!("`{}`", label), code, start..range.end, false)
+            }
+            None => {
+                let budget = self.config.model.context_tokens() * BYTES_PER_TOKEN;
+                let code = self.context_after(0, budget);
+                // the comment goes above the first line
+                (
+                    "the whole file".to_string(),
+                    code,
+                    0..self.line_end(0),
+                    true,
+                )
+            }
+        };
+        let (result_tx, result_rx) = oneshot::channel();
+        predictor
+            .clone()
+            .document_background(code, what, self.language.name(), result_tx);
+        let prefix = self.language.doc_prefix(module);
+        self.await_rewrite(range, RewriteKind::Doc { prefix }, result_rx);
+        self.status_message = Some("Writing the doc comment...".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::Editor;
+    use crate::models::backend::{Backend, MockBackend};
+    use crate::models::Predictor;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_doc_comment_goes_above_the_item() {
+        let (mut editor, prediction_tx) = Editor::new("test.rs".to_string(), Config::default());
+        editor.content =
+            "impl Point {\n    fn norm(&self) -> f64 {\n        self.x.hypot(self.y)\n    }\n}\n"
+                .to_string();
+        editor.cursor_position = 50;
+        editor.update_syntax_tree();
+        let backend = MockBackend::answering(&["/// Distance from the origin.\n\nNever negative."]);
+        let predictor = Arc::new(Predictor::new(
+            Backend::new("mock", backend),
+            prediction_tx,
+            "m".into(),
+        ));
+        editor.generate_doc_comment(&predictor);
+        while !editor.poll_rewrite() {
+            tokio::task::yield_now().await;
+        }
+        assert!(editor.rewrite_ready());
+        editor.handle_rewrite_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            editor.content,
+            "impl Point {\n    /// Distance from the origin.\n    ///\n    /// Never negative.\n    fn norm(&self) -> f64 {\n        self.x.hypot(self.y)\n    }\n}\n"
+        );
+        assert_eq!(editor.cursor_position, 13);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use anyhow::{anyhow, Result};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// The encoding a file is read and written in, the buffer itself is UTF-8
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    /// The file starts with a byte order mark, which is written back on save
+    pub bom: bool,
+}
+
+impl Default for FileEncoding {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+// UTF-16 without a byte order mark shows up as every other byte being zero
+// in mostly ASCII text, this picks the byte order from which ones are
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.is_empty() {
+        return None;
+    }
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let half = sample.len() / 2;
+    match (zeros(0), zeros(1)) {
+        (even, odd) if odd * 10 > half * 7 && even * 10 < half => Some(UTF_16LE),
+        (even, odd) if even * 10 > half * 7 && odd * 10 < half => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+impl FileEncoding {
+    /// Decodes a file, going by its byte order mark, whether it looks like
+    /// UTF-16 or is valid UTF-8, and otherwise reading it as Windows-1252
+    /// (a superset of latin-1), which accepts any bytes
+    pub fn decode(bytes: Vec<u8>) -> (String, Self) {
+        if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+            let (text, encoding) = Self::decode_as(encoding, &bytes[bom_len..]);
+            return (
+                text,
+                Self {
+                    bom: true,
+                    ..encoding
+                },
+            );
+        }
+        // the zeros of UTF-16 are valid UTF-8, so it's checked for first
+        let encoding = match sniff_utf16(&bytes) {
+            Some(encoding) => encoding,
+            None => match String::from_utf8(bytes) {
+                Ok(text) => return (text, Self::default()),
+                Err(e) => return Self::decode_as(WINDOWS_1252, &e.into_bytes()),
+            },
+        };
+        Self::decode_as(encoding, &bytes)
+    }
+
+    fn decode_as(encoding: &'static Encoding, bytes: &[u8]) -> (String, Self) {
+        let (text, _) = encoding.decode_without_bom_handling(bytes);
+        (
+            text.into_owned(),
+            Self {
+                encoding,
+                bom: false,
+            },
+        )
+    }
+
+    /// `text` in this encoding, failing if it has characters the encoding
+    /// can't represent
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(text.len());
+        if self.bom {
+            bytes.extend_from_slice(match self.encoding {
+                e if e == UTF_16LE => &[0xFF, 0xFE],
+                e if e == UTF_16BE => &[0xFE, 0xFF],
+                _ => &[0xEF, 0xBB, 0xBF],
+            });
+        }
+        // encoding_rs only encodes to the encodings web pages can be sent in,
+        // which leaves out UTF-16
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            for unit in text.encode_utf16() {
+                let unit = if self.encoding == UTF_16LE {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                };
+                bytes.extend_from_slice(&unit);
+            }
+            return Ok(bytes);
+        }
+        let (encoded, _, unmappable) = self.encoding.encode(text);
+        if unmappable {
+            return Err(anyhow!(
+                "the buffer has characters {} can't represent, change the encoding with alt+shift+e",
+                self.encoding.name()
+            ));
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+
+    /// Shown in the title for anything but plain UTF-8
+    pub fn label(&self) -> Option<String> {
+        match (self.encoding == UTF_8, self.bom) {
+            (true, false) => None,
+            (true, true) => Some("UTF-8 BOM".to_string()),
+            (false, _) => Some(self.encoding.name().to_string()),
+        }
+    }
+}
+
+impl Editor {
+    pub(crate) fn open_encoding_prompt(&mut self) {
+        self.prompt = Some(Prompt::new(
+            PromptKind::Encoding,
+            &format!(
+                "Save as encoding (now {}, e.g. utf-8, utf-16le, latin1, shift_jis): ",
+                self.file_encoding.encoding.name()
+            ),
+        ));
+    }
+
+    /// Changes the encoding the file is written in on the next save
+    pub(crate) fn confirm_encoding(&mut self) {
+        let label = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let Some(encoding) = Encoding::for_label_no_replacement(label.trim().as_bytes()) else {
+            self.status_message = Some(format!("Unknown encoding {}", label.trim()));
+            self.bell();
+            return;
+        };
+        // a byte order mark is how UTF-16 files are usually recognised
+        let bom = encoding == UTF_16LE || encoding == UTF_16BE;
+        self.file_encoding = FileEncoding { encoding, bom };
+        self.modified = true;
+        self.status_message = Some(format!(
+            "Encoding is now {}, ctrl+s writes it",
+            encoding.name()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_latin1_and_utf16() {
+        let (text, encoding) = FileEncoding::decode(b"caf\xe9\n".to_vec());
+        assert_eq!(text, "café\n");
+        assert_eq!(encoding.encoding, WINDOWS_1252);
+        assert_eq!(encoding.encode(&text).unwrap(), b"caf\xe9\n");
+        assert!(encoding.encode("→").is_err());
+        let utf16 = [0xFF, 0xFE, b'h', 0, b'i', 0];
+        let (text, encoding) = FileEncoding::decode(utf16.to_vec());
+        assert_eq!(text, "hi");
+        assert_eq!(encoding.encode(&text).unwrap(), utf16);
+        let (text, encoding) = FileEncoding::decode(vec![0, b'h', 0, b'i']);
+        assert_eq!((text.as_str(), encoding.encoding), ("hi", UTF_16BE));
+    }
+}
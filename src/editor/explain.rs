@@ -0,0 +1,65 @@
+use crate::editor::Editor;
+use crate::models::Predictor;
+use std::sync::Arc;
+
+impl Editor {
+    /// Has the model explain the selection, or the function around the
+    /// cursor, in the chat panel. The buffer is left alone.
+    pub(crate) fn explain_code(&mut self, predictor: &Arc<Predictor>) {
+        let selected = self
+            .selection_range()
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| ("the selected code".to_string(), start..end));
+        let Some((what, range)) = selected.or_else(|| {
+            self.enclosing_scope(|label| label.starts_with("fn "))
+                .map(|(label, range)| (format!("`{}`", label), range))
+        }) else {
+            self.status_message =
+                Some("Select some code or move into a function to explain it".to_string());
+            self.bell();
+            return;
+        };
+        let question = format!(
+            "Explain what {} does, briefly and without rewriting it:\n```{}\n{}\n```",
+            what,
+            self.language.name(),
+            &self.content[range]
+        );
+        self.ask_chat(predictor, question);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::Editor;
+    use crate::models::backend::{Backend, MockBackend};
+    use crate::models::Predictor;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_explains_the_function_around_the_cursor() {
+        let (mut editor, prediction_tx) = Editor::new("test.rs".to_string(), Config::default());
+        let content = "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\nfn main() {}\n";
+        editor.content = content.to_string();
+        editor.cursor_position = 38;
+        editor.update_syntax_tree();
+        let backend = MockBackend::answering(&["It adds", " two numbers."]);
+        let prompts = backend.prompts.clone();
+        let predictor = Arc::new(Predictor::new(
+            Backend::new("mock", backend),
+            prediction_tx,
+            "m".into(),
+        ));
+        editor.explain_code(&predictor);
+        while editor.chat.as_ref().unwrap().is_answering() {
+            editor.poll_chat();
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(editor.content, content);
+        let prompt = prompts.lock().unwrap()[0].clone();
+        assert!(prompt.contains("Explain what `fn add` does"));
+        assert!(prompt.contains("```rust\nfn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n```"));
+        assert!(!prompt.contains("```rust\nfn main"));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::editor::languages::Language;
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use anyhow::{anyhow, Result};
+use ratatui::style::{Color, Modifier, Style};
+use tree_sitter::{Parser, Tree};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Ansi,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Ansi => "ansi",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Highlights `content` with the grammar for `language` in `theme`'s colours,
+/// used by `nars highlight`
+pub fn export_source(
+    content: &str,
+    language: Language,
+    format: ExportFormat,
+    theme: &Theme,
+) -> Result<String> {
+    let mut parser = Parser::new();
+    parser.set_language(language.grammar())?;
+    let tree = parser
+        .parse(content, None)
+        .ok_or(anyhow!("Failed to parse {} source", language.name()))?;
+    Ok(render(content, &tree, format, language.name(), theme))
+}
+
+impl Editor {
+    /// Writes the highlighted buffer next to the file, e.g. `main.rs.html`,
+    /// in the colours of the current theme
+    pub(crate) fn export(&self, format: ExportFormat) -> Result<String> {
+        let tree = self
+            .tree
+            .as_ref()
+            .ok_or(anyhow!("No syntax tree to export"))?;
+        let path = format!(
+            "{}.{}",
+            self.filename.as_deref().unwrap_or("untitled"),
+            format.extension()
+        );
+        let title = self.filename.as_deref().unwrap_or("untitled");
+        std::fs::write(
+            &path,
+            render(&self.content, tree, format, title, &self.theme),
+        )?;
+        Ok(path)
+    }
+}
+
+fn render(content: &str, tree: &Tree, format: ExportFormat, title: &str, theme: &Theme) -> String {
+    let lines = styled_lines(content, tree, theme);
+    match format {
+        ExportFormat::Ansi => {
+            let mut out = String::new();
+            for line in lines {
+                for (text, style) in line {
+                    match ansi_codes(style) {
+                        Some(codes) => out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes, text)),
+                        None => out.push_str(&text),
+                    }
+                }
+                out.push('\n');
+            }
+            out
+        }
+        ExportFormat::Html => {
+            // the page is coloured like the panes
+            let (r, g, b) = rgb(theme.base.bg.unwrap_or(Color::Black));
+            let background = format!("#{:02x}{:02x}{:02x}", r, g, b);
+            let (r, g, b) = rgb(theme.base.fg.unwrap_or(Color::White));
+            let foreground = format!("#{:02x}{:02x}{:02x}", r, g, b);
+            let mut out = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body style=\"background:{};color:{}\">\n<pre>\n",
+                escape_html(title),
+                background,
+                foreground
+            );
+            for line in lines {
+                for (text, style) in line {
+                    match css(style) {
+                        Some(css) => out.push_str(&format!(
+                            "<span style=\"{}\">{}</span>",
+                            css,
+                            escape_html(&text)
+                        )),
+                        None => out.push_str(&escape_html(&text)),
+                    }
+                }
+                out.push('\n');
+            }
+            out.push_str("</pre>\n</body>\n</html>\n");
+            out
+        }
+    }
+}
+
+// splits every line into runs of text using the same styles as the editor view
+fn styled_lines(content: &str, tree: &Tree, theme: &Theme) -> Vec<Vec<(String, Style)>> {
+    let root = tree.root_node();
+    let mut result = Vec::new();
+    let mut line_start = 0;
+    for line in content.split('\n') {
+        let line_end = line_start + line.len();
+        let mut style_spans = Vec::new();
+        let mut cursor = root.walk();
+        Editor::visit_tree_syntax(line_start, line_end, &mut style_spans, &mut cursor, theme);
+        style_spans.sort_by_key(|&(start, _, _)| start);
+
+        let mut segments = Vec::new();
+        let mut current_pos = line_start;
+        for (start, end, style) in style_spans {
+            if start > current_pos {
+                segments.push((content[current_pos..start].to_string(), Style::default()));
+            }
+            if start >= current_pos {
+                segments.push((content[start..end].to_string(), style));
+                current_pos = end;
+            }
+        }
+        if current_pos < line_end {
+            segments.push((content[current_pos..line_end].to_string(), Style::default()));
+        }
+        result.push(segments);
+        line_start = line_end + 1;
+    }
+    result
+}
+
+fn ansi_codes(style: Style) -> Option<String> {
+    let mut codes = Vec::new();
+    if style.add_modifier.contains(Modifier::BOLD) {
+        codes.push("1".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        codes.push("3".to_string());
+    }
+    if let Some(color) = style.fg {
+        let (r, g, b) = rgb(color);
+        codes.push(format!("38;2;{};{};{}", r, g, b));
+    }
+    (!codes.is_empty()).then(|| codes.join(";"))
+}
+
+fn css(style: Style) -> Option<String> {
+    let mut rules = Vec::new();
+    if let Some(color) = style.fg {
+        let (r, g, b) = rgb(color);
+        rules.push(format!("color:#{:02x}{:02x}{:02x}", r, g, b));
+    }
+    if style.add_modifier.contains(Modifier::BOLD) {
+        rules.push("font-weight:bold".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        rules.push("font-style:italic".to_string());
+    }
+    (!rules.is_empty()).then(|| rules.join(";"))
+}
+
+// approximates the terminal palette so exports look the same everywhere
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 49, 49),
+        Color::Green => (13, 188, 121),
+        Color::Yellow => (229, 229, 16),
+        Color::Blue => (36, 114, 200),
+        Color::Magenta => (188, 63, 188),
+        Color::Cyan => (17, 168, 205),
+        Color::Gray => (204, 204, 204),
+        Color::DarkGray => (118, 118, 118),
+        Color::LightRed => (241, 76, 76),
+        Color::LightGreen => (35, 209, 139),
+        Color::LightYellow => (245, 245, 67),
+        Color::LightBlue => (59, 142, 234),
+        Color::LightMagenta => (214, 112, 214),
+        Color::LightCyan => (41, 184, 219),
+        _ => (255, 255, 255),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_styles_become_codes_and_css() {
+        let style = Style::default()
+            .fg(Color::Rgb(1, 2, 3))
+            .add_modifier(Modifier::BOLD);
+        assert_eq!(ansi_codes(style).unwrap(), "1;38;2;1;2;3");
+        assert_eq!(css(style).unwrap(), "color:#010203;font-weight:bold");
+        assert!(ansi_codes(Style::default()).is_none());
+        assert!(css(Style::default()).is_none());
+        assert_eq!(
+            escape_html("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_exports_every_line() {
+        let theme = Theme::default();
+        let source = "fn main() {\n    let x = a < b;\n}";
+        let html = export_source(source, Language::Rust, ExportFormat::Html, &theme).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;") && !html.contains(" < "));
+        assert!(html.contains("<span style=\""));
+        let ansi = export_source(source, Language::Rust, ExportFormat::Ansi, &theme).unwrap();
+        assert_eq!(ansi.lines().count(), 3);
+        assert!(ansi.contains("\x1b["));
+        let plain: String = ansi
+            .split('\x1b')
+            .map(|part| part.split_once('m').map_or(part, |(_, text)| text))
+            .collect();
+        assert_eq!(plain, format!("{}\n", source));
+    }
+}
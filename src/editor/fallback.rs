@@ -0,0 +1,143 @@
+use crate::editor::Editor;
+use crate::logger::log_to_file;
+use ratatui::text::Span;
+use regex::Regex;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+// how long to wait before reparsing a file the parser gave up on, doubled
+// after every retry that fails too
+const PARSE_RETRY_DELAY: Duration = Duration::from_secs(1);
+// retries before waiting for an edit to parse again
+const MAX_PARSE_RETRIES: u32 = 4;
+
+fn token_regex() -> &'static Regex {
+    static TOKENS: OnceLock<Regex> = OnceLock::new();
+    TOKENS.get_or_init(|| {
+        Regex::new(
+            r#"(?P<comment>//.*$)|(?P<string>"(?:\\.|[^"\\])*"?)|(?P<number>\b\d[\w.]*)|(?P<word>[A-Za-z_]\w*)"#,
+        )
+        .expect("invalid fallback token regex")
+    })
+}
+
+impl Editor {
+    /// True for buffers over `render.large_file_mb`. They are never parsed,
+    /// so they are highlighted line by line, and work that looks at the whole
+    /// file on every keystroke or frame (the git gutter, annotations and
+    /// predictions) is skipped.
+    pub(crate) fn large_file(&self) -> bool {
+        self.content.len() as u64 > self.config.render.large_file_mb * 1024 * 1024
+    }
+
+    /// True when the syntax tree can't be trusted for highlighting, either
+    /// because the parser gave up or nothing in the file parsed
+    pub(crate) fn syntax_degraded(&self) -> bool {
+        match &self.tree {
+            Some(tree) => tree.root_node().is_error(),
+            None => !self.content.is_empty(),
+        }
+    }
+
+    /// Reparses from scratch once the retry delay after a failed parse is up
+    pub(crate) fn retry_parse_if_due(&mut self) -> bool {
+        match self.parse_retry_at {
+            Some(at) if Instant::now() >= at => {
+                self.parse_syntax_tree();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Backs off after each failed parse of the same text, giving up until
+    /// the next edit once the retries run out
+    pub(crate) fn schedule_parse_retry(&mut self) {
+        if self.parse_retries >= MAX_PARSE_RETRIES {
+            log_to_file("Parser keeps failing, waiting for an edit to retry");
+            self.parse_retry_at = None;
+            return;
+        }
+        let delay = PARSE_RETRY_DELAY * 2u32.pow(self.parse_retries);
+        self.parse_retries += 1;
+        self.parse_retry_at = Some(Instant::now() + delay);
+    }
+
+    /// Regex based highlighting for a single line, used while the grammar
+    /// can't produce a usable tree
+    pub(crate) fn fallback_spans(&self, line: &str) -> Vec<Span<'static>> {
+        let keywords = self.language.keywords();
+        let mut spans = Vec::new();
+        let mut current_pos = 0;
+        for caps in token_regex().captures_iter(line) {
+            let Some(m) = caps.get(0) else {
+                continue;
+            };
+            let style = if caps.name("comment").is_some() {
+                Some(self.theme.comment)
+            } else if caps.name("string").is_some() {
+                Some(self.theme.string)
+            } else if caps.name("number").is_some() {
+                Some(self.theme.number)
+            } else if keywords.contains(&m.as_str()) {
+                Some(self.theme.keyword)
+            } else if m.as_str().starts_with(char::is_uppercase) {
+                Some(self.theme.type_name)
+            } else {
+                None
+            };
+            if let Some(style) = style {
+                if m.start() > current_pos {
+                    spans.push(Span::raw(line[current_pos..m.start()].to_string()));
+                }
+                spans.push(Span::styled(m.as_str().to_string(), style));
+                current_pos = m.end();
+            }
+        }
+        if current_pos < line.len() {
+            spans.push(Span::raw(line[current_pos..].to_string()));
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::test_editor;
+    use crate::editor::Editor;
+    use std::time::Instant;
+
+    #[test]
+    fn test_large_files_are_not_parsed() {
+        let mut config = Config::default();
+        config.render.large_file_mb = 1;
+        let (mut editor, _) = Editor::new("test.rs".to_string(), config);
+        editor.content = "fn main() {}\n".repeat(100_000);
+        editor.update_syntax_tree();
+        assert!(editor.large_file());
+        assert!(editor.tree.is_none());
+        editor.scroll_offset = 50_000;
+        let lines = editor.visible_lines();
+        assert_eq!(lines[0], (50_000, 50_000 * 13));
+        assert!(lines.len() < 100_000);
+    }
+
+    #[test]
+    fn test_parse_retries_back_off_then_stop() {
+        let mut editor = test_editor("", 0);
+        let mut delays = Vec::new();
+        while {
+            editor.schedule_parse_retry();
+            editor.parse_retry_at.is_some()
+        } {
+            delays.push(editor.parse_retry_at.unwrap() - Instant::now());
+        }
+        assert_eq!(delays.len(), 4);
+        assert!(delays.windows(2).all(|w| w[1] > w[0]));
+        // an edit gets a fresh set of retries
+        editor.content = "fn main() {}\n".to_string();
+        editor.update_syntax_tree();
+        assert_eq!(editor.parse_retries, 0);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use crate::logger::log_to_file;
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// a tree bigger than this is narrowed down by typing more
+const MAX_FILES: usize = 50_000;
+// results listed above the prompt
+const MAX_RESULTS: usize = 10;
+// left out when walking a directory that isn't a git repository
+const SKIPPED_DIRS: &[&str] = &[
+    "target",
+    "node_modules",
+    "zig-cache",
+    ".zig-cache",
+    "zig-out",
+];
+
+/// Files under the working directory, narrowed by the open file prompt
+pub struct Finder {
+    files: Vec<String>,
+    selected: usize,
+}
+
+// tracked and untracked files git doesn't ignore, None outside a repository
+fn git_files(root: &Path) -> Option<Vec<String>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "ls-files",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+            "-z",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let listing = String::from_utf8_lossy(&output.stdout);
+    Some(
+        listing
+            .split('\0')
+            // deleted files stay in the index until the deletion is staged
+            .filter(|path| !path.is_empty() && root.join(path).is_file())
+            .take(MAX_FILES)
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+// the patterns of the top level .gitignore, for walking outside of git
+fn ignore_patterns(root: &Path) -> Vec<glob::Pattern> {
+    let Ok(text) = fs::read_to_string(root.join(".gitignore")) else {
+        return Vec::new();
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .filter_map(|line| glob::Pattern::new(line.trim_matches('/')).ok())
+        .collect()
+}
+
+fn walk(root: &Path, dir: &Path, ignored: &[glob::Pattern], files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if files.len() >= MAX_FILES {
+            return;
+        }
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        if name.starts_with('.')
+            || ignored
+                .iter()
+                .any(|pattern| pattern.matches(&name) || pattern.matches(&relative))
+        {
+            continue;
+        }
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() && !SKIPPED_DIRS.contains(&name.as_str()) => {
+                walk(root, &path, ignored, files);
+            }
+            Ok(kind) if kind.is_file() => files.push(relative),
+            _ => {}
+        }
+    }
+}
+
+/// Every file under `root` that isn't ignored, relative to it
+pub fn list_files(root: &Path) -> Vec<String> {
+    if let Some(files) = git_files(root) {
+        return files;
+    }
+    let mut files = Vec::new();
+    walk(root, root, &ignore_patterns(root), &mut files);
+    files
+}
+
+/// How well `path` matches `query`, None unless every character of the query
+/// appears in it in order. Runs of characters and matches at the start of the
+/// file name or a word score higher.
+pub fn fuzzy_score(path: &str, query: &str) -> Option<i64> {
+    let chars: Vec<char> = path
+        .chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect();
+    let name_start = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut from = 0;
+    let mut previous = None;
+    for wanted in query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+    {
+        let at = (from..chars.len()).find(|&i| chars[i] == wanted)?;
+        score += 1;
+        if at > 0 && previous == Some(at - 1) {
+            score += 5;
+        }
+        if at == 0 || matches!(chars[at - 1], '/' | '_' | '-' | '.') {
+            score += 3;
+        }
+        if at >= name_start {
+            score += 2;
+        }
+        previous = Some(at);
+        from = at + 1;
+    }
+    Some(score)
+}
+
+impl Finder {
+    /// The best matches for `query`, shorter paths first among equals
+    pub fn matches(&self, query: &str) -> Vec<&str> {
+        let mut scored: Vec<(i64, &str)> = self
+            .files
+            .iter()
+            .filter_map(|path| Some((fuzzy_score(path, query)?, path.as_str())))
+            .collect();
+        scored.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then(a.len().cmp(&b.len()))
+                .then(a.cmp(b))
+        });
+        scored
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, path)| path)
+            .collect()
+    }
+
+    /// Lists the matches above the prompt, the best one nearest to it
+    pub fn render(&self, f: &mut Frame, area: Rect, query: &str, theme: &Theme) {
+        let matches = self.matches(query);
+        let height = (matches.len().max(1) as u16 + 2).min(area.height);
+        let list_area = Rect::new(area.x, area.y + area.height - height, area.width, height);
+        let mut lines: Vec<Line> = matches
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let style = if i == self.selected {
+                    theme.selection
+                } else {
+                    theme.base
+                };
+                Line::from(Span::styled(path.to_string(), style))
+            })
+            .rev()
+            .collect();
+        if lines.is_empty() {
+            lines.push(Line::from(Span::styled("no matches", theme.dim)));
+        }
+        f.render_widget(Clear, list_area);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("{} files", self.files.len()))
+                    .style(theme.base),
+            ),
+            list_area,
+        );
+    }
+}
+
+impl Editor {
+    /// Lists the files under the working directory to pick one to open in a
+    /// tab of its own
+    pub(crate) fn open_finder(&mut self) {
+        let root = std::env::current_dir().unwrap_or_else(|_| ".".into());
+        let files = list_files(&root);
+        log_to_file(&format!(
+            "finder listed {} files under {}",
+            files.len(),
+            root.display()
+        ));
+        if files.is_empty() {
+            self.status_message = Some("No files to open here".to_string());
+            return;
+        }
+        self.finder = Some(Finder { files, selected: 0 });
+        self.prompt = Some(Prompt::new(PromptKind::FindFile, "Open file: "));
+    }
+
+    pub(crate) fn cancel_finder(&mut self) {
+        self.prompt = None;
+        self.finder = None;
+    }
+
+    /// Up moves towards worse matches, which are drawn higher up
+    pub(crate) fn move_finder_selection(&mut self, up: bool) {
+        let query = self
+            .prompt
+            .as_ref()
+            .map(|p| p.input.clone())
+            .unwrap_or_default();
+        let Some(finder) = self.finder.as_mut() else {
+            return;
+        };
+        let count = finder.matches(&query).len();
+        finder.selected = if up {
+            (finder.selected + 1).min(count.saturating_sub(1))
+        } else {
+            finder.selected.saturating_sub(1)
+        };
+    }
+
+    /// The list changes with the query, so the best match is picked again
+    pub(crate) fn reset_finder_selection(&mut self) {
+        if let Some(finder) = self.finder.as_mut() {
+            finder.selected = 0;
+        }
+    }
+
+    pub(crate) fn confirm_finder(&mut self) {
+        let query = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let Some(finder) = self.finder.take() else {
+            return;
+        };
+        let Some(path) = finder
+            .matches(&query)
+            .get(finder.selected)
+            .map(|p| p.to_string())
+        else {
+            self.status_message = Some(format!("No file matches {}", query));
+            self.bell();
+            return;
+        };
+        if let Err(e) = self.open_in_buffer(path.clone()) {
+            self.status_message = Some(format!("Failed to open {}: {}", path, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranks_file_name_matches_first() {
+        let finder = Finder {
+            files: vec![
+                "src/editor/mod.rs".to_string(),
+                "src/models/mod.rs".to_string(),
+                "src/main.rs".to_string(),
+                "README.md".to_string(),
+            ],
+            selected: 0,
+        };
+        assert_eq!(finder.matches("main"), vec!["src/main.rs"]);
+        assert_eq!(finder.matches("edmod")[0], "src/editor/mod.rs");
+        assert_eq!(finder.matches("rd")[0], "README.md");
+        assert_eq!(finder.matches("").len(), 4);
+        assert!(fuzzy_score("src/main.rs", "nim").is_none());
+    }
+
+    #[test]
+    fn test_walk_skips_gitignored_files() {
+        let root = std::env::temp_dir().join(format!("nars-finder-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::write(root.join(".gitignore"), "/build/\n*.log\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(root.join("build/out.rs"), "").unwrap();
+        fs::write(root.join("debug.log"), "").unwrap();
+        let mut files = Vec::new();
+        walk(&root, &root, &ignore_patterns(&root), &mut files);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(files, vec!["src/lib.rs"]);
+    }
+}
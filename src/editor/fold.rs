@@ -0,0 +1,223 @@
+use crate::editor::Editor;
+use std::ops::Range;
+use tree_sitter::Node;
+
+// row of the last character of a node, line comments end after their newline
+fn last_row(node: &Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+impl Editor {
+    // multi-line nodes of the language's fold kinds and runs of comments
+    // overlapping `within`, outer regions before the ones inside them
+    fn foldable_ranges(&self, within: Range<usize>) -> Vec<Range<usize>> {
+        let Some(tree) = self.tree.as_ref().filter(|_| !self.syntax_degraded()) else {
+            return Vec::new();
+        };
+        let kinds = self.language.fold_nodes();
+        let mut ranges = Vec::new();
+        // the comment run being collected, with its first and last rows
+        let mut comments: Option<(Range<usize>, usize, usize)> = None;
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            let overlaps = node.start_byte() <= within.end && node.end_byte() >= within.start;
+            if overlaps && node.kind().ends_with("comment") {
+                let (start_row, end_row) = (node.start_position().row, last_row(&node));
+                comments = match comments.take() {
+                    Some((run, first, last)) if last + 1 == start_row => {
+                        Some((run.start..node.end_byte(), first, end_row))
+                    }
+                    Some((run, first, last)) => {
+                        if last > first {
+                            ranges.push(run);
+                        }
+                        Some((node.start_byte()..node.end_byte(), start_row, end_row))
+                    }
+                    None => Some((node.start_byte()..node.end_byte(), start_row, end_row)),
+                };
+            } else if overlaps
+                && kinds.contains(&node.kind())
+                && last_row(&node) > node.start_position().row
+            {
+                ranges.push(node.start_byte()..node.end_byte());
+            }
+            if overlaps && cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    if let Some((run, first, last)) = comments {
+                        if last > first {
+                            ranges.push(run);
+                        }
+                    }
+                    return ranges;
+                }
+            }
+        }
+    }
+
+    // a fold keeps the line its region starts on and hides the rest of it
+    fn fold_for(&self, range: &Range<usize>) -> Range<usize> {
+        let end = if self.content[..range.end].ends_with('\n') {
+            range.end - 1
+        } else {
+            range.end
+        };
+        self.line_end(range.start)..end
+    }
+
+    /// Folds the region starting on the cursor line, or else the innermost
+    /// one around the cursor. Unfolds it if it is already folded.
+    pub(crate) fn toggle_fold(&mut self) {
+        let line_end = self.line_end(self.cursor_position);
+        if let Some(idx) = self.folds.iter().position(|fold| fold.start == line_end) {
+            self.folds.remove(idx);
+            return;
+        }
+        let (line_start, pos) = (self.line_start(self.cursor_position), self.cursor_position);
+        let ranges = self.foldable_ranges(line_start..line_end);
+        let region = ranges
+            .iter()
+            .find(|range| range.start >= line_start && range.start <= line_end)
+            .or_else(|| ranges.iter().rev().find(|range| range.contains(&pos)));
+        let Some(region) = region.cloned() else {
+            self.status_message = Some("Nothing to fold here".to_string());
+            self.bell();
+            return;
+        };
+        let fold = self.fold_for(&region);
+        if self.cursor_position > fold.start {
+            self.cursor_position = region.start;
+        }
+        self.folds.push(fold);
+    }
+
+    /// Folds every outermost region, or unfolds everything if anything is
+    /// folded already
+    pub(crate) fn toggle_all_folds(&mut self) {
+        if !self.folds.is_empty() {
+            self.folds.clear();
+            return;
+        }
+        let mut outermost: Vec<Range<usize>> = Vec::new();
+        for range in self.foldable_ranges(0..self.content.len()) {
+            if outermost
+                .last()
+                .is_none_or(|outer| range.start >= outer.end)
+            {
+                outermost.push(range);
+            }
+        }
+        self.folds = outermost.iter().map(|range| self.fold_for(range)).collect();
+        if let Some(region) = outermost
+            .iter()
+            .find(|range| range.contains(&self.cursor_position))
+        {
+            if self.cursor_position > self.line_end(region.start) {
+                self.cursor_position = region.start;
+            }
+        }
+    }
+
+    /// Buffer lines hidden by each fold
+    pub(crate) fn folded_lines(&self) -> Vec<Range<usize>> {
+        let len = self.content.len();
+        self.folds
+            .iter()
+            .map(|fold| {
+                self.point_at(fold.start.min(len)).row + 1..self.point_at(fold.end.min(len)).row + 1
+            })
+            .collect()
+    }
+
+    /// `line`, or if a fold hides it the line the fold starts on (going up)
+    /// or the first line after it (going down)
+    pub(crate) fn unfolded_line(&self, line: usize, down: bool) -> usize {
+        let folded = self.folded_lines();
+        let mut line = line;
+        while let Some(range) = folded.iter().find(|range| range.contains(&line)) {
+            line = if down { range.end } else { range.start - 1 };
+        }
+        line
+    }
+
+    /// Moves a cursor that stepped into a fold over it, returns false if there
+    /// is nothing past the fold
+    pub(crate) fn skip_folded(&mut self, forward: bool) -> bool {
+        let line = self.point_at(self.cursor_position).row;
+        let target = self.unfolded_line(line, forward);
+        if target == line {
+            return true;
+        }
+        if target > self.content.matches('\n').count() {
+            return false;
+        }
+        let start = self.line_offset(target);
+        self.cursor_position = if forward { start } else { self.line_end(start) };
+        true
+    }
+
+    /// Opens the folds hiding the cursor, e.g. after a search jumped into one
+    pub(crate) fn reveal_cursor(&mut self) {
+        let line = self.point_at(self.cursor_position).row;
+        let mut hidden = self
+            .folded_lines()
+            .into_iter()
+            .map(|range| range.contains(&line));
+        self.folds.retain(|_| !hidden.next().unwrap_or(false));
+    }
+
+    /// Gutter marker for each of `lines`: ▸ where a fold starts and ▾ where a
+    /// region could be folded
+    pub(crate) fn fold_markers(&self, lines: &[(usize, usize)]) -> Vec<&'static str> {
+        let (Some(&(_, first)), Some(&(_, last))) = (lines.first(), lines.last()) else {
+            return Vec::new();
+        };
+        let folded: Vec<usize> = self.folded_lines().iter().map(|r| r.start - 1).collect();
+        let foldable: Vec<usize> = self
+            .foldable_ranges(first..self.line_end(last))
+            .iter()
+            .map(|range| self.point_at(range.start).row)
+            .collect();
+        lines
+            .iter()
+            .map(|(line, _)| {
+                if folded.contains(line) {
+                    "▸"
+                } else if foldable.contains(line) {
+                    "▾"
+                } else {
+                    " "
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_folds_functions_and_skips_them() {
+        let mut editor = test_editor("fn a() {\n    1;\n    2;\n}\n\nfn b() {}\n", 0);
+        editor.toggle_fold();
+        assert_eq!(editor.folded_lines(), vec![1..4]);
+        editor.move_cursor_down();
+        assert_eq!(editor.point_at(editor.cursor_position).row, 4);
+        editor.move_cursor_up();
+        assert_eq!(editor.point_at(editor.cursor_position).row, 0);
+        // an edit above the fold moves it, one inside opens it
+        editor.apply_edit(0, 0, "// a\n");
+        assert_eq!(editor.folded_lines(), vec![2..5]);
+        editor.apply_edit(20, 21, "");
+        assert!(editor.folds.is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::editor::Editor;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
+
+impl Editor {
+    /// Start of the grapheme cluster before `pos`, so emoji and combining
+    /// marks are stepped over and deleted as one character
+    pub(crate) fn prev_boundary(&self, pos: usize) -> Option<usize> {
+        GraphemeCursor::new(pos, self.content.len(), true)
+            .prev_boundary(&self.content, 0)
+            .ok()
+            .flatten()
+    }
+
+    pub(crate) fn next_boundary(&self, pos: usize) -> Option<usize> {
+        GraphemeCursor::new(pos, self.content.len(), true)
+            .next_boundary(&self.content, 0)
+            .ok()
+            .flatten()
+    }
+
+    /// Terminal columns between the start of the line and `pos`
+    pub(crate) fn column_of(&self, pos: usize) -> usize {
+        self.content[self.line_start(pos)..pos].width()
+    }
+
+    /// Offset of the grapheme at `column` on the line starting at `line_start`,
+    /// or the end of the line if it is shorter
+    pub(crate) fn offset_at_column(&self, line_start: usize, column: usize) -> usize {
+        let line = &self.content[line_start..self.line_end(line_start)];
+        let mut width = 0;
+        for (idx, grapheme) in line.grapheme_indices(true) {
+            width += grapheme.width();
+            if width > column {
+                return line_start + idx;
+            }
+        }
+        line_start + line.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_steps_and_deletes_whole_graphemes() {
+        // a family emoji is several code points joined into one grapheme
+        let family = "👨‍👩‍👧";
+        let content = format!("a{}é", family);
+        let mut editor = test_editor(&content, 1);
+        editor.move_cursor_right();
+        assert_eq!(editor.cursor_position, 1 + family.len());
+        editor.delete_char();
+        assert_eq!(editor.content, "aé");
+        assert_eq!(editor.cursor_position, 1);
+    }
+
+    #[test]
+    fn test_vertical_movement_keeps_display_column() {
+        // the wide characters take two columns each
+        let mut editor = test_editor("日本語x\nabcdefg", "日本".len());
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_position, "日本語x\nabcd".len());
+        editor.move_cursor_up();
+        assert_eq!(editor.cursor_position, "日本".len());
+    }
+
+    #[test]
+    fn test_goal_column_survives_short_lines() {
+        let mut editor = test_editor("abcdef\nab\nabcdef", 5);
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_position, "abcdef\nab".len());
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_position, "abcdef\nab\nabcde".len());
+    }
+}
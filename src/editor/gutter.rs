@@ -0,0 +1,221 @@
+use crate::config::GutterConfig;
+use crate::editor::diff::diff;
+use crate::editor::Editor;
+use crate::logger::log_to_file;
+use std::path::Path;
+use std::process::Command;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tokio::task;
+
+/// How a buffer line differs from the committed file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// Lines were removed just above this one
+    Removed,
+}
+
+// the file as of HEAD, None outside a repository or for untracked files
+fn committed_lines(path: &str) -> Option<Vec<String>> {
+    let path = Path::new(path);
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["show", &format!("HEAD:./{}", name)])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        log_to_file(&format!("No committed version of {}", path.display()));
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.split('\n').map(str::to_string).collect())
+}
+
+fn normalize<'a>(line: &'a str, config: &GutterConfig) -> std::borrow::Cow<'a, str> {
+    let line = if config.ignore_line_endings {
+        line.trim_end_matches('\r')
+    } else {
+        line
+    };
+    if config.ignore_whitespace {
+        line.chars().filter(|c| !c.is_whitespace()).collect()
+    } else {
+        line.into()
+    }
+}
+
+/// Change marker for every line of `new` compared with `old`
+pub fn line_changes<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Option<LineChange>> {
+    let mut changes = vec![None; new.len()];
+    for hunk in diff(old, new) {
+        for line in hunk.new.clone() {
+            changes[line] = Some(if line - hunk.new.start < hunk.old.len() {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            });
+        }
+        // removed lines are marked on the line that took their place
+        if let (true, Some(last)) = (hunk.new.is_empty(), new.len().checked_sub(1)) {
+            changes[hunk.new.start.min(last)] = Some(LineChange::Removed);
+        }
+    }
+    changes
+}
+
+impl Editor {
+    /// Reads the committed version of the file the gutter compares against.
+    /// Git runs on a background task so it doesn't hold up opening the file.
+    pub(crate) fn load_git_base(&mut self) {
+        let trackable = self.config.gutter.enabled
+            && !self.encrypted
+            && self.remote.is_none()
+            && !self.large_file();
+        self.git_base = None;
+        self.git_base_rx = None;
+        self.gutter_cache = None;
+        let Some(path) = self.filename.clone().filter(|_| trackable) else {
+            return;
+        };
+        let (base_tx, base_rx) = oneshot::channel();
+        task::spawn_blocking(move || {
+            let _ = base_tx.send(committed_lines(&path));
+        });
+        self.git_base_rx = Some(base_rx);
+    }
+
+    /// Picks up the committed file once git is done, returns true if it arrived
+    pub(crate) fn poll_git_base(&mut self) -> bool {
+        let Some(base_rx) = self.git_base_rx.as_mut() else {
+            return false;
+        };
+        match base_rx.try_recv() {
+            Ok(base) => self.git_base = base,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => {}
+        }
+        self.git_base_rx = None;
+        self.gutter_cache = None;
+        true
+    }
+
+    /// Gutter markers for every buffer line, empty when there is nothing to
+    /// compare against
+    pub(crate) fn gutter_changes(&self) -> &[Option<LineChange>] {
+        self.gutter_cache
+            .as_ref()
+            .map_or(&[], |(_, changes)| changes.as_slice())
+    }
+
+    /// Diffs the buffer against the committed file again, only if it was
+    /// edited or the committed file re-read since the last time
+    pub(crate) fn refresh_gutter_changes(&mut self) {
+        if let Some((revision, _)) = &self.gutter_cache {
+            if *revision == self.revision {
+                return;
+            }
+        }
+        self.gutter_cache = Some((self.revision, self.diff_against_base()));
+    }
+
+    fn diff_against_base(&self) -> Vec<Option<LineChange>> {
+        let Some(base) = &self.git_base else {
+            return Vec::new();
+        };
+        let config = &self.config.gutter;
+        let old: Vec<_> = base.iter().map(|line| normalize(line, config)).collect();
+        // compared as written, so converting line endings marks every line
+        let text = self.line_ending.apply(&self.content);
+        let new: Vec<_> = text
+            .split('\n')
+            .map(|line| normalize(line, config))
+            .collect();
+        line_changes(&old, &new)
+    }
+
+    /// Number shown next to buffer line `line`, its distance from the cursor
+    /// line when relative numbers are on. The cursor line keeps its own number.
+    pub(crate) fn line_label(&self, line: usize, cursor_line: usize) -> usize {
+        if self.relative_numbers && line != cursor_line {
+            line.abs_diff(cursor_line)
+        } else {
+            line + 1
+        }
+    }
+
+    pub(crate) fn toggle_relative_numbers(&mut self) {
+        self.relative_numbers = !self.relative_numbers;
+        self.status_message = Some(format!(
+            "Relative line numbers {}",
+            if self.relative_numbers { "on" } else { "off" }
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_marks_added_modified_and_removed_lines() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "B", "c", "x", "e"];
+        let changes = line_changes(&old, &new);
+        assert_eq!(changes[1], Some(LineChange::Modified));
+        assert_eq!(changes[3], Some(LineChange::Modified));
+        let changes = line_changes(&old, &["a", "b", "new", "c", "e"]);
+        assert_eq!(changes[2], Some(LineChange::Added));
+        assert_eq!(changes[4], Some(LineChange::Removed));
+        assert_eq!(changes.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn test_ignores_whitespace_and_line_endings_when_asked() {
+        let config = GutterConfig {
+            enabled: true,
+            ignore_whitespace: true,
+            ignore_line_endings: true,
+        };
+        assert_eq!(normalize("  let x =  1;\r", &config), "letx=1;");
+        let config = GutterConfig::default();
+        assert_eq!(normalize("x\r", &config), "x\r");
+    }
+
+    #[test]
+    fn test_changes_are_diffed_again_only_after_an_edit() {
+        let mut editor = test_editor("a\nb\n", 0);
+        editor.git_base = Some(vec!["a".to_string(), "b".to_string(), String::new()]);
+        editor.refresh_gutter_changes();
+        assert!(editor.gutter_changes().iter().all(Option::is_none));
+        // not an edit, so the markers drawn last time are kept
+        editor.content = "x\nb\n".to_string();
+        editor.refresh_gutter_changes();
+        assert_eq!(editor.gutter_changes()[0], None);
+        editor.apply_edit(2, 3, "y");
+        editor.refresh_gutter_changes();
+        assert_eq!(editor.gutter_changes()[0], Some(LineChange::Modified));
+        assert_eq!(editor.gutter_changes()[1], Some(LineChange::Modified));
+        editor.git_base = Some(vec!["x".to_string(), "y".to_string(), String::new()]);
+        editor.gutter_cache = None;
+        editor.refresh_gutter_changes();
+        assert!(editor.gutter_changes().iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_relative_line_numbers() {
+        let mut editor = test_editor("", 0);
+        assert_eq!(editor.line_label(7, 4), 8);
+        editor.toggle_relative_numbers();
+        assert_eq!(editor.line_label(7, 4), 3);
+        assert_eq!(editor.line_label(1, 4), 3);
+        assert_eq!(editor.line_label(4, 4), 5);
+    }
+}
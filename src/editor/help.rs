@@ -0,0 +1,156 @@
+use crate::editor::popup::Popup;
+use crate::editor::Editor;
+use ratatui::text::{Line, Span};
+
+/// Every key binding by section, shown by f1
+const KEYS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Files",
+        &[
+            ("ctrl+s", "save"),
+            ("ctrl+shift+s", "save as"),
+            (
+                "ctrl+o",
+                "open a file under the working directory in a new tab",
+            ),
+            ("ctrl+pagedown/pageup", "next/previous tab"),
+            ("ctrl+e", "export to HTML"),
+            ("alt+e", "switch between LF and CRLF"),
+            ("alt+shift+e", "change the encoding"),
+            (
+                "alt+x / alt+:",
+                "command line (a bare : types a colon): w, q, wq, e file, set option, line number",
+            ),
+            ("esc", "exit"),
+        ],
+    ),
+    (
+        "Predictions",
+        &[
+            ("tab tab", "predict the rest of the line"),
+            ("shift+tab", "predict the block"),
+            ("ctrl/alt+tab", "predict the rest of the function"),
+            ("ctrl/alt+shift+tab", "continue the file"),
+            (
+                "alt+enter",
+                "predict the lines up to the code after the cursor",
+            ),
+            ("tab", "accept the prediction at the cursor"),
+            ("shift+tab", "accept it on new lines below"),
+            ("ctrl/alt+tab", "accept it in place of the selection"),
+            ("ctrl+right", "accept its next word"),
+            ("ctrl+down", "accept its next line"),
+            ("ctrl+space", "pick from several predictions"),
+            ("alt+p", "preview what accepting changes"),
+            ("alt+a", "toggle function annotations"),
+            ("alt+m", "show the model's details"),
+            ("alt+shift+m", "switch to another installed model"),
+            ("alt+h", "chat about the file, again to close"),
+            ("alt+y", "insert the code from the chat"),
+            (
+                "alt+shift+h",
+                "explain the selection or function in the chat",
+            ),
+            ("alt+shift+r", "write a doc comment for the item or file"),
+            (
+                "alt+shift+t",
+                "write tests for the function or file in a new tab",
+            ),
+            ("alt+r", "rewrite the selection from an instruction"),
+        ],
+    ),
+    (
+        "Editing",
+        &[
+            ("ctrl+z", "undo"),
+            ("ctrl+shift+z / ctrl+r", "redo"),
+            ("shift+arrows", "select"),
+            ("ctrl+a / alt+w / alt+l", "select all / word / line"),
+            ("alt+v", "linewise selection: d, >, <, /"),
+            ("ctrl+d", "select the word, again for its next occurrence"),
+            ("alt+click", "add a cursor"),
+            ("alt+shift+arrows", "block selection"),
+            ("ctrl+k / ctrl+u / ctrl+y", "cut to line end / start, paste"),
+            ("ctrl+/", "toggle comments"),
+            ("alt+up/down", "move lines"),
+            ("ctrl+shift+d", "duplicate lines"),
+            ("alt+u / alt+shift+u / alt+c", "upper / lower / any case"),
+            ("alt+q", "re-wrap comments"),
+            ("alt+i", "switch between tabs and spaces"),
+            ("alt+.", "repeat the last edit"),
+            ("f3 / f4 / shift+f4", "record / replay / replay n macros"),
+        ],
+    ),
+    (
+        "Navigation",
+        &[
+            ("ctrl+f", "search, then n/N"),
+            ("ctrl+h", "find and replace"),
+            ("ctrl+t", "jump between code and its tests"),
+            ("alt+b / alt+j", "set / jump to a mark"),
+            ("alt+f / alt+shift+f", "fold the block / everything"),
+            ("ctrl+w", "splits: v, s, w, q, +, -, b"),
+            ("ctrl+b / f8", "run a task / next message"),
+        ],
+    ),
+    (
+        "View",
+        &[
+            ("alt+z", "soft wrap"),
+            ("alt+n", "relative line numbers"),
+            ("alt+s", "show whitespace"),
+            ("alt+t", "next theme"),
+            ("alt+d", "changes since the last commit"),
+            ("alt+g", "action log"),
+            ("f12", "editor log, ctrl+l filters by level"),
+            ("f1", "this help"),
+        ],
+    ),
+];
+
+impl Editor {
+    pub(crate) fn show_help(&mut self) {
+        let width = KEYS
+            .iter()
+            .flat_map(|(_, keys)| keys.iter())
+            .map(|(key, _)| key.len())
+            .max()
+            .unwrap_or(0);
+        let mut lines = Vec::new();
+        for (section, keys) in KEYS {
+            if !lines.is_empty() {
+                lines.push(Line::from(""));
+            }
+            lines.push(Line::from(Span::styled(*section, self.theme.keyword)));
+            for (key, action) in keys.iter() {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("  {:<width$}  ", key), self.theme.dim),
+                    Span::raw(*action),
+                ]));
+            }
+        }
+        self.popup = Some(Popup::new(
+            "Keys (up/down scroll, any other key closes)",
+            lines,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_help_lists_every_section_and_scrolls() {
+        let mut editor = test_editor("", 0);
+        editor.show_help();
+        let popup = editor.popup.as_mut().unwrap();
+        let text: Vec<String> = popup.lines.iter().map(|line| line.to_string()).collect();
+        assert_eq!(text[0], "Files");
+        assert!(text.iter().any(|line| line.contains("ctrl+o")));
+        popup.scroll_by(-1);
+        assert_eq!(popup.scroll, 0);
+        popup.scroll_by(1000);
+        assert_eq!(popup.scroll, popup.lines.len() - 1);
+    }
+}
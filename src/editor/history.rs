@@ -0,0 +1,166 @@
+use crate::editor::Editor;
+
+// steps kept to undo, the oldest are dropped past this
+const MAX_STEPS: usize = 1000;
+
+/// `removed` replaced by `inserted` at `start`
+struct Change {
+    start: usize,
+    removed: String,
+    inserted: String,
+}
+
+impl Change {
+    // text typed without spaces, which characters typed after it join so a
+    // word is undone at once
+    fn word(&self) -> bool {
+        self.removed.is_empty()
+            && !self.inserted.is_empty()
+            && !self.inserted.contains(char::is_whitespace)
+    }
+}
+
+/// The changes one command made, undone and redone together
+struct Step {
+    changes: Vec<Change>,
+    // where the cursor was before the command
+    cursor: usize,
+}
+
+/// Undo and redo of the edits to a buffer
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    // open while a command runs, so all its edits are one step
+    open: Option<Step>,
+    // set while undoing or redoing, whose edits aren't recorded
+    replaying: bool,
+}
+
+impl History {
+    /// Groups the edits from here to `end_step` into one step
+    pub(crate) fn begin_step(&mut self, cursor: usize) {
+        self.open = Some(Step {
+            changes: Vec::new(),
+            cursor,
+        });
+    }
+
+    pub(crate) fn end_step(&mut self) {
+        if let Some(step) = self.open.take().filter(|step| !step.changes.is_empty()) {
+            self.push(step);
+        }
+    }
+
+    /// Records an edit made by `apply_edit`, `cursor` being where the cursor
+    /// was before it
+    pub(crate) fn record(&mut self, start: usize, removed: String, inserted: &str, cursor: usize) {
+        if self.replaying {
+            return;
+        }
+        let change = Change {
+            start,
+            removed,
+            inserted: inserted.to_string(),
+        };
+        match self.open.as_mut() {
+            Some(step) => step.changes.push(change),
+            None => self.push(Step {
+                changes: vec![change],
+                cursor,
+            }),
+        }
+    }
+
+    fn push(&mut self, step: Step) {
+        self.redo.clear();
+        // a character typed straight after the last one joins its step
+        if let ([change], Some(last)) = (step.changes.as_slice(), self.undo.last_mut()) {
+            if let [previous] = last.changes.as_mut_slice() {
+                if change.word()
+                    && change.inserted.chars().count() == 1
+                    && previous.word()
+                    && previous.start + previous.inserted.len() == change.start
+                {
+                    previous.inserted.push_str(&change.inserted);
+                    return;
+                }
+            }
+        }
+        self.undo.push(step);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+    }
+}
+
+impl Editor {
+    /// Takes back the last step, putting the cursor where it was before it
+    pub(crate) fn undo(&mut self) {
+        let Some(step) = self.history.undo.pop() else {
+            self.status_message = Some("Nothing to undo".to_string());
+            self.bell();
+            return;
+        };
+        self.history.replaying = true;
+        for change in step.changes.iter().rev() {
+            let end = change.start + change.inserted.len();
+            self.apply_edit(change.start, end, &change.removed);
+        }
+        self.history.replaying = false;
+        self.clear_carets();
+        self.cursor_position = step.cursor.min(self.content.len());
+        self.update_syntax_tree();
+        self.history.redo.push(step);
+    }
+
+    /// Makes the last undone step again
+    pub(crate) fn redo(&mut self) {
+        let Some(step) = self.history.redo.pop() else {
+            self.status_message = Some("Nothing to redo".to_string());
+            self.bell();
+            return;
+        };
+        self.history.replaying = true;
+        for change in &step.changes {
+            let end = change.start + change.removed.len();
+            self.apply_edit(change.start, end, &change.inserted);
+        }
+        self.history.replaying = false;
+        self.clear_carets();
+        if let Some(last) = step.changes.last() {
+            self.cursor_position = last.start + last.inserted.len();
+        }
+        self.update_syntax_tree();
+        self.history.undo.push(step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_typing_is_undone_by_word_and_redone() {
+        let mut editor = test_editor("", 0);
+        for c in "let x".chars() {
+            editor.history.begin_step(editor.cursor_position);
+            editor.insert_text(&c.to_string());
+            editor.history.end_step();
+        }
+        editor.undo();
+        assert_eq!(editor.content, "let ");
+        assert_eq!(editor.cursor_position, 4);
+        editor.undo();
+        editor.undo();
+        assert_eq!(editor.content, "");
+        editor.redo();
+        editor.redo();
+        assert_eq!(editor.content, "let ");
+        // a new edit drops what was left to redo
+        editor.insert_text("y");
+        editor.redo();
+        assert_eq!(editor.content, "let y");
+    }
+}
@@ -0,0 +1,236 @@
+use crate::config::LanguageConfig;
+use crate::editor::Editor;
+use std::collections::HashMap;
+
+const OPENERS: [char; 3] = ['{', '(', '['];
+const CLOSERS: [char; 3] = ['}', ')', ']'];
+// space indent widths recognised when detecting a file's style
+const DETECTED_WIDTHS: [usize; 3] = [2, 4, 8];
+
+/// How one level of indentation is written in a buffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndentStyle {
+    pub width: usize,
+    pub tabs: bool,
+}
+
+impl IndentStyle {
+    pub fn from_config(config: &LanguageConfig) -> Self {
+        Self {
+            width: config.indent_width.max(1),
+            tabs: config.indent_with_tabs,
+        }
+    }
+
+    /// Guesses the style from the indented lines of `content`: tabs if most
+    /// of them start with one, shown `tab_width` wide, otherwise the most
+    /// common step in leading spaces between consecutive lines
+    pub fn detect(content: &str, tab_width: usize) -> Option<Self> {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut steps: HashMap<usize, usize> = HashMap::new();
+        let mut previous = 0;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if spaces > 0 {
+                space_lines += 1;
+            }
+            if spaces > previous {
+                *steps.entry(spaces - previous).or_default() += 1;
+            }
+            previous = spaces;
+        }
+        if tab_lines == 0 && space_lines == 0 {
+            return None;
+        }
+        if tab_lines > space_lines {
+            return Some(Self {
+                width: tab_width.max(1),
+                tabs: true,
+            });
+        }
+        DETECTED_WIDTHS
+            .iter()
+            .filter_map(|width| steps.get(width).map(|count| (*count, *width)))
+            .max()
+            .map(|(_, width)| Self { width, tabs: false })
+    }
+
+    /// The text for one level of indentation
+    pub fn unit(&self) -> String {
+        if self.tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.width)
+        }
+    }
+}
+
+impl Editor {
+    pub(crate) fn indent_unit(&self) -> String {
+        self.indent_style.unit()
+    }
+
+    /// Switches the buffer between tabs and spaces for new indentation
+    pub(crate) fn toggle_indent_tabs(&mut self) {
+        self.indent_style.tabs = !self.indent_style.tabs;
+        self.status_message = Some(if self.indent_style.tabs {
+            "Indenting with tabs".to_string()
+        } else {
+            format!("Indenting with {} spaces", self.indent_style.width)
+        });
+    }
+
+    /// Uses the language's configured style, or the one the file already
+    /// follows when detection is enabled
+    pub(crate) fn reset_indent_style(&mut self) {
+        let config = self.config.language(self.language.name());
+        let detected = config
+            .detect_indent
+            .then(|| IndentStyle::detect(&self.content, config.indent_width))
+            .flatten();
+        self.indent_style = detected.unwrap_or_else(|| IndentStyle::from_config(&config));
+    }
+
+    pub(crate) fn line_indent(&self, pos: usize) -> String {
+        self.content[self.line_start(pos)..]
+            .chars()
+            .take_while(|&c| c == ' ' || c == '\t')
+            .collect()
+    }
+
+    /// Indentation for a line broken at the cursor: one level past the line
+    /// that opens the innermost block-like node around the cursor. Without a
+    /// usable tree the current line's indentation is kept, one level deeper
+    /// after an opening bracket.
+    pub(crate) fn indent_for_newline(&self) -> String {
+        let pos = self.cursor_position;
+        if let Some(tree) = self.tree.as_ref().filter(|_| !self.syntax_degraded()) {
+            let kinds = self.language.indent_nodes();
+            let mut node = tree.root_node().descendant_for_byte_range(pos, pos);
+            while let Some(current) = node {
+                if kinds.contains(&current.kind())
+                    && current.start_byte() < pos
+                    && pos < current.end_byte()
+                {
+                    return format!(
+                        "{}{}",
+                        self.line_indent(current.start_byte()),
+                        self.indent_unit()
+                    );
+                }
+                node = current.parent();
+            }
+        }
+        let before = self.content[self.line_start(pos)..pos].trim_end();
+        let indent = self.line_indent(pos);
+        if before.ends_with(OPENERS) {
+            indent + &self.indent_unit()
+        } else {
+            indent
+        }
+    }
+
+    /// Breaks the line at the cursor and indents the new line. A closing
+    /// bracket right after the cursor stays at the outer level, and between a
+    /// pair of brackets it gets its own line.
+    pub(crate) fn insert_indented_newline(&mut self) {
+        let pos = self.cursor_position;
+        let indent = self.indent_for_newline();
+        let line_end = self.line_end(pos);
+        let rest = &self.content[pos..line_end];
+        // whitespace after the cursor would end up in front of the new indentation
+        let end = pos + rest.len() - rest.trim_start().len();
+        let closer = rest
+            .trim_start()
+            .chars()
+            .next()
+            .filter(|c| CLOSERS.contains(c));
+        let opener = self.content[self.line_start(pos)..pos]
+            .trim_end()
+            .chars()
+            .next_back();
+        let outer = indent
+            .strip_suffix(&self.indent_unit())
+            .unwrap_or("")
+            .to_string();
+
+        let (text, cursor) = match (opener, closer) {
+            (Some(open), Some(close))
+                if OPENERS.iter().position(|&c| c == open)
+                    == CLOSERS.iter().position(|&c| c == close) =>
+            {
+                (format!("\n{}\n{}", indent, outer), pos + 1 + indent.len())
+            }
+            (_, Some(_)) => (format!("\n{}", outer), pos + 1 + outer.len()),
+            _ => (format!("\n{}", indent), pos + 1 + indent.len()),
+        };
+        self.apply_edit(pos, end, &text);
+        self.cursor_position = cursor;
+        self.update_syntax_tree();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndentStyle;
+
+    use crate::editor::test_editor;
+    use crate::editor::Editor;
+
+    fn newline_at(content: &str, cursor: usize) -> Editor {
+        let mut editor = test_editor(content, cursor);
+        editor.insert_indented_newline();
+        editor
+    }
+
+    #[test]
+    fn test_indents_inside_nested_block() {
+        let content = "fn main() {\n    if x {\n        y();\n    }\n}";
+        let editor = newline_at(content, content.find("y();").unwrap() + 4);
+        assert_eq!(
+            editor.content,
+            "fn main() {\n    if x {\n        y();\n        \n    }\n}"
+        );
+        assert_eq!(editor.cursor_position, content.find("y();").unwrap() + 13);
+    }
+
+    #[test]
+    fn test_keeps_top_level_at_column_zero() {
+        let editor = newline_at("fn a() {}\nfn b() {}", 9);
+        assert_eq!(editor.content, "fn a() {}\n\nfn b() {}");
+    }
+
+    #[test]
+    fn test_detects_indent_style() {
+        let two = "fn a() {\n  if x {\n    y();\n  }\n}\n";
+        assert_eq!(
+            IndentStyle::detect(two, 4),
+            Some(IndentStyle {
+                width: 2,
+                tabs: false
+            })
+        );
+        let tabs = "fn a() {\n\tif x {\n\t\ty();\n\t}\n}\n";
+        assert_eq!(
+            IndentStyle::detect(tabs, 8),
+            Some(IndentStyle {
+                width: 8,
+                tabs: true
+            })
+        );
+        assert_eq!(IndentStyle::detect("fn a() {}\n", 4), None);
+    }
+
+    #[test]
+    fn test_splits_brackets_onto_their_own_lines() {
+        let editor = newline_at("fn main() {\n    foo(a)\n}", 20);
+        assert_eq!(editor.content, "fn main() {\n    foo(\n        a)\n}");
+        let editor = newline_at("fn main() {\n    foo(a)\n}", 21);
+        assert_eq!(editor.content, "fn main() {\n    foo(a\n    )\n}");
+    }
+}
@@ -0,0 +1,75 @@
+use crate::editor::repeat::Action;
+use crate::editor::Editor;
+
+impl Editor {
+    /// Cuts from the cursor to the end of the line, or to its start, into the
+    /// kill buffer. At the edge of a line the line break is cut instead, and
+    /// kills in a row collect into one buffer.
+    pub(crate) fn kill(&mut self, to_end: bool) {
+        let pos = self.cursor_position;
+        let (start, end) = if to_end {
+            match self.line_end(pos) {
+                end if end == pos => (pos, (pos + 1).min(self.content.len())),
+                end => (pos, end),
+            }
+        } else {
+            match self.line_start(pos) {
+                start if start == pos => (pos.saturating_sub(1), pos),
+                start => (start, pos),
+            }
+        };
+        if start == end {
+            self.bell();
+            return;
+        }
+        let text = self.content[start..end].to_string();
+        if !self.kill_run {
+            self.kill_buffer.clear();
+        }
+        if to_end {
+            self.kill_buffer.push_str(&text);
+        } else {
+            self.kill_buffer.insert_str(0, &text);
+        }
+        self.current_prediction = None;
+        self.prediction_start_position = None;
+        self.apply_edit(start, end, "");
+        self.cursor_position = start;
+        self.update_syntax_tree();
+        self.record(Action::Kill { to_end });
+        self.kill_run = true;
+    }
+
+    /// Pastes the kill buffer at every cursor
+    pub(crate) fn yank(&mut self) {
+        if self.kill_buffer.is_empty() {
+            self.status_message = Some("Nothing to yank".to_string());
+            self.bell();
+            return;
+        }
+        let text = self.kill_buffer.clone();
+        self.delete_selection();
+        self.for_each_caret(|e| e.insert_text(&text));
+        self.record(Action::Insert(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_kills_collect_and_yank_back() {
+        let mut editor = test_editor("let a = 1;\nlet b = 2;\n", 4);
+        editor.kill(true);
+        editor.kill(true);
+        assert_eq!(editor.content, "let let b = 2;\n");
+        assert_eq!(editor.kill_buffer, "a = 1;\n");
+        editor.kill_run = false;
+        editor.kill(false);
+        assert_eq!(editor.kill_buffer, "let ");
+        editor.cursor_position = editor.content.len();
+        editor.yank();
+        assert_eq!(editor.content, "let b = 2;\nlet ");
+    }
+}
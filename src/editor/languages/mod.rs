@@ -1,2 +1,180 @@
 pub mod rust;
 pub mod zig;
+
+use crate::editor::tasks::TaskPreset;
+use crate::logger::log_to_file;
+use glob::Pattern;
+use rust::tree_sitter_rust;
+use std::collections::HashMap;
+use std::path::Path;
+use tree_sitter::Node;
+use zig::tree_sitter_zig;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Rust,
+    Zig,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::Rust, Language::Zig];
+
+    pub fn from_path(path: &str) -> Self {
+        match path.split('.').next_back().unwrap_or("rs") {
+            "zig" => {
+                log_to_file("Loading Zig LSP");
+                Language::Zig
+            }
+            _ => {
+                log_to_file("Defaulting to Rust LSP");
+                Language::Rust
+            }
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rust" => Some(Language::Rust),
+            "zig" => Some(Language::Zig),
+            _ => None,
+        }
+    }
+
+    /// Language for `path`, checking the `file_types` globs from the config
+    /// before the built-in extensions. The longest matching glob wins.
+    pub fn detect(path: &str, file_types: &HashMap<String, String>) -> Self {
+        let file_name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+        let mut matches: Vec<(&String, &String)> = file_types
+            .iter()
+            .filter(|(glob, _)| {
+                Pattern::new(glob).is_ok_and(|p| p.matches(file_name) || p.matches(path))
+            })
+            .collect();
+        matches.sort_by_key(|(glob, _)| std::cmp::Reverse(glob.len()));
+        for (glob, name) in matches {
+            match Language::from_name(name) {
+                Some(language) => return language,
+                None => log_to_file(&format!("Unknown language {} for {}", name, glob)),
+            }
+        }
+        Language::from_path(path)
+    }
+
+    /// Key used for this language's section in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Zig => "zig",
+        }
+    }
+
+    pub fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => rust::KEYWORDS,
+            Language::Zig => zig::KEYWORDS,
+        }
+    }
+
+    pub fn comment_prefix(&self) -> &'static str {
+        match self {
+            Language::Rust => rust::COMMENT_PREFIX,
+            Language::Zig => zig::COMMENT_PREFIX,
+        }
+    }
+
+    /// Doc comment prefix for an item, or for the file with `module`
+    pub fn doc_prefix(&self, module: bool) -> &'static str {
+        match (self, module) {
+            (Language::Rust, false) => rust::DOC_PREFIX,
+            (Language::Rust, true) => rust::MODULE_DOC_PREFIX,
+            (Language::Zig, false) => zig::DOC_PREFIX,
+            (Language::Zig, true) => zig::MODULE_DOC_PREFIX,
+        }
+    }
+
+    pub fn fold_nodes(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => rust::FOLD_NODES,
+            Language::Zig => zig::FOLD_NODES,
+        }
+    }
+
+    pub fn indent_nodes(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => rust::INDENT_NODES,
+            Language::Zig => zig::INDENT_NODES,
+        }
+    }
+
+    /// Build tool commands offered by ctrl+b
+    pub fn task_presets(&self) -> &'static [TaskPreset] {
+        match self {
+            Language::Rust => &[],
+            Language::Zig => zig::TASKS,
+        }
+    }
+
+    /// Text a test block or module starts with, at the start of a line
+    pub fn test_marker(&self) -> &'static str {
+        match self {
+            Language::Rust => rust::TEST_MARKER,
+            Language::Zig => zig::TEST_MARKER,
+        }
+    }
+
+    pub fn test_template(&self) -> &'static str {
+        match self {
+            Language::Rust => rust::TEST_TEMPLATE,
+            Language::Zig => zig::TEST_TEMPLATE,
+        }
+    }
+
+    /// Breadcrumb label for `node` if it is a module, type, impl or function
+    pub fn scope_label(&self, node: &Node, source: &str) -> Option<String> {
+        match self {
+            Language::Rust => rust::scope_label(node, source),
+            Language::Zig => zig::scope_label(node, source),
+        }
+    }
+
+    /// Name of the type `node` defines, if it is a top level type definition
+    pub fn definition_name(&self, node: &Node, source: &str) -> Option<String> {
+        match self {
+            Language::Rust => rust::definition_name(node, source),
+            Language::Zig => zig::definition_name(node, source),
+        }
+    }
+
+    pub fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust(),
+            Language::Zig => tree_sitter_zig(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_type_overrides_win_over_extension() {
+        let file_types = HashMap::from([
+            ("*.zon".to_string(), "zig".to_string()),
+            ("*.in".to_string(), "zig".to_string()),
+            ("*.rs.in".to_string(), "rust".to_string()),
+        ]);
+        assert_eq!(
+            Language::detect("build.zig.zon", &file_types),
+            Language::Zig
+        );
+        assert_eq!(
+            Language::detect("src/lib.rs.in", &file_types),
+            Language::Rust
+        );
+        assert_eq!(Language::detect("main.zig", &HashMap::new()), Language::Zig);
+    }
+}
@@ -1,4 +1,4 @@
-use tree_sitter::Language;
+use tree_sitter::{Language, Node};
 
 pub fn tree_sitter_rust() -> Language {
     unsafe {
@@ -8,3 +8,88 @@ pub fn tree_sitter_rust() -> Language {
         tree_sitter_rust()
     }
 }
+
+/// Line comments, toggled with ctrl+/
+pub const COMMENT_PREFIX: &str = "//";
+
+/// Doc comments on the item below them and on the whole file
+pub const DOC_PREFIX: &str = "///";
+pub const MODULE_DOC_PREFIX: &str = "//!";
+
+/// Keywords highlighted by the regex fallback when the grammar can't parse the file
+pub const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Nodes whose contents are indented one level past the line they start on
+pub const INDENT_NODES: &[&str] = &[
+    "block",
+    "declaration_list",
+    "field_declaration_list",
+    "enum_variant_list",
+    "field_initializer_list",
+    "match_block",
+    "use_list",
+    "arguments",
+    "parameters",
+    "array_expression",
+    "tuple_expression",
+    "token_tree",
+];
+
+/// Nodes that can be folded away below the line they start on
+pub const FOLD_NODES: &[&str] = &[
+    "function_item",
+    "impl_item",
+    "trait_item",
+    "mod_item",
+    "struct_item",
+    "enum_item",
+    "macro_definition",
+];
+
+/// Starts the unit test module
+pub const TEST_MARKER: &str = "#[cfg(test)]";
+
+/// Appended when a file has no tests yet, `{{cursor}}` is where the cursor goes
+pub const TEST_TEMPLATE: &str = "\n#[cfg(test)]\nmod tests {\n    use super::*;\n\n    #[test]\n    fn test_{{name}}() {\n        {{cursor}}\n    }\n}\n";
+
+/// How a node that opens a scope reads in the breadcrumbs, e.g. `impl Display for Foo`
+pub fn scope_label(node: &Node, source: &str) -> Option<String> {
+    let field = |name: &str| {
+        node.child_by_field_name(name)
+            .and_then(|child| child.utf8_text(source.as_bytes()).ok())
+    };
+    let keyword = match node.kind() {
+        "mod_item" => "mod",
+        "trait_item" => "trait",
+        "struct_item" => "struct",
+        "enum_item" => "enum",
+        "function_item" => "fn",
+        "impl_item" => {
+            let type_name = field("type")?;
+            return Some(match field("trait") {
+                Some(trait_name) => format!("impl {} for {}", trait_name, type_name),
+                None => format!("impl {}", type_name),
+            });
+        }
+        _ => return None,
+    };
+    Some(format!("{} {}", keyword, field("name")?))
+}
+
+/// Name of `node` if it defines a type, for pulling definitions from other
+/// files into prompts
+pub fn definition_name(node: &Node, source: &str) -> Option<String> {
+    match node.kind() {
+        "struct_item" | "enum_item" | "union_item" | "trait_item" | "type_item" => node
+            .child_by_field_name("name")?
+            .utf8_text(source.as_bytes())
+            .ok()
+            .map(str::to_string),
+        _ => None,
+    }
+}
@@ -1,4 +1,5 @@
-use tree_sitter::Language;
+use crate::editor::tasks::TaskPreset;
+use tree_sitter::{Language, Node};
 
 pub fn tree_sitter_zig() -> Language {
     unsafe {
@@ -8,3 +9,140 @@ pub fn tree_sitter_zig() -> Language {
         tree_sitter_zig()
     }
 }
+
+/// Line comments, toggled with ctrl+/
+pub const COMMENT_PREFIX: &str = "//";
+
+/// Doc comments on the item below them and on the whole file
+pub const DOC_PREFIX: &str = "///";
+pub const MODULE_DOC_PREFIX: &str = "//!";
+
+/// Keywords highlighted by the regex fallback when the grammar can't parse the file
+pub const KEYWORDS: &[&str] = &[
+    "align",
+    "and",
+    "anytype",
+    "asm",
+    "break",
+    "catch",
+    "comptime",
+    "const",
+    "continue",
+    "defer",
+    "else",
+    "enum",
+    "errdefer",
+    "error",
+    "export",
+    "extern",
+    "false",
+    "fn",
+    "for",
+    "if",
+    "inline",
+    "null",
+    "or",
+    "orelse",
+    "packed",
+    "pub",
+    "return",
+    "struct",
+    "switch",
+    "test",
+    "true",
+    "try",
+    "undefined",
+    "union",
+    "unreachable",
+    "var",
+    "volatile",
+    "while",
+];
+
+/// Nodes whose contents are indented one level past the line they start on
+pub const INDENT_NODES: &[&str] = &[
+    "Block",
+    "ContainerDecl",
+    "SwitchExpr",
+    "InitList",
+    "FnCallArguments",
+    "ParamDeclList",
+];
+
+/// Nodes that can be folded away below the line they start on
+pub const FOLD_NODES: &[&str] = &["Decl", "TestDecl", "ContainerDecl"];
+
+pub const TASKS: &[TaskPreset] = &[
+    TaskPreset {
+        name: "build",
+        program: "zig",
+        args: &["build"],
+        root_marker: "build.zig",
+        formats_buffer: false,
+    },
+    TaskPreset {
+        name: "test",
+        program: "zig",
+        args: &["build", "test"],
+        root_marker: "build.zig",
+        formats_buffer: false,
+    },
+    TaskPreset {
+        name: "fmt",
+        program: "zig",
+        args: &["fmt", "--stdin"],
+        root_marker: "build.zig",
+        formats_buffer: true,
+    },
+];
+
+/// Starts a test block
+pub const TEST_MARKER: &str = "test ";
+
+/// Appended when a file has no tests yet, `{{cursor}}` is where the cursor goes
+pub const TEST_TEMPLATE: &str = "\ntest \"{{name}}\" {\n    {{cursor}}\n}\n";
+
+/// How a node that opens a scope reads in the breadcrumbs: `fn name` for
+/// functions, the name of a `const Foo = struct` and `test "name"`
+pub fn scope_label(node: &Node, source: &str) -> Option<String> {
+    let text = |node: Node| node.utf8_text(source.as_bytes()).ok().map(str::to_string);
+    let mut cursor = node.walk();
+    match node.kind() {
+        "Decl" => node
+            .named_children(&mut cursor)
+            .find_map(|child| match child.kind() {
+                "FnProto" => Some(format!(
+                    "fn {}",
+                    text(child.child_by_field_name("function")?)?
+                )),
+                "VarDecl" => text(child.child_by_field_name("variable_type_function")?),
+                _ => None,
+            }),
+        "TestDecl" => {
+            let name = node
+                .named_children(&mut cursor)
+                .find(|child| child.kind() != "Block")
+                .and_then(text);
+            Some(name.map_or("test".to_string(), |name| format!("test {}", name)))
+        }
+        _ => None,
+    }
+}
+
+/// Name of `node` if it declares a type, going by Zig's convention of
+/// capitalized type names, for pulling definitions from other files into
+/// prompts
+pub fn definition_name(node: &Node, source: &str) -> Option<String> {
+    if node.kind() != "Decl" {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let name = node
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "VarDecl")?
+        .child_by_field_name("variable_type_function")?
+        .utf8_text(source.as_bytes())
+        .ok()?;
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        .then(|| name.to_string())
+}
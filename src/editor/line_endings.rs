@@ -0,0 +1,87 @@
+use crate::editor::Editor;
+use std::borrow::Cow;
+
+/// How lines end in the file on disk. The buffer always uses `\n` and the
+/// file's style is put back when saving.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The style most lines of `text` use, LF for a file without any
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        if crlf > 0 && crlf >= text.matches('\n').count() - crlf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// True if `text` uses both styles
+    pub fn mixed(text: &str) -> bool {
+        let crlf = text.matches("\r\n").count();
+        crlf > 0 && crlf < text.matches('\n').count()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    /// `text` with `\n` line endings, as kept in the buffer
+    pub fn strip(text: String) -> String {
+        if text.contains("\r\n") {
+            text.replace("\r\n", "\n")
+        } else {
+            text
+        }
+    }
+
+    /// Buffer text as it is written to disk
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEnding::Lf => text.into(),
+            LineEnding::Crlf => text.replace('\n', "\r\n").into(),
+        }
+    }
+}
+
+impl Editor {
+    /// Switches the file between LF and CRLF, the change is written on save
+    pub(crate) fn toggle_line_ending(&mut self) {
+        self.line_ending = match self.line_ending {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        };
+        self.modified = true;
+        // the gutter compares the text as written
+        self.gutter_cache = None;
+        self.status_message = Some(format!(
+            "Line endings are now {}, ctrl+s writes them",
+            self.line_ending.name()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_and_restores_crlf() {
+        let text = "fn main() {\r\n    1;\r\n}\r\n".to_string();
+        let ending = LineEnding::detect(&text);
+        assert_eq!(ending, LineEnding::Crlf);
+        let buffer = LineEnding::strip(text.clone());
+        assert_eq!(buffer, "fn main() {\n    1;\n}\n");
+        assert_eq!(ending.apply(&buffer), text);
+        assert_eq!(LineEnding::detect("a\nb\r\nc\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no newline"), LineEnding::Lf);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::editor::repeat::Action;
+use crate::editor::Editor;
+
+impl Editor {
+    // first line start and last line end of the selection, or the current line
+    fn line_block(&self) -> (usize, usize) {
+        let starts = self.selected_line_starts();
+        let last = starts.last().copied().unwrap_or(self.cursor_position);
+        (starts[0], self.line_end(last))
+    }
+
+    /// Moves the current line, or every selected line, past its neighbour as a
+    /// single edit, keeping the cursor and selection on the moved text
+    pub(crate) fn move_lines(&mut self, up: bool) {
+        let (start, end) = self.line_block();
+        let block = self.content[start..end].to_string();
+        let (edit_start, edit_end, text, new_start) = if up {
+            if start == 0 {
+                self.bell();
+                return;
+            }
+            let prev_start = self.line_start(start - 1);
+            let prev = &self.content[prev_start..start - 1];
+            (prev_start, end, format!("{}\n{}", block, prev), prev_start)
+        } else {
+            if end == self.content.len() {
+                self.bell();
+                return;
+            }
+            let next_end = self.line_end(end + 1);
+            let next = &self.content[end + 1..next_end];
+            (
+                start,
+                next_end,
+                format!("{}\n{}", next, block),
+                start + next.len() + 1,
+            )
+        };
+        self.shift_onto_block(start, end, new_start, |editor| {
+            editor.apply_edit(edit_start, edit_end, &text)
+        });
+        self.record(Action::MoveLines { up });
+    }
+
+    /// Copies the current line, or the selected lines, below itself and moves
+    /// onto the copy
+    pub(crate) fn duplicate_lines(&mut self) {
+        let (start, end) = self.line_block();
+        let text = format!("\n{}", &self.content[start..end]);
+        self.shift_onto_block(start, end, end + 1, |editor| {
+            editor.apply_edit(end, end, &text)
+        });
+        self.record(Action::DuplicateLines);
+    }
+
+    // `apply_edit` can't know where text moved to, so the cursor and anchor are
+    // carried from the old block to the same offsets in the new one
+    fn shift_onto_block(
+        &mut self,
+        start: usize,
+        end: usize,
+        new_start: usize,
+        edit: impl FnOnce(&mut Editor),
+    ) {
+        let relocate = |pos: usize| {
+            (start..=end)
+                .contains(&pos)
+                .then(|| new_start + pos - start)
+        };
+        let cursor = relocate(self.cursor_position);
+        let anchor = self.selection.and_then(|sel| relocate(sel.anchor));
+        edit(self);
+        if let Some(cursor) = cursor {
+            self.cursor_position = cursor;
+        }
+        if let (Some(sel), Some(anchor)) = (self.selection.as_mut(), anchor) {
+            sel.anchor = anchor;
+        }
+        self.update_syntax_tree();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_move_line_keeps_cursor_on_it() {
+        let mut editor = test_editor("a\nbb\nccc", 4);
+        editor.move_lines(true);
+        assert_eq!(editor.content, "bb\na\nccc");
+        assert_eq!(editor.cursor_position, 2);
+        editor.move_lines(false);
+        editor.move_lines(false);
+        assert_eq!(editor.content, "a\nccc\nbb");
+        assert_eq!(editor.cursor_position, 8);
+        // each move is one undo step
+        editor.undo();
+        assert_eq!(editor.content, "a\nbb\nccc");
+        editor.undo();
+        editor.undo();
+        assert_eq!(editor.content, "a\nbb\nccc");
+        assert_eq!(editor.cursor_position, 4);
+    }
+
+    #[test]
+    fn test_duplicate_line() {
+        let mut editor = test_editor("let x = 1;\nx", 4);
+        editor.duplicate_lines();
+        assert_eq!(editor.content, "let x = 1;\nlet x = 1;\nx");
+        assert_eq!(editor.cursor_position, 15);
+        editor.undo();
+        assert_eq!(editor.content, "let x = 1;\nx");
+        assert_eq!(editor.cursor_position, 4);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use crate::logger::{Level, LOG_PATH};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Frame;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+// share of the height the log takes from the panes
+const PANEL_PERCENT: u16 = 30;
+// the editor logs every frame, so the file is only looked at this often
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+// how far back from the end of the file opening the log starts reading
+const TAIL_BYTES: u64 = 64 * 1024;
+const MAX_ENTRIES: usize = 2000;
+
+/// One message from the log, with the lines of a multi-line message (like a
+/// prompt) joined back together
+#[derive(Debug, PartialEq)]
+struct LogEntry {
+    level: Level,
+    text: String,
+}
+
+/// The tail of the editor's own log, shown in a panel under the panes
+pub struct LogView {
+    entries: Vec<LogEntry>,
+    // bytes of the file read so far
+    read: u64,
+    checked: Option<Instant>,
+    /// Lines below this level are left out
+    pub min_level: Level,
+    /// Only lines containing this are shown, ignoring case
+    pub query: String,
+}
+
+// `[2024-01-01 12:00:00] LEVEL message`, lines from before levels were
+// logged count as debug
+fn parse_line(line: &str) -> Option<LogEntry> {
+    let bytes = line.as_bytes();
+    if bytes.len() < 21 || bytes[0] != b'[' || bytes[20] != b']' {
+        return None;
+    }
+    let message = line[21..].trim_start();
+    let level = Level::ALL
+        .into_iter()
+        .find(|level| {
+            message
+                .strip_prefix(level.name())
+                .is_some_and(|rest| rest.starts_with(' '))
+        })
+        .unwrap_or(Level::Debug);
+    Some(LogEntry {
+        level,
+        text: line.to_string(),
+    })
+}
+
+impl LogView {
+    fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            read: 0,
+            checked: None,
+            min_level: Level::Debug,
+            query: String::new(),
+        }
+    }
+
+    // adds whole lines of `text` to the entries, continuation lines to the
+    // entry before them
+    fn push_text(&mut self, text: &str) {
+        for line in text.lines() {
+            match (parse_line(line), self.entries.last_mut()) {
+                (Some(entry), _) => self.entries.push(entry),
+                (None, Some(last)) => {
+                    last.text.push('\n');
+                    last.text.push_str(line);
+                }
+                (None, None) => {}
+            }
+        }
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+    }
+
+    /// Reads what was logged since the last check, true if anything was
+    pub fn refresh(&mut self) -> bool {
+        if self.checked.is_some_and(|at| at.elapsed() < CHECK_INTERVAL) {
+            return false;
+        }
+        self.checked = Some(Instant::now());
+        let Ok(mut file) = File::open(LOG_PATH) else {
+            return false;
+        };
+        let len = file.metadata().map_or(0, |m| m.len());
+        // the log was cleared or rotated
+        if len < self.read {
+            self.entries.clear();
+            self.read = 0;
+        }
+        let start = if self.read == 0 {
+            len.saturating_sub(TAIL_BYTES)
+        } else {
+            self.read
+        };
+        if start == len || file.seek(SeekFrom::Start(start)).is_err() {
+            return false;
+        }
+        let mut bytes = Vec::new();
+        if file.read_to_end(&mut bytes).is_err() {
+            return false;
+        }
+        // a line still being written is read again next time
+        let Some(end) = bytes.iter().rposition(|&b| b == b'\n') else {
+            return false;
+        };
+        let text = String::from_utf8_lossy(&bytes[..=end]);
+        // reading from the middle of the file starts partway through a line
+        let text = if self.read == 0 && start > 0 {
+            text.split_once('\n').map_or("", |(_, rest)| rest)
+        } else {
+            &text
+        };
+        self.push_text(text);
+        self.read = start + end as u64 + 1;
+        true
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level >= self.min_level
+            && entry
+                .text
+                .to_lowercase()
+                .contains(&self.query.to_lowercase())
+    }
+
+    /// Shows the next level up, going back to everything after errors
+    pub fn cycle_level(&mut self) {
+        let next = Level::ALL
+            .into_iter()
+            .find(|level| *level > self.min_level)
+            .unwrap_or(Level::Debug);
+        self.min_level = next;
+    }
+
+    /// Draws the matching lines scrolled to the newest
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let rows = area.height.saturating_sub(2) as usize;
+        let mut lines: Vec<Line> = Vec::new();
+        for entry in self.entries.iter().rev().filter(|e| self.matches(e)) {
+            if lines.len() >= rows {
+                break;
+            }
+            let style = match entry.level {
+                Level::Debug => theme.dim,
+                Level::Info => theme.base,
+                Level::Warn => theme.warning,
+                Level::Error => theme.removed,
+            };
+            for line in entry.text.lines().rev() {
+                lines.push(Line::from(Span::styled(line.to_string(), style)));
+            }
+        }
+        lines.truncate(rows);
+        lines.reverse();
+        let mut title = format!("Log {}+ (ctrl+l level)", self.min_level.name());
+        if !self.query.is_empty() {
+            title = format!("{} matching {:?}", title, self.query);
+        }
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .style(theme.base),
+            ),
+            area,
+        );
+    }
+}
+
+impl Editor {
+    /// The area left above the log panel and the panel's, if it is open
+    pub(crate) fn split_log(&self, area: Rect) -> (Rect, Option<Rect>) {
+        if self.log_view.is_none() {
+            return (area, None);
+        }
+        let panels = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Percentage(PANEL_PERCENT)])
+            .split(area);
+        (panels[0], Some(panels[1]))
+    }
+
+    /// Opens the log or moves to its search, from the search it closes the log
+    pub(crate) fn toggle_log(&mut self) {
+        if self.prompt.as_ref().map(|p| p.kind) == Some(PromptKind::LogSearch) {
+            self.prompt = None;
+            self.log_view = None;
+            return;
+        }
+        let view = self.log_view.get_or_insert_with(LogView::new);
+        let mut prompt = Prompt::new(PromptKind::LogSearch, "Search log: ");
+        prompt.input = view.query.clone();
+        self.prompt = Some(prompt);
+    }
+
+    /// Filters the log by what is typed in its search
+    pub(crate) fn update_log_search(&mut self) {
+        if let (Some(view), Some(prompt)) = (self.log_view.as_mut(), &self.prompt) {
+            view.query = prompt.input.clone();
+        }
+    }
+
+    /// Reads new lines into the open log, returns true if there were any
+    pub(crate) fn poll_log(&mut self) -> bool {
+        self.log_view.as_mut().is_some_and(LogView::refresh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_keep_their_level_and_continuation_lines() {
+        let mut view = LogView::new();
+        view.push_text(
+            "stray line\n[2024-01-01 12:00:00] old style\n[2024-01-01 12:00:01] ERROR Prediction error: refused\n[2024-01-01 12:00:02] DEBUG prompt\nsecond line\n",
+        );
+        let levels: Vec<Level> = view.entries.iter().map(|e| e.level).collect();
+        assert_eq!(levels, vec![Level::Debug, Level::Error, Level::Debug]);
+        assert!(view.entries[2].text.ends_with("prompt\nsecond line"));
+
+        view.cycle_level();
+        view.cycle_level();
+        let shown: Vec<&LogEntry> = view.entries.iter().filter(|e| view.matches(e)).collect();
+        assert_eq!(shown.len(), 1);
+        view.min_level = Level::Debug;
+        view.query = "SECOND".to_string();
+        assert!(view.matches(&view.entries[2]) && !view.matches(&view.entries[1]));
+    }
+}
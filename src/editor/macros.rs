@@ -0,0 +1,139 @@
+use super::handle_event;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use crate::models::Predictor;
+use anyhow::Result;
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::sync::Arc;
+
+/// Handles the macro keys and records every other event while recording.
+/// Returns true if the event was a macro key.
+pub(super) fn handle_macro_keys(
+    editor: &mut Editor,
+    predictor: &mut Arc<Predictor>,
+    event: &Event,
+) -> Result<bool> {
+    if let Event::Key(key) = event {
+        if key.kind != KeyEventKind::Release {
+            match key.code {
+                KeyCode::F(3) => {
+                    editor.toggle_macro_recording();
+                    return Ok(true);
+                }
+                KeyCode::F(4) if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                    editor.prompt =
+                        Some(Prompt::new(PromptKind::MacroCount, "Replay macro times: "));
+                    return Ok(true);
+                }
+                KeyCode::F(4) => {
+                    replay_macro(editor, predictor, 1)?;
+                    return Ok(true);
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some(events) = editor.macro_recording.as_mut() {
+        events.push(event.clone());
+    }
+    Ok(false)
+}
+
+/// Replays the last macro the number of times typed into the prompt
+pub(super) fn confirm_macro_count(editor: &mut Editor, predictor: &Arc<Predictor>) {
+    let input = editor.prompt.take().map(|p| p.input).unwrap_or_default();
+    let Ok(times) = input.trim().parse::<usize>() else {
+        editor.status_message = Some(format!("Not a count: {}", input.trim()));
+        editor.bell();
+        return;
+    };
+    if let Err(e) = replay_macro(editor, &mut predictor.clone(), times) {
+        editor.status_message = Some(format!("Macro failed: {}", e));
+    }
+}
+
+// runs the recorded events through the normal key handling. Replaying stops
+// early once a run changes nothing, or when the macro would quit the editor.
+fn replay_macro(editor: &mut Editor, predictor: &mut Arc<Predictor>, times: usize) -> Result<()> {
+    if editor.macro_recording.is_some() {
+        editor.status_message = Some("Stop recording with f3 first".to_string());
+        editor.bell();
+        return Ok(());
+    }
+    if editor.last_macro.is_empty() {
+        editor.status_message = Some("No macro recorded".to_string());
+        editor.bell();
+        return Ok(());
+    }
+    let events = editor.last_macro.clone();
+    editor.replaying = true;
+    let mut runs = 0;
+    let result = (|| {
+        while runs < times {
+            let before = (editor.content.clone(), editor.cursor_position);
+            for event in &events {
+                if handle_event(editor, predictor, event.clone())? {
+                    return Ok(());
+                }
+            }
+            runs += 1;
+            if before == (editor.content.clone(), editor.cursor_position) {
+                break;
+            }
+        }
+        Ok(())
+    })();
+    editor.replaying = false;
+    editor.status_message = Some(format!(
+        "Replayed macro {} time{}",
+        runs,
+        if runs == 1 { "" } else { "s" }
+    ));
+    result
+}
+
+impl Editor {
+    fn toggle_macro_recording(&mut self) {
+        match self.macro_recording.take() {
+            Some(events) if events.is_empty() => {
+                self.status_message = Some("Macro is empty, kept the previous one".to_string());
+            }
+            Some(events) => {
+                self.status_message = Some(format!("Recorded a macro of {} keys", events.len()));
+                self.last_macro = events;
+            }
+            None => {
+                self.macro_recording = Some(Vec::new());
+                self.status_message = Some("Recording macro, f3 stops".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor::test_editor;
+    use crate::models::backend::{Backend, MockBackend};
+    use ratatui::crossterm::event::KeyEvent;
+
+    #[test]
+    fn test_replays_recorded_keys() {
+        let mut editor = test_editor("a\nb\nc\nd", 0);
+        let backend = Backend::new("mock", MockBackend::default());
+        let mut predictor = Arc::new(Predictor::headless(backend, "test".to_string()));
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        for code in [
+            KeyCode::F(3),
+            KeyCode::Char('-'),
+            KeyCode::Down,
+            KeyCode::Left,
+            KeyCode::F(3),
+        ] {
+            handle_event(&mut editor, &mut predictor, key(code)).unwrap();
+        }
+        replay_macro(&mut editor, &mut predictor, 2).unwrap();
+        assert_eq!(editor.content, "-a\n-b\n-c\nd");
+    }
+}
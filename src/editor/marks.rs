@@ -0,0 +1,85 @@
+use crate::editor::Editor;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+// the mark a jump leaves behind, so jumping to it goes back
+const LAST_JUMP: char = '\'';
+
+/// What the letter typed after alt+b or alt+j does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarkKey {
+    Set,
+    Jump,
+}
+
+impl Editor {
+    /// Waits for the letter of a mark to set on the cursor line (alt+b) or to
+    /// jump to (alt+j)
+    pub(crate) fn begin_mark(&mut self, key: MarkKey) {
+        if key == MarkKey::Jump && self.marks.is_empty() {
+            self.status_message = Some("No marks set".to_string());
+            self.bell();
+            return;
+        }
+        self.status_message = Some(match key {
+            MarkKey::Set => "Mark the line as: press a letter".to_string(),
+            MarkKey::Jump => {
+                let names: Vec<String> = self.marks.keys().map(char::to_string).collect();
+                format!("Jump to mark: {}", names.join(" "))
+            }
+        });
+        self.pending_mark = Some(key);
+    }
+
+    /// Handles the key after alt+b or alt+j, anything but a letter cancels
+    pub(crate) fn finish_mark(&mut self, key: KeyEvent) {
+        let Some(pending) = self.pending_mark.take() else {
+            return;
+        };
+        self.status_message = None;
+        let KeyCode::Char(name) = key.code else {
+            return;
+        };
+        match pending {
+            MarkKey::Set if name.is_alphabetic() => {
+                self.marks.insert(name, self.cursor_position);
+                let line = self.point_at(self.cursor_position).row + 1;
+                self.status_message = Some(format!("Marked line {} as {}", line, name));
+            }
+            MarkKey::Jump if name.is_alphabetic() || name == LAST_JUMP => {
+                let Some(&position) = self.marks.get(&name) else {
+                    self.status_message = Some(format!("No mark {}", name));
+                    self.bell();
+                    return;
+                };
+                self.marks.insert(LAST_JUMP, self.cursor_position);
+                self.clear_selection();
+                self.cursor_position = position.min(self.content.len());
+                self.reveal_cursor();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor::test_editor;
+    use ratatui::crossterm::event::KeyModifiers;
+
+    #[test]
+    fn test_marks_follow_edits_above_them() {
+        let mut editor = test_editor("one\ntwo\nthree\n", 4);
+        editor.begin_mark(MarkKey::Set);
+        editor.finish_mark(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        editor.apply_edit(0, 0, "zero\n");
+        editor.cursor_position = 0;
+        editor.begin_mark(MarkKey::Jump);
+        editor.finish_mark(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        assert_eq!(&editor.content[editor.cursor_position..], "two\nthree\n");
+        editor.begin_mark(MarkKey::Jump);
+        editor.finish_mark(KeyEvent::new(KeyCode::Char('\''), KeyModifiers::NONE));
+        assert_eq!(editor.cursor_position, 0);
+    }
+}
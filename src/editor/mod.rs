@@ -1,29 +1,158 @@
-mod languages;
+mod annotations;
+mod audit;
+mod auto_predict;
+mod autoclose;
+mod autopairs;
+mod block;
+mod breadcrumbs;
+mod buffers;
+mod case;
+mod chat;
+mod command;
+mod completion;
+mod context;
+mod counterpart;
+mod diff;
+mod doc;
+mod encoding;
+mod explain;
+pub mod export;
+mod fallback;
+mod finder;
+mod fold;
+mod graphemes;
+mod gutter;
+mod help;
+mod history;
+mod indent;
+mod kill;
+pub mod languages;
+mod line_endings;
+mod lines;
+mod log_view;
+mod macros;
+mod marks;
+mod model_info;
+mod model_picker;
+mod mouse;
+mod open;
+mod multicursor;
+mod popup;
+mod prediction;
+mod prompt;
+mod reflow;
+mod reload;
+mod repeat;
+mod replace;
+mod rewrite;
+mod rulers;
+mod save_as;
+mod search;
+mod selection;
+mod split;
+mod status_line;
+mod symbols;
+mod tasks;
+mod template;
+pub mod theme;
+mod toast;
+mod unit_tests;
+mod unsaved;
+mod whitespace;
+mod wrap;
 
 use std::sync::Arc;
-use tokio::sync::mpsc;
-
-use crate::editor::languages::rust::tree_sitter_rust;
-use crate::editor::languages::zig::tree_sitter_zig;
-use crate::logger::log_to_file;
-use crate::models::Predictor;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::{BellStyle, Config};
+use crate::editor::annotations::Annotations;
+use crate::editor::audit::AuditEntry;
+use crate::editor::block::BlockSelection;
+use crate::editor::buffers::Buffer;
+use crate::editor::case::Case;
+use crate::editor::chat::Chat;
+use crate::editor::completion::{Completion, CompletionRequest};
+use crate::editor::encoding::FileEncoding;
+use crate::editor::export::ExportFormat;
+use crate::editor::finder::Finder;
+use crate::editor::gutter::LineChange;
+use crate::editor::history::History;
+use crate::editor::indent::IndentStyle;
+use crate::editor::languages::Language;
+use crate::editor::line_endings::LineEnding;
+use crate::editor::log_view::LogView;
+use crate::editor::marks::MarkKey;
+use crate::editor::model_info::ModelInfoRequest;
+use crate::editor::model_picker::ModelPicker;
+use crate::editor::multicursor::Caret;
+use crate::editor::open::FileLoad;
+use crate::editor::popup::Popup;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::prediction::Part;
+use crate::editor::repeat::Action;
+use crate::editor::replace::Replace;
+use crate::editor::rewrite::Rewrite;
+use crate::editor::search::Search;
+use crate::editor::selection::Selection;
+use crate::editor::split::{PaneView, Split};
+use crate::editor::symbols::SymbolIndex;
+use crate::editor::tasks::{Diagnostic, RunningTask};
+use crate::editor::template::TemplateFill;
+use crate::editor::theme::Theme;
+use crate::editor::toast::{Severity, Toast};
+use crate::editor::unit_tests::TestGeneration;
+use crate::editor::whitespace::show_whitespace;
+use crate::editor::wrap::wrap_lines;
+use crate::encryption;
+use crate::lockfile::{self, FileLock};
+use crate::logger::{log, log_to_file, Level};
+use crate::models::scope::PredictionScope;
+use crate::models::{PredictionDelta, PredictionTask, PredictionUpdate, Predictor};
+use crate::profiling::{profile_span, StartupProfile};
+use crate::remote::RemoteFile;
 use anyhow::{anyhow, Result};
 use ratatui::crossterm::{
-    event::{self, Event, KeyCode},
+    cursor::SetCursorStyle,
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+    },
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Margin, Position, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
-    Terminal,
+    Frame, Terminal,
 };
-use std::io::Stdout;
+use std::collections::BTreeMap;
+use std::io::{Stdout, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 use std::{fs, io};
-use tree_sitter::{Parser, Tree, TreeCursor};
+use tree_sitter::{InputEdit, Parser, Point, Tree, TreeCursor};
+use unicode_width::UnicodeWidthStr;
+
+const PARSE_TIMEOUT_MICROS: u64 = 500_000;
+// more rows than any terminal has, so a frame never walks the whole file
+const MAX_ROWS: usize = 1000;
+
+/// Where an accepted prediction goes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Placement {
+    // completes the current line at the cursor
+    Cursor,
+    // on new lines after the current line
+    Below,
+    // in place of the selection
+    Selection,
+}
 
 pub struct Editor {
     content: String,
@@ -32,30 +161,147 @@ pub struct Editor {
     parser: Parser,
     tree: Option<Tree>,
     filename: Option<String>,
-    prediction_rx: mpsc::Receiver<String>,
+    prediction_rx: mpsc::Receiver<PredictionUpdate>,
+    // the prediction being generated and where the cursor was when it was
+    // asked for, anything else streaming in is stale
+    pending_prediction: Option<(PredictionTask, usize)>,
     current_prediction: Option<String>,
     prediction_start_position: Option<usize>,
+    selection: Option<Selection>,
+    language: Language,
+    config: Config,
+    // decrypted buffers are never sent to the model
+    encrypted: bool,
+    remote: Option<RemoteFile>,
+    status_message: Option<String>,
+    lock: Option<FileLock>,
+    // modification time of the file when we last read or wrote it
+    disk_modified: Option<SystemTime>,
+    confirm_overwrite: bool,
+    // the border flashes until this instant after an invalid operation
+    bell_until: Option<Instant>,
+    prompt: Option<Prompt>,
+    search: Option<Search>,
+    replace: Option<Replace>,
+    // secondary carets, the primary one is cursor_position and selection
+    carets: Vec<Caret>,
+    template_fill: Option<TemplateFill>,
+    task: Option<RunningTask>,
+    // messages from the last task run, stepped through with f8
+    quickfix: Vec<Diagnostic>,
+    quickfix_index: Option<usize>,
+    // built in the background the first time it is needed, each saved file
+    // is indexed again on its own
+    symbol_index: Option<SymbolIndex>,
+    symbol_index_rx: Option<oneshot::Receiver<SymbolIndex>>,
+    annotations: Annotations,
+    model_info: Option<ModelInfoRequest>,
+    model_picker: Option<ModelPicker>,
+    popup: Option<Popup>,
+    // set when the parser gave up, so parsing is retried without waiting for an edit
+    parse_retry_at: Option<Instant>,
+    // failed parses of the text since the last edit
+    parse_retries: u32,
+    history: History,
+    // where ctrl+t jumped to the tests from, to jump back to
+    test_return: Option<usize>,
+    // long lines wrap at the pane width instead of being clipped
+    soft_wrap: bool,
+    // the gutter counts lines from the cursor line
+    relative_numbers: bool,
+    // spaces, tabs and trailing whitespace are drawn as glyphs
+    show_whitespace: bool,
+    // column vertical movement aims for, kept while passing shorter lines
+    goal_column: Option<usize>,
+    indent_style: IndentStyle,
+    // committed lines of the file for the change gutter, re-read on save
+    git_base: Option<Vec<String>>,
+    git_base_rx: Option<oneshot::Receiver<Option<Vec<String>>>>,
+    // bumped by every edit, so the gutter is only diffed again when it changed
+    revision: u64,
+    // gutter markers and the revision they were worked out for, dropped when
+    // the text changes some other way
+    gutter_cache: Option<(u64, Vec<Option<LineChange>>)>,
+    // replayed by alt+.
+    last_action: Option<Action>,
+    // typing adds to last_action while this is set
+    typing_run: bool,
+    // file being read in the background, the buffer is swapped when it's done
+    file_load: Option<FileLoad>,
+    // text cut by ctrl+k and ctrl+u, pasted by ctrl+y
+    kill_buffer: String,
+    // kills in a row add to the kill buffer instead of replacing it
+    kill_run: bool,
+    // opened and saved files, accepted predictions and tasks run, for alt+g
+    audit_log: Vec<AuditEntry>,
+    // rectangle grown by alt+shift+arrows, its lines are the carets
+    block: Option<BlockSelection>,
+    theme: Theme,
+    // what render.theme or alt+t last picked, to know which theme is next
+    theme_name: String,
+    // keys recorded since f3 started a macro
+    macro_recording: Option<Vec<Event>>,
+    // replayed by f4
+    last_macro: Vec<Event>,
+    replaying: bool,
+    // folded regions, from the end of the line they start on to where they end
+    folds: Vec<Range<usize>>,
+    // named positions set by alt+b, they move with edits like the cursor
+    marks: BTreeMap<char, usize>,
+    // alt+b or alt+j is waiting for the mark's letter
+    pending_mark: Option<MarkKey>,
+    // when the file's modification time was last looked at
+    disk_checked: Option<Instant>,
+    // modification time the reload prompt was already shown for
+    disk_dismissed: Option<SystemTime>,
+    // line endings of the file on disk, the buffer itself only has \n
+    line_ending: LineEnding,
+    // encoding of the file on disk, the buffer itself is UTF-8
+    file_encoding: FileEncoding,
+    // the buffer differs from what was last read or saved
+    modified: bool,
+    // files listed by ctrl+o while its prompt is open
+    finder: Option<Finder>,
+    // a second pane on the buffer, opened with ctrl+w
+    split: Option<Split>,
+    // ctrl+w is waiting for the window command
+    pending_window: bool,
+    // transient messages drawn over the top right corner
+    toasts: Vec<Toast>,
+    // candidates being sampled by ctrl+space
+    completion_request: Option<CompletionRequest>,
+    // the menu of sampled candidates under the cursor
+    completion: Option<Completion>,
+    // the conversation in the side panel, opened with alt+h
+    chat: Option<Chat>,
+    // the tail of the editor's log in a panel under the panes, opened with f12
+    log_view: Option<LogView>,
+    // predictions start by themselves once typing stops, toggled by :set autopredict
+    auto_predict: bool,
+    // when the idle timer started by the last keystroke runs out
+    auto_predict_at: Option<Instant>,
+    // set when `:set` changed a generation option the predictor hasn't got yet
+    options_changed: bool,
+    // the selection alt+r is having the model rewrite
+    rewrite: Option<Rewrite>,
+    // tests alt+shift+t is having the model write, opened in a new tab
+    test_generation: Option<TestGeneration>,
+    // the other open files, in tab order with the current one left out
+    buffers: Vec<Buffer>,
+    // the current buffer's tab
+    active_buffer: usize,
 }
 
 impl Editor {
-    pub fn new(path: String) -> (Self, mpsc::Sender<String>) {
+    pub fn new(path: String, config: Config) -> (Self, mpsc::Sender<PredictionUpdate>) {
         let (prediction_tx, prediction_rx) = mpsc::channel(32);
         let mut parser = Parser::new();
-        let filename = path.split(".").last().unwrap_or("rs");
-        match filename {
-            "zig" => {
-                log_to_file("Loading Zig LSP");
-                parser
-                    .set_language(tree_sitter_zig())
-                    .expect("Error loading Zig grammar");
-            }
-            _ => {
-                log_to_file("Defaulting to Rust LSP");
-                parser
-                    .set_language(tree_sitter_rust())
-                    .expect("Error loading Rust grammar");
-            }
-        }
+        let language = Language::detect(&path, &config.file_types);
+        parser
+            .set_language(language.grammar())
+            .unwrap_or_else(|_| panic!("Error loading {} grammar", language.name()));
+        // don't let a pathological file freeze the UI, highlighting degrades instead
+        parser.set_timeout_micros(PARSE_TIMEOUT_MICROS);
         (
             Editor {
                 content: String::new(),
@@ -67,6 +313,79 @@ impl Editor {
                 current_prediction: None,
                 prediction_start_position: None,
                 prediction_rx,
+                pending_prediction: None,
+                selection: None,
+                language,
+                soft_wrap: config.render.soft_wrap,
+                relative_numbers: config.render.relative_line_numbers,
+                show_whitespace: config.render.show_whitespace,
+                theme: Theme::named(&config.render.theme),
+                theme_name: config.render.theme.clone(),
+                indent_style: IndentStyle::from_config(&config.language(language.name())),
+                goal_column: None,
+                git_base: None,
+                git_base_rx: None,
+                revision: 0,
+                gutter_cache: None,
+                last_action: None,
+                typing_run: false,
+                file_load: None,
+                kill_buffer: String::new(),
+                kill_run: false,
+                audit_log: Vec::new(),
+                block: None,
+                macro_recording: None,
+                last_macro: Vec::new(),
+                replaying: false,
+                folds: Vec::new(),
+                marks: BTreeMap::new(),
+                pending_mark: None,
+                disk_checked: None,
+                disk_dismissed: None,
+                line_ending: LineEnding::Lf,
+                file_encoding: FileEncoding::default(),
+                modified: false,
+                finder: None,
+                split: None,
+                pending_window: false,
+                toasts: Vec::new(),
+                completion_request: None,
+                completion: None,
+                chat: None,
+                log_view: None,
+                auto_predict: config.model.auto_predict,
+                auto_predict_at: None,
+                options_changed: false,
+                rewrite: None,
+                test_generation: None,
+                buffers: Vec::new(),
+                active_buffer: 0,
+                config,
+                encrypted: false,
+                remote: None,
+                status_message: None,
+                lock: None,
+                disk_modified: None,
+                confirm_overwrite: false,
+                bell_until: None,
+                prompt: None,
+                search: None,
+                replace: None,
+                carets: Vec::new(),
+                template_fill: None,
+                task: None,
+                quickfix: Vec::new(),
+                quickfix_index: None,
+                symbol_index: None,
+                symbol_index_rx: None,
+                annotations: Annotations::default(),
+                model_info: None,
+                model_picker: None,
+                popup: None,
+                parse_retry_at: None,
+                parse_retries: 0,
+                history: History::default(),
+                test_return: None,
             },
             prediction_tx,
         )
@@ -74,51 +393,187 @@ impl Editor {
 
     fn save_file(&self) -> Result<()> {
         if let Some(path) = &self.filename {
-            fs::write(path, &self.content)?;
+            let local = match &self.remote {
+                Some(remote) => remote.local.to_string_lossy().to_string(),
+                None => path.clone(),
+            };
+            let text = self.line_ending.apply(&self.content);
+            if self.encrypted {
+                encryption::encrypt(&self.config.encryption, &local, &text)?;
+            } else {
+                fs::write(&local, self.file_encoding.encode(&text)?)?;
+            }
+            if let Some(remote) = &self.remote {
+                remote.push()?;
+            }
             return Ok(());
         }
         Err(anyhow!("No filename specified"))
     }
+    /// Saves the buffer, asking for confirmation first if the file was changed
+    /// on disk by someone else since we last read or wrote it.
+    fn save(&mut self) {
+        if !self.confirm_overwrite && self.changed_on_disk() {
+            self.confirm_overwrite = true;
+            self.status_message = Some(
+                "File changed on disk since it was opened, press ctrl+s again to overwrite"
+                    .to_string(),
+            );
+            return;
+        }
+        self.confirm_overwrite = false;
+        let result = self.save_file();
+        self.finish_save(result);
+    }
+
+    // reports how saving went and catches up with the file as written
+    fn finish_save(&mut self, result: Result<()>) {
+        if let (Ok(()), Some(path)) = (&result, self.filename.clone()) {
+            self.audit(format!("Saved {}", path));
+            self.modified = false;
+        }
+        match result {
+            Ok(()) => {
+                self.reindex_saved_file();
+                let message = match &self.remote {
+                    Some(remote) => format!("Saved to {}", remote.target()),
+                    None => "Saved".to_string(),
+                };
+                self.notify(Severity::Info, message);
+            }
+            Err(e) => self.notify(Severity::Error, format!("Save failed: {}", e)),
+        }
+        self.disk_modified = self.disk_modified_time();
+        self.load_git_base();
+        if let (Some(path), None) = (&self.filename, &self.remote) {
+            if let Some(holder) = lockfile::foreign_holder(Path::new(path)) {
+                self.notify(
+                    Severity::Warning,
+                    format!("Saved, but the file is also open in {}", holder),
+                );
+            }
+        }
+    }
+
+    fn disk_modified_time(&self) -> Option<SystemTime> {
+        if self.remote.is_some() {
+            return None;
+        }
+        fs::metadata(self.filename.as_ref()?)
+            .and_then(|m| m.modified())
+            .ok()
+    }
+
+    fn changed_on_disk(&self) -> bool {
+        matches!(
+            (self.disk_modified, self.disk_modified_time()),
+            (Some(seen), Some(now)) if now > seen
+        )
+    }
+
     pub fn load_file(&mut self, path: String) -> Result<()> {
-        self.content = fs::read_to_string(&path)?;
+        self.remote = RemoteFile::parse(&path);
+        let local = match &self.remote {
+            Some(remote) => {
+                remote.fetch()?;
+                self.status_message = Some(format!("Connected to {}", remote.host));
+                remote.local.to_string_lossy().to_string()
+            }
+            None => path.clone(),
+        };
+        self.encrypted = self.config.encryption.matches(&path);
+        let is_new = self.remote.is_none() && !Path::new(&local).exists();
+        let (content, encoding) = if is_new {
+            Default::default()
+        } else if self.encrypted {
+            (
+                encryption::decrypt(&self.config.encryption, &local)?,
+                FileEncoding::default(),
+            )
+        } else {
+            FileEncoding::decode(fs::read(&local)?)
+        };
+        self.install_file(path, content, encoding, is_new);
+        Ok(())
+    }
+
+    // makes a freshly read file the buffer
+    fn install_file(
+        &mut self,
+        path: String,
+        content: String,
+        encoding: FileEncoding,
+        is_new: bool,
+    ) {
+        self.file_encoding = encoding;
+        self.line_ending = LineEnding::detect(&content);
+        if LineEnding::mixed(&content) {
+            self.status_message = Some(format!(
+                "Mixed line endings, saving will use {}",
+                self.line_ending.name()
+            ));
+        }
+        self.content = LineEnding::strip(content);
+        self.modified = false;
+        self.gutter_cache = None;
+        self.set_language(Language::detect(&path, &self.config.file_types));
+        if self.large_file() {
+            self.status_message =
+                Some("Large file: highlighting is by line and the git gutter is off".to_string());
+        }
+        self.audit(format!("Opened {}", path));
+        if self.remote.is_none() {
+            let (lock, warning) = FileLock::acquire(&path);
+            self.lock = lock;
+            if warning.is_some() {
+                self.status_message = warning;
+            }
+        }
         self.filename = Some(path);
+        self.history = History::default();
+        self.disk_modified = self.disk_modified_time();
+        self.disk_dismissed = None;
         self.cursor_position = 0;
+        self.carets.clear();
+        self.folds.clear();
+        self.marks.clear();
         self.scroll_offset = 0;
+        if let Some(split) = self.split.as_mut() {
+            split.other = PaneView::default();
+        }
+        self.tree = None;
         self.update_syntax_tree();
-        Ok(())
+        self.reset_indent_style();
+        self.load_git_base();
+        if is_new {
+            self.offer_templates();
+        }
     }
 
-    fn highlight_syntax(&self, window_height: usize) -> Vec<Line> {
+    fn highlight_syntax(&self, window_height: usize) -> Vec<Line<'_>> {
         let mut result = Vec::new();
-        let lines: Vec<&str> = self.content.split('\n').collect();
-        let visible_lines = lines
-            .iter()
-            .skip(self.scroll_offset)
+        let rows: Vec<(usize, usize)> = self
+            .visible_lines()
+            .into_iter()
             .take(window_height)
-            .collect::<Vec<_>>();
+            .collect();
 
-        let (prediction_lines, prediction_start_line, cursor_column) =
-            self.get_updated_line_with_prediction();
+        // lines the prediction adds below are drawn by render_pane
+        let ghost = self.prediction_ghost();
+        let (ghost_style, _) = self.ghost_styles();
 
-        if let Some(tree) = &self.tree {
+        if let Some(tree) = self.tree.as_ref().filter(|_| !self.syntax_degraded()) {
             let root = tree.root_node();
 
-            for (line_idx, &line) in visible_lines.iter().enumerate() {
-                let absolute_line_idx = line_idx + self.scroll_offset;
-
-                // Calculate line start and end positions in the content
-                let line_start = if absolute_line_idx == 0 {
-                    0
-                } else {
-                    lines[..absolute_line_idx].join("\n").len() + 1
-                };
-                let line_end = line_start + line.len();
+            for &(line_idx, line_start) in &rows {
+                let line_end = self.line_end(line_start);
+                let line = &self.content[line_start..line_end];
 
                 let mut style_spans = Vec::new();
                 let mut cursor = root.walk();
                 cursor.reset(root);
 
-                let did_visit = Self::visit_tree_syntax(line_start, line_end, &mut style_spans, &mut cursor);
+                let did_visit = Self::visit_tree_syntax(line_start, line_end, &mut style_spans, &mut cursor, &self.theme);
                 if did_visit {
                     // todo:
                 }
@@ -141,87 +596,23 @@ impl Editor {
                 }
 
                 // we add the prediction to the file here
-                if let (Some(pred_lines), Some(start_line), Some(_)) =
-                    (&prediction_lines, prediction_start_line, cursor_column)
-                {
-                    if line_idx == start_line {
-                        log_to_file(format!("start index {} end {}", line_idx, start_line).as_str());
-                        if let Some(pred_line) = pred_lines.get(start_line) {
-                            let diff_string = find_difference(
-                                self.get_current_line_content().as_str(),
-                                pred_line.as_str(),
-                            );
-                            spans.push(Span::styled(
-                                diff_string,
-                                Style::default()
-                                    .fg(Color::LightBlue)
-                                    .add_modifier(Modifier::ITALIC),
-                            ));
-                        }
+                if let Some((start_line, predicted, _)) = &ghost {
+                    if line_idx == *start_line {
+                        spans = self.ghost_spans(spans, line, predicted, ghost_style);
                     }
                 }
 
                 result.push(Line::from(spans));
             }
-
-            if let (Some(pred_lines), _, _) =
-                (&prediction_lines, prediction_start_line, cursor_column)
-            {
-                let current_visible_end = self.scroll_offset + visible_lines.len();
-                for (idx, _) in pred_lines.iter().enumerate().skip(current_visible_end) {
-                    if idx - self.scroll_offset >= window_height {
-                        break;
-                    }
-
-                    result.push(Line::from(vec![Span::styled(
-                        pred_lines[idx].to_string(),
-                        Style::default()
-                            .fg(Color::DarkGray)
-                            .add_modifier(Modifier::ITALIC),
-                    )]));
-                }
-            }
         } else {
-            // high light syntax without tree
-            let max_lines = if let Some(pred_lines) = &prediction_lines {
-                pred_lines.len().max(lines.len())
-            } else {
-                lines.len()
-            };
-
-            for line_idx in self.scroll_offset..max_lines.min(self.scroll_offset + window_height) {
-                let mut spans = Vec::new();
-
-                if line_idx < lines.len() {
-                    spans.push(Span::raw(lines[line_idx].to_string()));
-
-                    if let (Some(pred_lines), Some(start_line), _) =
-                        (&prediction_lines, prediction_start_line, cursor_column)
-                    {
-                        if line_idx == start_line {
-                            // Add prediction after existing content
-                            if let Some(pred_line) = pred_lines.get(line_idx) {
-                                if lines[line_idx].len() < pred_line.len() {
-                                    spans.push(Span::styled(
-                                        pred_line[lines[line_idx].len()..].to_string(),
-                                        Style::default()
-                                            .fg(Color::DarkGray)
-                                            .add_modifier(Modifier::ITALIC),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                } else if let (Some(pred_lines), Some(start_line), _) =
-                    (&prediction_lines, prediction_start_line, cursor_column)
-                {
-                    if line_idx > start_line && line_idx < pred_lines.len() {
-                        spans.push(Span::styled(
-                            pred_lines[line_idx].to_string(),
-                            Style::default()
-                                .fg(Color::DarkGray)
-                                .add_modifier(Modifier::ITALIC),
-                        ));
+            // high light syntax without tree, falling back to regex highlighting
+            for &(line_idx, line_start) in &rows {
+                let line = &self.content[line_start..self.line_end(line_start)];
+                let mut spans = self.fallback_spans(line);
+
+                if let Some((start_line, predicted, _)) = &ghost {
+                    if line_idx == *start_line {
+                        spans = self.ghost_spans(spans, line, predicted, ghost_style);
                     }
                 }
 
@@ -229,11 +620,24 @@ impl Editor {
             }
         }
 
-
         result
     }
 
-    fn visit_tree_syntax(line_start: usize, line_end: usize, style_spans: &mut Vec<(usize, usize, Style)>, cursor: &mut TreeCursor) -> bool {
+    // the prediction drawn into the line it starts on
+    fn ghost_spans<'a>(
+        &self,
+        spans: Vec<Span<'a>>,
+        line: &str,
+        predicted: &str,
+        style: Style,
+    ) -> Vec<Span<'a>> {
+        match self.ghost_before_closers() {
+            Some((column, ghost)) => insert_at_column(spans, column, Span::styled(ghost, style)),
+            None => insert_ghosts(spans, line, predicted, style),
+        }
+    }
+
+    fn visit_tree_syntax(line_start: usize, line_end: usize, style_spans: &mut Vec<(usize, usize, Style)>, cursor: &mut TreeCursor, theme: &Theme) -> bool {
         let mut did_visit = false;
         loop {
             let node = cursor.node();
@@ -247,19 +651,13 @@ impl Editor {
                     | "mut" | "self" | "match" | "if" | "else" | "for" | "while"
                     | "loop" | "return" | "break" | "continue" | "const" | "static"
                     | "type" | "where" | "unsafe" | "async" | "await" | "move" | "ref"
-                    | "defer" | "try" | "var" => {
-                        Some(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
-                    }
+                    | "defer" | "try" | "var" => Some(theme.keyword),
 
                     // Module-level items
-                    "use_declaration" | "mod_item" => {
-                        Some(Style::default().fg(Color::Cyan))
-                    }
+                    "use_declaration" | "mod_item" => Some(theme.module),
 
                     // Types
-                    "type_identifier" | "primitive_type" => {
-                        Some(Style::default().fg(Color::Green))
-                    }
+                    "type_identifier" | "primitive_type" => Some(theme.type_name),
 
                     // Functions
                     "function_item" => {
@@ -268,11 +666,7 @@ impl Editor {
                             if start_byte == name.start_byte()
                                 && end_byte == name.end_byte()
                             {
-                                Some(
-                                    Style::default()
-                                        .fg(Color::Blue)
-                                        .add_modifier(Modifier::BOLD),
-                                )
+                                Some(theme.function)
                             } else {
                                 None
                             }
@@ -282,26 +676,18 @@ impl Editor {
                     }
 
                     // Variables and identifiers
-                    "identifier" => Some(Style::default().fg(Color::White)),
+                    "identifier" => Some(theme.identifier),
 
                     // Literals
-                    "string_literal" | "raw_string_literal" => {
-                        Some(Style::default().fg(Color::Yellow))
-                    }
-                    "integer_literal" | "float_literal" => {
-                        Some(Style::default().fg(Color::Magenta))
-                    }
+                    "string_literal" | "raw_string_literal" => Some(theme.string),
+                    "integer_literal" | "float_literal" => Some(theme.number),
 
                     // Comments
-                    "line_comment" | "block_comment" => Some(
-                        Style::default()
-                            .fg(Color::DarkGray)
-                            .add_modifier(Modifier::ITALIC),
-                    ),
+                    "line_comment" | "block_comment" => Some(theme.comment),
 
                     // Operators and punctuation
                     ":" | "::" | "->" | "=>" | "=" | "+" | "-" | "*" | "/" | "%" | "&"
-                    | "|" | "^" | "!" | "." => Some(Style::default().fg(Color::Yellow)),
+                    | "|" | "^" | "!" | "." => Some(theme.operator),
 
                     _ => None,
                 };
@@ -330,55 +716,11 @@ impl Editor {
         }
     }
 
-    fn get_updated_line_with_prediction(&self) -> (Option<Vec<String>>, Option<usize>, Option<usize>) {
-        if let (Some(pred), Some(start_pos)) =
-            (&self.current_prediction, self.prediction_start_position)
-        {
-            let start_line = self.content[..start_pos]
-                .chars()
-                .filter(|&c| c == '\n')
-                .count();
-
-            let line_start = self.content[..start_pos]
-                .rfind('\n')
-                .map(|pos| pos + 1)
-                .unwrap_or(0);
-            let cursor_column = start_pos - line_start;
-
-            let current_line = &self.content[line_start..start_pos];
-
-            let line_end = self.content[start_pos..]
-                .find('\n')
-                .map(|pos| start_pos + pos)
-                .unwrap_or(self.content.len());
-
-            // Get content after the current line
-            let post_content = if line_end < self.content.len() {
-                &self.content[line_end..]
-            } else {
-                ""
-            };
-
-            let new_prediction = if let Some(stripped) = pred.strip_prefix(current_line) {
-                stripped
-            } else {
-                pred
-            };
-
-            let full_content = format!("{}{}{}", current_line, new_prediction, post_content);
-
-            let pred_lines = full_content
-                .split('\n')
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>();
-
-            (Some(pred_lines), Some(start_line), Some(cursor_column))
-        } else {
-            (None, None, None)
+    fn accept_prediction(&mut self, placement: Placement) {
+        if placement != Placement::Cursor {
+            self.place_prediction(placement);
+            return;
         }
-    }
-
-    fn accept_prediction(&mut self) {
         if let (Some(pred), Some(start_pos)) = (
             self.current_prediction.take(),
             self.prediction_start_position.take(),
@@ -393,59 +735,268 @@ impl Editor {
                 .map(|pos| line_start + pos)
                 .unwrap_or(self.content.len());
 
-            let original_line = &self.content[line_start..line_end];
-            if pred.len() > original_line.len() {
-                let new_content = format!("{}{}", original_line, &pred[original_line.len()..]);
-                self.content
-                    .replace_range(line_start..line_end, &new_content);
+            if self.accept_before_closers(&pred, start_pos) {
+                self.current_prediction = None;
+                self.audit_accepted(start_pos, &pred);
+                self.hint_duplicate(&pred);
+                log(Level::Info, &format!("accepted prediction: {}", pred));
+                return;
             }
-            self.cursor_position = line_start + pred.len();
+            // the same line the ghost text was drawn from
+            let typed = &self.content[line_start..start_pos];
+            let predicted = format!("{}{}", typed, pred.strip_prefix(typed).unwrap_or(&pred));
+            let first_line = predicted.split('\n').next().unwrap_or_default();
+            let line = &self.content[line_start..line_end];
+            let Some(insertions) = diff::insertions(line, first_line) else {
+                self.status_message = Some("The prediction rewrites the line".to_string());
+                self.bell();
+                return;
+            };
+            if first_line.starts_with(line) {
+                let completion = predicted[line.len()..].to_string();
+                self.record(Action::Insert(completion.clone()));
+                // secondary carets get the same completion typed at their position
+                for idx in 0..self.carets.len() {
+                    let pos = self.carets[idx].position;
+                    self.apply_edit(pos, pos, &completion);
+                }
+                let line_end = self.line_end(line_start);
+                self.apply_edit(line_end, line_end, &completion);
+            } else {
+                // text predicted in the middle of the line goes in at the cursor only
+                let rest = &predicted[first_line.len()..];
+                for (at, text) in insertions.into_iter().rev() {
+                    self.apply_edit(line_start + at, line_start + at, text);
+                }
+                let line_end = self.line_end(line_start);
+                self.apply_edit(line_end, line_end, rest);
+            }
+            self.cursor_position = line_start + predicted.len();
             self.update_syntax_tree();
             self.current_prediction = None;
-            log_to_file(&format!("accepted prediction: {}", pred));
+            self.audit_accepted(start_pos, &pred);
+            self.hint_duplicate(&pred);
+            log(Level::Info, &format!("accepted prediction: {}", pred));
+        } else {
+            self.bell();
         }
     }
 
-    fn get_latest_prediction(&mut self) {
+    /// Inserts the predicted block on its own lines below the current line, or
+    /// in place of the selection
+    fn place_prediction(&mut self, placement: Placement) {
+        let (Some(pred), Some(start_pos)) = (
+            self.current_prediction.take(),
+            self.prediction_start_position.take(),
+        ) else {
+            self.bell();
+            return;
+        };
+        // the model echoes the start of the line, which is already in the buffer
+        let typed = &self.content[self.line_start(start_pos)..start_pos];
+        let block = pred
+            .strip_prefix(typed)
+            .unwrap_or(&pred)
+            .trim_start_matches('\n');
+        let (start, end, text) = match placement {
+            Placement::Below => {
+                let line_end = self.line_end(start_pos);
+                (line_end, line_end, format!("\n{}", block))
+            }
+            _ => match self.selection_range() {
+                Some((start, end)) => (start, end, block.to_string()),
+                None => {
+                    self.status_message = Some("No selection to replace".to_string());
+                    self.bell();
+                    return;
+                }
+            },
+        };
+        self.selection = None;
+        self.apply_edit(start, end, &text);
+        self.cursor_position = start + text.len();
+        self.update_syntax_tree();
+        self.record(match placement {
+            Placement::Below => Action::InsertBelow(block.to_string()),
+            _ => Action::Insert(text.clone()),
+        });
+        self.audit_accepted(start, &text);
+        self.hint_duplicate(&text);
+        log(Level::Info, &format!("accepted prediction {:?}: {}", placement, pred));
+    }
+
+    fn audit_accepted(&mut self, pos: usize, text: &str) {
+        let line = self.point_at(pos.min(self.content.len())).row + 1;
+        self.audit(format!("Accepted prediction at line {}: {:?}", line, text));
+    }
+
+    // returns true if a new prediction arrived
+    fn get_latest_prediction(&mut self) -> bool {
         log_to_file("checking latest prediction");
-        while let Ok(pred) = self.prediction_rx.try_recv() {
-            log_to_file(format!("got prediction from channel {}", pred).as_str());
-            self.current_prediction = Some(pred);
-            self.prediction_start_position = Some(self.cursor_position);
+        let mut received = false;
+        while let Ok(update) = self.prediction_rx.try_recv() {
+            let Some((_, at)) = self
+                .pending_prediction
+                .as_ref()
+                .filter(|(task, _)| task.id == update.id)
+            else {
+                log_to_file(&format!("dropped stale prediction {}", update.id));
+                continue;
+            };
+            let at = *at;
+            log_to_file(format!("got prediction from channel {:?}", update.delta).as_str());
+            match update.delta {
+                PredictionDelta::Replace(code) => self.current_prediction = Some(code),
+                PredictionDelta::Append(text) => match self.current_prediction.as_mut() {
+                    Some(prediction) => prediction.push_str(&text),
+                    // dismissed while it was still streaming
+                    None => continue,
+                },
+            }
+            self.prediction_start_position = Some(at);
+            received = true;
         }
-    }
-    fn get_current_line(&self) -> usize {
-        self.content[..self.cursor_position]
-            .chars()
-            .filter(|&c| c == '\n' || c == '\t')
-            .count()
+        received
     }
 
+    /// Stops the prediction being generated, if there is one. Whatever it
+    /// already sent is dropped when it arrives.
+    fn cancel_prediction(&mut self) {
+        if let Some((task, _)) = self.pending_prediction.take() {
+            log_to_file(&format!("cancelled prediction {}", task.id));
+            task.cancel();
+        }
+    }
     fn ensure_cursor_visible(&mut self, window_height: usize) {
-        let current_line = self.get_current_line();
+        self.reveal_cursor();
+        let current_line = self.point_at(self.cursor_position).row;
         if current_line < self.scroll_offset {
             self.scroll_offset = current_line;
+            return;
         }
-        if current_line >= self.scroll_offset + window_height {
-            self.scroll_offset = current_line - window_height + 1;
+        // folded lines take no rows, so count the rows up to the cursor
+        let folded = self.folded_lines();
+        let mut rows = 0;
+        for line in (self.scroll_offset..=current_line).rev() {
+            if folded.iter().any(|range| range.contains(&line)) {
+                continue;
+            }
+            rows += 1;
+            if rows == window_height {
+                self.scroll_offset = line;
+                break;
+            }
         }
     }
 
-    fn get_current_line_content(&self) -> String {
-        let line_start = self.content[..self.cursor_position]
-            .rfind('\n')
-            .map(|pos| pos + 1)
-            .unwrap_or(0);
+    /// Buffer lines in view from the scroll offset down with their start
+    /// offsets, leaving out folded lines
+    fn visible_lines(&self) -> Vec<(usize, usize)> {
+        let folded = self.folded_lines();
+        let mut line_start = match self.scroll_offset {
+            0 => 0,
+            n => match self.content.match_indices('\n').nth(n - 1) {
+                Some((pos, _)) => pos + 1,
+                None => return Vec::new(),
+            },
+        };
+        let mut lines = Vec::new();
+        for (idx, line) in self.content[line_start..].split('\n').enumerate() {
+            let idx = idx + self.scroll_offset;
+            if lines.len() == MAX_ROWS {
+                break;
+            }
+            if !folded.iter().any(|range| range.contains(&idx)) {
+                lines.push((idx, line_start));
+            }
+            line_start += line.len() + 1;
+        }
+        lines
+    }
 
-        let line_end = self.content[self.cursor_position..]
+    /// Start offsets of the buffer lines currently in view
+    fn visible_line_starts(&self) -> Vec<usize> {
+        self.visible_lines()
+            .into_iter()
+            .map(|(_, start)| start)
+            .collect()
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.content[..pos].rfind('\n').map(|p| p + 1).unwrap_or(0)
+    }
+
+    fn line_end(&self, pos: usize) -> usize {
+        self.content[pos..]
             .find('\n')
-            .map(|pos| self.cursor_position + pos)
-            .unwrap_or(self.content.len());
+            .map(|p| pos + p)
+            .unwrap_or(self.content.len())
+    }
 
-        self.content[line_start..line_end].to_string()
+    fn point_at(&self, pos: usize) -> Point {
+        Point::new(
+            self.content[..pos].matches('\n').count(),
+            pos - self.line_start(pos),
+        )
+    }
+
+    /// Replaces `start..end` with `text`, keeping the cursor and selection anchor
+    /// attached to the text around them. The syntax tree is told about the edit
+    /// so the next parse can reuse it; callers are responsible for reparsing.
+    fn apply_edit(&mut self, start: usize, end: usize, text: &str) {
+        let start_position = self.point_at(start);
+        let old_end_position = self.point_at(end);
+        let removed = self.content[start..end].to_string();
+        self.history.record(start, removed, text, self.cursor_position);
+        self.content.replace_range(start..end, text);
+        self.modified = true;
+        self.revision += 1;
+        let new_end_byte = start + text.len();
+        let new_end_position = self.point_at(new_end_byte);
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte: start,
+                old_end_byte: end,
+                new_end_byte,
+                start_position,
+                old_end_position,
+                new_end_position,
+            });
+        }
+        let shift = |pos: usize| {
+            if pos >= end {
+                pos - (end - start) + text.len()
+            } else if pos > start {
+                start
+            } else {
+                pos
+            }
+        };
+        self.cursor_position = shift(self.cursor_position);
+        if let Some(sel) = self.selection.as_mut() {
+            sel.anchor = shift(sel.anchor);
+        }
+        for caret in self.carets.iter_mut() {
+            caret.position = shift(caret.position);
+            if let Some(sel) = caret.selection.as_mut() {
+                sel.anchor = shift(sel.anchor);
+            }
+        }
+        for position in self.marks.values_mut() {
+            *position = shift(*position);
+        }
+        if let Some(split) = self.split.as_mut() {
+            split.other.cursor = shift(split.other.cursor);
+        }
+        // folds below the edit move with it, the ones it touches open up
+        let untouched = |fold: &Range<usize>| end <= fold.start || start >= fold.end;
+        self.folds.retain(untouched);
+        for fold in self.folds.iter_mut().filter(|fold| fold.start >= end) {
+            *fold = shift(fold.start)..shift(fold.end);
+        }
     }
 
-    fn clear_current_line(&mut self) {
+    fn get_current_line_content(&self) -> String {
         let line_start = self.content[..self.cursor_position]
             .rfind('\n')
             .map(|pos| pos + 1)
@@ -453,154 +1004,245 @@ impl Editor {
 
         let line_end = self.content[self.cursor_position..]
             .find('\n')
-            .map(|pos| self.cursor_position + pos + 1)
+            .map(|pos| self.cursor_position + pos)
             .unwrap_or(self.content.len());
 
-        // todo fix: updating position to avoid overflow when rmeoving lines
-        self.content.replace_range(line_start..line_end, "");
+        self.content[line_start..line_end].to_string()
     }
 
-    fn insert_char(&mut self, c: char, cursor_position: usize) {
+    fn insert_char(&mut self, c: char) {
         if c == '\n' {
             self.current_prediction = None;
             self.prediction_start_position = None;
         }
         if c == '\t' {
-            // Insert 4 spaces instead of a tab character
-            for _ in 0..4 {
-                self.content.insert(self.cursor_position, ' ');
-                self.cursor_position += 1;
-            }
+            // one level of the buffer's indentation rather than a literal tab
+            let unit = self.indent_unit();
+            self.insert_text(&unit);
         } else {
-            self.content.insert(self.cursor_position, c);
-            self.cursor_position += cursor_position;
+            self.insert_text(c.encode_utf8(&mut [0; 4]));
         }
+    }
+
+    fn insert_text(&mut self, text: &str) {
+        let pos = self.cursor_position;
+        self.apply_edit(pos, pos, text);
         self.update_syntax_tree();
     }
 
     fn delete_char(&mut self) {
-        if self.cursor_position > 0 {
-            self.content.remove(self.cursor_position - 1);
-            self.cursor_position -= 1;
-            self.update_syntax_tree();
+        match self.prev_boundary(self.cursor_position) {
+            Some(start) => {
+                let pos = self.cursor_position;
+                self.apply_edit(start, pos, "");
+                self.update_syntax_tree();
+            }
+            None => self.bell(),
+        }
+    }
+
+    /// Signals an invalid operation using the configured bell
+    fn bell(&mut self) {
+        match self.config.bell {
+            BellStyle::Visual => {
+                self.bell_until = Some(Instant::now() + Duration::from_millis(150));
+            }
+            BellStyle::Audible => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+            }
+            BellStyle::None => {}
         }
     }
 
     fn move_cursor_left(&mut self) {
-        if self.cursor_position > 0 {
-            self.cursor_position -= 1;
+        match self.prev_boundary(self.cursor_position) {
+            Some(pos) => {
+                self.cursor_position = pos;
+                self.skip_folded(false);
+            }
+            None => self.bell(),
         }
     }
 
     fn move_cursor_right(&mut self) {
-        if self.cursor_position < self.content.len() {
-            self.cursor_position += 1;
+        let from = self.cursor_position;
+        match self.next_boundary(self.cursor_position) {
+            Some(pos) => {
+                self.cursor_position = pos;
+                if !self.skip_folded(true) {
+                    self.cursor_position = from;
+                    self.bell();
+                }
+            }
+            None => self.bell(),
         }
     }
 
     fn move_cursor_up(&mut self) {
         self.current_prediction = None;
         self.prediction_start_position = None;
-        // Get current line's start position
-        let current_line_start = self.content[..self.cursor_position]
-            .rfind('\n')
-            .map(|pos| pos + 1)
-            .unwrap_or(0);
-
-        // Get position within current line
-        let line_offset = self.cursor_position - current_line_start;
-
-        // Find start of previous line
-        if let Some(prev_line_start) = self.content[..current_line_start.saturating_sub(1)]
-            .rfind('\n')
-            .map(|pos| pos + 1)
-        {
-            // Get length of previous line
-            let prev_line_length = self.content
-                [prev_line_start..current_line_start.saturating_sub(1)]
-                .chars()
-                .count();
-
-            // Move cursor to same offset in previous line, or end of line if shorter
-            self.cursor_position = prev_line_start + line_offset.min(prev_line_length);
-        } else if current_line_start > 0 {
-            // We're on the second line, move to first line
-            self.cursor_position = line_offset.min(current_line_start - 1);
+        let line_start = self.line_start(self.cursor_position);
+        if line_start == 0 {
+            self.bell();
+            return;
         }
+        // columns rather than bytes, so the cursor stays put visually across
+        // lines with wide or multi-byte characters
+        let column = self.goal_column();
+        let line = self.unfolded_line(self.point_at(line_start - 1).row, false);
+        self.cursor_position = self.offset_at_column(self.line_offset(line), column);
     }
 
     fn move_cursor_down(&mut self) {
-        let current_line_start = self.content[..self.cursor_position]
-            .rfind('\n')
-            .map(|pos| pos + 1)
-            .unwrap_or(0);
-        let current_line_end = self.content[self.cursor_position..]
-            .find('\n')
-            .map(|pos| self.cursor_position + pos)
-            .unwrap_or(self.content.len());
-
-        let line_offset = self.cursor_position - current_line_start;
-        if self.content.len() <= current_line_end + 1 {
+        let line = self.point_at(self.cursor_position).row;
+        let below = self.unfolded_line(line + 1, true);
+        if below > self.content.matches('\n').count() {
+            self.bell();
             return;
         }
-        if let Some(next_line_end) = self.content[current_line_end + 1..]
-            .find('\n')
-            .map(|pos| current_line_end + 1 + pos)
-            .or({
-                if current_line_end < self.content.len() {
-                    Some(self.content.len())
-                } else {
-                    None
-                }
-            })
-        {
-            let next_line_length = next_line_end - (current_line_end + 1);
-            self.cursor_position = (current_line_end + 1) + line_offset.min(next_line_length);
-        }
+        let column = self.goal_column();
+        self.cursor_position = self.offset_at_column(self.line_offset(below), column);
+    }
+
+    // the column the first of a run of vertical moves started from
+    fn goal_column(&mut self) -> usize {
+        let column = self
+            .goal_column
+            .unwrap_or_else(|| self.column_of(self.cursor_position));
+        self.goal_column = Some(column);
+        column
     }
 
     fn update_syntax_tree(&mut self) {
+        self.parse_retries = 0;
+        self.parse_syntax_tree();
+    }
+
+    // parses the buffer, scheduling a retry if the parser gives up
+    fn parse_syntax_tree(&mut self) {
+        if self.large_file() {
+            self.tree = None;
+            self.parse_retry_at = None;
+            return;
+        }
+        profile_span!("parse");
         self.tree = self.parser.parse(&self.content, self.tree.as_ref());
-        if let Some(tree) = &self.tree {
-            log_to_file("Syntax tree generated successfully");
-            let root = tree.root_node();
-            log_to_file(&format!("Root node type: {}", root.kind()));
-
-            // Log first few nodes for debugging
-            let mut cursor = root.walk();
-            let mut count = 0;
-            while cursor.goto_first_child() || cursor.goto_next_sibling() {
-                let node = cursor.node();
-                log_to_file(&format!(
-                    "Node {}: kind={}, text={:?}",
-                    count,
-                    node.kind(),
-                    self.content[node.start_byte()..node.end_byte()].to_string()
-                ));
-                count += 1;
-                if count >= 10 {
-                    break;
-                } // Log first 10 nodes only
-            }
+        if self.tree.is_none() {
+            log(Level::Warn, "Failed to generate syntax tree");
+            // a timed out parse resumes on the next call unless the parser is reset
+            self.parser.reset();
+            self.schedule_parse_retry();
         } else {
-            log_to_file("Failed to generate syntax tree");
+            self.parse_retry_at = None;
         }
     }
 }
 
-pub async fn run(mut editor: Editor, mut predictor: Arc<Predictor>) -> Result<()> {
+pub async fn run(
+    mut editor: Editor,
+    mut predictor: Arc<Predictor>,
+    startup: &mut StartupProfile,
+) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+    if editor.config.render.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    if editor.config.render.reduced_motion {
+        execute!(stdout, SetCursorStyle::SteadyBlock)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
+    startup.mark("terminal");
+
+    let poll_interval = Duration::from_millis(editor.config.render.poll_interval_ms);
+    let frame_interval = Duration::from_secs(1) / editor.config.render.max_fps.max(1);
+    let mut last_draw: Option<Instant> = None;
+    let mut last_size = None;
+    let mut dirty = true;
+    // the spinner frame and token count the status bar last showed
+    let mut progress = None;
 
     loop {
-        let window_height = terminal.size()?.height as usize - 2;
-        editor.ensure_cursor_visible(window_height);
-        editor.get_latest_prediction();
-        redraw_editor(&mut terminal, &mut editor)?;
-        if event::poll(std::time::Duration::from_millis(10))? {
+        let size = terminal.size()?;
+        // a resize is drawn straight away instead of waiting for the frame cap
+        if last_size != Some(size) {
+            last_size = Some(size);
+            last_draw = None;
+            dirty = true;
+        }
+        let window_height = editor.window_height(size.width, size.height);
+        editor.scroll_panes(window_height);
+        dirty |= editor.get_latest_prediction();
+        dirty |= editor.retry_parse_if_due();
+        if editor.poll_file_load() {
+            dirty = true;
+            startup.mark("file read");
+        }
+        editor.sync_exclusion(&predictor);
+        if editor.poll_git_base() {
+            dirty = true;
+            startup.mark("git");
+        }
+        dirty |= editor.poll_symbol_index();
+        dirty |= editor.poll_template_fill();
+        dirty |= editor.poll_task();
+        dirty |= editor.poll_annotations();
+        dirty |= editor.poll_model_info();
+        dirty |= editor.poll_model_picker(&predictor);
+        dirty |= editor.poll_completions();
+        dirty |= editor.poll_chat();
+        dirty |= editor.poll_rewrite();
+        dirty |= editor.poll_test_generation();
+        dirty |= editor.poll_log();
+        dirty |= editor.poll_auto_prediction(&predictor);
+        if std::mem::take(&mut editor.options_changed) {
+            predictor.set_options(editor.config.model.options.clone());
+        }
+        dirty |= editor.poll_disk_changes();
+        // the status bar shows when a prediction starts and stops, with a
+        // spinner and the tokens received while it streams
+        let now = predictor
+            .is_predicting()
+            .then(|| (status_line::spinner_frame(), predictor.streamed_tokens()));
+        if now != progress {
+            progress = now;
+            dirty = true;
+        }
+        if let Some(notice) = predictor.take_fallback() {
+            editor.notify(Severity::Warning, notice);
+            dirty = true;
+        }
+        if let Some(error) = predictor.take_failure() {
+            editor.notify(Severity::Error, error);
+            dirty = true;
+        }
+        dirty |= editor.poll_toasts();
+        if matches!(editor.bell_until, Some(until) if Instant::now() >= until) {
+            editor.bell_until = None;
+            dirty = true;
+        }
+
+        // redraws are capped at max_fps so a fast stream of prediction chunks
+        // can't drive the frame rate
+        let since_draw = last_draw.map_or(frame_interval, |t| t.elapsed());
+        if dirty && since_draw >= frame_interval {
+            editor.request_annotations(&predictor, window_height);
+            redraw_editor(&mut terminal, &mut editor, &predictor)?;
+            startup.mark("first frame");
+            last_draw = Some(Instant::now());
+            dirty = false;
+        }
+        let timeout = if dirty {
+            poll_interval.min(frame_interval.saturating_sub(since_draw))
+        } else {
+            poll_interval
+        };
+
+        if event::poll(timeout)? {
+            dirty = true;
             // return true to exit, else continue
             if let Ok(true) = handle_key_bindings(&mut editor, &mut predictor) {
                 break;
@@ -609,184 +1251,1306 @@ pub async fn run(mut editor: Editor, mut predictor: Arc<Predictor>) -> Result<()
     }
 
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if editor.config.render.mouse {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    if editor.config.render.reduced_motion {
+        execute!(terminal.backend_mut(), SetCursorStyle::DefaultUserShape)?;
+    }
+    execute!(
+        terminal.backend_mut(),
+        DisableBracketedPaste,
+        LeaveAlternateScreen
+    )?;
     Ok(())
 }
 
 fn handle_key_bindings(editor: &mut Editor, predictor: &mut Arc<Predictor>) -> Result<bool> {
-    if let Event::Key(key) = event::read()? {
+    let event = event::read()?;
+    // a prediction is for the spot it was asked for, typing or moving away
+    // makes it stale
+    let before = (editor.cursor_position, editor.content.len());
+    // everything one key does is undone in one go
+    editor.history.begin_step(editor.cursor_position);
+    let exit = handle_event(editor, predictor, event);
+    editor.history.end_step();
+    // the key may have switched to a buffer that mustn't be sent
+    editor.sync_exclusion(predictor);
+    if (editor.cursor_position, editor.content.len()) != before {
+        editor.cancel_prediction();
+    }
+    exit
+}
+
+fn handle_event(editor: &mut Editor, predictor: &mut Arc<Predictor>, event: Event) -> Result<bool> {
+    if let Event::Resize(width, height) = event {
+        editor.resize(width, height);
+        return Ok(false);
+    }
+    // the buffer is about to be replaced, so only cancelling is allowed
+    if editor.file_load.is_some() {
+        if let Event::Key(key) = event {
+            if key.code == KeyCode::Esc && key.kind != KeyEventKind::Release {
+                editor.cancel_file_load();
+            }
+        }
+        return Ok(false);
+    }
+    if let Event::Mouse(mouse) = event {
+        editor.auto_predict_at = None;
+        if editor.popup.take().is_some() || editor.prompt.is_some() {
+            return Ok(false);
+        }
+        let (width, height) = terminal::size()?;
+        // the bottom row is the status bar
+        editor.handle_mouse(mouse, Rect::new(0, 0, width, height.saturating_sub(1)));
+        return Ok(false);
+    }
+    if macros::handle_macro_keys(editor, predictor, &event)? {
+        return Ok(false);
+    }
+    if let Event::Paste(text) = &event {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        handle_paste(editor, &text);
+        return Ok(false);
+    }
+    if let Event::Key(key) = event {
+        if key.kind == KeyEventKind::Release {
+            return Ok(false);
+        }
+        // only a second ctrl+s confirms, anything else asks again
+        if !(key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL)) {
+            editor.confirm_overwrite = false;
+        }
+        // typing starts the timer again further down, anything else stops it
+        editor.auto_predict_at = None;
+        if printable_char(&key).is_none()
+            && !matches!(key.code, KeyCode::Enter | KeyCode::Backspace)
+        {
+            editor.end_typing_run();
+        }
+        if !(key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Char('k') | KeyCode::Char('u')))
+        {
+            editor.kill_run = false;
+        }
+        if !is_block_key(&key) {
+            editor.block = None;
+        }
+        // anything but up and down starts a new goal column
+        if !matches!(key.code, KeyCode::Up | KeyCode::Down) {
+            editor.goal_column = None;
+            for caret in editor.carets.iter_mut() {
+                caret.goal_column = None;
+            }
+        }
+        if editor.rewrite_ready() {
+            editor.handle_rewrite_key(key);
+            return Ok(false);
+        }
+        if let Some(popup) = editor.popup.as_mut() {
+            match key.code {
+                KeyCode::Up => popup.scroll_by(-1),
+                KeyCode::Down => popup.scroll_by(1),
+                KeyCode::PageUp => popup.scroll_by(-10),
+                KeyCode::PageDown => popup.scroll_by(10),
+                _ => editor.popup = None,
+            }
+            return Ok(false);
+        }
+        if editor.model_picker_open() {
+            editor.handle_model_picker_key(predictor, key);
+            return Ok(false);
+        }
+        if editor.pending_mark.is_some() {
+            editor.finish_mark(key);
+            return Ok(false);
+        }
+        if editor.pending_window {
+            editor.finish_window_command(key);
+            return Ok(false);
+        }
+        if editor.completion.is_some() && editor.handle_completion_key(key) {
+            return Ok(false);
+        }
+        if let Some(PromptKind::UnsavedChanges) = editor.prompt.as_ref().map(|p| p.kind) {
+            return Ok(editor.handle_unsaved_changes_key(key));
+        }
+        if let (Some(PromptKind::Command), KeyCode::Enter) =
+            (editor.prompt.as_ref().map(|p| p.kind), key.code)
+        {
+            return Ok(editor.run_command_line());
+        }
+        if editor.prompt.is_some() {
+            handle_prompt_key_bindings(editor, predictor, key);
+            return Ok(false);
+        }
+        if editor.replace.is_some() {
+            handle_replace_key_bindings(editor, key);
+            return Ok(false);
+        }
+        if editor.search.is_some() && handle_search_key_bindings(editor, key) {
+            return Ok(false);
+        }
+        if !editor.carets.is_empty() && key.code == KeyCode::Esc {
+            editor.clear_carets();
+            return Ok(false);
+        }
+        if editor.selection.is_some() && handle_selection_key_bindings(editor, key) {
+            return Ok(false);
+        }
         match key.code {
+            KeyCode::Char('s') | KeyCode::Char('S')
+                if key
+                    .modifiers
+                    .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) =>
+            {
+                editor.open_save_as_prompt();
+            }
+            // a new buffer has nowhere to go yet
+            KeyCode::Char('s')
+                if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                    && editor.filename.is_none() =>
+            {
+                editor.open_save_as_prompt();
+            }
             KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
-                editor.save_file()?;
+                editor.save();
+            }
+            KeyCode::Char('e') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.status_message = Some(match editor.export(ExportFormat::Html) {
+                    Ok(path) => format!("Exported to {}", path),
+                    Err(e) => format!("Export failed: {}", e),
+                });
+            }
+            KeyCode::Char('f') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.open_search();
+            }
+            KeyCode::Char('h') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.open_replace();
+            }
+            KeyCode::Char('b') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.open_task_prompt();
+            }
+            KeyCode::Char('o') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.open_finder();
+            }
+            KeyCode::Char('w') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.begin_window_command();
+            }
+            KeyCode::F(1) => editor.show_help(),
+            KeyCode::F(12) => editor.toggle_log(),
+            KeyCode::F(8) => {
+                editor.jump_to_diagnostic(!key.modifiers.contains(KeyModifiers::SHIFT));
+            }
+            KeyCode::Char('d') | KeyCode::Char('D')
+                if key
+                    .modifiers
+                    .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) =>
+            {
+                editor.duplicate_lines();
+            }
+            KeyCode::Char('d') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.add_next_occurrence();
+            }
+            KeyCode::Char('z') | KeyCode::Char('Z')
+                if key
+                    .modifiers
+                    .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) =>
+            {
+                editor.redo();
+            }
+            KeyCode::Char('z') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.undo();
+            }
+            KeyCode::Char('r') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.redo();
+            }
+            // terminals report ctrl+/ as ctrl+7 unless they support the kitty protocol
+            KeyCode::Char('/') | KeyCode::Char('7')
+                if key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+            {
+                editor.toggle_comment_selection();
+            }
+            KeyCode::Char('t') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.toggle_test();
             }
             KeyCode::Char('k') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
-                editor.clear_current_line();
+                editor.kill(true);
+            }
+            KeyCode::Char('u') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.kill(false);
+            }
+            KeyCode::Char('y') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.yank();
+            }
+            KeyCode::Char('a') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_annotations();
+            }
+            KeyCode::Char('a') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                editor.select_all();
+            }
+            KeyCode::Char('w') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.select_word();
+            }
+            KeyCode::Char('l') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.select_line();
+            }
+            KeyCode::Char('i') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_indent_tabs();
+            }
+            KeyCode::Char('.') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.repeat_last_action();
+            }
+            KeyCode::Char('u') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.transform_case(Case::Upper);
+            }
+            KeyCode::Char('U') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.transform_case(Case::Lower);
+            }
+            KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.open_case_prompt();
+            }
+            KeyCode::Char('d') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.show_git_diff();
+            }
+            KeyCode::Char('f') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_fold();
+            }
+            KeyCode::Char('F') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_all_folds();
+            }
+            KeyCode::Char('q') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.reflow_comment();
+            }
+            KeyCode::Char('g') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.open_audit_prompt();
+            }
+            KeyCode::Char('z') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_soft_wrap();
+            }
+            KeyCode::Char('t') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.cycle_theme();
+            }
+            KeyCode::Char('n') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_relative_numbers();
+            }
+            KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_whitespace();
+            }
+            KeyCode::Char('h') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_chat();
+            }
+            KeyCode::Char('H') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.explain_code(predictor);
+            }
+            KeyCode::Char('y') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.insert_chat_code();
+            }
+            KeyCode::Char('r') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.open_rewrite_prompt();
+            }
+            KeyCode::Char('R') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.generate_doc_comment(predictor);
+            }
+            KeyCode::Char('T') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.generate_tests(predictor);
+            }
+            KeyCode::PageDown if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                editor.cycle_buffer(true);
+            }
+            KeyCode::PageUp if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                editor.cycle_buffer(false);
             }
-            KeyCode::Tab => {
+            // there is no normal mode for a bare : to open it from, typing
+            // one has to insert a colon
+            KeyCode::Char('x') | KeyCode::Char(':')
+                if key.modifiers.contains(event::KeyModifiers::ALT) =>
+            {
+                editor.open_command_line();
+            }
+            KeyCode::Char('p') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.preview_prediction();
+            }
+            KeyCode::Char('m') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.request_model_info(predictor);
+            }
+            KeyCode::Char('M') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.open_model_picker(predictor);
+            }
+            KeyCode::Char('v') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_line_selection();
+            }
+            KeyCode::Char('b') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.begin_mark(MarkKey::Set);
+            }
+            KeyCode::Char('j') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.begin_mark(MarkKey::Jump);
+            }
+            KeyCode::Char('e') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.toggle_line_ending();
+            }
+            KeyCode::Char('E') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                editor.open_encoding_prompt();
+            }
+            _ if is_block_key(&key) => editor.extend_block(key.code),
+            KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                editor.move_lines(key.code == KeyCode::Up);
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down
+                if key.modifiers.contains(event::KeyModifiers::SHIFT) =>
+            {
+                editor.for_each_caret(|e| {
+                    e.begin_selection();
+                    move_cursor(e, key.code);
+                });
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                // ctrl+tab is swallowed by many terminals, so alt+tab works as well
+                let modified = key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+                let back = key.code == KeyCode::BackTab;
                 if editor.current_prediction.is_some() {
-                    editor.accept_prediction();
+                    editor.accept_prediction(match (back, modified) {
+                        (true, _) => Placement::Below,
+                        (false, true) => Placement::Selection,
+                        (false, false) => Placement::Cursor,
+                    });
                 } else {
-                    let content = editor.get_current_line_content();
-                    predictor.clone().stream_prediction_background(content);
+                    let scope = match (back, modified) {
+                        (false, false) => PredictionScope::Line,
+                        (true, false) => PredictionScope::Block,
+                        (false, true) => PredictionScope::Function,
+                        (true, true) => PredictionScope::File,
+                    };
+                    editor.request_prediction(predictor, scope);
                 }
             }
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => {
+                editor.request_prediction(predictor, PredictionScope::Multiline);
+            }
+            KeyCode::Right | KeyCode::Down
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && editor.current_prediction.is_some() =>
+            {
+                editor.accept_prediction_part(match key.code {
+                    KeyCode::Right => Part::Word,
+                    _ => Part::Line,
+                });
+            }
             KeyCode::Esc => {
                 editor.current_prediction = None;
                 editor.prediction_start_position = None;
-                return Ok(true);
+                editor.cancel_prediction();
+                return Ok(editor.request_quit());
             }
-            KeyCode::Char(c) => {
-                editor.current_prediction = None;
-                editor.prediction_start_position = None;
-                editor.insert_char(c, 1);
+            KeyCode::Char(' ') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                editor.request_completions(predictor);
+            }
+            KeyCode::Char(_) => {
+                if let Some(c) = printable_char(&key) {
+                    editor.current_prediction = None;
+                    editor.prediction_start_position = None;
+                    // macros keep every key as its own event
+                    let (text, next) = if editor.macro_recording.is_some() || editor.replaying {
+                        (c.to_string(), None)
+                    } else {
+                        read_queued_text(c)?
+                    };
+                    editor.for_each_caret(|e| e.type_text(&text));
+                    editor.record_typed(&text);
+                    editor.schedule_auto_prediction();
+                    if let Some(next) = next {
+                        return handle_event(editor, predictor, next);
+                    }
+                }
             }
             // KeyCode::Tab => editor.insert_char('\t', 4),
-            KeyCode::Enter => editor.insert_char('\n', 1),
-            KeyCode::Backspace => editor.delete_char(),
-            KeyCode::Left => editor.move_cursor_left(),
-            KeyCode::Right => editor.move_cursor_right(),
-            KeyCode::Up => editor.move_cursor_up(),
-            KeyCode::Down => editor.move_cursor_down(),
+            KeyCode::Enter => {
+                editor.for_each_caret(Editor::insert_newline);
+                editor.record_typed("\n");
+            }
+            KeyCode::Backspace => {
+                editor.for_each_caret(Editor::backspace);
+                editor.record_backspace();
+                editor.schedule_auto_prediction();
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
+                editor.for_each_caret(|e| {
+                    e.clear_selection();
+                    move_cursor(e, key.code);
+                });
+            }
             _ => {}
         }
     }
     Ok(false)
 }
 
+// alt+shift+arrows grow the block selection
+fn is_block_key(key: &KeyEvent) -> bool {
+    let alt_shift = KeyModifiers::ALT | KeyModifiers::SHIFT;
+    let arrow = matches!(
+        key.code,
+        KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down
+    );
+    arrow && key.modifiers.contains(alt_shift)
+}
+
+// AltGr arrives as ctrl+alt on some platforms and still produces a printable character
+fn printable_char(key: &KeyEvent) -> Option<char> {
+    let ctrl_alt = KeyModifiers::CONTROL | KeyModifiers::ALT;
+    match key.code {
+        KeyCode::Char(c)
+            if (key.modifiers & ctrl_alt).is_empty() || key.modifiers.contains(ctrl_alt) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+// Dead keys and IME commits can deliver a composed grapheme as several queued
+// key events, so everything already waiting is inserted as a single edit. The
+// first non printable event is handed back to be handled normally.
+fn read_queued_text(first: char) -> Result<(String, Option<Event>)> {
+    let mut text = first.to_string();
+    while event::poll(Duration::ZERO)? {
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Release => {}
+            Event::Key(key) => match printable_char(&key) {
+                Some(c) => text.push(c),
+                None => return Ok((text, Some(Event::Key(key)))),
+            },
+            event => return Ok((text, Some(event))),
+        }
+    }
+    Ok((text, None))
+}
+
+fn move_cursor(editor: &mut Editor, code: KeyCode) {
+    match code {
+        KeyCode::Left => editor.move_cursor_left(),
+        KeyCode::Right => editor.move_cursor_right(),
+        KeyCode::Up => editor.move_cursor_up(),
+        KeyCode::Down => editor.move_cursor_down(),
+        _ => {}
+    }
+}
+
+fn handle_prompt_key_bindings(editor: &mut Editor, predictor: &Arc<Predictor>, key: KeyEvent) {
+    let Some(prompt) = editor.prompt.as_mut() else {
+        return;
+    };
+    let kind = prompt.kind;
+    if kind == PromptKind::DiskChanged {
+        editor.handle_disk_changed_key(key);
+        return;
+    }
+    match key.code {
+        KeyCode::Esc => match kind {
+            PromptKind::Search => editor.cancel_search(),
+            PromptKind::ReplaceFind | PromptKind::ReplaceWith => editor.cancel_replace(),
+            PromptKind::FindFile => editor.cancel_finder(),
+            PromptKind::Template
+            | PromptKind::TemplateDescription
+            | PromptKind::Task
+            | PromptKind::Case
+            | PromptKind::AuditFilter
+            | PromptKind::MacroCount
+            | PromptKind::SaveAs
+            | PromptKind::ConfirmSaveAs
+            | PromptKind::DiskChanged
+            | PromptKind::Encoding
+            | PromptKind::UnsavedChanges
+            | PromptKind::Chat
+            | PromptKind::LogSearch
+            | PromptKind::Command => editor.prompt = None,
+            PromptKind::Rewrite => editor.cancel_rewrite(),
+        },
+        KeyCode::Enter => match kind {
+            PromptKind::Search => editor.confirm_search(),
+            PromptKind::ReplaceFind => editor.confirm_replace_pattern(),
+            PromptKind::ReplaceWith => editor.confirm_replacement(),
+            PromptKind::Template => editor.confirm_template(),
+            PromptKind::TemplateDescription => editor.confirm_template_description(predictor),
+            PromptKind::Task => editor.confirm_task(),
+            PromptKind::Case => editor.confirm_case(),
+            PromptKind::AuditFilter => editor.confirm_audit_filter(),
+            PromptKind::MacroCount => macros::confirm_macro_count(editor, predictor),
+            PromptKind::SaveAs => editor.confirm_save_as(),
+            PromptKind::ConfirmSaveAs => editor.confirm_save_as_overwrite(),
+            // handled before the prompt keys, they can exit
+            PromptKind::DiskChanged | PromptKind::UnsavedChanges | PromptKind::Command => {}
+            PromptKind::Encoding => editor.confirm_encoding(),
+            PromptKind::FindFile => editor.confirm_finder(),
+            PromptKind::Chat => editor.send_chat_message(predictor),
+            PromptKind::Rewrite => editor.confirm_rewrite_instruction(predictor),
+            // the log is filtered as the search is typed
+            PromptKind::LogSearch => editor.prompt = None,
+        },
+        KeyCode::F(12) if kind == PromptKind::LogSearch => editor.toggle_log(),
+        KeyCode::Char('l')
+            if kind == PromptKind::LogSearch && key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            if let Some(view) = editor.log_view.as_mut() {
+                view.cycle_level();
+            }
+        }
+        KeyCode::Char('h')
+            if kind == PromptKind::Chat && key.modifiers.contains(KeyModifiers::ALT) =>
+        {
+            editor.toggle_chat();
+        }
+        KeyCode::Up | KeyCode::Down if kind == PromptKind::FindFile => {
+            editor.move_finder_selection(key.code == KeyCode::Up);
+        }
+        KeyCode::Char('r')
+            if kind == PromptKind::ReplaceFind && key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            editor.toggle_replace_regex();
+        }
+        KeyCode::Char('s')
+            if kind == PromptKind::AuditFilter && key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            editor.export_audit_log();
+        }
+        KeyCode::Backspace => {
+            prompt.input.pop();
+        }
+        KeyCode::Char(_) => {
+            if let Some(c) = printable_char(&key) {
+                prompt.input.push(c);
+            }
+        }
+        _ => return,
+    }
+    let typed = matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace);
+    prompt_input_changed(editor, kind, typed);
+}
+
+// `typed` is set when the input itself changed rather than the selection
+fn prompt_input_changed(editor: &mut Editor, kind: PromptKind, typed: bool) {
+    if kind == PromptKind::Search && editor.prompt.is_some() {
+        editor.update_incremental_search();
+    }
+    if kind == PromptKind::LogSearch {
+        editor.update_log_search();
+    }
+    if kind == PromptKind::FindFile && typed {
+        editor.reset_finder_selection();
+    }
+}
+
+// a paste goes to whatever has the keyboard, and only edits the buffer when
+// nothing is open over it
+fn handle_paste(editor: &mut Editor, text: &str) {
+    if let Some(prompt) = editor.prompt.as_mut() {
+        let kind = prompt.kind;
+        if matches!(
+            kind,
+            PromptKind::UnsavedChanges | PromptKind::DiskChanged | PromptKind::ConfirmSaveAs
+        ) {
+            editor.bell();
+            return;
+        }
+        // prompts are a single line
+        prompt.input.push_str(&text.replace('\n', " "));
+        prompt_input_changed(editor, kind, true);
+        return;
+    }
+    if editor.popup.is_some()
+        || editor.rewrite_ready()
+        || editor.completion.is_some()
+        || editor.model_picker_open()
+        || editor.replace.is_some()
+        || editor.pending_mark.is_some()
+        || editor.pending_window
+    {
+        editor.bell();
+        return;
+    }
+    editor.search = None;
+    editor.goal_column = None;
+    for caret in editor.carets.iter_mut() {
+        caret.goal_column = None;
+    }
+    editor.for_each_caret(|e| {
+        e.delete_selection();
+        e.insert_text(text);
+    });
+    editor.record(Action::Insert(text.to_string()));
+    editor.hint_duplicate(text);
+}
+
+// once both replace prompts are answered each match is confirmed with y/n/a
+fn handle_replace_key_bindings(editor: &mut Editor, key: KeyEvent) {
+    if editor.replace.as_ref().is_some_and(|r| !r.confirming) {
+        return;
+    }
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Enter => editor.replace_current(),
+        KeyCode::Char('n') => editor.skip_current(),
+        KeyCode::Char('a') => editor.replace_all(),
+        KeyCode::Esc | KeyCode::Char('q') => editor.cancel_replace(),
+        _ => {}
+    }
+}
+
+// n/N step through the matches of a confirmed search, any other key ends it
+fn handle_search_key_bindings(editor: &mut Editor, key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('n') if key.modifiers.is_empty() => editor.jump_to_match(true),
+        KeyCode::Char('N') => editor.jump_to_match(false),
+        KeyCode::Enter => editor.jump_to_match(true),
+        KeyCode::Esc => editor.search = None,
+        _ => {
+            editor.search = None;
+            return false;
+        }
+    }
+    true
+}
+
+// returns true if the key was consumed by the active selection
+fn handle_selection_key_bindings(editor: &mut Editor, key: event::KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Esc => editor.clear_selection(),
+        KeyCode::Backspace | KeyCode::Delete => editor.for_each_caret(Editor::delete_selection),
+        // linewise selections behave like visual line mode, so plain keys are operators
+        KeyCode::Char('d') if editor.is_line_selection() => editor.delete_selection(),
+        // a showing prediction keeps tab for accepting it
+        KeyCode::Tab if key.modifiers.is_empty() && editor.current_prediction.is_none() => {
+            editor.indent_selection()
+        }
+        KeyCode::BackTab if editor.current_prediction.is_none() => editor.dedent_selection(),
+        KeyCode::Char('>') if editor.is_line_selection() => editor.indent_selection(),
+        KeyCode::Char('<') if editor.is_line_selection() => editor.dedent_selection(),
+        KeyCode::Char('/') if editor.is_line_selection() => editor.toggle_comment_selection(),
+        KeyCode::Char(_) => {
+            let Some(c) = printable_char(&key) else {
+                return false;
+            };
+            // any other plain key is swallowed in linewise mode
+            if !editor.is_line_selection() {
+                editor.for_each_caret(|e| {
+                    e.delete_selection();
+                    e.insert_char(c);
+                });
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
 fn redraw_editor(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     editor: &mut Editor,
+    predictor: &Predictor,
 ) -> Result<()> {
+    profile_span!("draw");
+    let theme = editor.theme.clone();
+    editor.refresh_gutter_changes();
     terminal.draw(|f| {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
-            .split(f.area());
-
-        let title = editor
-            .filename
-            .as_ref()
-            .map(|f| format!("nars - {}", f))
-            .unwrap_or_else(|| "nars".to_string());
-
-        let window_height = chunks[0].height as usize - 2; // Account for borders
+        let chunks = {
+            profile_span!("layout");
+            Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+                .split(f.area())
+        };
+
+        let (tabs, main) = editor.split_tabs(chunks[0]);
+        if let Some(bar) = tabs {
+            editor.render_tabs(f, bar, &theme);
+        }
+        let (main, log_area) = editor.split_log(main);
+        if let (Some(log_view), Some(area)) = (&editor.log_view, log_area) {
+            log_view.render(f, area, &theme);
+        }
+        if let (Some(chat), Some(area)) = (&editor.chat, editor.split_chat(main).1) {
+            chat.render(f, area, &theme);
+        }
+        let (focused, other) = editor.pane_areas(chunks[0]);
+        if let Some(area) = other {
+            editor.with_other_view(|editor| render_pane(f, editor, area, &theme, false));
+        }
+        let cursor = render_pane(f, editor, focused, &theme, true);
+        if let (Some(completion), Some(at)) = (&editor.completion, cursor) {
+            completion.render(f, focused, at, &theme);
+        }
 
-        // Calculate the maximum line number width
-        let total_lines = editor.content.matches('\n').count() + 1;
-        let line_num_width = total_lines.to_string().len() + 2; // calculate this based off number of total lines
+        if let Some(prompt) = &editor.prompt {
+            let mut text = format!("{}{}", prompt.label, prompt.input);
+            f.set_cursor_position((chunks[1].x + text.chars().count() as u16, chunks[1].y));
+            if prompt.kind == PromptKind::Search && !prompt.input.is_empty() {
+                text = format!(
+                    "{}  [{} matches]",
+                    text,
+                    editor.search_matches(&prompt.input).len()
+                );
+            } else if prompt.kind == PromptKind::ReplaceFind {
+                text = format!(
+                    "{}  [{} matches, ctrl+r toggles regex]",
+                    text,
+                    editor.replace_matches().len()
+                );
+            } else if prompt.kind == PromptKind::ReplaceWith {
+                text = format!("{}  [{} matches]", text, editor.replace_matches().len());
+            }
+            f.render_widget(Paragraph::new(text), chunks[1]);
+        } else {
+            let status = editor.status_line(predictor);
+            let bar = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(status.width() as u16),
+                ])
+                .split(chunks[1]);
+            if let Some(message) = &editor.status_message {
+                f.render_widget(Paragraph::new(message.as_str()).style(theme.dim), bar[0]);
+            }
+            f.render_widget(Paragraph::new(status), bar[1]);
+        }
+        editor.render_toasts(f, chunks[0], &theme);
+        if let Some(popup) = &editor.popup {
+            popup.render(f, chunks[0], &theme);
+        }
+        if let Some(picker) = &editor.model_picker {
+            picker.render(f, chunks[0], &predictor.model(), &theme);
+        }
+        if let (Some(finder), Some(prompt)) = (&editor.finder, &editor.prompt) {
+            finder.render(f, chunks[0], &prompt.input, &theme);
+        }
+    })?;
+    Ok(())
+}
 
-        // Create a horizontal split for line numbers and content
-        let horizontal_chunks = Layout::default()
+// draws the buffer as `editor` sees it from its cursor and scroll offset,
+// an unfocused pane gets a dim border and its cursor drawn as a cell.
+// Returns the cell the cursor is on if it is in view.
+fn render_pane(
+    f: &mut Frame,
+    editor: &Editor,
+    area: Rect,
+    theme: &Theme,
+    focused: bool,
+) -> Option<Position> {
+    let title = editor
+        .filename
+        .as_ref()
+        .map(|f| format!("nars - {}", f))
+        .unwrap_or_else(|| "nars".to_string());
+    let title = if editor.modified {
+        format!("{}*", title)
+    } else {
+        title
+    };
+    let title = if editor.large_file() {
+        format!("{} [large file]", title)
+    } else {
+        title
+    };
+    let title = match editor.line_ending {
+        LineEnding::Lf => title,
+        LineEnding::Crlf => format!("{} [CRLF]", title),
+    };
+    let title = match editor.file_encoding.label() {
+        Some(label) => format!("{} [{}]", title, label),
+        None => title,
+    };
+    let title = if editor.encrypted {
+        format!("{} [encrypted]", title)
+    } else {
+        title
+    };
+    let title = match editor.selection {
+        Some(_) if editor.is_line_selection() => format!("{} [VISUAL LINE]", title),
+        Some(_) => format!("{} [SELECT]", title),
+        None => title,
+    };
+    let title = if editor.carets.is_empty() {
+        title
+    } else {
+        format!("{} [{} cursors]", title, editor.carets.len() + 1)
+    };
+    // last, so a narrow pane cuts the scope off before the file name
+    let crumbs = editor.breadcrumbs();
+    let title = if crumbs.is_empty() {
+        title
+    } else {
+        format!("{} › {}", title, crumbs.join(" › "))
+    };
+
+    let window_height = area.height as usize - 2; // Account for borders
+
+    let line_num_width = editor.gutter_width() as usize;
+
+    // Create a horizontal split for line numbers and content
+    let horizontal_chunks = {
+        profile_span!("layout");
+        Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
                 Constraint::Length(line_num_width as u16),
                 Constraint::Min(1),
             ])
-            .split(chunks[0]);
+            .split(area)
+    };
+
+    let mut styled_lines = {
+        profile_span!("highlight");
+        editor.highlight_syntax(window_height)
+    };
+    let mut line_numbers = Vec::new();
+
+    // buffer line shown on each row, folded lines take none. Prediction
+    // lines past the end of the buffer carry on the numbering.
+    let visible = editor.visible_lines();
+    let rows: Vec<usize> = visible.iter().map(|&(line, _)| line).collect();
+    let line_at = |row: usize| {
+        rows.get(row).copied().unwrap_or_else(|| {
+            rows.last().map_or(editor.scroll_offset, |last| last + 1) + row - rows.len()
+        })
+    };
+
+    // Generate line numbers, with the fold and git change markers in the
+    // last columns
+    let changes = editor.gutter_changes();
+    let cursor_line = editor.point_at(editor.cursor_position).row;
+    let folds = editor.fold_markers(&visible[..visible.len().min(styled_lines.len())]);
+    for i in 0..styled_lines.len() {
+        let line = line_at(i);
+        let marker = match changes.get(line).copied().flatten() {
+            Some(LineChange::Added) => Span::styled("▎", theme.added),
+            Some(LineChange::Modified) => Span::styled("▎", theme.modified),
+            Some(LineChange::Removed) => Span::styled("▔", theme.removed),
+            None => Span::raw(" "),
+        };
+        line_numbers.push(Line::from(vec![
+            Span::styled(
+                format!(
+                    "{:>width$}",
+                    editor.line_label(line, cursor_line),
+                    width = line_num_width - 3
+                ),
+                theme.dim,
+            ),
+            Span::styled(folds.get(i).copied().unwrap_or(" "), theme.dim),
+            marker,
+        ]));
+    }
+
+    // Folded lines are summed up after the line the fold starts on
+    for range in editor.folded_lines() {
+        if let Some(line) = rows
+            .iter()
+            .position(|&line| line == range.start - 1)
+            .and_then(|row| styled_lines.get_mut(row))
+        {
+            let hidden = format!(" ⋯ {} lines", range.len());
+            line.spans.push(Span::styled(hidden, theme.dim));
+        }
+    }
+
+    // Highlight the active selection
+    let selection_style = theme.selection;
+    let selections = editor
+        .selection_range()
+        .map(|range| (range, editor.is_line_selection()))
+        .into_iter()
+        .chain(
+            editor
+                .caret_selection_ranges()
+                .into_iter()
+                .map(|range| (range, false)),
+        );
+    for ((sel_start, sel_end), linewise) in selections {
+        for (line, line_start) in styled_lines.iter_mut().zip(editor.visible_line_starts()) {
+            let line_end = editor.line_end(line_start);
+            if sel_start <= line_end
+                && (sel_end > line_start || (linewise && sel_end >= line_start))
+            {
+                let from = sel_start.max(line_start) - line_start;
+                let to = sel_end.min(line_end) - line_start;
+                *line = highlight_columns(line, from, to, selection_style);
+                if linewise && line_end == line_start {
+                    line.spans.push(Span::styled(" ", selection_style));
+                }
+            }
+        }
+    }
+
+    // Highlight search matches, with the one under the cursor stronger
+    let matches = editor.highlighted_matches();
+    if !matches.is_empty() {
+        let match_style = theme.search_match;
+        let current_style = theme.current_match;
+        for (line, line_start) in styled_lines.iter_mut().zip(editor.visible_line_starts()) {
+            let line_end = editor.line_end(line_start);
+            for &(start, end) in matches
+                .iter()
+                .filter(|(start, end)| *start < line_end && *end > line_start)
+            {
+                let style = if start == editor.cursor_position {
+                    current_style
+                } else {
+                    match_style
+                };
+                *line = highlight_columns(
+                    line,
+                    start - line_start,
+                    end.min(line_end) - line_start,
+                    style,
+                );
+            }
+        }
+    }
 
-        let mut styled_lines = editor.highlight_syntax(window_height);
-        let mut line_numbers = Vec::new();
+    // Secondary carets are drawn as a block over the character they are on
+    let caret_style = theme.caret;
+    for caret in &editor.carets {
+        let line_start = editor.line_start(caret.position);
+        let line_idx = editor.content[..line_start].matches('\n').count();
+        let Some(line) = rows
+            .iter()
+            .position(|&line| line == line_idx)
+            .and_then(|row| styled_lines.get_mut(row))
+        else {
+            continue;
+        };
+        let column = caret.position - line_start;
+        match editor.next_boundary(caret.position) {
+            Some(end) if !editor.content[caret.position..end].starts_with('\n') => {
+                *line = highlight_columns(line, column, end - line_start, caret_style)
+            }
+            _ => line.spans.push(Span::styled(" ", caret_style)),
+        }
+    }
 
-        // Generate line numbers
-        for i in 0..styled_lines.len() {
-            let line_num = editor.scroll_offset + i + 1; // +1 because line numbers are 1-indexed
-            line_numbers.push(Line::from(vec![Span::styled(
-                format!("{:>width$} ", line_num, width = line_num_width - 1),
-                Style::default().fg(Color::DarkGray),
-            )]));
+    // Whitespace glyphs go in last, they change the byte length of spans
+    if editor.show_whitespace {
+        for (line, line_start) in styled_lines.iter_mut().zip(editor.visible_line_starts()) {
+            let line_end = editor.line_end(line_start);
+            let text = &editor.content[line_start..line_end];
+            *line = show_whitespace(line, text.trim_end().len(), text.len(), theme);
         }
+    }
 
-        // Add cursor indicator
-        let current_line_number = editor.content[..editor.cursor_position]
-            .chars()
-            .filter(|&c| c == '\n')
-            .count();
+    let current_line_number = editor.content[..editor.cursor_position]
+        .chars()
+        .filter(|&c| c == '\n')
+        .count();
+    let line_start = editor.line_start(editor.cursor_position);
+    let cursor_column = editor.content[line_start..editor.cursor_position].width();
+
+    // Only show cursor if the line is currently visible
+    let cursor_row = rows
+        .iter()
+        .position(|&line| line == current_line_number)
+        .filter(|&row| row < window_height);
+    if let Some(cursor_row) = cursor_row {
+        if let Some(line) = styled_lines.get_mut(cursor_row) {
+            // a cell for the cursor to sit on past the end of the line
+            if line.width() <= cursor_column {
+                line.spans.push(Span::raw(" "));
+            }
 
-        // Only show cursor if the line is currently visible
-        if current_line_number >= editor.scroll_offset
-            && current_line_number < editor.scroll_offset + window_height
-        {
-            if let Some(line) = styled_lines.get_mut(current_line_number - editor.scroll_offset) {
-                // Calculate cursor position within the line
-                let line_start = editor.content[..editor.cursor_position]
-                    .rfind('\n')
-                    .map(|pos| pos + 1)
-                    .unwrap_or(0);
-                let cursor_offset = editor.cursor_position - line_start;
-
-                // Create a new list of spans with the cursor
-                let mut new_spans = Vec::new();
-                let mut current_pos = 0;
-
-                for span in line.spans.iter() {
-                    let span_len = span.content.len();
-                    if current_pos + span_len > cursor_offset && current_pos <= cursor_offset {
-                        // Split this span to insert the cursor
-                        let cursor_rel_pos = cursor_offset - current_pos;
-                        if cursor_rel_pos > 0 {
-                            new_spans.push(Span::styled(
-                                span.content[..cursor_rel_pos].to_string(),
-                                span.style,
-                            ));
-                        }
-                        // Add the cursor
-                        new_spans.push(Span::styled(
-                            "|".to_string(),
-                            Style::default()
-                                .fg(Color::LightYellow)
-                                .add_modifier(Modifier::RAPID_BLINK),
-                        ));
-                        if cursor_rel_pos < span_len {
-                            new_spans.push(Span::styled(
-                                span.content[cursor_rel_pos..].to_string(),
-                                span.style,
-                            ));
-                        }
-                    } else {
-                        new_spans.push(span.clone());
-                    }
-                    current_pos += span_len;
+            // a dim mark where the cursor will be again on a longer line
+            if let Some(goal) = editor.goal_column {
+                let column = editor.column_of(editor.cursor_position);
+                if goal > column {
+                    line.spans.push(Span::raw(" ".repeat(goal - column - 1)));
+                    line.spans.push(Span::styled("·", theme.dim));
                 }
+            }
+
+            if focused {
+                let width = horizontal_chunks[1].width.saturating_sub(2) as usize;
+                highlight_line(line, theme.current_line, width, editor.soft_wrap);
+            }
+        }
+    }
 
-                // If cursor is at the end of the line
-                if cursor_offset >= current_pos {
-                    new_spans.push(Span::styled(
-                        "|".to_string(),
-                        Style::default()
-                            .fg(Color::Rgb(169, 183, 198))
-                            .add_modifier(Modifier::RAPID_BLINK),
-                    ));
+    // row of the cursor line, moved down by annotations above it
+    let visible_cursor = cursor_row.is_some();
+    let cursor_row = cursor_row.unwrap_or(0);
+    let mut cursor_line = cursor_row;
+
+    // Summaries from the model go above their function and the lines a
+    // prediction adds go below the line it starts on, as virtual lines
+    let annotations = editor.visible_annotations(window_height);
+    let ghost_below = editor
+        .prediction_ghost()
+        .filter(|(_, _, below)| !below.is_empty());
+    if !annotations.is_empty() || ghost_below.is_some() {
+        let annotation_style = theme.dim.add_modifier(Modifier::ITALIC);
+        let ghost_style = editor.ghost_styles().1;
+        let line_starts = editor.visible_line_starts();
+        let mut lines = Vec::new();
+        let mut numbers = Vec::new();
+        let mut inserted = 0;
+        for (row, (line, number)) in styled_lines.into_iter().zip(line_numbers).enumerate() {
+            let annotation = line_starts.get(row).and_then(|start| {
+                annotations
+                    .iter()
+                    .find(|(line_start, _)| line_start == start)
+            });
+            // lines above the cursor only get one while it stays in view
+            if let Some((_, text)) = annotation {
+                if row > cursor_row || cursor_row + inserted + 1 < window_height {
+                    lines.push(Line::from(Span::styled(text.clone(), annotation_style)));
+                    numbers.push(Line::from(""));
+                    inserted += 1;
                 }
+            }
+            if row == cursor_row {
+                cursor_line = lines.len();
+            }
+            lines.push(line);
+            numbers.push(number);
+            let below = ghost_below
+                .as_ref()
+                .filter(|(start_line, _, _)| rows.get(row) == Some(start_line));
+            if let Some((_, _, below)) = below {
+                if row >= cursor_row || cursor_row + inserted + below.len() < window_height {
+                    for text in below {
+                        lines.push(Line::from(Span::styled(text.clone(), ghost_style)));
+                        numbers.push(Line::from(""));
+                    }
+                    inserted += below.len();
+                }
+            }
+        }
+        lines.truncate(window_height);
+        numbers.truncate(window_height);
+        styled_lines = lines;
+        line_numbers = numbers;
+    }
 
-                *line = Line::from(new_spans);
+    // row and column of the cursor within the text
+    let mut cursor_cell = visible_cursor.then_some((cursor_line, cursor_column));
+    if editor.soft_wrap {
+        let width = horizontal_chunks[1].width.saturating_sub(2) as usize;
+        let wrapped = wrap_lines(styled_lines, line_numbers, width);
+        let (wrap_row, wrap_column) = editor.cursor_wrap_cell(width);
+        let cursor_row = wrapped
+            .row_starts
+            .get(cursor_line)
+            .map_or(0, |row| row + wrap_row);
+        // long wrapped lines can push the cursor below the pane
+        let skip = (cursor_row + 1).saturating_sub(window_height);
+        cursor_cell = cursor_cell.map(|_| (cursor_row - skip, wrap_column));
+        styled_lines = wrapped
+            .lines
+            .into_iter()
+            .skip(skip)
+            .take(window_height)
+            .collect();
+        line_numbers = wrapped
+            .numbers
+            .into_iter()
+            .skip(skip)
+            .take(window_height)
+            .collect();
+    }
+
+    // Render line numbers
+    let line_numbers_widget = Paragraph::new(line_numbers)
+        .block(Block::default().borders(Borders::RIGHT))
+        .style(theme.base);
+
+    let border_style = match editor.bell_until {
+        _ if !focused => theme.dim,
+        Some(until) if Instant::now() < until => theme.bell,
+        _ => Style::default(),
+    };
+
+    // Render main content
+    let paragraph = Paragraph::new(styled_lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style)
+                .title(title)
+                .style(theme.base),
+        )
+        .style(theme.base);
+
+    f.render_widget(line_numbers_widget, horizontal_chunks[0]);
+    f.render_widget(paragraph, horizontal_chunks[1]);
+
+    let text = horizontal_chunks[1].inner(Margin::new(1, 1));
+    editor.draw_rulers(f.buffer_mut(), text, theme);
+
+    // the focused pane gets the terminal's cursor, so its shape and blinking
+    // apply, the other pane's is drawn as a cell
+    let (row, column) = cursor_cell
+        .filter(|&(row, column)| row < text.height as usize && column < text.width as usize)?;
+    let position = Position::new(text.x + column as u16, text.y + row as u16);
+    if !focused {
+        f.buffer_mut()[position].set_style(theme.cursor.add_modifier(Modifier::REVERSED));
+    } else if editor.popup.is_none() {
+        f.set_cursor_position(position);
+    }
+    Some(position)
+}
+
+// gives the parts of a rendered line without a background of their own the
+// background of `style`, padded to the edge of the pane
+fn highlight_line(line: &mut Line, style: Style, width: usize, wrapped: bool) {
+    let Some(bg) = style.bg else {
+        return;
+    };
+    for span in line.spans.iter_mut().filter(|span| span.style.bg.is_none()) {
+        span.style = span.style.bg(bg);
+    }
+    let used = line.width();
+    // a wrapped line only needs its last row filled
+    let pad = match (wrapped, width) {
+        (_, 0) => 0,
+        (true, width) => (width - used % width) % width,
+        (false, width) => width.saturating_sub(used),
+    };
+    let padding = Span::styled(" ".repeat(pad), Style::default().bg(bg));
+    line.spans.push(padding);
+}
+
+// patches `style` onto the byte columns `start..end` of a rendered line
+fn highlight_columns<'a>(line: &Line<'a>, start: usize, end: usize, style: Style) -> Line<'a> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for span in line.spans.iter() {
+        let len = span.content.len();
+        let from = start.clamp(pos, pos + len) - pos;
+        let to = end.clamp(pos, pos + len) - pos;
+        if from == to {
+            spans.push(span.clone());
+        } else {
+            if from > 0 {
+                spans.push(Span::styled(span.content[..from].to_string(), span.style));
+            }
+            spans.push(Span::styled(
+                span.content[from..to].to_string(),
+                span.style.patch(style),
+            ));
+            if to < len {
+                spans.push(Span::styled(span.content[to..].to_string(), span.style));
             }
         }
+        pos += len;
+    }
+    Line::from(spans)
+}
 
-        // Render line numbers
-        let line_numbers_widget = Paragraph::new(line_numbers)
-            .block(Block::default().borders(Borders::RIGHT))
-            .style(Style::default().bg(Color::Black));
+// splits the span under byte column `at` to make room for `inserted`
+fn insert_at_column<'a>(spans: Vec<Span<'a>>, at: usize, inserted: Span<'a>) -> Vec<Span<'a>> {
+    let mut result = Vec::new();
+    let mut inserted = Some(inserted);
+    let mut pos = 0;
+    for span in spans {
+        let len = span.content.len();
+        if pos + len > at {
+            if let Some(ghost) = inserted.take() {
+                let split = at - pos;
+                if split > 0 {
+                    result.push(Span::styled(span.content[..split].to_string(), span.style));
+                }
+                result.push(ghost);
+                result.push(Span::styled(span.content[split..].to_string(), span.style));
+                pos += len;
+                continue;
+            }
+        }
+        result.push(span);
+        pos += len;
+    }
+    result.extend(inserted);
+    result
+}
 
-        // Render main content
-        let paragraph = Paragraph::new(styled_lines)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(title)
-                    .style(Style::default().bg(Color::Black).fg(Color::White)),
-            )
-            .style(Style::default().bg(Color::Black));
+// draws what the predicted line adds to `line` as ghost text where it goes,
+// nothing if the prediction would change what is already typed
+fn insert_ghosts<'a>(
+    mut spans: Vec<Span<'a>>,
+    line: &str,
+    predicted: &str,
+    style: Style,
+) -> Vec<Span<'a>> {
+    let insertions = diff::insertions(line, predicted).unwrap_or_default();
+    for (at, text) in insertions.into_iter().rev() {
+        spans = insert_at_column(spans, at, Span::styled(text.to_string(), style));
+    }
+    spans
+}
 
-        f.render_widget(line_numbers_widget, horizontal_chunks[0]);
-        f.render_widget(paragraph, horizontal_chunks[1]);
-    })?;
-    Ok(())
+/// A parsed Rust buffer holding `content`, with the cursor at `cursor`
+#[cfg(test)]
+pub(crate) fn test_editor(content: &str, cursor: usize) -> Editor {
+    let (mut editor, _) = Editor::new("test.rs".to_string(), Config::default());
+    editor.content = content.to_string();
+    editor.cursor_position = cursor;
+    editor.update_syntax_tree();
+    editor
 }
 
-fn find_difference(s1: &str, s2: &str) -> String {
-    if !s2.starts_with(s1) {
-        return String::new(); // Return empty string if they don't match
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::selection::SelectionMode;
+    use crate::models::backend::{Backend, MockBackend};
+
+    fn paste(editor: &mut Editor, text: &str) {
+        let backend = Backend::new("mock", MockBackend::default());
+        let mut predictor = Arc::new(Predictor::headless(backend, "test".to_string()));
+        handle_event(editor, &mut predictor, Event::Paste(text.to_string())).unwrap();
+    }
+
+    #[test]
+    fn test_typing_s_cancels_the_overwrite_confirmation() {
+        let mut editor = test_editor("", 0);
+        editor.confirm_overwrite = true;
+        // keys are handled one at a time without a terminal to read ahead from
+        editor.replaying = true;
+        let backend = Backend::new("mock", MockBackend::default());
+        let mut predictor = Arc::new(Predictor::headless(backend, "test".to_string()));
+        let s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE);
+        handle_event(&mut editor, &mut predictor, Event::Key(s)).unwrap();
+        assert_eq!(editor.content, "s");
+        assert!(!editor.confirm_overwrite);
     }
 
-    s2[s1.len()..].to_string() // Return the remainder of s2 after s1's length
+    #[test]
+    fn test_paste_replaces_the_selection() {
+        let mut editor = test_editor("let x = old;", 0);
+        editor.selection = Some(Selection {
+            anchor: 8,
+            mode: SelectionMode::Char,
+        });
+        editor.cursor_position = 11;
+        editor.goal_column = Some(20);
+        paste(&mut editor, "new\r\n");
+        assert_eq!(editor.content, "let x = new\n;");
+        assert_eq!(editor.cursor_position, 12);
+        assert!(editor.selection.is_none());
+        assert!(editor.goal_column.is_none());
+    }
+
+    #[test]
+    fn test_paste_goes_to_the_open_prompt() {
+        let mut editor = test_editor("fn main() {}", 0);
+        editor.open_search();
+        paste(&mut editor, "main\n");
+        assert_eq!(editor.content, "fn main() {}");
+        assert_eq!(editor.prompt.as_ref().unwrap().input, "main ");
+        // a popup over the buffer swallows it
+        editor.prompt = None;
+        editor.search = None;
+        editor.popup = Some(Popup::new("Info", vec![Line::from("text")]));
+        paste(&mut editor, "x");
+        assert_eq!(editor.content, "fn main() {}");
+    }
 }
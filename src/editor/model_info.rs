@@ -0,0 +1,153 @@
+use crate::editor::popup::Popup;
+use crate::editor::Editor;
+use crate::models::ollama::ModelInfo;
+use crate::models::Predictor;
+use anyhow::{anyhow, Result};
+use ratatui::text::{Line, Span};
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+// template lines shown before the rest is cut off
+const MAX_TEMPLATE_LINES: usize = 12;
+
+pub struct ModelInfoRequest {
+    model: String,
+    result_rx: oneshot::Receiver<Result<ModelInfo>>,
+}
+
+impl Editor {
+    pub(crate) fn request_model_info(&mut self, predictor: &Arc<Predictor>) {
+        let (result_tx, result_rx) = oneshot::channel();
+        predictor.clone().model_info_background(result_tx);
+        self.model_info = Some(ModelInfoRequest {
+            model: predictor.model(),
+            result_rx,
+        });
+        self.status_message = Some(format!("Asking ollama about {}...", predictor.model()));
+    }
+
+    /// Opens the model info popup once ollama answers, returns true if it did
+    pub(crate) fn poll_model_info(&mut self) -> bool {
+        let Some(request) = self.model_info.as_mut() else {
+            return false;
+        };
+        let result = match request.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => Err(anyhow!("request was dropped")),
+        };
+        let Some(ModelInfoRequest { model, .. }) = self.model_info.take() else {
+            return false;
+        };
+        let info = match result {
+            Ok(info) => info,
+            Err(e) => {
+                self.status_message = Some(format!("Failed to get info for {}: {}", model, e));
+                return true;
+            }
+        };
+
+        let unknown = || "unknown".to_string();
+        let mut lines = vec![
+            Line::from(format!("Parameters:    {}", info.parameter_size)),
+            Line::from(format!("Quantization:  {}", info.quantization_level)),
+            Line::from(format!(
+                "Context:       {} tokens",
+                info.context_length.map_or_else(unknown, |n| n.to_string())
+            )),
+            Line::from(format!(
+                "num_ctx:       {}",
+                info.num_ctx.map_or_else(unknown, |n| n.to_string())
+            )),
+        ];
+        // ollama runs with num_ctx when it is set, which is often far below what
+        // the model supports. One set in the config is already kept to.
+        let budget = self.config.model.context_budget as u64;
+        let window = match self.config.model.options.num_ctx {
+            Some(_) => None,
+            None => info.num_ctx.or(info.context_length),
+        };
+        self.status_message = None;
+        if let Some(window) = window.filter(|&window| budget > window) {
+            let warning = format!(
+                "context_budget of {} tokens is larger than the {} token window",
+                budget, window
+            );
+            lines.push(Line::from(Span::styled(
+                warning.clone(),
+                self.theme.warning,
+            )));
+            self.status_message = Some(warning);
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from("Template:"));
+        let template: Vec<&str> = info.template.lines().collect();
+        for line in template.iter().take(MAX_TEMPLATE_LINES) {
+            lines.push(Line::from(Span::styled(line.to_string(), self.theme.dim)));
+        }
+        if template.len() > MAX_TEMPLATE_LINES {
+            lines.push(Line::from(format!(
+                "... {} more lines",
+                template.len() - MAX_TEMPLATE_LINES
+            )));
+        }
+        self.popup = Some(Popup::new(&format!(" {} ", model), lines));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::test_editor;
+
+    fn answer(editor: &mut Editor, result: Result<ModelInfo>) {
+        let (result_tx, result_rx) = oneshot::channel();
+        editor.model_info = Some(ModelInfoRequest {
+            model: "qwen".to_string(),
+            result_rx,
+        });
+        assert!(!editor.poll_model_info());
+        let _ = result_tx.send(result);
+        assert!(editor.poll_model_info());
+    }
+
+    fn info() -> ModelInfo {
+        ModelInfo {
+            context_length: Some(32768),
+            num_ctx: Some(2048),
+            template: "line\n".repeat(20),
+            ..ModelInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_warns_when_the_budget_is_past_num_ctx() {
+        let mut editor = test_editor("", 0);
+        editor.config.model.context_budget = 4096;
+        answer(&mut editor, Ok(info()));
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("context_budget of 4096 tokens is larger than the 2048 token window")
+        );
+        let lines = &editor.popup.as_ref().unwrap().lines;
+        assert_eq!(lines[3].to_string(), "num_ctx:       2048");
+        assert_eq!(lines.last().unwrap().to_string(), "... 8 more lines");
+
+        // num_ctx from the config is what requests are sent with
+        editor.config.model.options.num_ctx = Some(4096);
+        answer(&mut editor, Ok(info()));
+        assert!(editor.status_message.is_none());
+    }
+
+    #[test]
+    fn test_failure_is_reported() {
+        let mut editor = test_editor("", 0);
+        answer(&mut editor, Err(anyhow!("connection refused")));
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Failed to get info for qwen: connection refused")
+        );
+        assert!(editor.popup.is_none());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use crate::models::Predictor;
+use anyhow::{anyhow, Result};
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// The list of models to switch to, from asking for it to picking one
+pub enum ModelPicker {
+    /// Asked for from the command line, which has no predictor to ask with
+    Wanted,
+    Loading(oneshot::Receiver<Result<Vec<String>>>),
+    Open {
+        models: Vec<String>,
+        selected: usize,
+    },
+}
+
+impl ModelPicker {
+    /// Lists the models in the middle of `area`, the active one marked
+    pub fn render(&self, f: &mut Frame, area: Rect, current: &str, theme: &Theme) {
+        let ModelPicker::Open { models, selected } = self else {
+            return;
+        };
+        let width = (area.width / 2).max(30).min(area.width);
+        let height = (models.len() as u16 + 2).min(area.height);
+        let picker_area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        // keeps the selection in view when there are more models than rows
+        let rows = height.saturating_sub(2) as usize;
+        let skip = (selected + 1).saturating_sub(rows);
+        let lines: Vec<Line> = models
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(i, model)| {
+                let style = if i == *selected {
+                    theme.selection
+                } else {
+                    theme.base
+                };
+                let marker = if model == current { "● " } else { "  " };
+                Line::from(Span::styled(format!("{}{}", marker, model), style))
+            })
+            .collect();
+        f.render_widget(Clear, picker_area);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Switch model (enter picks, esc closes) ")
+                    .style(theme.base),
+            ),
+            picker_area,
+        );
+    }
+}
+
+impl Editor {
+    /// Asks the backend for its models to pick the active one from
+    pub(crate) fn open_model_picker(&mut self, predictor: &Arc<Predictor>) {
+        let (result_tx, result_rx) = oneshot::channel();
+        predictor.clone().models_background(result_tx);
+        self.model_picker = Some(ModelPicker::Loading(result_rx));
+        self.status_message = Some("Listing models...".to_string());
+    }
+
+    /// Starts a picker the command line asked for and opens it once the
+    /// models are in, returns true if it did
+    pub(crate) fn poll_model_picker(&mut self, predictor: &Arc<Predictor>) -> bool {
+        let result = match self.model_picker.as_mut() {
+            Some(ModelPicker::Wanted) => {
+                self.open_model_picker(predictor);
+                return true;
+            }
+            Some(ModelPicker::Loading(result_rx)) => match result_rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Closed) => Err(anyhow!("request was dropped")),
+            },
+            _ => return false,
+        };
+        self.status_message = None;
+        self.model_picker = match result {
+            Ok(models) if models.is_empty() => {
+                self.status_message = Some("No models installed".to_string());
+                None
+            }
+            Ok(mut models) => {
+                models.sort();
+                let current = predictor.model();
+                let selected = models.iter().position(|m| *m == current).unwrap_or(0);
+                Some(ModelPicker::Open { models, selected })
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Failed to list models: {}", e));
+                None
+            }
+        };
+        true
+    }
+
+    /// Up and down move through the models, enter switches to the selected
+    /// one and any other key closes the picker
+    pub(crate) fn handle_model_picker_key(&mut self, predictor: &Arc<Predictor>, key: KeyEvent) {
+        let Some(ModelPicker::Open { models, selected }) = self.model_picker.as_mut() else {
+            // still loading, keys go on to the buffer
+            return;
+        };
+        let count = models.len();
+        match key.code {
+            KeyCode::Up => *selected = (*selected + count - 1) % count,
+            KeyCode::Down => *selected = (*selected + 1) % count,
+            KeyCode::Enter => {
+                let model = models[*selected].clone();
+                self.model_picker = None;
+                self.status_message = Some(format!("Predicting with {}", model));
+                predictor.set_model(model);
+            }
+            _ => self.model_picker = None,
+        }
+    }
+
+    pub(crate) fn model_picker_open(&self) -> bool {
+        matches!(self.model_picker, Some(ModelPicker::Open { .. }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelPicker;
+    use crate::config::Config;
+    use crate::editor::Editor;
+    use crate::models::backend::{Backend, MockBackend};
+    use crate::models::Predictor;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    #[test]
+    fn test_picking_a_model_switches_the_predictor() {
+        let (mut editor, prediction_tx) = Editor::new("test.rs".to_string(), Config::default());
+        let backend = Backend::new("mock", MockBackend::default());
+        let predictor = Arc::new(Predictor::new(backend, prediction_tx, "b".into()));
+        let (result_tx, result_rx) = oneshot::channel();
+        editor.model_picker = Some(ModelPicker::Loading(result_rx));
+        assert!(!editor.poll_model_picker(&predictor));
+        let models = vec!["c".to_string(), "b".to_string(), "a".to_string()];
+        result_tx.send(Ok(models)).unwrap();
+        assert!(editor.poll_model_picker(&predictor));
+        let Some(ModelPicker::Open { models, selected }) = &editor.model_picker else {
+            panic!("picker didn't open");
+        };
+        assert_eq!((models[*selected].as_str(), models.len()), ("b", 3));
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        editor.handle_model_picker_key(&predictor, key(KeyCode::Down));
+        editor.handle_model_picker_key(&predictor, key(KeyCode::Enter));
+        assert!(editor.model_picker.is_none());
+        assert_eq!(predictor.model(), "c");
+    }
+}
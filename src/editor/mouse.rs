@@ -0,0 +1,241 @@
+use crate::editor::Editor;
+use ratatui::crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Position, Rect};
+use unicode_width::UnicodeWidthChar;
+
+// lines moved by one notch of the scroll wheel
+const WHEEL_LINES: usize = 3;
+
+impl Editor {
+    /// Columns taken by the line numbers and markers left of a pane
+    pub(crate) fn gutter_width(&self) -> u16 {
+        let total_lines = self.content.matches('\n').count() + 1;
+        // the number, the fold marker, the git change marker and the border
+        (total_lines.to_string().len() + 4) as u16
+    }
+
+    // the cells inside a pane's border, right of the gutter
+    fn text_area(&self, pane: Rect) -> Rect {
+        let gutter = self.gutter_width().min(pane.width);
+        Rect::new(
+            pane.x + gutter + 1,
+            pane.y + 1,
+            pane.width.saturating_sub(gutter + 2),
+            pane.height.saturating_sub(2),
+        )
+    }
+
+    // walks the cells the line `start..end` is drawn in, wrapping at `width`:
+    // the offset drawn at `row`, `column` of the line, or the rows the line
+    // takes if it ends before that row
+    fn locate_in_line(
+        &self,
+        start: usize,
+        end: usize,
+        width: usize,
+        row: usize,
+        column: usize,
+    ) -> Result<usize, usize> {
+        let mut line_row = 0;
+        let mut used = 0;
+        for (i, c) in self.content[start..end].char_indices() {
+            let offset = start + i;
+            let w = c.width().unwrap_or(0);
+            if used + w > width && used > 0 {
+                if line_row == row {
+                    return Ok(offset);
+                }
+                line_row += 1;
+                used = 0;
+            }
+            if line_row == row && column < used + w {
+                return Ok(offset);
+            }
+            used += w;
+        }
+        if line_row == row {
+            Ok(end)
+        } else {
+            Err(line_row + 1)
+        }
+    }
+
+    /// Buffer offset drawn at a cell of the text area, clicks below the text
+    /// land at the end of the last line
+    pub(crate) fn position_at(&self, text: Rect, column: u16, row: u16) -> usize {
+        let width = if self.soft_wrap {
+            (text.width as usize).max(1)
+        } else {
+            usize::MAX
+        };
+        let row = row.saturating_sub(text.y) as usize;
+        let column = column.saturating_sub(text.x) as usize;
+        let annotated: Vec<usize> = self
+            .visible_annotations(text.height as usize)
+            .into_iter()
+            .map(|(start, _)| start)
+            .collect();
+        let mut screen_row = 0;
+        let mut last_end = self.content.len();
+        for (_, start) in self.visible_lines() {
+            // a summary line above a function belongs to the line below it
+            if annotated.contains(&start) {
+                if screen_row == row {
+                    return start;
+                }
+                screen_row += 1;
+            }
+            let end = self.line_end(start);
+            match self.locate_in_line(start, end, width, row - screen_row, column) {
+                Ok(offset) => return offset,
+                Err(rows) => screen_row += rows,
+            }
+            last_end = end;
+        }
+        last_end
+    }
+
+    // scrolls the view, taking the cursor along when it would leave it
+    fn scroll_view(&mut self, down: bool, window_height: usize) {
+        let last_line = self.content.matches('\n').count();
+        self.scroll_offset = if down {
+            (self.scroll_offset + WHEEL_LINES).min(last_line)
+        } else {
+            self.scroll_offset.saturating_sub(WHEEL_LINES)
+        };
+        let lines = self.visible_lines();
+        let (Some(&first), Some(&last)) = (
+            lines.first(),
+            lines.get(window_height.saturating_sub(1)).or(lines.last()),
+        ) else {
+            return;
+        };
+        let line = self.point_at(self.cursor_position).row;
+        let column = self.column_of(self.cursor_position);
+        if line < first.0 {
+            self.cursor_position = self.offset_at_column(first.1, column);
+        } else if line > last.0 {
+            self.cursor_position = self.offset_at_column(last.1, column);
+        }
+    }
+
+    /// Clicking moves the cursor or switches tabs, alt+click adds a caret,
+    /// dragging selects and the wheel scrolls the pane under the pointer.
+    /// `area` is where the tab bar and panes are drawn.
+    pub(crate) fn handle_mouse(&mut self, event: MouseEvent, area: Rect) {
+        let at = Position::new(event.column, event.row);
+        let (focused, other) = self.pane_areas(area);
+        let in_other = other.is_some_and(|pane| pane.contains(at));
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(bar) = self.split_tabs(area).0.filter(|bar| bar.contains(at)) {
+                    if let Some(tab) = self.tab_at(bar, at.x) {
+                        self.switch_buffer(tab);
+                    }
+                    return;
+                }
+                if in_other {
+                    self.focus_other_pane();
+                }
+                let pane = self.pane_areas(area).0;
+                if !pane.contains(at) {
+                    return;
+                }
+                let position = self.position_at(self.text_area(pane), at.x, at.y);
+                if event.modifiers.contains(KeyModifiers::ALT) && !in_other {
+                    self.add_caret_at(position);
+                    return;
+                }
+                self.clear_selection();
+                self.clear_carets();
+                self.goal_column = None;
+                self.cursor_position = position;
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let position = self.position_at(self.text_area(focused), at.x, at.y);
+                if position != self.cursor_position {
+                    self.begin_selection();
+                    self.cursor_position = position;
+                }
+            }
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let down = event.kind == MouseEventKind::ScrollDown;
+                if let Some(pane) = other.filter(|_| in_other) {
+                    let height = pane.height.saturating_sub(2) as usize;
+                    self.with_other_view(|editor| editor.scroll_view(down, height));
+                    return;
+                }
+                let before = self.scroll_offset;
+                self.scroll_view(down, focused.height.saturating_sub(2) as usize);
+                let after = self.scroll_offset;
+                if self.split.as_ref().is_some_and(|split| split.scrollbind) {
+                    self.with_other_view(|editor| {
+                        editor.scroll_offset = (editor.scroll_offset + after).saturating_sub(before)
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    use ratatui::crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use ratatui::layout::Rect;
+
+    fn mouse(kind: MouseEventKind, column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn test_click_and_drag_select() {
+        let mut editor = test_editor("fn main() {\n    let x = 1;\n}\n", 0);
+        let area = Rect::new(0, 0, 80, 20);
+        // the gutter is 5 wide and the border 1, so text starts at column 6
+        let left = MouseEventKind::Down(MouseButton::Left);
+        editor.handle_mouse(mouse(left, 10, 2), area);
+        assert_eq!(editor.cursor_position, 16);
+        editor.handle_mouse(mouse(MouseEventKind::Drag(MouseButton::Left), 16, 2), area);
+        assert_eq!(editor.selection_range(), Some((16, 22)));
+        // below the text lands at the end of the last line
+        editor.handle_mouse(mouse(left, 30, 15), area);
+        assert_eq!(editor.cursor_position, editor.content.len());
+    }
+
+    #[test]
+    fn test_alt_click_adds_a_caret() {
+        let mut editor = test_editor("let a = 1;\nlet b = 2;\n", 0);
+        let area = Rect::new(0, 0, 80, 20);
+        editor.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 6, 1), area);
+        let mut alt_click = mouse(MouseEventKind::Down(MouseButton::Left), 6, 2);
+        alt_click.modifiers = KeyModifiers::ALT;
+        editor.handle_mouse(alt_click, area);
+        assert_eq!(editor.cursor_position, 11);
+        assert_eq!(editor.carets.len(), 1);
+        assert_eq!(editor.carets[0].position, 0);
+        editor.for_each_caret(|e| e.insert_text("x"));
+        assert_eq!(editor.content, "xlet a = 1;\nxlet b = 2;\n");
+        // a plain click drops the extra carets
+        editor.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 6, 1), area);
+        assert!(editor.carets.is_empty());
+    }
+
+    #[test]
+    fn test_wheel_takes_the_cursor_along() {
+        let mut editor = test_editor(&"line\n".repeat(100), 0);
+        let area = Rect::new(0, 0, 80, 12);
+        for _ in 0..2 {
+            editor.handle_mouse(mouse(MouseEventKind::ScrollDown, 10, 5), area);
+        }
+        assert_eq!(editor.scroll_offset, 6);
+        assert_eq!(editor.point_at(editor.cursor_position).row, 6);
+    }
+}
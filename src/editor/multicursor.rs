@@ -0,0 +1,175 @@
+use crate::editor::selection::{Selection, SelectionMode};
+use crate::editor::Editor;
+use std::mem;
+
+/// A secondary caret. The primary caret stays in `cursor_position` and
+/// `selection` so single cursor editing is unaffected.
+#[derive(Clone, Copy, Debug)]
+pub struct Caret {
+    pub position: usize,
+    pub selection: Option<Selection>,
+    pub goal_column: Option<usize>,
+}
+
+impl Editor {
+    /// Runs `edit` at the primary caret and then at every secondary caret.
+    /// Each caret is swapped into the primary slot in turn, and since
+    /// `apply_edit` shifts all carets the offsets stay valid between runs.
+    pub(crate) fn for_each_caret(&mut self, mut edit: impl FnMut(&mut Editor)) {
+        edit(self);
+        for idx in 0..self.carets.len() {
+            self.swap_caret(idx);
+            edit(self);
+            self.swap_caret(idx);
+        }
+        self.merge_carets();
+    }
+
+    fn swap_caret(&mut self, idx: usize) {
+        let caret = &mut self.carets[idx];
+        mem::swap(&mut self.cursor_position, &mut caret.position);
+        mem::swap(&mut self.selection, &mut caret.selection);
+        mem::swap(&mut self.goal_column, &mut caret.goal_column);
+    }
+
+    // carets that collapsed onto each other after an edit become one
+    fn merge_carets(&mut self) {
+        let primary = self.cursor_position;
+        self.carets.retain(|caret| caret.position != primary);
+        self.carets.sort_by_key(|caret| caret.position);
+        self.carets.dedup_by_key(|caret| caret.position);
+    }
+
+    pub(crate) fn clear_carets(&mut self) {
+        self.carets.clear();
+        self.selection = None;
+    }
+
+    /// Selects the word under the cursor, or if something is already selected
+    /// adds a caret selecting its next occurrence, wrapping at the end of the file.
+    pub(crate) fn add_next_occurrence(&mut self) {
+        let selected = self
+            .selection_range()
+            .filter(|&(start, end)| start < end && !self.is_line_selection());
+        let Some((start, end)) = selected else {
+            match self.word_range_at(self.cursor_position) {
+                Some((start, end)) => {
+                    self.selection = Some(Selection {
+                        anchor: start,
+                        mode: SelectionMode::Char,
+                    });
+                    self.cursor_position = end;
+                }
+                None => self.bell(),
+            }
+            return;
+        };
+
+        let needle = &self.content[start..end];
+        let taken: Vec<usize> = self
+            .carets
+            .iter()
+            .filter_map(|caret| caret.selection.map(|sel| sel.anchor.min(caret.position)))
+            .chain([start])
+            .collect();
+        let occurrences: Vec<usize> = self
+            .content
+            .match_indices(needle)
+            .map(|(pos, _)| pos)
+            .filter(|pos| !taken.contains(pos))
+            .collect();
+        let next = occurrences
+            .iter()
+            .find(|&&pos| pos >= end)
+            .or(occurrences.first())
+            .copied();
+        let Some(next) = next else {
+            self.status_message = Some("No more occurrences".to_string());
+            self.bell();
+            return;
+        };
+
+        // the new occurrence becomes the primary caret so the view follows it
+        self.carets.push(Caret {
+            position: self.cursor_position,
+            selection: self.selection,
+            goal_column: None,
+        });
+        self.selection = Some(Selection {
+            anchor: next,
+            mode: SelectionMode::Char,
+        });
+        self.cursor_position = next + (end - start);
+    }
+
+    /// Adds a caret at `position`, which becomes the primary one. The carets
+    /// already there stay put.
+    pub(crate) fn add_caret_at(&mut self, position: usize) {
+        self.carets.push(Caret {
+            position: self.cursor_position,
+            selection: self.selection.take(),
+            goal_column: self.goal_column.take(),
+        });
+        self.cursor_position = position;
+        self.merge_carets();
+    }
+
+    /// Byte ranges selected by the secondary carets
+    pub(crate) fn caret_selection_ranges(&self) -> Vec<(usize, usize)> {
+        self.carets
+            .iter()
+            .filter_map(|caret| {
+                caret
+                    .selection
+                    .map(|sel| self.range_of(sel, caret.position))
+            })
+            .collect()
+    }
+
+    /// Range of the identifier touching `pos`, if there is one
+    pub(crate) fn word_range_at(&self, pos: usize) -> Option<(usize, usize)> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let start = self.content[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_word(c))
+            .last()
+            .map_or(pos, |(idx, _)| idx);
+        let end = self.content[pos..]
+            .char_indices()
+            .find(|&(_, c)| !is_word(c))
+            .map_or(self.content.len(), |(idx, _)| pos + idx);
+        (start < end).then_some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_typing_replaces_every_occurrence() {
+        let mut editor = test_editor("let foo = foo + bar(foo);", 5);
+        for _ in 0..3 {
+            editor.add_next_occurrence();
+        }
+        assert_eq!(editor.carets.len(), 2);
+        editor.for_each_caret(|e| {
+            e.delete_selection();
+            e.insert_text("x");
+        });
+        assert_eq!(editor.content, "let x = x + bar(x);");
+    }
+
+    #[test]
+    fn test_backspace_at_every_caret() {
+        let mut editor = test_editor("ab\nab\n", 2);
+        editor.add_next_occurrence();
+        editor.add_next_occurrence();
+        editor.for_each_caret(|e| {
+            e.clear_selection();
+            e.delete_char();
+        });
+        assert_eq!(editor.content, "a\na\n");
+    }
+}
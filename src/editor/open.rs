@@ -0,0 +1,154 @@
+use crate::editor::encoding::FileEncoding;
+use crate::editor::Editor;
+use crate::remote::RemoteFile;
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tokio::task;
+
+// bytes read between progress updates and cancellation checks
+const CHUNK: usize = 64 * 1024;
+
+/// Progress of a file being read on a background task
+#[derive(Default)]
+struct Progress {
+    read: AtomicU64,
+    // 0 until the size is known
+    total: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+pub struct FileLoad {
+    path: String,
+    progress: Arc<Progress>,
+    // percentage last put in the status bar, to only redraw when it moves
+    shown: Option<u64>,
+    // None for a file that doesn't exist yet
+    result_rx: oneshot::Receiver<Result<Option<(String, FileEncoding)>>>,
+}
+
+fn read_with_progress(path: &Path, progress: &Progress) -> Result<Option<(String, FileEncoding)>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let total = file.metadata().map_or(0, |m| m.len());
+    progress.total.store(total, Ordering::Relaxed);
+    let mut bytes = Vec::with_capacity(total as usize);
+    let mut chunk = vec![0; CHUNK];
+    loop {
+        if progress.cancelled.load(Ordering::Relaxed) {
+            return Err(anyhow!("cancelled"));
+        }
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..n]);
+        progress.read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+    Ok(Some(FileEncoding::decode(bytes)))
+}
+
+impl Editor {
+    /// Opens a file without blocking the UI, the buffer is swapped in once it
+    /// has been read. Remote and encrypted files are still read in place.
+    pub fn open_file(&mut self, path: String) -> Result<()> {
+        if RemoteFile::parse(&path).is_some() || self.config.encryption.matches(&path) {
+            return self.load_file(path);
+        }
+        self.cancel_file_load();
+        let progress = Arc::new(Progress::default());
+        let (result_tx, result_rx) = oneshot::channel();
+        let task_path = path.clone();
+        let task_progress = progress.clone();
+        task::spawn_blocking(move || {
+            let _ = result_tx.send(read_with_progress(Path::new(&task_path), &task_progress));
+        });
+        self.status_message = Some(format!("Opening {}... (esc cancels)", path));
+        self.file_load = Some(FileLoad {
+            path,
+            progress,
+            shown: None,
+            result_rx,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn cancel_file_load(&mut self) {
+        if let Some(load) = self.file_load.take() {
+            load.progress.cancelled.store(true, Ordering::Relaxed);
+            self.status_message = Some(format!("Stopped opening {}", load.path));
+        }
+    }
+
+    /// Shows how far the file has been read and swaps it in when done, returns
+    /// true when there is something new to draw
+    pub(crate) fn poll_file_load(&mut self) -> bool {
+        let Some(load) = self.file_load.as_mut() else {
+            return false;
+        };
+        let result = match load.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => {
+                let total = load.progress.total.load(Ordering::Relaxed);
+                if total == 0 {
+                    return false;
+                }
+                let read = load.progress.read.load(Ordering::Relaxed);
+                let percent = (read * 100 / total).min(100);
+                if load.shown == Some(percent) {
+                    return false;
+                }
+                load.shown = Some(percent);
+                self.status_message = Some(format!(
+                    "Opening {}... {}% (esc cancels)",
+                    load.path, percent
+                ));
+                return true;
+            }
+            Err(TryRecvError::Closed) => Err(anyhow!("file load went away")),
+        };
+        let Some(FileLoad { path, .. }) = self.file_load.take() else {
+            return false;
+        };
+        match result {
+            Ok(content) => {
+                // lock warnings and template offers replace this
+                self.status_message = Some(format!("Opened {}", path));
+                self.remote = None;
+                self.encrypted = false;
+                let is_new = content.is_none();
+                let (content, encoding) = content.unwrap_or_default();
+                self.install_file(path, content, encoding, is_new);
+            }
+            Err(e) => self.status_message = Some(format!("Failed to open {}: {}", path, e)),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_reads_until_cancelled() {
+        let path = std::env::temp_dir().join(format!("nars-open-{}.txt", std::process::id()));
+        fs::write(&path, "hello").unwrap();
+        let progress = Progress::default();
+        let content = read_with_progress(&path, &progress).unwrap();
+        assert_eq!(content.map(|(text, _)| text).as_deref(), Some("hello"));
+        assert_eq!(progress.read.load(Ordering::Relaxed), 5);
+        progress.cancelled.store(true, Ordering::Relaxed);
+        assert!(read_with_progress(&path, &progress).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(read_with_progress(&path, &progress).unwrap().is_none());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::editor::theme::Theme;
+use ratatui::layout::Rect;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use ratatui::Frame;
+
+/// Read-only box drawn over the middle of the editor, closed by any key but
+/// the ones that scroll it
+pub struct Popup {
+    pub title: String,
+    pub lines: Vec<Line<'static>>,
+    // lines scrolled past at the top
+    pub scroll: usize,
+}
+
+impl Popup {
+    pub fn new(title: &str, lines: Vec<Line<'static>>) -> Self {
+        Self {
+            title: title.to_string(),
+            lines,
+            scroll: 0,
+        }
+    }
+
+    /// Scrolls by `lines`, up when negative, keeping the last line in the box
+    pub fn scroll_by(&mut self, lines: isize) {
+        let last = self.lines.len().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(lines).min(last);
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let width = (area.width * 3 / 4).max(20).min(area.width);
+        let height = (self.lines.len() as u16 + 2).min(area.height);
+        let popup_area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(Clear, popup_area);
+        f.render_widget(
+            Paragraph::new(self.lines.clone())
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll as u16, 0))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(self.title.as_str())
+                        .style(theme.base),
+                ),
+            popup_area,
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use crate::editor::diff::{self, unified};
+use crate::editor::popup::Popup;
+use crate::editor::repeat::Action;
+use crate::editor::symbols::extract_functions;
+use crate::editor::Editor;
+use crate::logger::{log, Level};
+use crate::models::prompt::PromptTemplate;
+use crate::models::scope::{PredictionRequest, PredictionScope};
+use crate::models::Predictor;
+use ratatui::style::Style;
+use std::sync::Arc;
+
+// rough size of a token in source code, to turn the token budget into bytes
+pub(crate) const BYTES_PER_TOKEN: usize = 4;
+
+/// How much of a prediction to accept when not taking all of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Part {
+    Word,
+    Line,
+}
+
+impl Part {
+    // the start of `text` this part covers: a word and the whitespace before
+    // it, or the rest of the line, or the next line when at the end of one
+    fn of(self, text: &str) -> &str {
+        match self {
+            Part::Word => {
+                let word = text.trim_start();
+                let skipped = text.len() - word.len();
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                let first_is_word = word.starts_with(is_word);
+                let len = word
+                    .find(|c: char| c.is_whitespace() || is_word(c) != first_is_word)
+                    .unwrap_or(word.len());
+                &text[..skipped + len]
+            }
+            Part::Line => {
+                let skipped = if text.starts_with('\n') { 1 } else { 0 };
+                let len = text[skipped..].find('\n').unwrap_or(text.len() - skipped);
+                &text[..skipped + len]
+            }
+        }
+    }
+}
+
+impl Editor {
+    /// Whether the buffer must stay away from the model: decrypted files and
+    /// anything matching `model.exclude`
+    pub(crate) fn ai_excluded(&self) -> bool {
+        self.encrypted
+            || self
+                .filename
+                .as_deref()
+                .is_some_and(|path| self.config.model.excludes(path))
+    }
+
+    /// Tells the predictor whether the buffer may be sent. It refuses every
+    /// request while it may not, so features don't check for themselves.
+    pub(crate) fn sync_exclusion(&self, predictor: &Predictor) {
+        predictor.set_excluded(self.ai_excluded());
+    }
+
+    /// The request for the rest of line `line` (counting from 1) of `path`,
+    /// with the context the editor would send from there, for `nars complete`
+    pub(crate) fn line_request(
+        &mut self,
+        path: &str,
+        content: String,
+        line: usize,
+    ) -> PredictionRequest {
+        self.filename = Some(path.to_string());
+        self.content = content;
+        self.gutter_cache = None;
+        self.update_syntax_tree();
+        self.go_to_line(line);
+        self.cursor_position = self.line_end(self.cursor_position);
+        self.prediction_request(PredictionScope::Line)
+    }
+
+    /// Builds the request for a prediction of `scope` at the cursor
+    pub(crate) fn prediction_request(&self, scope: PredictionScope) -> PredictionRequest {
+        let line = self.get_current_line_content();
+        let cursor = self.cursor_position;
+        let budget = self.config.model.context_tokens() * BYTES_PER_TOKEN;
+        // types from other files go first, in up to a quarter of the budget
+        let related = self.related_definitions(budget / 4);
+        let budget = budget - related.len();
+        // models that fill in the middle get the code on both sides of the
+        // cursor, a quarter of the budget going to what follows
+        let fill = self.config.model.fim.then(|| {
+            (
+                format!(
+                    "{}{}",
+                    related,
+                    self.context_before(cursor, budget - budget / 4)
+                ),
+                self.context_after(cursor, budget / 4),
+            )
+        });
+        let column = cursor - self.line_start(cursor);
+        let prompts = self.config.language(self.language.name()).prompts;
+        let template = prompts
+            .get(scope.key())
+            .or(prompts.get("default"))
+            .map(|text| PromptTemplate {
+                text: text.clone(),
+                language: self.language.name().to_string(),
+                filename: self.filename.clone().unwrap_or_default(),
+            });
+        let end = self.line_end(cursor);
+        // a multi-line prediction keeps a quarter of the budget for what follows
+        let suffix = match scope {
+            PredictionScope::Multiline => self.context_after(end, budget / 4),
+            _ => String::new(),
+        };
+        let context = self.context_before(end, budget - suffix.len());
+        // how many blocks are open between the enclosing function and the cursor
+        let depth = extract_functions(&self.content)
+            .into_iter()
+            .find(|(_, range)| range.contains(&cursor))
+            .map_or(1, |(_, range)| {
+                let before = &self.content[range.start..cursor];
+                before
+                    .matches('{')
+                    .count()
+                    .saturating_sub(before.matches('}').count())
+            });
+        PredictionRequest {
+            scope,
+            line,
+            column,
+            context,
+            suffix,
+            depth,
+            fill,
+            template,
+            related,
+        }
+    }
+
+    /// Starts a prediction of `scope` at the cursor
+    pub(crate) fn request_prediction(
+        &mut self,
+        predictor: &Arc<Predictor>,
+        scope: PredictionScope,
+    ) {
+        if self.large_file() {
+            self.status_message = Some("Predictions are off for large files".to_string());
+        } else {
+            if scope != PredictionScope::Line {
+                self.status_message = Some(format!("Predicting {}...", scope.name()));
+            }
+            self.cancel_prediction();
+            self.load_symbol_index();
+            let task = predictor
+                .clone()
+                .stream_prediction_background(self.prediction_request(scope));
+            self.pending_prediction = Some((task, self.cursor_position));
+        }
+    }
+
+    /// The line the prediction starts on, that line as it would read with
+    /// the prediction, and the lines the prediction adds below it
+    pub(crate) fn prediction_ghost(&self) -> Option<(usize, String, Vec<String>)> {
+        let (pred, start) = (
+            self.current_prediction.as_ref()?,
+            self.prediction_start_position?,
+        );
+        let typed = &self.content[self.line_start(start)..start];
+        let predicted = format!("{}{}", typed, pred.strip_prefix(typed).unwrap_or(pred));
+        let mut lines = predicted.split('\n').map(str::to_string);
+        let first = lines.next().unwrap_or_default();
+        Some((self.point_at(start).row, first, lines.collect()))
+    }
+
+    /// Styles for prediction text on the cursor line and below it, both stale
+    /// once the cursor has moved away from where the prediction started
+    pub(crate) fn ghost_styles(&self) -> (Style, Style) {
+        if self.prediction_start_position == Some(self.cursor_position) {
+            (self.theme.ghost, self.theme.ghost_below)
+        } else {
+            (self.theme.ghost_stale, self.theme.ghost_stale)
+        }
+    }
+
+    // the buffer as tab would leave it, going the same way as accept_prediction,
+    // None if the prediction rewrites what is already typed
+    fn accepted_content(&self) -> Option<String> {
+        let (pred, start) = (
+            self.current_prediction.as_ref()?,
+            self.prediction_start_position?,
+        );
+        let line_start = self.line_start(start);
+        let typed = &self.content[line_start..start];
+        let mut content = self.content.clone();
+        if let (Some(closers), Some(completion)) =
+            (self.closers_after(start), pred.strip_prefix(typed))
+        {
+            content.replace_range(start..start + closers.len(), completion);
+            return Some(content);
+        }
+        let predicted = format!("{}{}", typed, pred.strip_prefix(typed).unwrap_or(pred));
+        let first_line = predicted.split('\n').next().unwrap_or_default();
+        let line_end = self.line_end(line_start);
+        let insertions = diff::insertions(&self.content[line_start..line_end], first_line)?;
+        // the rest of the prediction goes after everything inserted on the line
+        content.insert_str(line_end, &predicted[first_line.len()..]);
+        for (at, text) in insertions.into_iter().rev() {
+            content.insert_str(line_start + at, text);
+        }
+        Some(content)
+    }
+
+    /// Accepts the next word or line of the prediction at the cursor and
+    /// keeps the rest of it as ghost text
+    pub(crate) fn accept_prediction_part(&mut self, part: Part) {
+        let (Some(pred), Some(start)) = (
+            self.current_prediction.as_ref(),
+            self.prediction_start_position,
+        ) else {
+            return;
+        };
+        let typed = &self.content[self.line_start(start)..start];
+        let Some(rest) = pred
+            .strip_prefix(typed)
+            .filter(|_| self.cursor_position == start)
+        else {
+            self.status_message =
+                Some("Only the whole prediction can be accepted here".to_string());
+            self.bell();
+            return;
+        };
+        let taken = part.of(rest).to_string();
+        let rest = rest[taken.len()..].to_string();
+        self.record(Action::Insert(taken.clone()));
+        self.apply_edit(start, start, &taken);
+        self.update_syntax_tree();
+        self.audit_accepted(start, &taken);
+        log(
+            Level::Info,
+            &format!("accepted part of prediction: {}", taken),
+        );
+        if rest.is_empty() {
+            self.current_prediction = None;
+            self.prediction_start_position = None;
+            return;
+        }
+        let at = self.cursor_position;
+        self.current_prediction = Some(format!(
+            "{}{}",
+            &self.content[self.line_start(at)..at],
+            rest
+        ));
+        self.prediction_start_position = Some(at);
+    }
+
+    /// Shows what accepting the prediction with tab will change
+    pub(crate) fn preview_prediction(&mut self) {
+        if self.current_prediction.is_none() {
+            self.status_message = Some("No prediction to preview".to_string());
+            self.bell();
+            return;
+        }
+        let Some(accepted) = self.accepted_content() else {
+            self.status_message = Some("The prediction rewrites the line".to_string());
+            self.bell();
+            return;
+        };
+        let old: Vec<&str> = self.content.split('\n').collect();
+        let new: Vec<&str> = accepted.split('\n').collect();
+        let lines = unified(&old, &new, &self.theme);
+        self.popup = Some(Popup::new("What tab will change (any key closes)", lines));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::prediction::Part;
+    use crate::editor::test_editor;
+    use crate::editor::{Editor, Placement};
+    use crate::models::scope::PredictionScope;
+    use crate::models::{PredictionDelta, PredictionTask, PredictionUpdate};
+
+    fn predicting(content: &str, cursor: usize, prediction: &str) -> Editor {
+        let mut editor = test_editor(content, cursor);
+        editor.current_prediction = Some(prediction.to_string());
+        editor.prediction_start_position = Some(cursor);
+        editor
+    }
+
+    #[test]
+    fn test_prediction_goes_stale_when_the_cursor_moves() {
+        let mut editor = predicting("let x\n", 5, " = 1;");
+        assert_eq!(editor.ghost_styles().0, editor.theme.ghost);
+        editor.cursor_position = 2;
+        assert_eq!(editor.ghost_styles().0, editor.theme.ghost_stale);
+    }
+
+    #[test]
+    fn test_multiline_prediction_is_drawn_below_its_line() {
+        let editor = predicting(
+            "fn main() {\n    let x\n}\n",
+            21,
+            "    let x = [\n        1,\n    ];",
+        );
+        let (line, first, below) = editor.prediction_ghost().unwrap();
+        assert_eq!((line, first.as_str()), (1, "    let x = ["));
+        assert_eq!(below, vec!["        1,", "    ];"]);
+        let request = editor.prediction_request(PredictionScope::Multiline);
+        assert_eq!(request.context, "fn main() {\n    let x");
+        assert_eq!(request.suffix, "\n}\n");
+        let (prefix, suffix) = request.fill.unwrap();
+        assert_eq!(prefix, "fn main() {\n    let x");
+        assert_eq!(suffix, "\n}\n");
+    }
+
+    #[test]
+    fn test_preview_matches_what_tab_does() {
+        for (content, cursor, prediction) in [
+            (
+                "fn main() {\n    let x\n}\n",
+                21,
+                "    let x = 1;\n    let y = 2;",
+            ),
+            ("fn main() {\n    foo()\n}\n", 20, "    foo(1, 2)"),
+            ("fn main() {\n    if x  {}\n}\n", 21, "    if x == 1 {}"),
+        ] {
+            let mut editor = predicting(content, cursor, prediction);
+            let preview = editor.accepted_content().unwrap();
+            editor.accept_prediction(Placement::Cursor);
+            assert_eq!(preview, editor.content);
+        }
+        let editor = predicting("let x = 1;\n", 4, "let y");
+        assert!(editor.accepted_content().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stale_predictions_are_dropped() {
+        let (mut editor, tx) = Editor::new("test.rs".to_string(), Config::default());
+        editor.content = "let x\n".to_string();
+        editor.cursor_position = 5;
+        let abort = tokio::spawn(async {}).abort_handle();
+        editor.pending_prediction = Some((PredictionTask { id: 2, abort }, 5));
+        let updates = [
+            (1, PredictionDelta::Replace(" = 0;".to_string())),
+            (2, PredictionDelta::Replace(" = 1".to_string())),
+            (3, PredictionDelta::Replace(" = 2;".to_string())),
+            (2, PredictionDelta::Append(";".to_string())),
+        ];
+        for (id, delta) in updates {
+            tx.try_send(PredictionUpdate { id, delta }).unwrap();
+        }
+        assert!(editor.get_latest_prediction());
+        assert_eq!(editor.current_prediction.as_deref(), Some(" = 1;"));
+        assert_eq!(editor.prediction_start_position, Some(5));
+
+        editor.cancel_prediction();
+        let delta = PredictionDelta::Append(" // 3".to_string());
+        tx.try_send(PredictionUpdate { id: 2, delta }).unwrap();
+        assert!(!editor.get_latest_prediction());
+        assert_eq!(editor.current_prediction.as_deref(), Some(" = 1;"));
+    }
+
+    #[test]
+    fn test_accepting_a_prediction_word_by_word_and_line_by_line() {
+        let mut editor = predicting(
+            "fn main() {\n    let x\n}\n",
+            21,
+            "    let x = foo();\n    bar(x);",
+        );
+        editor.accept_prediction_part(Part::Word);
+        assert_eq!(editor.content, "fn main() {\n    let x =\n}\n");
+        editor.accept_prediction_part(Part::Word);
+        assert_eq!(editor.content, "fn main() {\n    let x = foo\n}\n");
+        editor.accept_prediction_part(Part::Line);
+        assert_eq!(editor.content, "fn main() {\n    let x = foo();\n}\n");
+        let (_, first, below) = editor.prediction_ghost().unwrap();
+        assert_eq!(
+            (first.as_str(), below),
+            ("    let x = foo();", vec!["    bar(x);".to_string()])
+        );
+        editor.accept_prediction_part(Part::Line);
+        assert_eq!(
+            editor.content,
+            "fn main() {\n    let x = foo();\n    bar(x);\n}\n"
+        );
+        assert!(editor.current_prediction.is_none());
+    }
+
+    #[test]
+    fn test_language_prompts_from_the_config_are_used() {
+        let mut config = Config::default();
+        let mut rust = config.language("rust");
+        rust.prompts.insert(
+            "default".to_string(),
+            "{language} {filename}: {prefix}".to_string(),
+        );
+        config.languages.insert("rust".to_string(), rust);
+        let (mut editor, _) = Editor::new("test.rs".to_string(), config);
+        editor.content = "fn main() {\n    let x\n}\n".to_string();
+        editor.cursor_position = 21;
+        editor.filename = Some("main.rs".to_string());
+        let request = editor.prediction_request(PredictionScope::Block);
+        assert_eq!(request.prompt(), "rust main.rs: fn main() {\n    let x");
+        let request = editor.prediction_request(PredictionScope::Line);
+        assert_eq!(request.prompt(), "rust main.rs: fn main() {\n    let x");
+    }
+}
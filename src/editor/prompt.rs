@@ -0,0 +1,40 @@
+/// What a bottom bar prompt is collecting input for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PromptKind {
+    Search,
+    ReplaceFind,
+    ReplaceWith,
+    Template,
+    TemplateDescription,
+    Task,
+    Case,
+    AuditFilter,
+    MacroCount,
+    SaveAs,
+    ConfirmSaveAs,
+    DiskChanged,
+    Encoding,
+    UnsavedChanges,
+    FindFile,
+    Chat,
+    Rewrite,
+    LogSearch,
+    Command,
+}
+
+/// Single line input shown in the bottom bar
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub label: String,
+    pub input: String,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, label: &str) -> Self {
+        Self {
+            kind,
+            label: label.to_string(),
+            input: String::new(),
+        }
+    }
+}
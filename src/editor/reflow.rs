@@ -0,0 +1,222 @@
+use crate::editor::Editor;
+use unicode_width::UnicodeWidthStr;
+
+// bytes taken by a list marker and the space after it, e.g. `- ` or `12. `
+fn list_marker(text: &str) -> Option<usize> {
+    if ["- ", "* ", "+ "].iter().any(|m| text.starts_with(m)) {
+        return Some(2);
+    }
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    let rest = &text[digits..];
+    (digits > 0 && (rest.starts_with(". ") || rest.starts_with(") "))).then_some(digits + 2)
+}
+
+// fills `words` into lines of at most `width` columns. The first line starts
+// with `lead` and the rest with `hang`, both after `prefix`.
+fn fill(prefix: &str, lead: &str, hang: &str, words: &[&str], width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = format!("{}{}", prefix, lead);
+    let mut empty = true;
+    for word in words {
+        if !empty && line.width() + 1 + word.width() > width {
+            lines.push(std::mem::replace(&mut line, format!("{}{}", prefix, hang)));
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+        empty = false;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Re-wraps the text of consecutive comment lines to `width` columns, each
+/// line starting with `prefix`. Blank lines and list items start new
+/// paragraphs, list items keep a hanging indent and fenced code is kept as is.
+pub fn reflow(prefix: &str, texts: &[&str], width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut words: Vec<&str> = Vec::new();
+    // indentation of the paragraph's first line and of the lines after it
+    let (mut lead, mut hang) = (String::new(), String::new());
+    let mut in_fence = false;
+    let verbatim = |text: &str| format!("{}{}", prefix, text).trim_end().to_string();
+    for text in texts {
+        let trimmed = text.trim_start();
+        let indent = text.len() - trimmed.len();
+        let marker = list_marker(trimmed);
+        let new_paragraph = trimmed.is_empty()
+            || trimmed.starts_with("```")
+            || in_fence
+            || marker.is_some()
+            || (!hang.is_empty() && indent < hang.len());
+        if new_paragraph && !words.is_empty() {
+            out.extend(fill(prefix, &lead, &hang, &words, width));
+            words.clear();
+        }
+        if trimmed.starts_with("```") || in_fence || trimmed.is_empty() {
+            in_fence ^= trimmed.starts_with("```");
+            out.push(verbatim(text));
+            continue;
+        }
+        if words.is_empty() {
+            lead = text[..indent + marker.unwrap_or(0)].to_string();
+            hang = " ".repeat(lead.width());
+        }
+        words.extend(trimmed[marker.unwrap_or(0)..].split_whitespace());
+    }
+    if !words.is_empty() {
+        out.extend(fill(prefix, &lead, &hang, &words, width));
+    }
+    out
+}
+
+impl Editor {
+    // indentation and comment marker of a line holding only a comment, like
+    // `    ///` or ` *` inside a block comment. The lines that open and close
+    // a block comment are left alone.
+    fn comment_prefix(&self, line_start: usize) -> Option<String> {
+        let line = &self.content[line_start..self.line_end(line_start)];
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let pos = line_start + indent.len();
+        let tree = self.tree.as_ref().filter(|_| !trimmed.is_empty())?;
+        // the outermost comment node, doc comments have comment children
+        let mut node = tree.root_node().descendant_for_byte_range(pos, pos);
+        let mut comment = None;
+        while let Some(current) = node {
+            if current.kind().ends_with("comment") {
+                comment = Some(current);
+            }
+            node = current.parent();
+        }
+        let comment = comment?;
+        let text = &self.content[comment.start_byte()..comment.end_byte()];
+        let marker = if text.starts_with("//") {
+            if comment.start_byte() != pos {
+                return None;
+            }
+            ["///", "//!", "//"]
+                .into_iter()
+                .find(|marker| trimmed.starts_with(marker))?
+        } else if trimmed.starts_with("/*") || trimmed.contains("*/") {
+            return None;
+        } else if trimmed.starts_with('*') {
+            "*"
+        } else {
+            ""
+        };
+        Some(format!("{}{}", indent, marker))
+    }
+
+    /// Re-wraps the comment around the cursor, or the comments in the
+    /// selection, to the language's `text_width`
+    pub(crate) fn reflow_comment(&mut self) {
+        let width = self.config.language(self.language.name()).text_width;
+        let mut starts = self.selected_line_starts();
+        if self.selection.is_none() {
+            let Some(prefix) = self.comment_prefix(starts[0]) else {
+                self.status_message = Some("Not in a comment".to_string());
+                self.bell();
+                return;
+            };
+            // grow to the whole run of lines with the same comment marker
+            while let Some(above) = starts[0].checked_sub(1).map(|end| self.line_start(end)) {
+                if self.comment_prefix(above).as_ref() != Some(&prefix) {
+                    break;
+                }
+                starts.insert(0, above);
+            }
+            loop {
+                let below = self.line_end(starts[starts.len() - 1]) + 1;
+                if below > self.content.len()
+                    || self.comment_prefix(below).as_ref() != Some(&prefix)
+                {
+                    break;
+                }
+                starts.push(below);
+            }
+        }
+        // runs of comment lines with the same prefix, code between them is kept
+        let mut runs: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut in_run = false;
+        for start in starts {
+            let prefix = self.comment_prefix(start);
+            let continues = in_run && runs.last().map(|(run, _)| run) == prefix.as_ref();
+            in_run = prefix.is_some();
+            match (prefix, runs.last_mut()) {
+                (Some(_), Some((_, lines))) if continues => lines.push(start),
+                (Some(prefix), _) => runs.push((prefix, vec![start])),
+                (None, _) => {}
+            }
+        }
+        let Some(first) = runs.first().map(|(_, lines)| lines[0]) else {
+            self.status_message = Some("No comments selected".to_string());
+            self.bell();
+            return;
+        };
+        // bottom up, so the offsets of the runs above stay valid
+        for (prefix, lines) in runs.into_iter().rev() {
+            let start = lines[0];
+            let end = self.line_end(lines[lines.len() - 1]);
+            // the space after a comment marker belongs to the prefix
+            let marked = !prefix.trim_start().is_empty();
+            let texts: Vec<&str> = lines
+                .iter()
+                .map(|&line| {
+                    let text = &self.content[line + prefix.len()..self.line_end(line)];
+                    match text.strip_prefix(' ') {
+                        Some(text) if marked => text,
+                        _ => text,
+                    }
+                })
+                .collect();
+            let prefix = if marked {
+                format!("{} ", prefix)
+            } else {
+                prefix
+            };
+            let text = reflow(&prefix, &texts, width).join("\n");
+            if text != self.content[start..end] {
+                self.apply_edit(start, end, &text);
+            }
+        }
+        self.selection = None;
+        self.cursor_position = first;
+        self.update_syntax_tree();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reflows_paragraphs_and_lists() {
+        let texts = [
+            "Loads the file and",
+            "parses it into lines.",
+            "",
+            "- a list item that is long enough to wrap",
+            "- short",
+            "```",
+            "let x = some_function_call(with, arguments);",
+            "```",
+        ];
+        assert_eq!(
+            reflow("    /// ", &texts, 36),
+            vec![
+                "    /// Loads the file and parses it",
+                "    /// into lines.",
+                "    ///",
+                "    /// - a list item that is long",
+                "    ///   enough to wrap",
+                "    /// - short",
+                "    /// ```",
+                "    /// let x = some_function_call(with, arguments);",
+                "    /// ```",
+            ]
+        );
+    }
+}
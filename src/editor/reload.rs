@@ -0,0 +1,151 @@
+use crate::editor::diff::{diff, unified};
+use crate::editor::encoding::FileEncoding;
+use crate::editor::line_endings::LineEnding;
+use crate::editor::popup::Popup;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use crate::encryption;
+use anyhow::Result;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use std::fs;
+use std::time::{Duration, Instant};
+
+// how often the file's modification time is looked at
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+impl Editor {
+    fn read_from_disk(&self) -> Result<String> {
+        let path = self.filename.as_deref().unwrap_or_default();
+        if self.encrypted {
+            encryption::decrypt(&self.config.encryption, path)
+        } else {
+            Ok(FileEncoding::decode(fs::read(path)?).0)
+        }
+    }
+
+    /// Offers to reload the file once it has been changed by another program,
+    /// returns true if the prompt was opened
+    pub(crate) fn poll_disk_changes(&mut self) -> bool {
+        if self
+            .disk_checked
+            .is_some_and(|at| at.elapsed() < DISK_CHECK_INTERVAL)
+        {
+            return false;
+        }
+        self.disk_checked = Some(Instant::now());
+        // don't interrupt something that is being typed
+        if self.prompt.is_some() || self.replace.is_some() || !self.changed_on_disk() {
+            return false;
+        }
+        let modified = self.disk_modified_time();
+        if modified == self.disk_dismissed {
+            return false;
+        }
+        self.disk_dismissed = modified;
+        self.prompt = Some(Prompt::new(
+            PromptKind::DiskChanged,
+            "File changed on disk: r reload, k keep mine, d diff",
+        ));
+        true
+    }
+
+    /// Handles r, k and d while the reload prompt is showing
+    pub(crate) fn handle_disk_changed_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('r') => {
+                self.prompt = None;
+                self.reload_from_disk();
+            }
+            KeyCode::Char('k') | KeyCode::Esc => {
+                self.prompt = None;
+                self.status_message =
+                    Some("Kept your version, ctrl+s asks before overwriting the file".to_string());
+            }
+            KeyCode::Char('d') => self.show_disk_diff(),
+            _ => {}
+        }
+    }
+
+    // replaces only the lines that differ, so the cursor, marks and folds
+    // elsewhere in the file stay where they are
+    fn reload_from_disk(&mut self) {
+        let disk = match self.read_from_disk() {
+            Ok(disk) => {
+                self.line_ending = LineEnding::detect(&disk);
+                self.gutter_cache = None;
+                LineEnding::strip(disk)
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Reload failed: {}", e));
+                self.bell();
+                return;
+            }
+        };
+        let old: Vec<&str> = self.content.split_inclusive('\n').collect();
+        let new: Vec<&str> = disk.split_inclusive('\n').collect();
+        let offset = |lines: &[&str], idx: usize| lines[..idx].iter().map(|l| l.len()).sum();
+        let edits: Vec<(usize, usize, String)> = diff(&old, &new)
+            .into_iter()
+            .map(|hunk| {
+                let start: usize = offset(&old, hunk.old.start);
+                let end: usize = offset(&old, hunk.old.end);
+                (start, end, new[hunk.new].concat())
+            })
+            .collect();
+        for (start, end, text) in edits.into_iter().rev() {
+            self.apply_edit(start, end, &text);
+        }
+        self.update_syntax_tree();
+        self.modified = false;
+        self.disk_modified = self.disk_modified_time();
+        self.disk_dismissed = None;
+        self.audit(format!(
+            "Reloaded {}",
+            self.filename.clone().unwrap_or_default()
+        ));
+        self.load_git_base();
+        self.status_message = Some("Reloaded from disk".to_string());
+    }
+
+    // what saving the buffer would change in the file on disk
+    fn show_disk_diff(&mut self) {
+        let disk = match self.read_from_disk() {
+            Ok(disk) => LineEnding::strip(disk),
+            Err(e) => {
+                self.status_message = Some(format!("Can't read the file: {}", e));
+                return;
+            }
+        };
+        let old: Vec<&str> = disk.split('\n').collect();
+        let new: Vec<&str> = self.content.split('\n').collect();
+        let lines = unified(&old, &new, &self.theme);
+        if lines.is_empty() {
+            self.status_message = Some("The file on disk matches the buffer".to_string());
+            return;
+        }
+        self.popup = Some(Popup::new("Buffer against the file on disk", lines));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::Editor;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_reload_keeps_the_cursor_on_unchanged_lines() {
+        let path = std::env::temp_dir().join(format!("nars-reload-{}.rs", std::process::id()));
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        let (mut editor, _) = Editor::new(path.to_string_lossy().to_string(), Config::default());
+        editor
+            .load_file(path.to_string_lossy().to_string())
+            .unwrap();
+        editor.cursor_position = 2;
+        fs::write(&path, "new\na\nb\nc changed\n").unwrap();
+        editor.reload_from_disk();
+        assert_eq!(editor.content, "new\na\nb\nc changed\n");
+        assert_eq!(&editor.content[editor.cursor_position..], "b\nc changed\n");
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::editor::case::Case;
+use crate::editor::Editor;
+
+// stands in for backspace inside a typed run
+const BACKSPACE: char = '\u{8}';
+
+/// An edit that alt+. can replay at the cursor
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Keys typed in a row, with enter as `\n` and backspace as `\u{8}`
+    Typed(String),
+    /// Text inserted in one go, e.g. a paste or an accepted prediction
+    Insert(String),
+    /// A prediction placed on new lines below the current one
+    InsertBelow(String),
+    DuplicateLines,
+    MoveLines {
+        up: bool,
+    },
+    Kill {
+        to_end: bool,
+    },
+    Indent,
+    Dedent,
+    ToggleComment,
+    TransformCase(Case),
+}
+
+impl Editor {
+    pub(crate) fn record(&mut self, action: Action) {
+        self.last_action = Some(action);
+        self.typing_run = false;
+    }
+
+    /// Adds typed text to the current run, or starts a new one after
+    /// anything else happened
+    pub(crate) fn record_typed(&mut self, text: &str) {
+        match &mut self.last_action {
+            Some(Action::Typed(run)) if self.typing_run => run.push_str(text),
+            _ => self.last_action = Some(Action::Typed(text.to_string())),
+        }
+        self.typing_run = true;
+    }
+
+    pub(crate) fn record_backspace(&mut self) {
+        self.record_typed(BACKSPACE.encode_utf8(&mut [0; 4]));
+    }
+
+    pub(crate) fn end_typing_run(&mut self) {
+        self.typing_run = false;
+    }
+
+    /// Replays the last edit at the cursor
+    pub(crate) fn repeat_last_action(&mut self) {
+        let Some(action) = self.last_action.clone() else {
+            self.bell();
+            return;
+        };
+        self.current_prediction = None;
+        self.prediction_start_position = None;
+        match &action {
+            Action::Typed(run) => {
+                for c in run.chars() {
+                    match c {
+                        '\n' => self.for_each_caret(Editor::insert_newline),
+                        BACKSPACE => self.for_each_caret(Editor::backspace),
+                        c => {
+                            let text = c.to_string();
+                            self.for_each_caret(|e| e.type_text(&text));
+                        }
+                    }
+                }
+            }
+            Action::Insert(text) => {
+                self.delete_selection();
+                self.insert_text(text);
+            }
+            Action::InsertBelow(text) => {
+                let end = self.line_end(self.cursor_position);
+                let text = format!("\n{}", text);
+                self.apply_edit(end, end, &text);
+                self.cursor_position = end + text.len();
+                self.update_syntax_tree();
+            }
+            Action::DuplicateLines => self.duplicate_lines(),
+            Action::MoveLines { up } => self.move_lines(*up),
+            Action::Kill { to_end } => self.kill(*to_end),
+            Action::Indent => self.indent_selection(),
+            Action::Dedent => self.dedent_selection(),
+            Action::ToggleComment => self.toggle_comment_selection(),
+            Action::TransformCase(case) => self.transform_case(*case),
+        }
+        self.record(action);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_repeats_typed_run_with_backspace() {
+        let mut editor = test_editor("", 0);
+        for c in ["a", "x"] {
+            editor.type_text(c);
+            editor.record_typed(c);
+        }
+        editor.backspace();
+        editor.record_backspace();
+        editor.type_text("(");
+        editor.record_typed("(");
+        editor.end_typing_run();
+        editor.cursor_position = editor.content.len();
+        editor.repeat_last_action();
+        assert_eq!(editor.content, "a()a()");
+    }
+}
@@ -0,0 +1,225 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use regex::Regex;
+
+pub struct Replace {
+    pub pattern: String,
+    pub replacement: String,
+    pub regex: bool,
+    // set once both prompts are answered and y/n/a decide each match
+    pub confirming: bool,
+    pub replaced: usize,
+}
+
+impl Replace {
+    fn compile(&self, pattern: &str) -> Option<Regex> {
+        if pattern.is_empty() {
+            return None;
+        }
+        if self.regex {
+            Regex::new(pattern).ok()
+        } else {
+            Regex::new(&regex::escape(pattern)).ok()
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        if self.regex {
+            "Replace (regex): "
+        } else {
+            "Replace: "
+        }
+    }
+}
+
+impl Editor {
+    pub(crate) fn open_replace(&mut self) {
+        let replace = Replace {
+            pattern: String::new(),
+            replacement: String::new(),
+            regex: false,
+            confirming: false,
+            replaced: 0,
+        };
+        self.prompt = Some(Prompt::new(PromptKind::ReplaceFind, replace.label()));
+        self.replace = Some(replace);
+    }
+
+    pub(crate) fn toggle_replace_regex(&mut self) {
+        if let (Some(replace), Some(prompt)) = (self.replace.as_mut(), self.prompt.as_mut()) {
+            replace.regex = !replace.regex;
+            prompt.label = replace.label().to_string();
+        }
+    }
+
+    fn replace_regex(&self) -> Option<Regex> {
+        let replace = self.replace.as_ref()?;
+        match &self.prompt {
+            Some(prompt) if prompt.kind == PromptKind::ReplaceFind => {
+                replace.compile(&prompt.input)
+            }
+            _ => replace.compile(&replace.pattern),
+        }
+    }
+
+    /// Non-empty matches of the pattern being typed or confirmed
+    pub(crate) fn replace_matches(&self) -> Vec<(usize, usize)> {
+        let Some(re) = self.replace_regex() else {
+            return Vec::new();
+        };
+        re.find_iter(&self.content)
+            .filter(|m| !m.is_empty())
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+
+    pub(crate) fn confirm_replace_pattern(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match self.replace.as_mut() {
+            Some(replace) if replace.compile(&prompt.input).is_some() => {
+                replace.pattern = prompt.input;
+                self.prompt = Some(Prompt::new(PromptKind::ReplaceWith, "Replace with: "));
+            }
+            _ => {
+                self.replace = None;
+                self.status_message = Some("Invalid pattern".to_string());
+                self.bell();
+            }
+        }
+    }
+
+    pub(crate) fn confirm_replacement(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        if let Some(replace) = self.replace.as_mut() {
+            replace.replacement = prompt.input;
+            replace.confirming = true;
+        }
+        self.next_replace_match(self.cursor_position);
+    }
+
+    pub(crate) fn cancel_replace(&mut self) {
+        self.prompt = None;
+        if let Some(replace) = self.replace.take() {
+            if replace.confirming {
+                self.status_message = Some(format!("Replaced {} matches", replace.replaced));
+            }
+        }
+    }
+
+    // moves to the first match at or after `from`, finishing when none are left
+    fn next_replace_match(&mut self, from: usize) {
+        let matches = self.replace_matches();
+        match matches.iter().position(|(start, _)| *start >= from) {
+            Some(idx) => {
+                self.cursor_position = matches[idx].0;
+                self.status_message = Some(format!(
+                    "Replace match {} of {}? (y)es (n)o (a)ll (esc)",
+                    idx + 1,
+                    matches.len()
+                ));
+            }
+            None => {
+                if self.replace.as_ref().is_some_and(|r| r.replaced == 0) {
+                    self.bell();
+                }
+                self.cancel_replace();
+            }
+        }
+    }
+
+    fn replacement_for(&self, re: &Regex, start: usize) -> Option<(usize, usize, String)> {
+        let replace = self.replace.as_ref()?;
+        let caps = re.captures_at(&self.content, start)?;
+        let m = caps.get(0)?;
+        let text = if replace.regex {
+            let mut text = String::new();
+            caps.expand(&replace.replacement, &mut text);
+            text
+        } else {
+            replace.replacement.clone()
+        };
+        Some((m.start(), m.end(), text))
+    }
+
+    pub(crate) fn replace_current(&mut self) {
+        let Some(re) = self.replace_regex() else {
+            return;
+        };
+        match self.replacement_for(&re, self.cursor_position) {
+            Some((start, end, text)) if start == self.cursor_position => {
+                self.apply_edit(start, end, &text);
+                self.update_syntax_tree();
+                if let Some(replace) = self.replace.as_mut() {
+                    replace.replaced += 1;
+                }
+                self.next_replace_match(start + text.len());
+            }
+            _ => self.next_replace_match(self.cursor_position),
+        }
+    }
+
+    pub(crate) fn skip_current(&mut self) {
+        self.next_replace_match(self.cursor_position + 1);
+    }
+
+    /// Replaces every remaining match as one batch, back to front so earlier
+    /// offsets stay valid, with a single reparse at the end
+    pub(crate) fn replace_all(&mut self) {
+        let Some(re) = self.replace_regex() else {
+            return;
+        };
+        let edits: Vec<(usize, usize, String)> = self
+            .replace_matches()
+            .into_iter()
+            .filter(|(start, _)| *start >= self.cursor_position)
+            .filter_map(|(start, _)| self.replacement_for(&re, start))
+            .collect();
+        for (start, end, text) in edits.iter().rev() {
+            self.apply_edit(*start, *end, text);
+        }
+        self.update_syntax_tree();
+        if let Some(replace) = self.replace.as_mut() {
+            replace.replaced += edits.len();
+        }
+        self.cancel_replace();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    fn replace_all(content: &str, pattern: &str, replacement: &str, regex: bool) -> String {
+        let mut editor = test_editor(content, 0);
+        editor.open_replace();
+        if regex {
+            editor.toggle_replace_regex();
+        }
+        editor.prompt.as_mut().unwrap().input = pattern.to_string();
+        editor.confirm_replace_pattern();
+        editor.prompt.as_mut().unwrap().input = replacement.to_string();
+        editor.confirm_replacement();
+        editor.replace_all();
+        editor.content
+    }
+
+    #[test]
+    fn test_replace_all_literal() {
+        assert_eq!(
+            replace_all("let a = a.b(a);", "a.b", "c", false),
+            "let a = c(a);"
+        );
+    }
+
+    #[test]
+    fn test_replace_all_regex_captures() {
+        assert_eq!(
+            replace_all("foo(1); foo(22);", r"foo\((\d+)\)", "bar($1)", true),
+            "bar(1); bar(22);"
+        );
+    }
+}
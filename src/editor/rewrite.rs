@@ -0,0 +1,304 @@
+use crate::editor::diff::unified;
+use crate::editor::popup::Popup;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use crate::models::Predictor;
+use anyhow::{anyhow, Result};
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// What the model's answer turns into before it replaces the code
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RewriteKind {
+    /// The rewritten code itself
+    Instruction,
+    /// The text of a doc comment to put above the code, its lines starting
+    /// with `prefix`
+    Doc { prefix: &'static str },
+}
+
+impl RewriteKind {
+    fn finish(self, original: &str, answer: &str) -> String {
+        match self {
+            RewriteKind::Instruction => keep_layout(original, answer),
+            RewriteKind::Doc { prefix } => {
+                format!("{}{}", doc_comment(prefix, original, answer), original)
+            }
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            RewriteKind::Instruction => "Rewrite (y/enter accepts, n/esc rejects)",
+            RewriteKind::Doc { .. } => "Doc comment (y/enter adds it, n/esc drops it)",
+        }
+    }
+}
+
+/// Code being rewritten by the model, from an instruction for a selection or
+/// to document an item
+pub struct Rewrite {
+    range: Range<usize>,
+    original: String,
+    kind: RewriteKind,
+    // set once the instruction is sent
+    result_rx: Option<oneshot::Receiver<Result<String>>>,
+    // what the model wrote, shown as a diff until accepted or rejected
+    rewritten: Option<String>,
+}
+
+// the model's text as comment lines starting with `prefix`, indented like
+// the code they go above
+fn doc_comment(prefix: &str, original: &str, text: &str) -> String {
+    let indent = &original[..original.len() - original.trim_start_matches([' ', '\t']).len()];
+    let mut comment = String::new();
+    for line in text.trim().lines() {
+        // in case the model wrote the markers anyway
+        let line = line.trim();
+        let line = ["///", "//!", "//"]
+            .iter()
+            .find_map(|marker| line.strip_prefix(marker))
+            .unwrap_or(line)
+            .trim();
+        if line.is_empty() {
+            comment.push_str(&format!("{}{}\n", indent, prefix));
+        } else {
+            comment.push_str(&format!("{}{} {}\n", indent, prefix, line));
+        }
+    }
+    comment
+}
+
+// the model's code comes back trimmed, so it gets the selection's leading
+// indentation and trailing newline back
+fn keep_layout(original: &str, rewritten: &str) -> String {
+    let indent = &original[..original.len() - original.trim_start().len()];
+    let mut text = if rewritten.starts_with(char::is_whitespace) {
+        rewritten.to_string()
+    } else {
+        format!("{}{}", indent.trim_start_matches('\n'), rewritten)
+    };
+    if original.ends_with('\n') && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+impl Editor {
+    /// Asks how to rewrite the selection
+    pub(crate) fn open_rewrite_prompt(&mut self) {
+        let Some((start, end)) = self.selection_range().filter(|(s, e)| s < e) else {
+            self.status_message = Some("Select the code to rewrite".to_string());
+            self.bell();
+            return;
+        };
+        self.rewrite = Some(Rewrite {
+            range: start..end,
+            original: self.content[start..end].to_string(),
+            kind: RewriteKind::Instruction,
+            result_rx: None,
+            rewritten: None,
+        });
+        self.prompt = Some(Prompt::new(
+            PromptKind::Rewrite,
+            "Rewrite the selection to: ",
+        ));
+    }
+
+    pub(crate) fn confirm_rewrite_instruction(&mut self, predictor: &Arc<Predictor>) {
+        let instruction = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let Some(rewrite) = self.rewrite.as_mut() else {
+            return;
+        };
+        if instruction.trim().is_empty() {
+            self.rewrite = None;
+            return;
+        }
+        let (result_tx, result_rx) = oneshot::channel();
+        predictor.clone().edit_background(
+            rewrite.original.clone(),
+            format!(
+                "{} (the code is part of a {} file, keep its indentation)",
+                instruction.trim(),
+                self.language.name()
+            ),
+            result_tx,
+        );
+        rewrite.result_rx = Some(result_rx);
+        self.status_message = Some("Rewriting...".to_string());
+    }
+
+    /// Waits on the model's answer for `range`, to show what it makes of the
+    /// code there as a diff
+    pub(crate) fn await_rewrite(
+        &mut self,
+        range: Range<usize>,
+        kind: RewriteKind,
+        result_rx: oneshot::Receiver<Result<String>>,
+    ) {
+        self.rewrite = Some(Rewrite {
+            original: self.content[range.clone()].to_string(),
+            range,
+            kind,
+            result_rx: Some(result_rx),
+            rewritten: None,
+        });
+    }
+
+    pub(crate) fn cancel_rewrite(&mut self) {
+        self.prompt = None;
+        self.rewrite = None;
+    }
+
+    /// Shows the model's rewrite as a diff over the selection once it is
+    /// done, returns true if anything changed
+    pub(crate) fn poll_rewrite(&mut self) -> bool {
+        let Some(result_rx) = self.rewrite.as_mut().and_then(|r| r.result_rx.as_mut()) else {
+            return false;
+        };
+        let result = match result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => Err(anyhow!("the model went away")),
+        };
+        let Some(mut rewrite) = self.rewrite.take() else {
+            return false;
+        };
+        rewrite.result_rx = None;
+        let rewritten = match result {
+            Ok(_) if self.content.get(rewrite.range.clone()) != Some(rewrite.original.as_str()) => {
+                self.status_message = Some("Buffer was edited, dropped the rewrite".to_string());
+                return true;
+            }
+            Ok(code) if code.trim().is_empty() => {
+                self.status_message = Some("The model returned nothing".to_string());
+                return true;
+            }
+            Ok(code) => rewrite.kind.finish(&rewrite.original, &code),
+            Err(e) => {
+                self.status_message = Some(format!("Rewrite failed: {}", e));
+                return true;
+            }
+        };
+        if rewritten == rewrite.original {
+            self.status_message = Some("The rewrite changes nothing".to_string());
+            return true;
+        }
+        let old: Vec<&str> = rewrite.original.split('\n').collect();
+        let new: Vec<&str> = rewritten.split('\n').collect();
+        let lines = unified(&old, &new, &self.theme);
+        self.popup = Some(Popup::new(rewrite.kind.title(), lines));
+        self.status_message = None;
+        rewrite.rewritten = Some(rewritten);
+        self.rewrite = Some(rewrite);
+        true
+    }
+
+    /// Whether the diff of a finished rewrite is showing, taking the keys
+    pub(crate) fn rewrite_ready(&self) -> bool {
+        self.popup.is_some() && self.rewrite.as_ref().is_some_and(|r| r.rewritten.is_some())
+    }
+
+    /// y or enter puts the rewrite in place of the selection in one edit,
+    /// n or esc drops it and the arrows scroll the diff
+    pub(crate) fn handle_rewrite_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => self.accept_rewrite(),
+            KeyCode::Char('n') | KeyCode::Esc => {
+                self.rewrite = None;
+                self.popup = None;
+                self.status_message = Some("Rewrite rejected".to_string());
+            }
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => {
+                let lines = match key.code {
+                    KeyCode::Up => -1,
+                    KeyCode::Down => 1,
+                    KeyCode::PageUp => -10,
+                    _ => 10,
+                };
+                if let Some(popup) = self.popup.as_mut() {
+                    popup.scroll_by(lines);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn accept_rewrite(&mut self) {
+        self.popup = None;
+        let Some(Rewrite {
+            range,
+            original,
+            kind,
+            rewritten: Some(rewritten),
+            ..
+        }) = self.rewrite.take()
+        else {
+            return;
+        };
+        self.selection = None;
+        self.apply_edit(range.start, range.end, &rewritten);
+        self.update_syntax_tree();
+        // a doc comment leaves the cursor on it, a rewrite after the new code
+        self.cursor_position = match kind {
+            RewriteKind::Instruction => range.start + rewritten.len(),
+            RewriteKind::Doc { .. } => range.start,
+        };
+        self.audit(format!(
+            "Rewrote {:?} with the model's output: {:?}",
+            original, rewritten
+        ));
+        self.status_message = Some(
+            match kind {
+                RewriteKind::Instruction => "Rewritten, ctrl+z puts the original back",
+                RewriteKind::Doc { .. } => "Documented, ctrl+z takes it out",
+            }
+            .to_string(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor::selection::{Selection, SelectionMode};
+    use crate::editor::test_editor;
+    use ratatui::crossterm::event::KeyModifiers;
+
+    #[test]
+    fn test_rewrite_is_shown_then_applied_and_undone() {
+        let mut editor = test_editor("", 0);
+        let original = "    for x in xs {\n        total += x;\n    }\n";
+        editor.content = format!("fn main() {{\n{}}}\n", original);
+        editor.selection = Some(Selection {
+            anchor: 12,
+            mode: SelectionMode::Char,
+        });
+        editor.cursor_position = 12 + original.len();
+        editor.open_rewrite_prompt();
+        assert_eq!(editor.prompt.as_ref().unwrap().kind, PromptKind::Rewrite);
+        editor.prompt = None;
+        let (result_tx, result_rx) = oneshot::channel();
+        editor.rewrite.as_mut().unwrap().result_rx = Some(result_rx);
+        assert!(!editor.poll_rewrite());
+        result_tx
+            .send(Ok("total += xs.iter().sum::<i32>();".to_string()))
+            .unwrap();
+        assert!(editor.poll_rewrite());
+        assert!(editor.rewrite_ready());
+        assert!(editor.content.contains("for x in xs"));
+
+        editor.handle_rewrite_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        assert_eq!(
+            editor.content,
+            "fn main() {\n    total += xs.iter().sum::<i32>();\n}\n"
+        );
+        assert!(editor.rewrite.is_none() && editor.popup.is_none());
+
+        editor.undo();
+        assert_eq!(editor.content, format!("fn main() {{\n{}}}\n", original));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+
+impl Editor {
+    /// Colours the language's ruler columns in `text`, the cells inside a
+    /// pane's border. Cells with a highlight of their own, like selections
+    /// and matches, keep it.
+    pub(crate) fn draw_rulers(&self, buf: &mut Buffer, text: Rect, theme: &Theme) {
+        let Some(bg) = theme.ruler.bg else {
+            return;
+        };
+        let plain = [
+            theme.base.bg.unwrap_or(Color::Reset),
+            theme.current_line.bg.unwrap_or(Color::Reset),
+        ];
+        let rulers = self.config.language(self.language.name()).rulers;
+        for column in rulers
+            .into_iter()
+            .filter(|&c| c > 0 && c <= text.width as usize)
+        {
+            let x = text.x + column as u16 - 1;
+            for y in text.top()..text.bottom() {
+                let cell = &mut buf[(x, y)];
+                if plain.contains(&cell.bg) {
+                    cell.set_bg(bg);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_rulers_leave_highlights_alone() {
+        let mut config = Config::default();
+        let mut rust = config.language("rust");
+        rust.rulers = vec![3, 40];
+        config.languages.insert("rust".to_string(), rust);
+        let (editor, _) = Editor::new("test.rs".to_string(), config);
+        let theme = Theme::default();
+        let area = Rect::new(0, 0, 10, 2);
+        let mut buf = Buffer::empty(area);
+        buf.set_style(area, theme.base);
+        buf[(2, 1)].set_style(theme.selection);
+        editor.draw_rulers(&mut buf, area, &theme);
+        assert_eq!(buf[(2, 0)].bg, theme.ruler.bg.unwrap());
+        assert_eq!(buf[(2, 1)].bg, theme.selection.bg.unwrap());
+        assert_eq!(buf[(3, 0)].bg, theme.base.bg.unwrap());
+    }
+}
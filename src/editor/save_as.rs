@@ -0,0 +1,160 @@
+use crate::editor::languages::Language;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use crate::lockfile::FileLock;
+use crate::logger::{log, Level};
+use std::fs;
+use std::path::Path;
+
+impl Editor {
+    /// Asks for the path to save the buffer to, starting from the current one
+    pub(crate) fn open_save_as_prompt(&mut self) {
+        let mut prompt = Prompt::new(PromptKind::SaveAs, "Save as: ");
+        prompt.input = self.filename.clone().unwrap_or_default();
+        self.prompt = Some(prompt);
+    }
+
+    pub(crate) fn confirm_save_as(&mut self) {
+        let path = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return;
+        }
+        if Path::new(&path).is_dir() {
+            self.status_message = Some(format!("{} is a directory", path));
+            self.bell();
+            return;
+        }
+        if Path::new(&path).exists() && self.filename.as_deref() != Some(path.as_str()) {
+            let mut prompt = Prompt::new(
+                PromptKind::ConfirmSaveAs,
+                "File exists, enter to overwrite, esc to cancel: ",
+            );
+            prompt.input = path;
+            self.prompt = Some(prompt);
+            return;
+        }
+        self.save_as(path);
+    }
+
+    pub(crate) fn confirm_save_as_overwrite(&mut self) {
+        if let Some(prompt) = self.prompt.take() {
+            self.save_as(prompt.input.trim().to_string());
+        }
+    }
+
+    // makes `path` the buffer's file and writes it there. The buffer only
+    // becomes the new file once writing it worked.
+    pub(crate) fn save_as(&mut self, path: String) {
+        let encrypted = self.config.encryption.matches(&path);
+        if self.encrypted && !encrypted {
+            self.status_message = Some(format!(
+                "{} isn't covered by encryption.patterns and would be written in the clear",
+                path
+            ));
+            self.bell();
+            return;
+        }
+        if let Some(parent) = Path::new(&path)
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+        {
+            if let Err(e) = fs::create_dir_all(parent) {
+                self.status_message = Some(format!("Can't create {}: {}", parent.display(), e));
+                self.bell();
+                return;
+            }
+        }
+        let renamed = self.filename.as_deref() != Some(path.as_str()) || self.remote.is_some();
+        let previous = (
+            self.filename.replace(path.clone()),
+            self.remote.take(),
+            std::mem::replace(&mut self.encrypted, encrypted),
+        );
+        let result = self.save_file();
+        if result.is_err() {
+            (self.filename, self.remote, self.encrypted) = previous;
+            self.finish_save(result);
+            return;
+        }
+        let mut warning = None;
+        if renamed {
+            // the old file's lock goes when the buffer stops being that file
+            self.lock = None;
+            let (lock, lock_warning) = FileLock::acquire(&path);
+            (self.lock, warning) = (lock, lock_warning);
+        }
+        self.set_language(Language::detect(&path, &self.config.file_types));
+        self.finish_save(result);
+        if warning.is_some() {
+            self.status_message = warning;
+        }
+    }
+
+    // switches the grammar and language settings, e.g. after the extension changed
+    pub(crate) fn set_language(&mut self, language: Language) {
+        if language == self.language {
+            return;
+        }
+        if let Err(e) = self.parser.set_language(language.grammar()) {
+            log(
+                Level::Error,
+                &format!("Error loading {} grammar: {}", language.name(), e),
+            );
+            return;
+        }
+        self.language = language;
+        self.folds.clear();
+        self.tree = None;
+        self.update_syntax_tree();
+        self.reset_indent_style();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::languages::Language;
+    use crate::editor::Editor;
+    use std::fs;
+
+    // saving starts loading the git base on a blocking task
+    #[tokio::test]
+    async fn test_failed_save_as_keeps_the_old_file() {
+        let (mut editor, _) = Editor::new("a.rs".to_string(), Config::default());
+        editor.filename = Some("a.rs".to_string());
+        editor.content = "fn main() {}\n".to_string();
+        // writing over a directory fails
+        editor.save_as("src".to_string());
+        assert_eq!(editor.filename.as_deref(), Some("a.rs"));
+        assert_eq!(editor.language, Language::Rust);
+    }
+
+    #[test]
+    fn test_save_as_refuses_to_drop_encryption() {
+        let mut config = Config::default();
+        config.encryption.patterns = vec!["*.secret".to_string()];
+        let (mut editor, _) = Editor::new("a.secret".to_string(), config);
+        editor.filename = Some("a.secret".to_string());
+        editor.encrypted = true;
+        let path = std::env::temp_dir().join(format!("nars-plain-{}.rs", std::process::id()));
+        editor.save_as(path.to_string_lossy().to_string());
+        assert!(!path.exists());
+        assert_eq!(editor.filename.as_deref(), Some("a.secret"));
+        assert!(editor.encrypted);
+    }
+
+    #[tokio::test]
+    async fn test_save_as_creates_directories_and_switches_language() {
+        let dir = std::env::temp_dir().join(format!("nars-save-as-{}", std::process::id()));
+        let path = dir.join("src").join("main.zig");
+        let (mut editor, _) = Editor::new(".rs".to_string(), Config::default());
+        editor.content = "const x = 1;\n".to_string();
+        editor.open_save_as_prompt();
+        editor.prompt.as_mut().unwrap().input = path.to_string_lossy().to_string();
+        editor.confirm_save_as();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "const x = 1;\n");
+        assert_eq!(editor.language, Language::Zig);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+
+/// A confirmed search, kept around so n/N can step through its matches
+pub struct Search {
+    pub query: String,
+    // where the cursor was when the search was opened, restored on cancel
+    pub origin: usize,
+}
+
+impl Editor {
+    pub(crate) fn open_search(&mut self) {
+        self.prompt = Some(Prompt::new(PromptKind::Search, "Search: "));
+        self.search = Some(Search {
+            query: String::new(),
+            origin: self.cursor_position,
+        });
+    }
+
+    /// Query whose matches should be highlighted, either being typed or confirmed
+    pub(crate) fn active_search_query(&self) -> Option<&str> {
+        match &self.prompt {
+            Some(prompt) if prompt.kind == PromptKind::Search => Some(prompt.input.as_str()),
+            Some(_) => None,
+            None => self.search.as_ref().map(|search| search.query.as_str()),
+        }
+        .filter(|query| !query.is_empty())
+    }
+
+    /// Matches to highlight for whichever of search or replace is active
+    pub(crate) fn highlighted_matches(&self) -> Vec<(usize, usize)> {
+        match self.active_search_query() {
+            Some(query) => self.search_matches(query),
+            None => self.replace_matches(),
+        }
+    }
+
+    /// Byte ranges of every match. Lowercase queries match case-insensitively.
+    pub(crate) fn search_matches(&self, query: &str) -> Vec<(usize, usize)> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let smart_case = !query.chars().any(|c| c.is_uppercase());
+        let (haystack, needle) = if smart_case {
+            (
+                self.content.to_ascii_lowercase(),
+                query.to_ascii_lowercase(),
+            )
+        } else {
+            (self.content.clone(), query.to_string())
+        };
+        haystack
+            .match_indices(&needle)
+            .map(|(start, m)| (start, start + m.len()))
+            .collect()
+    }
+
+    /// Moves to the first match at or after where the search started, as the
+    /// query is typed
+    pub(crate) fn update_incremental_search(&mut self) {
+        let (Some(prompt), Some(search)) = (&self.prompt, &self.search) else {
+            return;
+        };
+        let origin = search.origin;
+        let matches = self.search_matches(&prompt.input);
+        match matches
+            .iter()
+            .find(|(start, _)| *start >= origin)
+            .or(matches.first())
+        {
+            Some((start, _)) => self.cursor_position = *start,
+            None => {
+                self.cursor_position = origin;
+                if !prompt.input.is_empty() {
+                    self.bell();
+                }
+            }
+        }
+    }
+
+    pub(crate) fn confirm_search(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match self.search.as_mut() {
+            Some(search) if !prompt.input.is_empty() => search.query = prompt.input,
+            _ => self.search = None,
+        }
+    }
+
+    pub(crate) fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if self.prompt.take().is_some() {
+                self.cursor_position = search.origin;
+            }
+        }
+    }
+
+    /// Jumps to the next (or previous) match, wrapping around the buffer
+    pub(crate) fn jump_to_match(&mut self, forward: bool) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let matches = self.search_matches(&search.query);
+        let target = if forward {
+            matches
+                .iter()
+                .find(|(start, _)| *start > self.cursor_position)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|(start, _)| *start < self.cursor_position)
+                .or(matches.last())
+        };
+        match target {
+            Some((start, _)) => self.cursor_position = *start,
+            None => self.bell(),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use crate::editor::repeat::Action;
+use crate::editor::Editor;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionMode {
+    Char,
+    Line,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Selection {
+    pub anchor: usize,
+    pub mode: SelectionMode,
+}
+
+impl Editor {
+    pub(crate) fn toggle_line_selection(&mut self) {
+        match self.selection {
+            Some(Selection {
+                mode: SelectionMode::Line,
+                ..
+            }) => self.selection = None,
+            Some(sel) => {
+                self.selection = Some(Selection {
+                    anchor: sel.anchor,
+                    mode: SelectionMode::Line,
+                })
+            }
+            None => {
+                self.selection = Some(Selection {
+                    anchor: self.cursor_position,
+                    mode: SelectionMode::Line,
+                })
+            }
+        }
+    }
+
+    /// Starts a character-wise selection at the cursor unless one is already active
+    pub(crate) fn begin_selection(&mut self) {
+        if self.selection.is_none() {
+            self.selection = Some(Selection {
+                anchor: self.cursor_position,
+                mode: SelectionMode::Char,
+            });
+        }
+    }
+
+    pub(crate) fn select_all(&mut self) {
+        self.selection = Some(Selection {
+            anchor: 0,
+            mode: SelectionMode::Char,
+        });
+        self.cursor_position = self.content.len();
+    }
+
+    /// Selects the identifier under the cursor
+    pub(crate) fn select_word(&mut self) {
+        match self.word_range_at(self.cursor_position) {
+            Some((start, end)) => {
+                self.selection = Some(Selection {
+                    anchor: start,
+                    mode: SelectionMode::Char,
+                });
+                self.cursor_position = end;
+            }
+            None => self.bell(),
+        }
+    }
+
+    /// Selects the current line including its newline, or extends a selection
+    /// that already ends on a line boundary by the next line
+    pub(crate) fn select_line(&mut self) {
+        let extend = self.selection_range().filter(|&(start, end)| {
+            !self.is_line_selection()
+                && start == self.line_start(start)
+                && end == self.cursor_position
+                && (end == self.content.len() || self.content[..end].ends_with('\n'))
+        });
+        let (anchor, from) = match extend {
+            Some((start, end)) => (start, end),
+            None => {
+                let start = self.line_start(self.cursor_position);
+                (start, start)
+            }
+        };
+        if extend.is_some() && from == self.content.len() {
+            self.bell();
+            return;
+        }
+        self.selection = Some(Selection {
+            anchor,
+            mode: SelectionMode::Char,
+        });
+        self.cursor_position = (self.line_end(from) + 1).min(self.content.len());
+    }
+
+    pub(crate) fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+
+    pub(crate) fn is_line_selection(&self) -> bool {
+        matches!(
+            self.selection,
+            Some(Selection {
+                mode: SelectionMode::Line,
+                ..
+            })
+        )
+    }
+
+    /// Byte range covered by the selection. Linewise selections are expanded
+    /// to the start of the first line and the end of the last line.
+    pub(crate) fn selection_range(&self) -> Option<(usize, usize)> {
+        self.selection
+            .map(|sel| self.range_of(sel, self.cursor_position))
+    }
+
+    pub(crate) fn range_of(&self, sel: Selection, cursor: usize) -> (usize, usize) {
+        let start = sel.anchor.min(cursor);
+        let end = sel.anchor.max(cursor);
+        match sel.mode {
+            SelectionMode::Char => (start, end),
+            SelectionMode::Line => (self.line_start(start), self.line_end(end)),
+        }
+    }
+
+    /// Start offsets of every line touched by the selection, or the current line
+    /// when nothing is selected.
+    pub(crate) fn selected_line_starts(&self) -> Vec<usize> {
+        let (start, end) = self
+            .selection_range()
+            .unwrap_or((self.cursor_position, self.cursor_position));
+        let mut starts = vec![self.line_start(start)];
+        let mut pos = starts[0];
+        while let Some(offset) = self.content[pos..end].find('\n') {
+            pos += offset + 1;
+            starts.push(pos);
+        }
+        starts
+    }
+
+    pub(crate) fn delete_selection(&mut self) {
+        let Some((mut start, mut end)) = self.selection_range() else {
+            return;
+        };
+        if self.is_line_selection() {
+            // take the newline with the lines so no blank line is left behind
+            if end < self.content.len() {
+                end += 1;
+            } else {
+                start = start.saturating_sub(1);
+            }
+        }
+        self.selection = None;
+        self.apply_edit(start, end, "");
+        self.cursor_position = start.min(self.content.len());
+        self.update_syntax_tree();
+    }
+
+    pub(crate) fn indent_selection(&mut self) {
+        let unit = self.indent_unit();
+        for start in self.selected_line_starts().into_iter().rev() {
+            self.apply_edit(start, start, &unit);
+        }
+        self.update_syntax_tree();
+        self.record(Action::Indent);
+    }
+
+    pub(crate) fn dedent_selection(&mut self) {
+        for start in self.selected_line_starts().into_iter().rev() {
+            let line = &self.content[start..self.line_end(start)];
+            let width = if line.starts_with('\t') {
+                1
+            } else {
+                line.chars()
+                    .take(self.indent_style.width)
+                    .take_while(|&c| c == ' ')
+                    .count()
+            };
+            if width > 0 {
+                self.apply_edit(start, start + width, "");
+            }
+        }
+        self.update_syntax_tree();
+        self.record(Action::Dedent);
+    }
+
+    /// Comments out every selected line, or uncomments them if they are all
+    /// already commented.
+    pub(crate) fn toggle_comment_selection(&mut self) {
+        let prefix = self.language.comment_prefix();
+        let starts: Vec<usize> = self
+            .selected_line_starts()
+            .into_iter()
+            .filter(|&start| !self.content[start..self.line_end(start)].trim().is_empty())
+            .collect();
+        let all_commented = starts.iter().all(|&start| {
+            self.content[start..self.line_end(start)]
+                .trim_start()
+                .starts_with(prefix)
+        });
+
+        for start in starts.into_iter().rev() {
+            let line = &self.content[start..self.line_end(start)];
+            let indent = line.len() - line.trim_start().len();
+            let text_start = start + indent;
+            if all_commented {
+                let rest = &self.content[text_start + prefix.len()..];
+                let width = prefix.len() + usize::from(rest.starts_with(' '));
+                self.apply_edit(text_start, text_start + width, "");
+            } else {
+                self.apply_edit(text_start, text_start, &format!("{} ", prefix));
+            }
+        }
+        self.update_syntax_tree();
+        self.record(Action::ToggleComment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_select_word_takes_the_identifier_under_the_cursor() {
+        let mut editor = test_editor("let foo_bar = 1;", 6);
+        editor.select_word();
+        assert_eq!(editor.selection_range(), Some((4, 11)));
+        assert_eq!(editor.cursor_position, 11);
+        // nothing to take between words
+        let mut editor = test_editor("a  = 1;", 2);
+        editor.select_word();
+        assert!(editor.selection.is_none());
+    }
+
+    #[test]
+    fn test_select_line_extends_down_to_the_end() {
+        let mut editor = test_editor("one\ntwo\nthree", 5);
+        editor.select_line();
+        assert_eq!(editor.selection_range(), Some((4, 8)));
+        editor.select_line();
+        assert_eq!(editor.selection_range(), Some((4, 13)));
+        // the last line has no newline to go past
+        editor.select_line();
+        assert_eq!(editor.selection_range(), Some((4, 13)));
+        // a selection that doesn't end on a line boundary starts over
+        let mut editor = test_editor("one\ntwo\n", 1);
+        editor.begin_selection();
+        editor.cursor_position = 5;
+        editor.select_line();
+        assert_eq!(editor.selection_range(), Some((4, 8)));
+    }
+}
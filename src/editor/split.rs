@@ -0,0 +1,273 @@
+use crate::editor::Editor;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+
+// how much ctrl+w + and - move the divider, in percent
+const RESIZE_STEP: u16 = 5;
+
+/// Whether the panes sit next to each other or one above the other
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitDirection {
+    SideBySide,
+    Stacked,
+}
+
+/// Where a pane is looking in the buffer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PaneView {
+    pub cursor: usize,
+    pub scroll_offset: usize,
+}
+
+/// Two panes on the same buffer. The focused one uses the editor's own cursor
+/// and scroll offset, the other one's are kept here.
+pub struct Split {
+    pub direction: SplitDirection,
+    // share of the area the first pane gets, in percent
+    pub ratio: u16,
+    pub focus_second: bool,
+    pub other: PaneView,
+    // scrolling the focused pane scrolls the other one by as many lines
+    pub scrollbind: bool,
+}
+
+impl Editor {
+    /// Waits for the key after ctrl+w: v and s split, w switches panes, q
+    /// closes the focused one, + and - resize it and b binds their scrolling
+    pub(crate) fn begin_window_command(&mut self) {
+        self.status_message = Some(
+            "Window: v side by side, s stacked, w switch, q close, +/- resize, b scrollbind"
+                .to_string(),
+        );
+        self.pending_window = true;
+    }
+
+    pub(crate) fn finish_window_command(&mut self, key: KeyEvent) {
+        self.pending_window = false;
+        self.status_message = None;
+        match key.code {
+            KeyCode::Char('v') => self.split_view(SplitDirection::SideBySide),
+            KeyCode::Char('s') => self.split_view(SplitDirection::Stacked),
+            KeyCode::Char('w')
+            | KeyCode::Tab
+            | KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down => self.focus_other_pane(),
+            KeyCode::Char('q') | KeyCode::Char('c') => self.close_pane(),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.resize_pane(true),
+            KeyCode::Char('-') => self.resize_pane(false),
+            KeyCode::Char('b') => self.toggle_scrollbind(),
+            _ => {}
+        }
+    }
+
+    /// Shows the buffer in a second pane starting where the cursor is, or
+    /// turns an existing split the other way
+    pub(crate) fn split_view(&mut self, direction: SplitDirection) {
+        if let Some(split) = self.split.as_mut() {
+            split.direction = direction;
+            return;
+        }
+        self.split = Some(Split {
+            direction,
+            ratio: 50,
+            focus_second: false,
+            other: PaneView {
+                cursor: self.cursor_position,
+                scroll_offset: self.scroll_offset,
+            },
+            scrollbind: false,
+        });
+    }
+
+    fn swap_views(&mut self) {
+        let Some(split) = self.split.as_mut() else {
+            return;
+        };
+        std::mem::swap(&mut self.cursor_position, &mut split.other.cursor);
+        std::mem::swap(&mut self.scroll_offset, &mut split.other.scroll_offset);
+        self.cursor_position = self.cursor_position.min(self.content.len());
+    }
+
+    pub(crate) fn focus_other_pane(&mut self) {
+        if self.split.is_none() {
+            self.bell();
+            return;
+        }
+        // selections, carets and predictions belong to where the cursor was
+        self.clear_selection();
+        self.clear_carets();
+        self.goal_column = None;
+        self.current_prediction = None;
+        self.prediction_start_position = None;
+        self.swap_views();
+        if let Some(split) = self.split.as_mut() {
+            split.focus_second = !split.focus_second;
+        }
+    }
+
+    /// Closes the focused pane, the other one takes the whole view
+    pub(crate) fn close_pane(&mut self) {
+        if self.split.is_none() {
+            self.bell();
+            return;
+        }
+        self.focus_other_pane();
+        self.split = None;
+    }
+
+    fn resize_pane(&mut self, grow: bool) {
+        let Some(split) = self.split.as_mut() else {
+            self.bell();
+            return;
+        };
+        // the ratio is the first pane's share
+        split.ratio = if grow == split.focus_second {
+            split.ratio.saturating_sub(RESIZE_STEP).max(10)
+        } else {
+            (split.ratio + RESIZE_STEP).min(90)
+        };
+    }
+
+    fn toggle_scrollbind(&mut self) {
+        let Some(split) = self.split.as_mut() else {
+            self.bell();
+            return;
+        };
+        split.scrollbind = !split.scrollbind;
+        self.status_message = Some(
+            if split.scrollbind {
+                "Panes scroll together"
+            } else {
+                "Panes scroll on their own"
+            }
+            .to_string(),
+        );
+    }
+
+    /// The focused pane's area and the other pane's, if the view is split,
+    /// within what the tab bar and chat panel leave of `area`
+    pub(crate) fn pane_areas(&self, area: Rect) -> (Rect, Option<Rect>) {
+        let area = self.split_chat(self.split_log(self.split_tabs(area).1).0).0;
+        let Some(split) = &self.split else {
+            return (area, None);
+        };
+        let panes = Layout::default()
+            .direction(match split.direction {
+                SplitDirection::SideBySide => Direction::Horizontal,
+                SplitDirection::Stacked => Direction::Vertical,
+            })
+            .constraints([Constraint::Percentage(split.ratio), Constraint::Min(1)])
+            .split(area);
+        if split.focus_second {
+            (panes[1], Some(panes[0]))
+        } else {
+            (panes[0], Some(panes[1]))
+        }
+    }
+
+    /// Rows of text the focused pane shows on a `width` by `height` terminal,
+    /// the bottom row of which is the status bar
+    pub(crate) fn window_height(&self, width: u16, height: u16) -> usize {
+        let area = Rect::new(0, 0, width, height.saturating_sub(1));
+        self.pane_areas(area).0.height.saturating_sub(2) as usize
+    }
+
+    /// Scrolls both panes so their cursors stay in view after the terminal
+    /// was resized
+    pub(crate) fn resize(&mut self, width: u16, height: u16) {
+        let area = Rect::new(0, 0, width, height.saturating_sub(1));
+        let (focused, other) = self.pane_areas(area);
+        self.ensure_cursor_visible(focused.height.saturating_sub(2) as usize);
+        if let Some(other) = other {
+            let other_height = other.height.saturating_sub(2) as usize;
+            self.with_other_view(|editor| editor.ensure_cursor_visible(other_height));
+        }
+    }
+
+    /// Keeps the cursor in view and, with scrollbind on, moves the other pane
+    /// by as many lines as the focused one scrolled
+    pub(crate) fn scroll_panes(&mut self, window_height: usize) {
+        let before = self.scroll_offset;
+        self.ensure_cursor_visible(window_height);
+        let after = self.scroll_offset;
+        let last_line = self.content.matches('\n').count();
+        if let Some(split) = self.split.as_mut().filter(|split| split.scrollbind) {
+            let scroll = &mut split.other.scroll_offset;
+            *scroll = (*scroll + after).saturating_sub(before).min(last_line);
+        }
+    }
+
+    /// Runs `f` with the other pane's view in place of the focused one's, to
+    /// draw it
+    pub(crate) fn with_other_view<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.swap_views();
+        let selection = self.selection.take();
+        let carets = std::mem::take(&mut self.carets);
+        let prediction = self.current_prediction.take();
+        let goal_column = self.goal_column.take();
+        let result = f(self);
+        self.selection = selection;
+        self.carets = carets;
+        self.current_prediction = prediction;
+        self.goal_column = goal_column;
+        self.swap_views();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_panes_keep_their_own_place() {
+        let mut editor = test_editor("one\ntwo\nthree\n", 8);
+        editor.split_view(SplitDirection::Stacked);
+        editor.focus_other_pane();
+        editor.cursor_position = 0;
+        editor.apply_edit(0, 0, "zero\n");
+        editor.focus_other_pane();
+        // the edit in the second pane moved the first pane's cursor with it
+        assert_eq!(editor.cursor_position, 13);
+        assert_eq!(editor.with_other_view(|e| e.cursor_position), 5);
+        let area = Rect::new(0, 0, 80, 20);
+        assert_eq!(editor.pane_areas(area).0.height, 10);
+        editor.resize_pane(true);
+        assert_eq!(editor.pane_areas(area).0.height, 11);
+        editor.close_pane();
+        assert!(editor.split.is_none());
+        assert_eq!(editor.cursor_position, 5);
+    }
+
+    #[test]
+    fn test_scrollbind_moves_both_panes() {
+        let mut editor = test_editor(&"line\n".repeat(100), 0);
+        editor.split_view(SplitDirection::SideBySide);
+        editor.toggle_scrollbind();
+        editor.cursor_position = 50 * 5;
+        editor.scroll_panes(10);
+        assert_eq!(editor.scroll_offset, 41);
+        assert_eq!(editor.with_other_view(|e| e.scroll_offset), 41);
+    }
+
+    #[test]
+    fn test_shrinking_keeps_both_cursors_in_view() {
+        let mut editor = test_editor(&"line\n".repeat(100), 0);
+        editor.split_view(SplitDirection::Stacked);
+        editor.cursor_position = 30 * 5;
+        editor.with_other_view(|e| e.cursor_position = 60 * 5);
+        editor.resize(80, 81);
+        assert_eq!(editor.window_height(80, 81), 38);
+        assert_eq!(editor.scroll_offset, 0);
+        assert_eq!(editor.with_other_view(|e| e.scroll_offset), 23);
+        editor.resize(80, 21);
+        assert_eq!(editor.window_height(80, 21), 8);
+        assert_eq!(editor.scroll_offset, 23);
+        assert_eq!(editor.with_other_view(|e| e.scroll_offset), 53);
+        assert_eq!(editor.window_height(80, 0), 0);
+    }
+}
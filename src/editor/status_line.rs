@@ -0,0 +1,96 @@
+use crate::editor::Editor;
+use crate::models::Predictor;
+use ratatui::text::{Line, Span};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+// how long each spinner frame shows
+const SPINNER_FRAME_MS: u128 = 100;
+
+/// The spinner frame for now, the same in every pane and every redraw
+/// within a frame's time
+pub(crate) fn spinner_frame() -> usize {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    (millis / SPINNER_FRAME_MS) as usize % SPINNER.len()
+}
+
+impl Editor {
+    /// Right hand side of the bottom bar: cursor position, language, file
+    /// format, model and what the prediction is doing
+    pub(crate) fn status_line(&self, predictor: &Predictor) -> Line<'static> {
+        let dim = self.theme.dim;
+        let mut spans = Vec::new();
+        let mut item = |text: String, style| {
+            if !spans.is_empty() {
+                spans.push(Span::styled("  ", dim));
+            }
+            spans.push(Span::styled(text, style));
+        };
+        // large files aren't parsed on purpose, the title says so
+        if self.syntax_degraded() && !self.large_file() {
+            item("syntax: degraded".to_string(), self.theme.warning);
+        }
+        if self.modified {
+            item("modified".to_string(), self.theme.modified);
+        }
+        let line = self.point_at(self.cursor_position).row + 1;
+        let column = self.column_of(self.cursor_position) + 1;
+        item(format!("{}:{}", line, column), dim);
+        item(self.language.name().to_string(), dim);
+        item(
+            format!(
+                "{} {}",
+                self.file_encoding.encoding.name(),
+                self.line_ending.name()
+            ),
+            dim,
+        );
+        if self.ai_excluded() {
+            item("model off".to_string(), dim);
+        } else {
+            item(predictor.model(), dim);
+            if self.current_prediction.is_some() {
+                item("ready".to_string(), self.theme.ghost);
+            } else if predictor.is_predicting() {
+                // the count shows whether anything is arriving at all
+                let progress = match predictor.streamed_tokens() {
+                    0 => "predicting...".to_string(),
+                    1 => "predicting 1 token".to_string(),
+                    n => format!("predicting {} tokens", n),
+                };
+                if self.config.render.reduced_motion {
+                    item(progress, self.theme.ghost);
+                } else {
+                    item(
+                        format!("{} {}", SPINNER[spinner_frame()], progress),
+                        self.theme.ghost,
+                    );
+                }
+            }
+        }
+        spans.push(Span::raw(" "));
+        Line::from(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::Editor;
+    use crate::models::backend::{Backend, MockBackend};
+    use crate::models::Predictor;
+
+    #[test]
+    fn test_status_line_shows_position_and_model() {
+        let (mut editor, prediction_tx) = Editor::new("test.rs".to_string(), Config::default());
+        let backend = Backend::new("mock", MockBackend::default());
+        let predictor = Predictor::new(backend, prediction_tx, "m".into());
+        editor.apply_edit(0, 0, "fn main() {\n    1\n}");
+        editor.update_syntax_tree();
+        editor.cursor_position = 16;
+        let text = editor.status_line(&predictor).to_string();
+        assert_eq!(text, "modified  2:5  rust  UTF-8 LF  m ");
+    }
+}
@@ -0,0 +1,494 @@
+use crate::config::ModelConfig;
+use crate::editor::languages::Language;
+use crate::editor::Editor;
+use crate::logger::log_to_file;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tokio::task;
+use tree_sitter::Parser;
+
+// keeps the first index of a huge tree from stalling the editor
+const MAX_INDEXED_FILES: usize = 1000;
+const IGNORED_DIRS: &[&str] = &["target", ".git", "zig-cache", ".zig-cache", "zig-out"];
+// blocks shorter than this are too generic to be worth a hint
+const MIN_TOKENS: usize = 20;
+const SIMILARITY_THRESHOLD: f64 = 0.8;
+// a type longer than this is left out of prompts rather than cut short
+const MAX_DEFINITION_BYTES: usize = 2000;
+
+/// A function found in the project, reduced to what similarity needs
+pub struct FunctionSymbol {
+    pub name: String,
+    pub path: PathBuf,
+    shingles: HashSet<String>,
+}
+
+/// A type defined in the project, kept whole to show the model
+pub struct Definition {
+    pub name: String,
+    pub path: PathBuf,
+    pub code: String,
+}
+
+/// Functions and types defined in the files around the open one
+#[derive(Default)]
+pub struct SymbolIndex {
+    pub functions: Vec<FunctionSymbol>,
+    pub definitions: Vec<Definition>,
+    // the files indexed, so a saved file of another kind is left out
+    extension: String,
+}
+
+fn fn_regex() -> &'static Regex {
+    static FN: OnceLock<Regex> = OnceLock::new();
+    FN.get_or_init(|| Regex::new(r"\bfn\s+([A-Za-z_]\w*)\s*\(").expect("invalid fn regex"))
+}
+
+fn token_regex() -> &'static Regex {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    TOKEN.get_or_init(|| Regex::new(r"\w+|[^\w\s]").expect("invalid token regex"))
+}
+
+fn tokens(code: &str) -> Vec<&str> {
+    token_regex().find_iter(code).map(|m| m.as_str()).collect()
+}
+
+// pairs of adjacent tokens, so formatting differences don't matter but order does
+fn shingles(code: &str) -> HashSet<String> {
+    let tokens = tokens(code);
+    if tokens.len() < MIN_TOKENS {
+        return HashSet::new();
+    }
+    tokens
+        .windows(2)
+        .map(|pair| format!("{} {}", pair[0], pair[1]))
+        .collect()
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+/// Finds `fn name(...) { ... }` definitions, which reads the same in Rust and
+/// Zig, by matching braces from the signature
+pub fn extract_functions(source: &str) -> Vec<(String, Range<usize>)> {
+    let mut functions = Vec::new();
+    for caps in fn_regex().captures_iter(source) {
+        let (Some(whole), Some(name)) = (caps.get(0), caps.get(1)) else {
+            continue;
+        };
+        let Some(open) = source[whole.end()..]
+            .find(['{', ';'])
+            .map(|p| whole.end() + p)
+        else {
+            continue;
+        };
+        if source.as_bytes()[open] == b';' {
+            continue;
+        }
+        let mut depth = 0;
+        for (offset, c) in source[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                functions.push((name.as_str().to_string(), whole.start()..open + offset + 1));
+                break;
+            }
+        }
+    }
+    functions
+}
+
+impl SymbolIndex {
+    /// Indexes every file under `root` with the given extension, parsing
+    /// them as `language` for their type definitions
+    pub fn build(root: &Path, extension: &str, language: Language, model: &ModelConfig) -> Self {
+        let pattern = format!("{}/**/*.{}", root.display(), extension);
+        let Ok(paths) = glob::glob(&pattern) else {
+            return Self::default();
+        };
+        let mut index = Self {
+            extension: extension.to_string(),
+            ..Self::default()
+        };
+        let mut parser = Parser::new();
+        let parsed = parser.set_language(language.grammar()).is_ok();
+        let files = paths.filter_map(|path| path.ok()).filter(|path| {
+            !path.components().any(|part| {
+                IGNORED_DIRS
+                    .iter()
+                    .any(|ignored| part.as_os_str() == *ignored)
+            }) && !model.excludes(&path.to_string_lossy())
+        });
+        for path in files.take(MAX_INDEXED_FILES) {
+            let Ok(source) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            index.add_file(relative, &source, parsed.then_some(&mut parser), language);
+        }
+        log_to_file(&format!(
+            "indexed {} functions and {} types under {}",
+            index.functions.len(),
+            index.definitions.len(),
+            root.display()
+        ));
+        index
+    }
+
+    /// Replaces what was indexed from `path` with what `source` defines now
+    pub fn update_file(&mut self, path: &Path, source: &str, language: Language) {
+        self.functions.retain(|function| function.path != path);
+        self.definitions
+            .retain(|definition| definition.path != path);
+        if path.extension().and_then(|e| e.to_str()) != Some(self.extension.as_str()) {
+            return;
+        }
+        let mut parser = Parser::new();
+        let parsed = parser.set_language(language.grammar()).is_ok();
+        self.add_file(
+            path.to_path_buf(),
+            source,
+            parsed.then_some(&mut parser),
+            language,
+        );
+    }
+
+    fn add_file(
+        &mut self,
+        path: PathBuf,
+        source: &str,
+        parser: Option<&mut Parser>,
+        language: Language,
+    ) {
+        for (name, range) in extract_functions(source) {
+            self.functions.push(FunctionSymbol {
+                name,
+                path: path.clone(),
+                shingles: shingles(&source[range]),
+            });
+        }
+        let Some(tree) = parser.and_then(|parser| parser.parse(source, None)) else {
+            return;
+        };
+        let root_node = tree.root_node();
+        let mut cursor = root_node.walk();
+        for node in root_node.children(&mut cursor) {
+            let Some(name) = language.definition_name(&node, source) else {
+                continue;
+            };
+            if node.byte_range().len() <= MAX_DEFINITION_BYTES {
+                self.definitions.push(Definition {
+                    name,
+                    path: path.clone(),
+                    code: source[node.byte_range()].to_string(),
+                });
+            }
+        }
+    }
+
+    /// The indexed function most like `code`, if it is close enough to be a copy
+    pub fn most_similar(&self, code: &str) -> Option<&FunctionSymbol> {
+        let target = shingles(code);
+        self.functions
+            .iter()
+            .map(|function| (function, similarity(&target, &function.shingles)))
+            .filter(|&(_, score)| score >= SIMILARITY_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(function, _)| function)
+    }
+
+    /// Definitions of the types named in `code`, in the order they come up,
+    /// leaving out the ones in `path` and any past `budget` bytes
+    pub fn definitions_used_in(&self, code: &str, path: &Path, budget: usize) -> Vec<&Definition> {
+        let mut seen = HashSet::new();
+        let mut used = Vec::new();
+        let mut size = 0;
+        for name in token_regex().find_iter(code).map(|m| m.as_str()) {
+            if !seen.insert(name) {
+                continue;
+            }
+            let found = self
+                .definitions
+                .iter()
+                .filter(|definition| definition.name == name && definition.path != path);
+            for definition in found {
+                if size + definition.code.len() > budget {
+                    continue;
+                }
+                size += definition.code.len();
+                used.push(definition);
+            }
+        }
+        used
+    }
+}
+
+impl Editor {
+    /// Starts indexing the project on a blocking task the first time it is
+    /// needed, prompts go without related definitions until it is done
+    pub(crate) fn load_symbol_index(&mut self) {
+        if self.symbol_index.is_some() || self.symbol_index_rx.is_some() {
+            return;
+        }
+        let extension = self
+            .filename
+            .as_deref()
+            .and_then(|f| Path::new(f).extension())
+            .and_then(|e| e.to_str())
+            .unwrap_or("rs")
+            .to_string();
+        let (language, model) = (self.language, self.config.model.clone());
+        let (index_tx, index_rx) = oneshot::channel();
+        task::spawn_blocking(move || {
+            let _ = index_tx.send(SymbolIndex::build(
+                Path::new("."),
+                &extension,
+                language,
+                &model,
+            ));
+        });
+        self.symbol_index_rx = Some(index_rx);
+    }
+
+    /// Picks up the index once it is built, returns true if it arrived
+    pub(crate) fn poll_symbol_index(&mut self) -> bool {
+        let Some(index_rx) = self.symbol_index_rx.as_mut() else {
+            return false;
+        };
+        match index_rx.try_recv() {
+            Ok(index) => self.symbol_index = Some(index),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => {}
+        }
+        self.symbol_index_rx = None;
+        true
+    }
+
+    /// Brings the index up to date with the file just saved, rather than
+    /// indexing the whole project again
+    pub(crate) fn reindex_saved_file(&mut self) {
+        if self.remote.is_some() {
+            return;
+        }
+        // an excluded file only loses what was indexed from it
+        let source = if self.ai_excluded() {
+            ""
+        } else {
+            &self.content
+        };
+        let (Some(index), Some(filename)) = (self.symbol_index.as_mut(), &self.filename) else {
+            return;
+        };
+        let path = Path::new(filename);
+        let relative = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| path.strip_prefix(cwd).ok())
+            .unwrap_or_else(|| path.strip_prefix(".").unwrap_or(path));
+        index.update_file(relative, source, self.language);
+    }
+
+    /// Definitions from other files of the types used on the cursor line,
+    /// each under a comment with its path, in at most `budget` bytes
+    pub(crate) fn related_definitions(&self, budget: usize) -> String {
+        let Some(index) = &self.symbol_index else {
+            return String::new();
+        };
+        let path = self.filename.as_deref().unwrap_or_default();
+        let path = Path::new(path.strip_prefix("./").unwrap_or(path));
+        let prefix = self.language.comment_prefix();
+        let mut related = String::new();
+        for definition in index.definitions_used_in(&self.get_current_line_content(), path, budget)
+        {
+            let block = format!(
+                "{} {}\n{}\n\n",
+                prefix,
+                definition.path.display(),
+                definition.code
+            );
+            if related.len() + block.len() > budget {
+                break;
+            }
+            related.push_str(&block);
+        }
+        related
+    }
+
+    /// Points out when a pasted or generated block looks like a function that
+    /// already exists, without getting in the way. Short blocks are skipped
+    /// before anything is indexed, and nothing is hinted until the index is
+    /// built
+    pub(crate) fn hint_duplicate(&mut self, code: &str) {
+        if tokens(code).len() < MIN_TOKENS {
+            return;
+        }
+        self.load_symbol_index();
+        let hint = self
+            .symbol_index
+            .as_ref()
+            .and_then(|index| index.most_similar(code))
+            .map(|function| {
+                format!(
+                    "similar to {}() in {}",
+                    function.name,
+                    function.path.display()
+                )
+            });
+        if hint.is_some() {
+            self.status_message = hint;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::editor::test_editor;
+
+    const SOURCE: &str = "fn add_all(values: &[i32]) -> i32 {\n    let mut total = 0;\n    for value in values {\n        if *value > 0 { total += value; }\n    }\n    total\n}\n\nfn other();\n";
+
+    #[test]
+    fn test_extract_functions() {
+        let functions = extract_functions(SOURCE);
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].0, "add_all");
+        assert!(SOURCE[functions[0].1.clone()].ends_with("total\n}"));
+    }
+
+    #[test]
+    fn test_detects_renamed_copy() {
+        let index = SymbolIndex {
+            functions: vec![FunctionSymbol {
+                name: "add_all".to_string(),
+                path: PathBuf::from("util.rs"),
+                shingles: shingles(&SOURCE[extract_functions(SOURCE)[0].1.clone()]),
+            }],
+            definitions: Vec::new(),
+            extension: "rs".to_string(),
+        };
+        let pasted = "fn sum(values: &[i32]) -> i32 {\n    let mut total = 0;\n    for value in values {\n        if *value > 0 {\n            total += value;\n        }\n    }\n    total\n}";
+        assert_eq!(
+            index.most_similar(pasted).map(|f| f.name.as_str()),
+            Some("add_all")
+        );
+        assert!(index
+            .most_similar("fn main() { println!(\"hello\"); }")
+            .is_none());
+    }
+
+    #[test]
+    fn test_types_used_on_a_line_come_from_other_files() {
+        let root = std::env::temp_dir().join(format!("nars-symbols-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        let point = "pub struct Point {\n    pub x: i32,\n    pub y: i32,\n}";
+        fs::write(
+            root.join("src/geometry.rs"),
+            format!("use std::fmt;\n\n{}\n", point),
+        )
+        .unwrap();
+        fs::write(root.join("src/main.rs"), "enum Shape {}\n").unwrap();
+        let index = SymbolIndex::build(&root, "rs", Language::Rust, &ModelConfig::default());
+        fs::remove_dir_all(&root).unwrap();
+        let line = "    let p: Point = Shape::new(Point::default());";
+        let used = index.definitions_used_in(line, Path::new("src/main.rs"), 1000);
+        assert_eq!(used.len(), 1);
+        assert_eq!(used[0].path, Path::new("src/geometry.rs"));
+        assert_eq!(used[0].code, point);
+        assert!(index
+            .definitions_used_in(line, Path::new("src/main.rs"), 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_saved_file_replaces_its_entries() {
+        let (mut editor, _) = Editor::new("./src/shapes.rs".to_string(), Config::default());
+        editor.filename = Some("./src/shapes.rs".to_string());
+        let mut index = SymbolIndex {
+            extension: "rs".to_string(),
+            ..SymbolIndex::default()
+        };
+        index.update_file(
+            Path::new("src/shapes.rs"),
+            "struct Circle;\n",
+            Language::Rust,
+        );
+        index.update_file(Path::new("src/main.rs"), "struct Circle;\n", Language::Rust);
+        editor.symbol_index = Some(index);
+        editor.content = "struct Square;\n".to_string();
+        editor.reindex_saved_file();
+        let index = editor.symbol_index.as_ref().unwrap();
+        let mut names: Vec<_> = index
+            .definitions
+            .iter()
+            .map(|d| (d.name.as_str(), d.path.to_string_lossy()))
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("Circle", "src/main.rs".into()),
+                ("Square", "src/shapes.rs".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_files_of_another_kind_stay_out() {
+        let mut index = SymbolIndex {
+            extension: "rs".to_string(),
+            ..SymbolIndex::default()
+        };
+        index.update_file(Path::new("build.zig"), SOURCE, Language::Rust);
+        assert!(index.functions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_is_built_in_the_background() {
+        let (mut editor, _) = Editor::new("src/main.rs".to_string(), Config::default());
+        editor.load_symbol_index();
+        assert!(editor.symbol_index.is_none());
+        assert!(editor.related_definitions(1000).is_empty());
+        for _ in 0..500 {
+            if editor.poll_symbol_index() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let index = editor.symbol_index.as_ref().expect("index never arrived");
+        assert!(
+            index
+                .functions
+                .iter()
+                .any(|f| f.name == "load_symbol_index"
+                    && f.path == Path::new("src/editor/symbols.rs"))
+        );
+    }
+
+    #[test]
+    fn test_short_blocks_are_not_checked() {
+        let mut editor = test_editor("", 0);
+        editor.hint_duplicate("fn f() {}");
+        assert!(editor.symbol_index_rx.is_none());
+        assert!(editor.status_message.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_long_blocks_wait_for_the_index() {
+        let mut editor = test_editor("", 0);
+        editor.hint_duplicate(SOURCE);
+        assert!(editor.symbol_index_rx.is_some());
+        assert!(editor.status_message.is_none());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tokio::task;
+
+/// A build tool invocation offered for a language
+#[derive(Clone, Copy, Debug)]
+pub struct TaskPreset {
+    pub name: &'static str,
+    pub program: &'static str,
+    pub args: &'static [&'static str],
+    /// File marking the project root the command runs in, e.g. `build.zig`
+    pub root_marker: &'static str,
+    /// Pipes the buffer through the command and replaces it with the output
+    pub formats_buffer: bool,
+}
+
+/// A compiler message, in the file and at the 1-based line and column it names
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+pub struct RunningTask {
+    preset: TaskPreset,
+    // where the command ran, relative paths in its output are resolved against it
+    dir: PathBuf,
+    // buffer piped to a formatter, its output is dropped if the buffer changed since
+    input: Option<String>,
+    result_rx: oneshot::Receiver<Result<Output>>,
+}
+
+fn diagnostic_regex() -> &'static Regex {
+    static DIAGNOSTIC: OnceLock<Regex> = OnceLock::new();
+    DIAGNOSTIC.get_or_init(|| {
+        Regex::new(r"^(?P<path>[^:\s][^:]*):(?P<line>\d+):(?P<column>\d+): (?P<message>(?:error|warning|note): .*)$")
+            .expect("invalid diagnostic regex")
+    })
+}
+
+/// Parses `file:line:column: error: message` lines, the format zig uses
+pub fn parse_diagnostics(output: &str, dir: &Path) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| diagnostic_regex().captures(line))
+        .filter_map(|caps| {
+            Some(Diagnostic {
+                path: dir.join(&caps["path"]),
+                line: caps["line"].parse().ok()?,
+                column: caps["column"].parse().ok()?,
+                message: caps["message"].to_string(),
+            })
+        })
+        .collect()
+}
+
+// zig build has to run next to build.zig, which is usually above the file
+pub(crate) fn project_root(file: &Path, marker: &str) -> PathBuf {
+    let dir = file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    dir.ancestors()
+        .find(|dir| dir.join(marker).exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+fn run_preset(preset: TaskPreset, dir: &Path, input: Option<String>) -> Result<Output> {
+    let mut child = Command::new(preset.program)
+        .args(preset.args)
+        .current_dir(dir)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {}: {}", preset.program, e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+impl Editor {
+    pub(crate) fn open_task_prompt(&mut self) {
+        let presets = self.language.task_presets();
+        if presets.is_empty() {
+            self.status_message = Some(format!("No tasks for {} files", self.language.name()));
+            self.bell();
+            return;
+        }
+        let names: Vec<&str> = presets.iter().map(|preset| preset.name).collect();
+        self.prompt = Some(Prompt::new(
+            PromptKind::Task,
+            &format!("Run ({}): ", names.join(", ")),
+        ));
+    }
+
+    pub(crate) fn confirm_task(&mut self) {
+        let name = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let preset = self
+            .language
+            .task_presets()
+            .iter()
+            .find(|preset| preset.name == name.trim());
+        match preset {
+            Some(&preset) => self.run_task(preset),
+            None => {
+                self.status_message = Some(format!("No task named {}", name.trim()));
+                self.bell();
+            }
+        }
+    }
+
+    fn run_task(&mut self, preset: TaskPreset) {
+        if self.task.is_some() {
+            self.status_message = Some("A task is already running".to_string());
+            return;
+        }
+        let file = PathBuf::from(self.filename.as_deref().unwrap_or("."));
+        let dir = project_root(&file, preset.root_marker);
+        let input = preset.formats_buffer.then(|| self.content.clone());
+        let (result_tx, result_rx) = oneshot::channel();
+        let task_dir = dir.clone();
+        let task_input = input.clone();
+        task::spawn_blocking(move || {
+            let _ = result_tx.send(run_preset(preset, &task_dir, task_input));
+        });
+        self.task = Some(RunningTask {
+            preset,
+            dir,
+            input,
+            result_rx,
+        });
+        self.status_message = Some(format!("Running {}...", preset.name));
+    }
+
+    /// Collects the output of a finished task into the quickfix list, returns
+    /// true once it has finished
+    pub(crate) fn poll_task(&mut self) -> bool {
+        let Some(running) = self.task.as_mut() else {
+            return false;
+        };
+        let result = match running.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => Err(anyhow!("task went away")),
+        };
+        let Some(RunningTask {
+            preset, dir, input, ..
+        }) = self.task.take()
+        else {
+            return false;
+        };
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                self.status_message = Some(e.to_string());
+                return true;
+            }
+        };
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        self.quickfix = parse_diagnostics(&stderr, &dir);
+        if preset.formats_buffer {
+            // formatting reads stdin, so its messages are about this buffer
+            let file = PathBuf::from(self.filename.as_deref().unwrap_or_default());
+            for diagnostic in self.quickfix.iter_mut() {
+                diagnostic.path = file.clone();
+            }
+        }
+        self.quickfix_index = None;
+
+        if output.status.success() && input.is_some_and(|input| input == self.content) {
+            let formatted = String::from_utf8_lossy(&output.stdout).to_string();
+            if formatted != self.content {
+                let cursor = self.cursor_position;
+                let len = self.content.len();
+                self.apply_edit(0, len, &formatted);
+                self.cursor_position = self.line_start(cursor.min(self.content.len()));
+                self.update_syntax_tree();
+                self.audit(format!("Formatted the buffer with {}", preset.name));
+            }
+        }
+        self.audit(format!(
+            "Ran {}: {}",
+            preset.name,
+            if output.status.success() {
+                "ok"
+            } else {
+                "failed"
+            }
+        ));
+        self.status_message = Some(if output.status.success() {
+            format!("{}: ok", preset.name)
+        } else if self.quickfix.is_empty() {
+            let first_line = stderr.lines().next().unwrap_or("no output");
+            format!("{} failed: {}", preset.name, first_line)
+        } else {
+            format!(
+                "{} failed with {} messages, f8 steps through them",
+                preset.name,
+                self.quickfix.len()
+            )
+        });
+        true
+    }
+
+    /// Steps through the quickfix list, moving the cursor when the message is
+    /// about the open file
+    pub(crate) fn jump_to_diagnostic(&mut self, forward: bool) {
+        if self.quickfix.is_empty() {
+            self.bell();
+            return;
+        }
+        let len = self.quickfix.len();
+        let idx = match (self.quickfix_index, forward) {
+            (None, _) => 0,
+            (Some(idx), true) => (idx + 1) % len,
+            (Some(idx), false) => (idx + len - 1) % len,
+        };
+        self.quickfix_index = Some(idx);
+        let diagnostic = self.quickfix[idx].clone();
+
+        let same_file = self.filename.as_ref().is_some_and(|file| {
+            match (
+                Path::new(file).canonicalize(),
+                diagnostic.path.canonicalize(),
+            ) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => Path::new(file) == diagnostic.path,
+            }
+        });
+        if same_file {
+            let line_start = self
+                .content
+                .split_inclusive('\n')
+                .take(diagnostic.line.saturating_sub(1))
+                .map(str::len)
+                .sum::<usize>()
+                .min(self.content.len());
+            let line_end = self.line_end(line_start);
+            let mut pos = (line_start + diagnostic.column.saturating_sub(1)).min(line_end);
+            while !self.content.is_char_boundary(pos) {
+                pos -= 1;
+            }
+            self.cursor_position = pos;
+        }
+        self.status_message = Some(format!(
+            "[{}/{}] {}:{}:{}: {}",
+            idx + 1,
+            len,
+            diagnostic.path.display(),
+            diagnostic.line,
+            diagnostic.column,
+            diagnostic.message
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_diagnostics;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_zig_diagnostics() {
+        let output = "install\n+- install hello\nsrc/main.zig:4:5: error: use of undeclared identifier 'x'\n    x += 1;\n    ^\nsrc/main.zig:2:1: note: function declared here\n";
+        let diagnostics = parse_diagnostics(output, Path::new("/project"));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].path, Path::new("/project/src/main.zig"));
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (4, 5));
+        assert_eq!(
+            diagnostics[0].message,
+            "error: use of undeclared identifier 'x'"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::config::config_dir;
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use crate::models::Predictor;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+const TEMPLATE_DIR: &str = "templates";
+
+/// A template being filled in by the model in the background
+pub struct TemplateFill {
+    // buffer the model was given, the result is dropped if it was edited since
+    base: String,
+    result_rx: oneshot::Receiver<Result<String>>,
+}
+
+/// Templates live in `~/.config/nars/templates` and apply to files with the
+/// same extension, e.g. `main.rs` or `test.rs` are offered for new `.rs` files
+fn templates_for(path: &str) -> Vec<(String, PathBuf)> {
+    let Some(extension) = Path::new(path).extension().and_then(|e| e.to_str()) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(config_dir().join(TEMPLATE_DIR)) else {
+        return Vec::new();
+    };
+    let mut templates: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some(extension))
+        .filter_map(|p| Some((p.file_stem()?.to_str()?.to_string(), p)))
+        .collect();
+    templates.sort();
+    templates
+}
+
+impl Editor {
+    /// Asks which template to start a new file from, if any match its extension
+    pub(crate) fn offer_templates(&mut self) {
+        let Some(path) = &self.filename else {
+            return;
+        };
+        let names: Vec<String> = templates_for(path)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        if !names.is_empty() {
+            self.prompt = Some(Prompt::new(
+                PromptKind::Template,
+                &format!(
+                    "New file, template ({}) or enter to skip: ",
+                    names.join(", ")
+                ),
+            ));
+        }
+    }
+
+    pub(crate) fn confirm_template(&mut self) {
+        let name = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        let (name, Some(path)) = (name.trim(), self.filename.clone()) else {
+            return;
+        };
+        if name.is_empty() {
+            return;
+        }
+        let template = templates_for(&path).into_iter().find(|(n, _)| n == name);
+        let Some((_, template)) = template else {
+            self.status_message = Some(format!("No template named {}", name));
+            self.bell();
+            return;
+        };
+        let text = match fs::read_to_string(&template) {
+            Ok(text) => text,
+            Err(e) => {
+                self.status_message = Some(format!("Failed to read template: {}", e));
+                return;
+            }
+        };
+        let file_name = Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.replace_buffer(&text.replace("{{filename}}", &file_name));
+        if !self.ai_excluded() {
+            self.prompt = Some(Prompt::new(
+                PromptKind::TemplateDescription,
+                "Describe the file to fill in the template with AI, or enter to skip: ",
+            ));
+        }
+    }
+
+    pub(crate) fn confirm_template_description(&mut self, predictor: &Arc<Predictor>) {
+        let description = self.prompt.take().map(|p| p.input).unwrap_or_default();
+        if description.trim().is_empty() {
+            return;
+        }
+        let (result_tx, result_rx) = oneshot::channel();
+        predictor.clone().edit_background(
+            self.content.clone(),
+            format!(
+                "Fill in this {} file template so that it does the following: {}",
+                self.language.name(),
+                description.trim()
+            ),
+            result_tx,
+        );
+        self.template_fill = Some(TemplateFill {
+            base: self.content.clone(),
+            result_rx,
+        });
+        self.status_message = Some("Filling in template...".to_string());
+    }
+
+    /// Swaps in the filled template once the model is done, returns true if
+    /// anything changed
+    pub(crate) fn poll_template_fill(&mut self) -> bool {
+        let Some(fill) = self.template_fill.as_mut() else {
+            return false;
+        };
+        let result = match fill.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => Err(anyhow!("the model went away")),
+        };
+        let base = self.template_fill.take().map(|fill| fill.base);
+        self.status_message = Some(match result {
+            Ok(_) if base.as_deref() != Some(self.content.as_str()) => {
+                "Buffer was edited, dropped the filled in template".to_string()
+            }
+            Ok(content) if content.trim().is_empty() => {
+                "The model returned nothing, kept the template".to_string()
+            }
+            Ok(content) => {
+                self.replace_buffer(&content);
+                self.audit(format!(
+                    "Filled in template with the model's output: {:?}",
+                    content
+                ));
+                "Filled in template".to_string()
+            }
+            Err(e) => format!("Failed to fill in template: {}", e),
+        });
+        true
+    }
+
+    fn replace_buffer(&mut self, text: &str) {
+        let len = self.content.len();
+        self.apply_edit(0, len, text);
+        self.cursor_position = 0;
+        self.update_syntax_tree();
+    }
+}
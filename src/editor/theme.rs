@@ -0,0 +1,413 @@
+use crate::config::config_dir;
+use crate::editor::Editor;
+use crate::logger::log_to_file;
+use anyhow::{anyhow, Result};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Colours for everything the editor draws
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// Background and default text of the panes
+    pub base: Style,
+    pub keyword: Style,
+    pub module: Style,
+    pub type_name: Style,
+    pub function: Style,
+    pub identifier: Style,
+    pub string: Style,
+    pub number: Style,
+    pub comment: Style,
+    pub operator: Style,
+    /// Prediction text on the cursor line
+    pub ghost: Style,
+    /// Prediction lines below the cursor line
+    pub ghost_below: Style,
+    /// Prediction text once the cursor has moved away from where it started
+    pub ghost_stale: Style,
+    /// Line numbers, annotations, status messages and other secondary text
+    pub dim: Style,
+    /// Cursor of the pane without focus, the focused one uses the terminal's
+    pub cursor: Style,
+    pub caret: Style,
+    pub selection: Style,
+    /// Background of the line the cursor is on
+    pub current_line: Style,
+    /// Background of the ruler columns
+    pub ruler: Style,
+    pub search_match: Style,
+    pub current_match: Style,
+    pub added: Style,
+    pub modified: Style,
+    pub removed: Style,
+    pub warning: Style,
+    /// Border colour while the bell is flashing
+    pub bell: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            base: Style::default().bg(Color::Black).fg(Color::White),
+            keyword: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            module: Style::default().fg(Color::Cyan),
+            type_name: Style::default().fg(Color::Green),
+            function: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            identifier: Style::default().fg(Color::White),
+            string: Style::default().fg(Color::Yellow),
+            number: Style::default().fg(Color::Magenta),
+            comment: Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+            operator: Style::default().fg(Color::Yellow),
+            ghost: Style::default()
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::ITALIC),
+            ghost_below: Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+            ghost_stale: Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC | Modifier::CROSSED_OUT),
+            dim: Style::default().fg(Color::DarkGray),
+            cursor: Style::default().fg(Color::LightYellow),
+            caret: Style::default().bg(Color::LightYellow).fg(Color::Black),
+            selection: Style::default().bg(Color::Rgb(68, 71, 90)),
+            current_line: Style::default().bg(Color::Rgb(30, 30, 36)),
+            ruler: Style::default().bg(Color::Rgb(40, 40, 50)),
+            search_match: Style::default().bg(Color::Yellow).fg(Color::Black),
+            current_match: Style::default().bg(Color::LightRed).fg(Color::Black),
+            added: Style::default().fg(Color::Green),
+            modified: Style::default().fg(Color::Yellow),
+            removed: Style::default().fg(Color::Red),
+            warning: Style::default().fg(Color::Yellow),
+            bell: Style::default().fg(Color::Red),
+        }
+    }
+}
+
+/// One entry of a theme file, colours are names like `lightblue`, `#rrggbb`
+/// or a 256 colour index
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleSpec {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    bold: bool,
+    #[serde(default)]
+    italic: bool,
+    #[serde(default)]
+    underlined: bool,
+    #[serde(default)]
+    dim: bool,
+    #[serde(default)]
+    crossed_out: bool,
+}
+
+impl StyleSpec {
+    fn style(&self) -> Result<Style> {
+        let color = |name: &str| {
+            name.parse::<Color>()
+                .map_err(|_| anyhow!("unknown colour {:?}", name))
+        };
+        let mut style = Style::default();
+        if let Some(fg) = &self.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(color(bg)?);
+        }
+        for (set, modifier) in [
+            (self.bold, Modifier::BOLD),
+            (self.italic, Modifier::ITALIC),
+            (self.underlined, Modifier::UNDERLINED),
+            (self.dim, Modifier::DIM),
+            (self.crossed_out, Modifier::CROSSED_OUT),
+        ] {
+            if set {
+                style = style.add_modifier(modifier);
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// A theme file: a built in theme to start from and the styles it changes
+#[derive(Deserialize)]
+struct ThemeFile {
+    extends: Option<String>,
+    #[serde(flatten)]
+    styles: HashMap<String, StyleSpec>,
+}
+
+impl Theme {
+    /// Built in themes accepted by `render.theme`, `auto` picks dark or light
+    pub const NAMES: &[&str] = &["auto", "default", "dark", "light", "high-contrast"];
+    // the themes alt+t steps through
+    const CYCLE: &[&str] = &["dark", "light", "high-contrast"];
+
+    /// Black text on a white background for light terminals
+    pub fn light() -> Self {
+        Self {
+            base: Style::default().bg(Color::White).fg(Color::Black),
+            keyword: Style::default()
+                .fg(Color::Rgb(175, 0, 219))
+                .add_modifier(Modifier::BOLD),
+            module: Style::default().fg(Color::Rgb(0, 112, 193)),
+            type_name: Style::default().fg(Color::Rgb(38, 127, 153)),
+            function: Style::default()
+                .fg(Color::Rgb(121, 94, 38))
+                .add_modifier(Modifier::BOLD),
+            identifier: Style::default().fg(Color::Black),
+            string: Style::default().fg(Color::Rgb(163, 21, 21)),
+            number: Style::default().fg(Color::Rgb(9, 134, 88)),
+            comment: Style::default()
+                .fg(Color::Rgb(0, 128, 0))
+                .add_modifier(Modifier::ITALIC),
+            operator: Style::default().fg(Color::Black),
+            ghost: Style::default()
+                .fg(Color::Rgb(90, 90, 200))
+                .add_modifier(Modifier::ITALIC),
+            ghost_below: Style::default()
+                .fg(Color::Rgb(140, 140, 140))
+                .add_modifier(Modifier::ITALIC),
+            ghost_stale: Style::default()
+                .fg(Color::Rgb(170, 170, 170))
+                .add_modifier(Modifier::ITALIC | Modifier::CROSSED_OUT),
+            dim: Style::default().fg(Color::Rgb(110, 110, 110)),
+            cursor: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            caret: Style::default().bg(Color::Blue).fg(Color::White),
+            selection: Style::default().bg(Color::Rgb(173, 214, 255)),
+            current_line: Style::default().bg(Color::Rgb(240, 240, 244)),
+            ruler: Style::default().bg(Color::Rgb(228, 228, 236)),
+            search_match: Style::default()
+                .bg(Color::Rgb(255, 235, 120))
+                .fg(Color::Black),
+            current_match: Style::default()
+                .bg(Color::Rgb(255, 160, 80))
+                .fg(Color::Black),
+            added: Style::default().fg(Color::Rgb(40, 150, 40)),
+            modified: Style::default().fg(Color::Rgb(190, 130, 0)),
+            removed: Style::default().fg(Color::Rgb(200, 30, 30)),
+            warning: Style::default().fg(Color::Rgb(190, 130, 0)),
+            bell: Style::default().fg(Color::Red),
+        }
+    }
+
+    /// Pure black and white with bright accents, nothing drawn in dark grey
+    pub fn high_contrast() -> Self {
+        let bold = |color| Style::default().fg(color).add_modifier(Modifier::BOLD);
+        Self {
+            base: Style::default().bg(Color::Black).fg(Color::White),
+            keyword: bold(Color::LightYellow),
+            module: bold(Color::LightCyan),
+            type_name: bold(Color::LightGreen),
+            function: bold(Color::LightCyan),
+            identifier: Style::default().fg(Color::White),
+            string: Style::default().fg(Color::LightGreen),
+            number: Style::default().fg(Color::LightMagenta),
+            comment: Style::default()
+                .fg(Color::Gray)
+                .add_modifier(Modifier::ITALIC),
+            operator: Style::default().fg(Color::White),
+            ghost: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::ITALIC | Modifier::UNDERLINED),
+            ghost_below: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::ITALIC),
+            ghost_stale: Style::default()
+                .fg(Color::Gray)
+                .add_modifier(Modifier::ITALIC | Modifier::CROSSED_OUT),
+            dim: Style::default().fg(Color::Gray),
+            cursor: bold(Color::White),
+            caret: Style::default().bg(Color::White).fg(Color::Black),
+            selection: Style::default().bg(Color::White).fg(Color::Black),
+            current_line: Style::default().bg(Color::Rgb(38, 38, 38)),
+            ruler: Style::default().bg(Color::Rgb(58, 58, 58)),
+            search_match: Style::default().bg(Color::LightYellow).fg(Color::Black),
+            current_match: Style::default().bg(Color::LightCyan).fg(Color::Black),
+            added: bold(Color::LightGreen),
+            modified: bold(Color::LightYellow),
+            removed: bold(Color::LightRed),
+            warning: bold(Color::LightYellow),
+            bell: bold(Color::LightRed),
+        }
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Self::built_in(detect_background()),
+            "default" | "dark" => Some(Self::default()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    // a path to a .toml file, or the name of one in the themes directory
+    fn file_path(name: &str) -> PathBuf {
+        if name.ends_with(".toml") {
+            PathBuf::from(name)
+        } else {
+            config_dir().join("themes").join(format!("{}.toml", name))
+        }
+    }
+
+    /// The built in theme called `name`, or the theme file it names
+    pub fn load(name: &str) -> Result<Self> {
+        if let Some(theme) = Self::built_in(name) {
+            return Ok(theme);
+        }
+        let path = Self::file_path(name);
+        let text = fs::read_to_string(&path).map_err(|e| {
+            anyhow!(
+                "theme {:?} is neither built in ({}) nor readable at {}: {}",
+                name,
+                Self::NAMES.join(", "),
+                path.display(),
+                e
+            )
+        })?;
+        Self::parse(&text).map_err(|e| anyhow!("Invalid theme {}: {}", path.display(), e))
+    }
+
+    /// Reads a theme file's text
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(text)?;
+        let extends = file.extends.as_deref().unwrap_or("default");
+        let mut theme = Self::built_in(extends)
+            .ok_or_else(|| anyhow!("can't extend unknown theme {:?}", extends))?;
+        for (name, spec) in &file.styles {
+            let style = theme
+                .style_mut(name)
+                .ok_or_else(|| anyhow!("unknown style {:?}", name))?;
+            *style = spec.style().map_err(|e| anyhow!("{}: {}", name, e))?;
+        }
+        Ok(theme)
+    }
+
+    fn style_mut(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "base" => &mut self.base,
+            "keyword" => &mut self.keyword,
+            "module" => &mut self.module,
+            "type_name" => &mut self.type_name,
+            "function" => &mut self.function,
+            "identifier" => &mut self.identifier,
+            "string" => &mut self.string,
+            "number" => &mut self.number,
+            "comment" => &mut self.comment,
+            "operator" => &mut self.operator,
+            "ghost" => &mut self.ghost,
+            "ghost_below" => &mut self.ghost_below,
+            "ghost_stale" => &mut self.ghost_stale,
+            "dim" => &mut self.dim,
+            "cursor" => &mut self.cursor,
+            "caret" => &mut self.caret,
+            "selection" => &mut self.selection,
+            "current_line" => &mut self.current_line,
+            "ruler" => &mut self.ruler,
+            "search_match" => &mut self.search_match,
+            "current_match" => &mut self.current_match,
+            "added" => &mut self.added,
+            "modified" => &mut self.modified,
+            "removed" => &mut self.removed,
+            "warning" => &mut self.warning,
+            "bell" => &mut self.bell,
+            _ => return None,
+        })
+    }
+
+    /// The theme `render.theme` names, the default one if it can't be loaded
+    pub fn named(name: &str) -> Self {
+        Self::load(name).unwrap_or_else(|e| {
+            log_to_file(&format!("{}, using the default theme", e));
+            Self::default()
+        })
+    }
+}
+
+// terminals like konsole, rxvt and iTerm2 set COLORFGBG to "fg;bg" with
+// the colours as ANSI numbers, 7 and 9 to 15 being the light ones
+fn background_from_colorfgbg(value: Option<&str>) -> &'static str {
+    match value.and_then(|v| v.rsplit(';').next()?.parse::<u8>().ok()) {
+        Some(7 | 9..=15) => "light",
+        _ => "dark",
+    }
+}
+
+/// "light" when the terminal says its background is light, "dark" when it
+/// says otherwise or doesn't say
+pub fn detect_background() -> &'static str {
+    background_from_colorfgbg(env::var("COLORFGBG").ok().as_deref())
+}
+
+impl Editor {
+    /// Switches to the next built in theme
+    pub(crate) fn cycle_theme(&mut self) {
+        let current = match self.theme_name.as_str() {
+            "auto" => detect_background(),
+            "default" => "dark",
+            name => name,
+        };
+        let next = Theme::CYCLE
+            .iter()
+            .position(|&name| name == current)
+            .map_or(0, |i| (i + 1) % Theme::CYCLE.len());
+        self.theme_name = Theme::CYCLE[next].to_string();
+        self.theme = Theme::named(&self.theme_name);
+        self.status_message = Some(format!("Theme: {}", self.theme_name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_file_overrides_styles() {
+        let theme = Theme::parse(
+            "extends = \"high-contrast\"\nkeyword = { fg = \"#ff8000\", bold = true }\nbase = { fg = \"black\", bg = \"white\" }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            theme.keyword,
+            Style::default()
+                .fg(Color::Rgb(255, 128, 0))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(
+            theme.base,
+            Style::default().fg(Color::Black).bg(Color::White)
+        );
+        assert_eq!(theme.string, Theme::high_contrast().string);
+        let theme =
+            Theme::parse("ghost_stale = { bg = \"#202020\", dim = true, crossed_out = true }")
+                .unwrap();
+        assert_eq!(
+            theme.ghost_stale,
+            Style::default()
+                .bg(Color::Rgb(32, 32, 32))
+                .add_modifier(Modifier::DIM | Modifier::CROSSED_OUT)
+        );
+        assert!(Theme::parse("keywrd = { fg = \"red\" }").is_err());
+        assert!(Theme::parse("keyword = { fg = \"reddish\" }").is_err());
+    }
+
+    #[test]
+    fn test_detects_light_background() {
+        assert_eq!(background_from_colorfgbg(Some("0;15")), "light");
+        assert_eq!(background_from_colorfgbg(Some("15;default;0")), "dark");
+        assert_eq!(background_from_colorfgbg(None), "dark");
+    }
+}
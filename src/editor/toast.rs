@@ -0,0 +1,122 @@
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use crate::logger::log_to_file;
+use ratatui::layout::Rect;
+use ratatui::style::Modifier;
+use ratatui::widgets::{Clear, Paragraph};
+use ratatui::Frame;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+// older toasts make way for new ones past this many
+const MAX_TOASTS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    // errors stay up long enough to be read
+    fn duration(self) -> Duration {
+        Duration::from_secs(match self {
+            Severity::Info => 2,
+            Severity::Warning => 4,
+            Severity::Error => 8,
+        })
+    }
+
+    fn style(self, theme: &Theme) -> ratatui::style::Style {
+        let colour = match self {
+            Severity::Info => theme.added,
+            Severity::Warning => theme.warning,
+            Severity::Error => theme.removed,
+        };
+        theme.base.patch(colour).add_modifier(Modifier::REVERSED)
+    }
+}
+
+/// A message shown in the top right corner until it times out
+pub struct Toast {
+    pub message: String,
+    pub severity: Severity,
+    until: Instant,
+}
+
+impl Editor {
+    /// Shows `message` for a while, showing the same message again restarts
+    /// its time
+    pub(crate) fn notify(&mut self, severity: Severity, message: impl Into<String>) {
+        let message = message.into();
+        log_to_file(&format!("{:?}: {}", severity, message));
+        self.toasts.retain(|toast| toast.message != message);
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.toasts.push(Toast {
+            message,
+            severity,
+            until: Instant::now() + severity.duration(),
+        });
+    }
+
+    /// Drops the toasts that timed out, true if any did
+    pub(crate) fn poll_toasts(&mut self) -> bool {
+        let now = Instant::now();
+        let before = self.toasts.len();
+        self.toasts.retain(|toast| toast.until > now);
+        self.toasts.len() != before
+    }
+
+    /// Stacks the toasts down from the top right of `area`, newest first
+    pub(crate) fn render_toasts(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        for (row, toast) in self.toasts.iter().rev().enumerate() {
+            let row = row as u16 + 1;
+            if row >= area.height {
+                break;
+            }
+            let width = (toast.message.width() as u16 + 2).min(area.width.saturating_sub(2));
+            let toast_area = Rect::new(
+                area.x + area.width.saturating_sub(width + 1),
+                area.y + row,
+                width,
+                1,
+            );
+            f.render_widget(Clear, toast_area);
+            f.render_widget(
+                Paragraph::new(format!(" {} ", toast.message)).style(toast.severity.style(theme)),
+                toast_area,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor::test_editor;
+
+    #[test]
+    fn test_toasts_replace_repeats_and_time_out() {
+        let mut editor = test_editor("", 0);
+        editor.notify(Severity::Info, "Saved");
+        editor.notify(Severity::Error, "Prediction failed: connection refused");
+        editor.notify(Severity::Info, "Saved");
+        let messages: Vec<&str> = editor.toasts.iter().map(|t| t.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec!["Prediction failed: connection refused", "Saved"]
+        );
+        for i in 0..4 {
+            editor.notify(Severity::Warning, format!("warning {}", i));
+        }
+        assert_eq!(editor.toasts.len(), MAX_TOASTS);
+        assert!(!editor.poll_toasts());
+        editor.toasts[0].until = Instant::now();
+        assert!(editor.poll_toasts());
+        assert_eq!(editor.toasts.len(), MAX_TOASTS - 1);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::editor::prediction::BYTES_PER_TOKEN;
+use crate::editor::Editor;
+use crate::models::Predictor;
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// Unit tests being written by the model, for a new buffer
+pub struct TestGeneration {
+    // what the tests are for, e.g. "`fn parse`"
+    what: String,
+    result_rx: oneshot::Receiver<Result<String>>,
+}
+
+impl Editor {
+    /// Asks the model for unit tests of the function the cursor is in, or of
+    /// the file outside of one
+    pub(crate) fn generate_tests(&mut self, predictor: &Arc<Predictor>) {
+        let (what, code) = match self.enclosing_scope(|label| label.starts_with("fn ")) {
+            Some((label, range)) => (format!("`{}`", label), self.content[range].to_string()),
+            None => {
+                let budget = self.config.model.context_tokens() * BYTES_PER_TOKEN;
+                ("the whole file".to_string(), self.context_after(0, budget))
+            }
+        };
+        let (result_tx, result_rx) = oneshot::channel();
+        predictor.clone().tests_background(
+            code,
+            what.clone(),
+            self.language.name(),
+            self.language.test_marker(),
+            result_tx,
+        );
+        self.status_message = Some(format!("Writing tests for {}...", what));
+        self.test_generation = Some(TestGeneration { what, result_rx });
+    }
+
+    /// Opens the tests in a new buffer once the model is done, returns true
+    /// if anything changed
+    pub(crate) fn poll_test_generation(&mut self) -> bool {
+        let Some(generation) = self.test_generation.as_mut() else {
+            return false;
+        };
+        let result = match generation.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => Err(anyhow!("the model went away")),
+        };
+        let what = self
+            .test_generation
+            .take()
+            .map(|generation| generation.what)
+            .unwrap_or_default();
+        match result {
+            Ok(tests) if tests.trim().is_empty() => {
+                self.status_message = Some("The model returned no tests".to_string());
+            }
+            Ok(tests) => {
+                self.open_scratch_buffer(&format!("{}\n", tests.trim_end()));
+                self.audit(format!(
+                    "Opened the model's tests for {} in a new buffer: {:?}",
+                    what, tests
+                ));
+                self.status_message = Some(format!("Tests for {}, ctrl+s saves them", what));
+            }
+            Err(e) => self.status_message = Some(format!("Failed to write tests: {}", e)),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::editor::Editor;
+    use crate::models::backend::{Backend, MockBackend};
+    use crate::models::Predictor;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_tests_open_in_a_new_buffer() {
+        let (mut editor, prediction_tx) = Editor::new("test.rs".to_string(), Config::default());
+        let source = "fn double(x: i32) -> i32 {\n    x * 2\n}\n";
+        editor.content = source.to_string();
+        editor.filename = Some("src/lib.rs".to_string());
+        editor.cursor_position = 30;
+        editor.update_syntax_tree();
+        let backend = MockBackend::answering(&[
+            "Here are the tests:\n```rust\n#[cfg(test)]\nmod tests {\n",
+            "    #[test]\n    fn doubles() {}\n}\n```\nThey cover the basics.",
+        ]);
+        let prompts = backend.prompts.clone();
+        let predictor = Arc::new(Predictor::new(
+            Backend::new("mock", backend),
+            prediction_tx,
+            "m".into(),
+        ));
+        editor.generate_tests(&predictor);
+        while !editor.poll_test_generation() {
+            tokio::task::yield_now().await;
+        }
+        assert!(prompts.lock().unwrap()[0].contains("tests for `fn double`"));
+        assert_eq!(editor.buffer_count(), 2);
+        assert_eq!(
+            editor.content,
+            "#[cfg(test)]\nmod tests {\n    #[test]\n    fn doubles() {}\n}\n"
+        );
+        assert!(editor.filename.is_none() && editor.modified);
+        editor.switch_buffer(0);
+        assert_eq!(editor.content, source);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::editor::prompt::{Prompt, PromptKind};
+use crate::editor::Editor;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+impl Editor {
+    /// Returns true if nars can exit now, otherwise switches to a buffer with
+    /// unsaved changes and asks what to do with them first
+    pub(crate) fn request_quit(&mut self) -> bool {
+        if !self.modified {
+            match self.modified_buffer() {
+                Some(tab) => self.switch_buffer(tab),
+                None => return true,
+            }
+        }
+        let label = if self.modified_buffer().is_some() {
+            "Unsaved changes in several files: s save this one, d discard all and quit, esc cancel"
+        } else {
+            "Unsaved changes: s save and quit, d discard and quit, esc cancel"
+        };
+        self.prompt = Some(Prompt::new(PromptKind::UnsavedChanges, label));
+        false
+    }
+
+    /// Handles s, d and esc while the unsaved changes prompt is showing,
+    /// returns true to exit
+    pub(crate) fn handle_unsaved_changes_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('s') if self.filename.is_none() => {
+                self.open_save_as_prompt();
+                self.status_message = Some("Esc quits once the file is saved".to_string());
+                false
+            }
+            KeyCode::Char('s') => {
+                self.prompt = None;
+                self.save();
+                // a failed save shows its reason as a toast, other buffers
+                // with changes ask next
+                !self.modified && self.request_quit()
+            }
+            KeyCode::Char('d') => true,
+            KeyCode::Esc => {
+                self.prompt = None;
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::test_editor;
+
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn test_quitting_with_changes_asks_first() {
+        let mut editor = test_editor("", 0);
+        assert!(editor.request_quit());
+        editor.apply_edit(0, 0, "fn main() {}");
+        assert!(!editor.request_quit());
+        assert!(editor.prompt.is_some());
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert!(!editor.handle_unsaved_changes_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        assert!(editor.prompt.is_none());
+        editor.request_quit();
+        assert!(editor.handle_unsaved_changes_key(key('d')));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::editor::theme::Theme;
+use crate::editor::Editor;
+use ratatui::text::{Line, Span};
+
+// the glyph drawn instead of a whitespace character, one column wide like
+// the character itself
+fn glyph(c: char) -> Option<char> {
+    match c {
+        ' ' => Some('·'),
+        '\t' => Some('→'),
+        '\u{a0}' => Some('⍽'),
+        _ => None,
+    }
+}
+
+/// Draws spaces, tabs and non-breaking spaces in `line` as dim glyphs.
+/// Non-breaking spaces and the trailing whitespace between bytes `trailing`
+/// and `end` get the warning colour. Dim spans, like fold summaries, are left
+/// alone.
+pub fn show_whitespace(
+    line: &Line<'_>,
+    trailing: usize,
+    end: usize,
+    theme: &Theme,
+) -> Line<'static> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut offset = 0;
+    for span in &line.spans {
+        if span.style == theme.dim {
+            offset += span.content.len();
+            spans.push(Span::styled(span.content.to_string(), span.style));
+            continue;
+        }
+        let mut run = String::new();
+        let mut run_style = span.style;
+        for c in span.content.chars() {
+            let (text, style) = match glyph(c) {
+                Some(g) if (trailing..end).contains(&offset) || c == '\u{a0}' => {
+                    (g, span.style.patch(theme.warning))
+                }
+                Some(g) => (g, span.style.patch(theme.dim)),
+                None => (c, span.style),
+            };
+            if style != run_style && !run.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut run), run_style));
+            }
+            run_style = style;
+            run.push(text);
+            offset += c.len_utf8();
+        }
+        if !run.is_empty() {
+            spans.push(Span::styled(run, run_style));
+        }
+    }
+    let mut shown = Line::from(spans);
+    shown.style = line.style;
+    shown.alignment = line.alignment;
+    shown
+}
+
+impl Editor {
+    pub(crate) fn toggle_whitespace(&mut self) {
+        self.show_whitespace = !self.show_whitespace;
+        self.status_message = Some(format!(
+            "Whitespace {}",
+            if self.show_whitespace {
+                "shown"
+            } else {
+                "hidden"
+            }
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Style;
+
+    #[test]
+    fn test_marks_trailing_whitespace_and_keeps_dim_spans() {
+        let theme = Theme::default();
+        let line = Line::from(vec![
+            Span::raw("\tlet x = 1;  "),
+            Span::styled(" ⋯ 3 lines", theme.dim),
+        ]);
+        let shown = show_whitespace(&line, 11, 13, &theme);
+        assert_eq!(shown.to_string(), "→let·x·=·1;·· ⋯ 3 lines");
+        let trailing = shown
+            .spans
+            .iter()
+            .find(|span| span.content == "··")
+            .unwrap();
+        assert_eq!(trailing.style, Style::default().patch(theme.warning));
+        assert_eq!(shown.width(), line.width());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::editor::Editor;
+use ratatui::text::{Line, Span};
+use unicode_width::UnicodeWidthChar;
+
+/// Rendered lines split into rows no wider than the content pane
+pub struct Wrapped<'a> {
+    pub lines: Vec<Line<'a>>,
+    pub numbers: Vec<Line<'a>>,
+    /// First row of every input line
+    pub row_starts: Vec<usize>,
+}
+
+// row and column of the cursor after `text` when wrapped at `width` columns,
+// the cursor's cell wraps like the `cell` wide character it is on would
+fn cursor_cell(text: &str, cell: usize, width: usize) -> (usize, usize) {
+    let mut row = 0;
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width && used > 0 {
+            row += 1;
+            used = 0;
+        }
+        used += w;
+    }
+    if used + cell > width && used > 0 {
+        (row + 1, 0)
+    } else {
+        (row, used)
+    }
+}
+
+// splits a line when it gets wider than `width` columns, keeping span styles
+fn wrap_line(line: Line<'_>, width: usize) -> Vec<Line<'_>> {
+    let mut rows = vec![Vec::new()];
+    let mut used = 0;
+    for span in line.spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width && used > 0 {
+                let done = Span::styled(std::mem::take(&mut text), span.style);
+                rows.last_mut().expect("rows start non-empty").push(done);
+                rows.push(Vec::new());
+                used = 0;
+            }
+            text.push(c);
+            used += w;
+        }
+        if !text.is_empty() {
+            rows.last_mut()
+                .expect("rows start non-empty")
+                .push(Span::styled(text, span.style));
+        }
+    }
+    rows.into_iter().map(Line::from).collect()
+}
+
+/// Wraps every line at `width` columns. Continuation rows get a blank line number.
+pub fn wrap_lines<'a>(lines: Vec<Line<'a>>, numbers: Vec<Line<'a>>, width: usize) -> Wrapped<'a> {
+    let mut wrapped = Wrapped {
+        lines: Vec::new(),
+        numbers: Vec::new(),
+        row_starts: Vec::new(),
+    };
+    for (line, number) in lines.into_iter().zip(numbers) {
+        wrapped.row_starts.push(wrapped.lines.len());
+        for (idx, row) in wrap_line(line, width.max(1)).into_iter().enumerate() {
+            wrapped.lines.push(row);
+            wrapped.numbers.push(if idx == 0 {
+                number.clone()
+            } else {
+                Line::from("")
+            });
+        }
+    }
+    wrapped
+}
+
+impl Editor {
+    pub(crate) fn toggle_soft_wrap(&mut self) {
+        self.soft_wrap = !self.soft_wrap;
+        self.status_message = Some(format!(
+            "Soft wrap {}",
+            if self.soft_wrap { "on" } else { "off" }
+        ));
+    }
+
+    /// Row and column of the cursor within its wrapped line
+    pub(crate) fn cursor_wrap_cell(&self, width: usize) -> (usize, usize) {
+        let line_start = self.line_start(self.cursor_position);
+        // past the end of the line the cursor gets a blank cell
+        let cell = match self.content[self.cursor_position..].chars().next() {
+            Some(c) if c != '\n' => c.width().unwrap_or(1),
+            _ => 1,
+        };
+        cursor_cell(
+            &self.content[line_start..self.cursor_position],
+            cell,
+            width.max(1),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Color, Style};
+
+    #[test]
+    fn test_wraps_across_spans() {
+        let red = Style::default().fg(Color::Red);
+        let line = Line::from(vec![Span::raw("abc"), Span::styled("defgh", red)]);
+        let wrapped = wrap_lines(
+            vec![line, Line::from("x")],
+            vec![Line::from("1"), Line::from("2")],
+            4,
+        );
+        let rows: Vec<String> = wrapped.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(rows, ["abcd", "efgh", "x"]);
+        assert_eq!(wrapped.lines[1].spans[0].style, red);
+        assert_eq!(wrapped.row_starts, [0, 2]);
+        assert_eq!(wrapped.numbers[1].to_string(), "");
+        // the cursor on a wide character moves to the next row with it
+        assert_eq!(cursor_cell("abc", 1, 4), (0, 3));
+        assert_eq!(cursor_cell("abc", 2, 4), (1, 0));
+        assert_eq!(cursor_cell("abcd", 1, 4), (1, 0));
+    }
+}
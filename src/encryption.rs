@@ -0,0 +1,150 @@
+use crate::config::{EncryptionBackend, EncryptionConfig};
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// the command that prints the decrypted file
+fn decrypt_command(config: &EncryptionConfig, path: &str) -> Command {
+    match config.backend {
+        EncryptionBackend::Gpg => {
+            let mut command = Command::new("gpg");
+            command.args(["--quiet", "--decrypt", path]);
+            command
+        }
+        EncryptionBackend::Age => {
+            let mut command = Command::new("age");
+            command.arg("--decrypt");
+            if let Some(identity) = &config.identity {
+                command.args(["--identity", identity]);
+            }
+            command.arg(path);
+            command
+        }
+    }
+}
+
+// the command that writes what it reads on stdin to `path`, encrypted
+fn encrypt_command(config: &EncryptionConfig, path: &str) -> Command {
+    let mut command = match config.backend {
+        EncryptionBackend::Gpg => {
+            let mut command = Command::new("gpg");
+            command.args(["--quiet", "--batch", "--yes", "--encrypt", "--output", path]);
+            command
+        }
+        EncryptionBackend::Age => {
+            let mut command = Command::new("age");
+            command.args(["--encrypt", "--output", path]);
+            command
+        }
+    };
+    for recipient in &config.recipients {
+        command.args(["--recipient", recipient]);
+    }
+    command
+}
+
+pub fn decrypt(config: &EncryptionConfig, path: &str) -> Result<String> {
+    let output = decrypt_command(config, path)
+        .stderr(Stdio::piped())
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to decrypt {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+pub fn encrypt(config: &EncryptionConfig, path: &str, plaintext: &str) -> Result<()> {
+    if config.recipients.is_empty() {
+        return Err(anyhow!("No encryption recipients configured for {}", path));
+    }
+    let mut child = encrypt_command(config, path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or(anyhow!("Failed to open encryption stdin"))?
+        .write_all(plaintext.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to encrypt {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_commands_for_each_backend() {
+        let mut config = EncryptionConfig {
+            backend: EncryptionBackend::Age,
+            recipients: vec!["age1abc".to_string(), "age1def".to_string()],
+            identity: Some("key.txt".to_string()),
+            ..EncryptionConfig::default()
+        };
+        let command = decrypt_command(&config, "a.age");
+        assert_eq!(command.get_program(), "age");
+        assert_eq!(
+            args(&command),
+            ["--decrypt", "--identity", "key.txt", "a.age"]
+        );
+        assert_eq!(
+            args(&encrypt_command(&config, "a.age")),
+            [
+                "--encrypt",
+                "--output",
+                "a.age",
+                "--recipient",
+                "age1abc",
+                "--recipient",
+                "age1def"
+            ]
+        );
+        // gpg finds the key through its agent
+        config.backend = EncryptionBackend::Gpg;
+        assert_eq!(
+            args(&decrypt_command(&config, "a.gpg")),
+            ["--quiet", "--decrypt", "a.gpg"]
+        );
+        assert_eq!(
+            args(&encrypt_command(&config, "a.gpg"))[..6],
+            [
+                "--quiet",
+                "--batch",
+                "--yes",
+                "--encrypt",
+                "--output",
+                "a.gpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refuses_to_encrypt_for_nobody() {
+        let config = EncryptionConfig::default();
+        let error = encrypt(&config, "a.gpg", "secret").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No encryption recipients configured for a.gpg"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Advisory lock marking a file as open in this nars instance. The lock is
+/// only removed on drop if this instance created it.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Takes the lock for `file`, returning a warning when another editor
+    /// already appears to have it open.
+    pub fn acquire(file: &str) -> (Option<Self>, Option<String>) {
+        let path = lock_path(Path::new(file));
+        if let Some(holder) = foreign_holder(Path::new(file)) {
+            return (None, Some(format!("{} is also open in {}", file, holder)));
+        }
+        match fs::write(&path, std::process::id().to_string()) {
+            Ok(()) => (Some(Self { path }), None),
+            Err(_) => (None, None),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_path(file: &Path) -> PathBuf {
+    sibling(file, |name| format!(".{}.nars-lock", name))
+}
+
+fn sibling(file: &Path, name: impl Fn(&str) -> String) -> PathBuf {
+    let file_name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    file.with_file_name(name(&file_name))
+}
+
+/// Describes who else has `file` open, checking nars locks as well as vim swap
+/// files and emacs lock links.
+pub fn foreign_holder(file: &Path) -> Option<String> {
+    if let Ok(pid) = fs::read_to_string(lock_path(file)) {
+        let pid = pid.trim();
+        if pid != std::process::id().to_string() && process_alive(pid) {
+            return Some(format!("nars (pid {})", pid));
+        }
+    }
+    if sibling(file, |name| format!(".{}.swp", name)).exists() {
+        return Some("vim".to_string());
+    }
+    if fs::symlink_metadata(sibling(file, |name| format!(".#{}", name))).is_ok() {
+        return Some("emacs".to_string());
+    }
+    None
+}
+
+fn process_alive(pid: &str) -> bool {
+    let proc = Path::new("/proc");
+    // without procfs we can't tell, so assume the other instance is still running
+    !proc.exists() || proc.join(pid).exists()
+}
@@ -1,15 +1,38 @@
 use std::fs::OpenOptions;
 use std::io::Write;
 
+pub const LOG_PATH: &str = "./logs/editor_debug.log";
+
+/// How much a log line matters, the log viewer filters on it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
 pub fn log_to_file(message: &str) {
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("./logs/editor_debug.log")
-    {
+    log(Level::Debug, message);
+}
+
+pub fn log(level: Level, message: &str) {
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(LOG_PATH) {
         let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-        if let Err(e) = writeln!(file, "[{}] {}", timestamp, message) {
-            eprintln!("Failed to write to log file: {}", e);
-        }
+        // stderr is the editor's screen, so a failed write is dropped
+        let _ = writeln!(file, "[{}] {} {}", timestamp, level.name(), message);
     }
 }
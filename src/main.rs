@@ -1,27 +1,78 @@
+use crate::cli::Command;
+use crate::config::Config;
 use crate::editor::{run, Editor};
-use crate::models::ollama::OllamaClient;
+use crate::models::backend::{local_ollama, Backend};
 use crate::models::Predictor;
-use anyhow::Result;
+use crate::profiling::StartupProfile;
+use anyhow::{anyhow, Result};
 use std::env;
 use std::sync::Arc;
+use std::time::Instant;
 
+mod cli;
+mod config;
+mod doctor;
 mod editor;
+mod encryption;
+mod lockfile;
 mod logger;
 mod models;
+mod profiling;
+mod remote;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let client = Arc::new(OllamaClient::new());
-    let args: Vec<String> = env::args().collect();
+    let start = Instant::now();
+    let mut args: Vec<String> = env::args().collect();
+    let profile_startup = args.iter().any(|arg| arg == "--profile-startup");
+    args.retain(|arg| arg != "--profile-startup");
+    let ollama_host = take_flag_value(&mut args, "--ollama-host")?;
+    let mut startup = StartupProfile::new(profile_startup, start);
+    let command = Command::parse(&args)?;
+    // the doctor reports a broken config instead of failing to load it
+    if let Some(Command::Doctor { model }) = &command {
+        return doctor::run(model, ollama_host.as_deref()).await;
+    }
+    let mut config = Config::load()?;
+    config.model.resolve_ollama_host(ollama_host.as_deref());
+    startup.mark("config");
+    if let Some(command) = command {
+        return cli::run(command, config).await;
+    }
+    let local = Backend::ollama(local_ollama(&config.model)?);
+    let backends = Backend::all_from_config(&config.model.backends)?;
+    startup.mark("backends");
     let filename = args.get(1).cloned();
-    let (mut editor, prediction_tx) = Editor::new(filename.clone().unwrap_or(".rs".to_string()));
+    let reduced_motion = config.render.reduced_motion;
+    let options = config.model.options.clone();
+    let model = args.get(2).cloned().unwrap_or(config.model.name.clone());
+    let (mut editor, prediction_tx) =
+        Editor::new(filename.clone().unwrap_or(".rs".to_string()), config);
+    startup.mark("editor");
     if let Some(path) = filename {
-        editor.load_file(path)?;
+        editor.open_file(path)?;
     }
-    let mut model = "qwen2.5-coder:7b".to_string();
-    if args.len() >= 2 {
-        model = args.get(2).cloned().unwrap_or(model.to_string());
+    startup.mark("open");
+    let predictor = Predictor::new(local, prediction_tx, model)
+        .with_backends(backends)
+        .with_options(options)
+        .with_partial_predictions(!reduced_motion);
+    let predictor = Arc::new(predictor);
+    let _profile = profiling::init();
+    let result = run(editor, predictor, &mut startup).await;
+    startup.print();
+    result
+}
+
+// removes `flag` and the value after it from the arguments, wherever they are
+fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>> {
+    let Some(at) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    if at + 1 >= args.len() {
+        return Err(anyhow!("{} needs a value", flag));
     }
-    let predictor = Arc::new(Predictor::new(client, prediction_tx, model));
-    run(editor, predictor).await
+    let value = args.remove(at + 1);
+    args.remove(at);
+    Ok(Some(value))
 }
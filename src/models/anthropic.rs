@@ -0,0 +1,223 @@
+use crate::config::GenerationOptions;
+use crate::models::backend::ModelBackend;
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::{FutureExt, StreamExt, TryStreamExt};
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+const API_VERSION: &str = "2023-06-01";
+// the messages API needs a limit, this leaves room for a whole function
+const MAX_TOKENS: u32 = 4096;
+// what a model name meant for ollama, like the default, is sent as instead
+const DEFAULT_MODEL: &str = "claude-3-5-haiku-latest";
+
+#[derive(Serialize)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: u32,
+    messages: Vec<Message>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: &'static str,
+    content: String,
+}
+
+// the events of a streamed response, only text deltas and errors matter here
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    ContentBlockDelta {
+        delta: Delta,
+    },
+    Error {
+        error: ApiError,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Delta {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// The model id to ask the API for: `haiku`, `sonnet` and `opus` stand for
+/// the latest of each, other Claude ids are kept and anything else falls
+/// back to haiku
+pub fn model_id(model: &str) -> String {
+    match model {
+        "haiku" => DEFAULT_MODEL.to_string(),
+        "sonnet" => "claude-sonnet-4-0".to_string(),
+        "opus" => "claude-opus-4-0".to_string(),
+        _ if model.starts_with("claude-") => model.to_string(),
+        _ => DEFAULT_MODEL.to_string(),
+    }
+}
+
+// text from the whole server sent event lines in `buffer`, leaving a line
+// split across chunks for the next one
+fn take_text(buffer: &mut Vec<u8>) -> Result<String> {
+    let mut text = String::new();
+    while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            continue;
+        };
+        match serde_json::from_str(data.trim())? {
+            Event::ContentBlockDelta { delta } => text.extend(delta.text),
+            Event::Error { error } => return Err(anyhow!("Anthropic error: {}", error.message)),
+            Event::Other => {}
+        }
+    }
+    Ok(text)
+}
+
+/// Client for Anthropic's messages API
+#[derive(Clone)]
+pub struct AnthropicClient {
+    client: Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl AnthropicClient {
+    /// `url` is the API root, e.g. `https://api.anthropic.com`
+    pub fn new(url: &str, api_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: format!("{}/v1", url.trim_end_matches('/').trim_end_matches("/v1")),
+            api_key,
+        }
+    }
+
+    fn authorized(&self, builder: RequestBuilder) -> RequestBuilder {
+        builder
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+    }
+
+    /// Ids of the models the API offers
+    pub async fn models(&self) -> Result<Vec<String>> {
+        let list: ModelList = self
+            .authorized(self.client.get(format!("{}/models", self.base_url)))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(list.data.into_iter().map(|model| model.id).collect())
+    }
+
+    pub async fn stream_generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<BoxStream<'static, Result<String>>> {
+        // the API has no seed or repeat penalty
+        let request = MessagesRequest {
+            model: model_id(model),
+            max_tokens: options.num_predict.unwrap_or(MAX_TOKENS),
+            messages: vec![Message {
+                role: "user",
+                content: prompt.to_string(),
+            }],
+            stream: true,
+            temperature: options.temperature,
+            top_p: options.top_p,
+        };
+        let response = self
+            .authorized(self.client.post(format!("{}/messages", self.base_url)))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut buffer = Vec::new();
+        Ok(response
+            .bytes_stream()
+            .map_err(|e| anyhow!("Stream error: {}", e))
+            .map(move |chunk| -> Result<String> {
+                buffer.extend_from_slice(&chunk?);
+                take_text(&mut buffer)
+            })
+            .boxed())
+    }
+}
+
+impl ModelBackend for AnthropicClient {
+    fn stream_generate<'a>(
+        &'a self,
+        model: &'a str,
+        prompt: &'a str,
+        _raw: bool,
+        options: &'a GenerationOptions,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        self.stream_generate(model, prompt, options).boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        self.models().boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{model_id, take_text};
+
+    #[test]
+    fn test_text_is_taken_from_whole_events() {
+        let mut buffer = b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{}}\n\n\
+            event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"let x\"}}\n\
+            data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_de"
+            .to_vec();
+        assert_eq!(take_text(&mut buffer).unwrap(), "let x");
+        buffer.extend_from_slice(
+            b"lta\",\"text\":\" = 1;\"}}\n\nevent: ping\ndata: {\"type\":\"ping\"}\n",
+        );
+        assert_eq!(take_text(&mut buffer).unwrap(), " = 1;");
+        let mut buffer =
+            b"data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n"
+                .to_vec();
+        assert!(take_text(&mut buffer)
+            .unwrap_err()
+            .to_string()
+            .contains("Overloaded"));
+    }
+
+    #[test]
+    fn test_model_names_map_to_claude_ids() {
+        assert_eq!(model_id("sonnet"), "claude-sonnet-4-0");
+        assert_eq!(
+            model_id("claude-3-7-sonnet-latest"),
+            "claude-3-7-sonnet-latest"
+        );
+        assert_eq!(model_id("qwen2.5-coder:7b"), "claude-3-5-haiku-latest");
+    }
+}
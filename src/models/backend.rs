@@ -0,0 +1,270 @@
+use crate::config::{BackendConfig, BackendKind, GenerationOptions, ModelConfig};
+use crate::logger::{log, Level};
+use crate::models::anthropic::AnthropicClient;
+use crate::models::ollama::{self, ModelInfo, OllamaClient};
+use crate::models::openai::OpenAiClient;
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::FutureExt;
+use std::env;
+use std::time::Duration;
+
+// the key in the environment variable `var` names, if the config names one
+fn api_key(var: &Option<String>, url: &str) -> Result<Option<String>> {
+    match var {
+        Some(var) => Ok(Some(
+            env::var(var).map_err(|_| anyhow!("{} is not set for {}", var, url))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// The ollama used when no backends are configured, at `model.ollama_host`
+pub fn local_ollama(config: &ModelConfig) -> Result<OllamaClient> {
+    let url = ollama::host_url(&config.ollama_host);
+    let api_key = api_key(&config.ollama_api_key_env, &url)?;
+    Ok(OllamaClient::with_url(&url).with_api_key(api_key))
+}
+
+// the key written in the config, or else the one in its environment variable
+fn backend_api_key(config: &BackendConfig) -> Result<Option<String>> {
+    match &config.api_key {
+        Some(key) => Ok(Some(key.clone())),
+        None => api_key(&config.api_key_env, &config.url),
+    }
+}
+
+/// What the predictor needs from a model server. Each provider implements
+/// this next to its client, so adding one doesn't touch the predictor or the
+/// editor.
+pub trait ModelBackend: Send + Sync {
+    /// Streams what `model` generates for `prompt`, sampled with `options`.
+    /// A `raw` prompt is sent as is, only backends that `takes_raw_prompts`
+    /// honour it.
+    fn stream_generate<'a>(
+        &'a self,
+        model: &'a str,
+        prompt: &'a str,
+        raw: bool,
+        options: &'a GenerationOptions,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>>;
+
+    /// Models the server has available
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    /// Whether the server can be reached, without generating anything
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        self.list_models().map(|models| models.map(|_| ())).boxed()
+    }
+
+    /// Whether the server can be sent a prompt without the model's template
+    /// around it, which fill-in-the-middle prompts need
+    fn takes_raw_prompts(&self) -> bool {
+        false
+    }
+
+    /// Details of `model`, for servers that report them
+    fn model_info<'a>(&'a self, _model: &'a str) -> BoxFuture<'a, Result<ModelInfo>> {
+        async { Err(anyhow!("this backend doesn't report model details")) }.boxed()
+    }
+}
+
+/// One server predictions can be generated on
+pub struct Backend {
+    pub name: String,
+    client: Box<dyn ModelBackend>,
+    /// Used instead of the predictor's model when set
+    pub model: Option<String>,
+    /// How long to wait for the first token before failing over
+    pub timeout: Duration,
+}
+
+impl Backend {
+    pub fn new(name: &str, client: impl ModelBackend + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            client: Box::new(client),
+            model: None,
+            timeout: Duration::from_millis(BackendConfig::default().timeout_ms),
+        }
+    }
+
+    pub fn ollama(client: OllamaClient) -> Self {
+        Self::new(&format!("ollama at {}", client.url()), client)
+    }
+
+    pub fn from_config(config: &BackendConfig) -> Result<Self> {
+        let (kind, client): (_, Box<dyn ModelBackend>) = match config.kind {
+            BackendKind::Ollama => (
+                "ollama",
+                Box::new(
+                    OllamaClient::with_url(&config.url).with_api_key(backend_api_key(config)?),
+                ),
+            ),
+            BackendKind::OpenAi => (
+                "openai",
+                Box::new(OpenAiClient::new(&config.url, backend_api_key(config)?)),
+            ),
+            BackendKind::Anthropic => {
+                let key = match backend_api_key(config)? {
+                    Some(key) => key,
+                    None => env::var("ANTHROPIC_API_KEY")
+                        .map_err(|_| anyhow!("ANTHROPIC_API_KEY is not set for {}", config.url))?,
+                };
+                (
+                    "anthropic",
+                    Box::new(AnthropicClient::new(&config.url, key)),
+                )
+            }
+        };
+        Ok(Self {
+            name: format!("{} at {}", kind, config.url),
+            client,
+            model: config.model.clone(),
+            timeout: Duration::from_millis(config.timeout_ms),
+        })
+    }
+
+    /// Backends for every `[[model.backends]]` entry, in order. An entry that
+    /// can't be set up, e.g. because its API key isn't set, is logged and left
+    /// out, so this only fails when none of them can be.
+    pub fn all_from_config(configs: &[BackendConfig]) -> Result<Vec<Self>> {
+        let mut backends = Vec::new();
+        let mut last_error = None;
+        for config in configs {
+            match Backend::from_config(config) {
+                Ok(backend) => backends.push(backend),
+                Err(e) => {
+                    log(
+                        Level::Warn,
+                        &format!("Skipping backend at {}: {}", config.url, e),
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if backends.is_empty() => Err(e),
+            _ => Ok(backends),
+        }
+    }
+}
+
+// a backend is used through its client, with the name and timeout around it
+impl ModelBackend for Backend {
+    fn stream_generate<'a>(
+        &'a self,
+        model: &'a str,
+        prompt: &'a str,
+        raw: bool,
+        options: &'a GenerationOptions,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        self.client.stream_generate(model, prompt, raw, options)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        self.client.list_models()
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        self.client.health_check()
+    }
+
+    fn takes_raw_prompts(&self) -> bool {
+        self.client.takes_raw_prompts()
+    }
+
+    fn model_info<'a>(&'a self, model: &'a str) -> BoxFuture<'a, Result<ModelInfo>> {
+        self.client.model_info(model)
+    }
+}
+
+/// A backend answering every prompt with the same chunks, failing or never
+/// answering, and keeping the prompts it was sent
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    pub chunks: Vec<String>,
+    pub fail: bool,
+    pub hang: bool,
+    pub prompts: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl MockBackend {
+    pub fn answering(chunks: &[&str]) -> Self {
+        Self {
+            chunks: chunks.iter().map(|chunk| chunk.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn failing() -> Self {
+        Self {
+            fail: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn hanging() -> Self {
+        Self {
+            hang: true,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl ModelBackend for MockBackend {
+    fn stream_generate<'a>(
+        &'a self,
+        _model: &'a str,
+        prompt: &'a str,
+        _raw: bool,
+        _options: &'a GenerationOptions,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        use futures_util::StreamExt;
+        let chunks = self.chunks.clone();
+        let (fail, hang) = (self.fail, self.hang);
+        async move {
+            if hang {
+                futures_util::future::pending::<()>().await;
+            }
+            if fail {
+                return Err(anyhow!("connection refused"));
+            }
+            Ok(futures_util::stream::iter(chunks.into_iter().map(Ok)).boxed())
+        }
+        .boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        async { Ok(vec!["mock".to_string()]) }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Backend;
+    use crate::config::{BackendConfig, BackendKind};
+
+    fn openai(api_key_env: Option<&str>) -> BackendConfig {
+        BackendConfig {
+            kind: BackendKind::OpenAi,
+            url: "http://localhost:8080/v1".to_string(),
+            api_key_env: api_key_env.map(str::to_string),
+            ..BackendConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_skips_backends_that_cant_be_set_up() {
+        let unset = Some("NARS_TEST_UNSET_API_KEY");
+        let backends = Backend::all_from_config(&[openai(unset), openai(None)]).unwrap();
+        assert_eq!(backends.len(), 1);
+        assert!(Backend::all_from_config(&[openai(unset)]).is_err());
+        assert!(Backend::all_from_config(&[]).unwrap().is_empty());
+    }
+}
@@ -1,41 +1,312 @@
-use crate::logger::log_to_file;
-use crate::models::ollama::OllamaClient;
-use anyhow::Result;
+use crate::config::GenerationOptions;
+use crate::logger::{log, log_to_file, Level};
+use crate::models::ollama::ModelInfo;
+use anyhow::{anyhow, Result};
+use backend::{Backend, ModelBackend};
+use futures_util::future::join_all;
+use futures_util::stream::{self, BoxStream};
 use futures_util::StreamExt;
 use parser::{parse_code_output, ParsedCode};
-use std::sync::Arc;
-use tokio::sync::mpsc;
-use tokio::task;
+use prompt::ModelPrompt;
+use scope::PredictionRequest;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{self, AbortHandle};
+use tokio::time::timeout;
 
+pub mod anthropic;
+pub mod backend;
 pub mod ollama;
+pub mod openai;
 pub mod parser;
+pub mod prompt;
+pub mod scope;
+
+pub const DEFAULT_MODEL: &str = "qwen2.5-coder:7b";
+
+/// Why a request was refused without being sent: the buffer it comes from is
+/// encrypted or matches `model.exclude`
+#[derive(Debug)]
+pub struct Excluded;
+
+impl fmt::Display for Excluded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "this file is never sent to the model")
+    }
+}
+
+impl std::error::Error for Excluded {}
+
+/// A change to a prediction streamed to the editor, tagged with the request
+/// it answers
+#[derive(Debug)]
+pub struct PredictionUpdate {
+    pub id: u64,
+    pub delta: PredictionDelta,
+}
+
+/// How a prediction changed since the last update, so the editor only
+/// redraws when there is something new and mostly only extends what it shows
+#[derive(Debug, Clone, PartialEq)]
+pub enum PredictionDelta {
+    /// Text added at the end, the usual case while a prediction streams
+    Append(String),
+    /// The whole prediction, the first update or when it changed before the end
+    Replace(String),
+}
+
+impl PredictionDelta {
+    /// What turns the prediction `sent` so far into `code`, None when they
+    /// are the same
+    pub fn between(sent: &str, code: &str) -> Option<Self> {
+        if code == sent {
+            return None;
+        }
+        match code.strip_prefix(sent) {
+            Some(added) if !sent.is_empty() => Some(PredictionDelta::Append(added.to_string())),
+            _ => Some(PredictionDelta::Replace(code.to_string())),
+        }
+    }
+}
+
+/// A prediction being generated in the background
+pub struct PredictionTask {
+    pub id: u64,
+    pub abort: AbortHandle,
+}
+
+impl PredictionTask {
+    /// Stops the request, closing the stream so the server stops generating
+    pub fn cancel(&self) {
+        self.abort.abort();
+    }
+}
+
+// counts a request as in flight until it is dropped, which also covers
+// requests cancelled halfway through
+struct Busy<'a>(&'a AtomicUsize);
+
+impl<'a> Busy<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
 
 pub struct Predictor {
-    client: Arc<OllamaClient>,
-    prediction_tx: mpsc::Sender<String>,
-    model: String,
+    // headless predictors have no editor to stream partial predictions to
+    prediction_tx: Option<mpsc::Sender<PredictionUpdate>>,
+    // can be switched while the editor runs
+    model: Mutex<String>,
+    // sampling options for every request, also changeable while running
+    options: Mutex<GenerationOptions>,
+    // tried in order for every request
+    backends: Vec<Backend>,
+    // set when a request had to fall back, until the editor picks it up
+    fallback: Mutex<Option<String>>,
+    // why the last prediction failed, until the editor picks it up
+    failure: Mutex<Option<String>>,
+    // send every chunk to the editor, or only the finished prediction
+    stream_partial: bool,
+    // predictions being generated right now
+    in_flight: AtomicUsize,
+    // chunks the latest prediction has streamed in so far
+    streamed: AtomicUsize,
+    // id for the next background prediction
+    next_id: AtomicU64,
+    // set while the editor is on a buffer that must not be sent, every
+    // request is refused until it is cleared
+    excluded: AtomicBool,
 }
 
 impl Predictor {
-    pub fn new(client: Arc<OllamaClient>, prediction_tx: mpsc::Sender<String>, model: String) -> Self {
+    pub fn new(
+        backend: Backend,
+        prediction_tx: mpsc::Sender<PredictionUpdate>,
+        model: String,
+    ) -> Self {
+        Predictor {
+            backends: vec![backend],
+            prediction_tx: Some(prediction_tx),
+            model: Mutex::new(model),
+            options: Mutex::new(GenerationOptions::default()),
+            fallback: Mutex::new(None),
+            failure: Mutex::new(None),
+            stream_partial: true,
+            in_flight: AtomicUsize::new(0),
+            streamed: AtomicUsize::new(0),
+            next_id: AtomicU64::new(1),
+            excluded: AtomicBool::new(false),
+        }
+    }
+
+    pub fn headless(backend: Backend, model: String) -> Self {
         Predictor {
-            client,
-            prediction_tx,
-            model,
+            backends: vec![backend],
+            prediction_tx: None,
+            model: Mutex::new(model),
+            options: Mutex::new(GenerationOptions::default()),
+            fallback: Mutex::new(None),
+            failure: Mutex::new(None),
+            stream_partial: true,
+            in_flight: AtomicUsize::new(0),
+            streamed: AtomicUsize::new(0),
+            next_id: AtomicU64::new(1),
+            excluded: AtomicBool::new(false),
         }
     }
 
-    async fn stream_prediction(&self, line: String) -> Result<String> {
-        let prompt = format!("Complete the code on this line, returning only the raw code without any formatting, comments, or extra text. Example input: 'let x = '  Example output: 'let x = Some(42);'. Here is the code {}", line);
-        log_to_file(&prompt);
-        let mut stream = self
-            .client
-            .stream_generate(self.model.as_str(), prompt.as_str())
-            .await?;
+    /// Replaces the default backend with the configured ones, if there are any
+    pub fn with_backends(mut self, backends: Vec<Backend>) -> Self {
+        if !backends.is_empty() {
+            self.backends = backends;
+        }
+        self
+    }
+
+    pub fn with_options(self, options: GenerationOptions) -> Self {
+        self.set_options(options);
+        self
+    }
+
+    /// Holds predictions back until they are complete instead of showing them
+    /// growing as they stream in
+    pub fn with_partial_predictions(mut self, stream_partial: bool) -> Self {
+        self.stream_partial = stream_partial;
+        self
+    }
+
+    /// The backend the last request fell back to, if it wasn't the first one
+    pub fn take_fallback(&self) -> Option<String> {
+        self.fallback.lock().unwrap().take()
+    }
+
+    /// Refuses every request with `Excluded` while `excluded` is set
+    pub fn set_excluded(&self, excluded: bool) {
+        self.excluded.store(excluded, Ordering::Relaxed);
+    }
+
+    /// Why the last prediction failed, if it did
+    pub fn take_failure(&self) -> Option<String> {
+        self.failure.lock().unwrap().take()
+    }
+
+    /// Generates the prediction for `request`, streaming it to the editor
+    /// tagged with `id`
+    pub async fn predict(&self, request: &PredictionRequest, id: u64) -> Result<String> {
+        let _busy = Busy::new(&self.in_flight);
+        self.streamed.store(0, Ordering::Relaxed);
+        self.generate(request.model_prompt(), Some(request), Some(id))
+            .await
+    }
+
+    /// Up to `count` different predictions for the same request, sampled at
+    /// the same time and returned once they are all done instead of streamed
+    pub async fn candidates(
+        &self,
+        request: &PredictionRequest,
+        count: usize,
+    ) -> Result<Vec<String>> {
+        let busy = Busy::new(&self.in_flight);
+        let prompt = request.model_prompt();
+        let results = join_all(
+            (0..count.max(1)).map(|_| self.generate(prompt.clone(), Some(request), None)),
+        )
+        .await;
+        drop(busy);
+        let mut candidates: Vec<String> = Vec::new();
+        let mut last_error = None;
+        for result in results {
+            match result {
+                Ok(code) if !code.trim().is_empty() && !candidates.contains(&code) => {
+                    candidates.push(code)
+                }
+                Ok(_) => {}
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if candidates.is_empty() => Err(e),
+            _ => Ok(candidates),
+        }
+    }
+
+    /// True while a prediction is being generated
+    pub fn is_predicting(&self) -> bool {
+        self.in_flight.load(Ordering::Relaxed) > 0
+    }
+
+    /// Tokens the latest prediction has received, ollama streams one per chunk
+    pub fn streamed_tokens(&self) -> usize {
+        self.streamed.load(Ordering::Relaxed)
+    }
+
+    pub async fn edit(&self, content: &str, instruction: &str) -> Result<String> {
+        let prompt = format!("Apply the following instruction to the code and return the complete updated code in a single code block without any explanation. Instruction: {}\n\nHere is the code:\n{}", instruction, content);
+        self.generate(ModelPrompt::instruction(prompt), None, None)
+            .await
+    }
+
+    pub async fn summarize(&self, code: &str) -> Result<String> {
+        let prompt = format!("Summarise what this function does in one short sentence, returning only the sentence without any formatting or code. Here is the function:\n{}", code);
+        self.generate(ModelPrompt::instruction(prompt), None, None)
+            .await
+    }
+
+    /// The text of a doc comment for `code`, without the comment markers
+    pub async fn document(&self, code: &str, what: &str, language: &str) -> Result<String> {
+        let prompt = format!("Write the doc comment for {} in this {} code, saying what it does and anything a caller needs to know in as few sentences as it takes. Return only the text of the comment without comment markers, code or formatting. Here is the code:\n{}", what, language, code);
+        self.generate(ModelPrompt::instruction(prompt), None, None)
+            .await
+    }
+
+    /// Unit tests for `code`, the code block of the answer taken out of it
+    pub async fn write_tests(
+        &self,
+        code: &str,
+        what: &str,
+        language: &str,
+        marker: &str,
+    ) -> Result<String> {
+        let prompt = format!("Write unit tests for {} in this {} code, covering the normal cases and the edge cases. Follow the usual conventions of the language, starting the tests with `{}`, and return only the tests in a single code block without any explanation. Here is the code:\n{}", what, language, marker.trim(), code);
+        self.generate(ModelPrompt::instruction(prompt), None, None)
+            .await
+    }
+
+    // only predictions are streamed to the editor, whole file rewrites would
+    // show up as ghost text, and only when `send` has the id to tag them
+    // with. Predictions stop as soon as their scope is done.
+    async fn generate(
+        &self,
+        prompt: ModelPrompt,
+        prediction: Option<&PredictionRequest>,
+        send: Option<u64>,
+    ) -> Result<String> {
+        log_to_file(&prompt.instruction);
+        let (mut stream, filling) = self.connect(&prompt).await?;
         let mut pred = "".to_string();
+        // what the editor was sent so far, updates only carry what changed
+        let mut sent = String::new();
         let mut output = ParsedCode {
             code: "".to_string(),
         };
+        // filled in code is raw and goes at the cursor, there are no fences
+        // to strip or lines to anchor to
+        let finish = |request: &PredictionRequest, pred: &str, output: &ParsedCode| {
+            if filling {
+                request.finish_fill(pred)
+            } else {
+                request.finish(&output.code)
+            }
+        };
 
         while let Some(chunk) = stream.next().await {
             match chunk {
@@ -43,29 +314,336 @@ impl Predictor {
                     pred = format!("{}{}", pred, text);
                     log_to_file(format!("Next chunk {}", pred).as_str());
                     // refactor as this is not needed or return this?
-                    output = parse_code_output(&pred)?;
-                    match self.prediction_tx.send(pred.to_string()).await {
-                        Ok(_) => {
-                            log_to_file(format!("Send pred to channel {}", pred).as_str());
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to send prediction: {}", e);
-                        }
+                    if !filling {
+                        output = parse_code_output(&pred)?;
+                    }
+                    let Some(request) = prediction else {
+                        continue;
+                    };
+                    if send.is_some() {
+                        self.streamed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    let (code, done) = finish(request, &pred, &output);
+                    if let Some(id) = send.filter(|_| self.stream_partial || done) {
+                        self.send_prediction(id, &mut sent, &code).await;
+                    }
+                    if done {
+                        log_to_file(&pred);
+                        return Ok(code);
                     }
                 }
-                Err(e) => eprintln!("Error: {}", e),
+                // the stream is broken, what arrived so far was already sent
+                Err(e) => return Err(e),
             }
         }
         log_to_file(&pred);
-        Ok(output.code)
+        Ok(match prediction {
+            Some(request) => {
+                let code = finish(request, &pred, &output).0;
+                if let Some(id) = send.filter(|_| !self.stream_partial) {
+                    self.send_prediction(id, &mut sent, &code).await;
+                }
+                code
+            }
+            None => output.code,
+        })
+    }
+
+    async fn send_prediction(&self, id: u64, sent: &mut String, code: &str) {
+        let Some(delta) = PredictionDelta::between(sent, code) else {
+            return;
+        };
+        *sent = code.to_string();
+        if let Some(prediction_tx) = &self.prediction_tx {
+            let update = PredictionUpdate { id, delta };
+            match prediction_tx.send(update).await {
+                Ok(_) => {
+                    log_to_file(format!("Send pred to channel {}", code).as_str());
+                }
+                Err(e) => {
+                    log(Level::Warn, &format!("Failed to send prediction: {}", e));
+                }
+            }
+        }
     }
 
-    pub fn stream_prediction_background(self: Arc<Self>, content: String) {
+    // tries each backend in turn until one starts answering, the last one is
+    // given as long as it needs. Also returns whether the backend was asked
+    // to fill in the middle rather than follow the instruction.
+    async fn connect(
+        &self,
+        prompt: &ModelPrompt,
+    ) -> Result<(BoxStream<'static, Result<String>>, bool)> {
+        // every request goes through here, so this is the one place that
+        // keeps excluded buffers from being sent
+        if self.excluded.load(Ordering::Relaxed) {
+            return Err(Excluded.into());
+        }
+        let mut last_error = anyhow!("no backends configured");
+        let default_model = self.model();
+        let options = self.options();
+        for (idx, backend) in self.backends.iter().enumerate() {
+            let model = backend.model.as_deref().unwrap_or(&default_model);
+            let fim = backend
+                .takes_raw_prompts()
+                .then(|| prompt.fim_for(model))
+                .flatten();
+            let attempt = async {
+                let mut stream = match &fim {
+                    Some(raw) => backend.stream_generate(model, raw, true, &options).await?,
+                    None => {
+                        backend
+                            .stream_generate(model, &prompt.instruction, false, &options)
+                            .await?
+                    }
+                };
+                let first = stream.next().await.transpose()?;
+                Ok::<_, anyhow::Error>(stream::iter(first.map(Ok)).chain(stream).boxed())
+            };
+            let result = if idx + 1 == self.backends.len() {
+                attempt.await
+            } else {
+                timeout(backend.timeout, attempt)
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("timed out after {:?}", backend.timeout)))
+            };
+            match result {
+                Ok(stream) => {
+                    if idx > 0 {
+                        *self.fallback.lock().unwrap() =
+                            Some(format!("Fell back to {}", backend.name));
+                    }
+                    return Ok((stream, fim.is_some()));
+                }
+                Err(e) => {
+                    log(Level::Warn, &format!("Backend {} failed: {}", backend.name, e));
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Starts generating the prediction for `request`, the editor keeps the
+    /// task to match up what streams in and to cancel it
+    pub fn stream_prediction_background(self: Arc<Self>, request: PredictionRequest) -> PredictionTask {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
         let prediction_handler = self.clone();
+        let handle = task::spawn(async move {
+            if let Err(e) = prediction_handler.predict(&request, id).await {
+                log(Level::Error, format!("Prediction error: {}", e).as_str());
+                // predictions start on their own while typing, refusing them
+                // isn't worth a toast each time
+                if e.is::<Excluded>() {
+                    return;
+                }
+                *prediction_handler.failure.lock().unwrap() =
+                    Some(format!("Prediction failed: {}", e));
+            }
+        });
+        PredictionTask {
+            id,
+            abort: handle.abort_handle(),
+        }
+    }
+
+    pub fn candidates_background(
+        self: Arc<Self>,
+        request: PredictionRequest,
+        count: usize,
+        result_tx: oneshot::Sender<Result<Vec<String>>>,
+    ) {
+        task::spawn(async move {
+            let result = self.candidates(&request, count).await;
+            if let Err(e) = &result {
+                log(Level::Error, format!("Completion error: {}", e).as_str());
+            }
+            let _ = result_tx.send(result);
+        });
+    }
+
+    /// Streams the answer to a chat prompt back chunk by chunk, ending with
+    /// the error if the request fails
+    pub fn chat_background(
+        self: Arc<Self>,
+        prompt: String,
+        chunk_tx: mpsc::Sender<Result<String>>,
+    ) {
+        task::spawn(async move {
+            log_to_file(&prompt);
+            let mut stream = match self.connect(&ModelPrompt::instruction(prompt)).await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    let _ = chunk_tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            while let Some(chunk) = stream.next().await {
+                let failed = chunk.is_err();
+                // the editor dropped the answer or the stream broke
+                if chunk_tx.send(chunk).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+    }
+
+    pub fn summarize_background(
+        self: Arc<Self>,
+        key: u64,
+        code: String,
+        result_tx: mpsc::Sender<(u64, Result<String>)>,
+    ) {
         task::spawn(async move {
-            if let Err(e) = prediction_handler.stream_prediction(content).await {
-                log_to_file(format!("Prediction error: {}", e).as_str());
+            let result = self.summarize(&code).await;
+            if let Err(e) = &result {
+                log(Level::Error, format!("Summary error: {}", e).as_str());
             }
+            let _ = result_tx.send((key, result)).await;
         });
     }
+
+    pub fn model_info_background(self: Arc<Self>, result_tx: oneshot::Sender<Result<ModelInfo>>) {
+        task::spawn(async move {
+            let backend = &self.backends[0];
+            let model = backend.model.clone().unwrap_or_else(|| self.model());
+            let _ = result_tx.send(backend.model_info(&model).await);
+        });
+    }
+
+    /// Lists the models on the first backend, which is what `/api/tags`
+    /// answers for ollama
+    pub fn models_background(self: Arc<Self>, result_tx: oneshot::Sender<Result<Vec<String>>>) {
+        task::spawn(async move {
+            let _ = result_tx.send(self.backends[0].list_models().await);
+        });
+    }
+
+    pub fn model(&self) -> String {
+        self.model.lock().unwrap().clone()
+    }
+
+    pub fn options(&self) -> GenerationOptions {
+        self.options.lock().unwrap().clone()
+    }
+
+    /// Samples every request from now on with `options`
+    pub fn set_options(&self, options: GenerationOptions) {
+        log(Level::Info, &format!("generation options: {:?}", options));
+        *self.options.lock().unwrap() = options;
+    }
+
+    /// Uses `model` for every request from now on
+    pub fn set_model(&self, model: String) {
+        log(Level::Info, &format!("switched model to {}", model));
+        *self.model.lock().unwrap() = model;
+    }
+
+    pub fn document_background(
+        self: Arc<Self>,
+        code: String,
+        what: String,
+        language: &'static str,
+        result_tx: oneshot::Sender<Result<String>>,
+    ) {
+        task::spawn(async move {
+            let result = self.document(&code, &what, language).await;
+            if let Err(e) = &result {
+                log(Level::Error, format!("Doc comment error: {}", e).as_str());
+            }
+            let _ = result_tx.send(result);
+        });
+    }
+
+    pub fn tests_background(
+        self: Arc<Self>,
+        code: String,
+        what: String,
+        language: &'static str,
+        marker: &'static str,
+        result_tx: oneshot::Sender<Result<String>>,
+    ) {
+        task::spawn(async move {
+            let result = self.write_tests(&code, &what, language, marker).await;
+            if let Err(e) = &result {
+                log(Level::Error, format!("Test generation error: {}", e).as_str());
+            }
+            let _ = result_tx.send(result);
+        });
+    }
+
+    pub fn edit_background(
+        self: Arc<Self>,
+        content: String,
+        instruction: String,
+        result_tx: oneshot::Sender<Result<String>>,
+    ) {
+        task::spawn(async move {
+            let result = self.edit(&content, &instruction).await;
+            if let Err(e) = &result {
+                log(Level::Error, format!("Edit error: {}", e).as_str());
+            }
+            let _ = result_tx.send(result);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backend::{Backend, MockBackend};
+    use super::scope::PredictionRequest;
+    use super::{Excluded, PredictionDelta, Predictor};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_falls_back_to_the_next_backend() {
+        let answering = MockBackend::answering(&["let x", " = 1;"]);
+        let prompts = answering.prompts.clone();
+        let predictor = Predictor::headless(Backend::new("down", MockBackend::failing()), "m".into())
+            .with_backends(vec![
+                Backend::new("down", MockBackend::failing()),
+                Backend::new("mock", answering),
+            ]);
+        let request = PredictionRequest::line("let x".to_string());
+        let prediction = predictor.predict(&request, 0).await.unwrap();
+        assert_eq!(prediction, "let x = 1;");
+        assert_eq!(predictor.take_fallback().as_deref(), Some("Fell back to mock"));
+        assert_eq!(prompts.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_when_a_backend_doesnt_answer_in_time() {
+        let mut slow = Backend::new("slow", MockBackend::hanging());
+        slow.timeout = Duration::from_millis(10);
+        let answering = Backend::new("mock", MockBackend::answering(&["let x = 1;"]));
+        let predictor = Predictor::headless(Backend::new("mock", MockBackend::default()), "m".into())
+            .with_backends(vec![slow, answering]);
+        let request = PredictionRequest::line("let x".to_string());
+        assert_eq!(predictor.predict(&request, 0).await.unwrap(), "let x = 1;");
+        assert!(predictor.take_fallback().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_requests_while_excluded() {
+        let backend = MockBackend::answering(&["let x = 1;"]);
+        let prompts = backend.prompts.clone();
+        let predictor = Predictor::headless(Backend::new("mock", backend), "m".into());
+        predictor.set_excluded(true);
+        let request = PredictionRequest::line("let x".to_string());
+        let error = predictor.predict(&request, 0).await.unwrap_err();
+        assert!(error.is::<Excluded>());
+        assert!(predictor.summarize("fn f() {}").await.is_err());
+        assert!(prompts.lock().unwrap().is_empty());
+        predictor.set_excluded(false);
+        assert_eq!(predictor.predict(&request, 0).await.unwrap(), "let x = 1;");
+    }
+
+    #[test]
+    fn test_updates_only_carry_what_changed() {
+        let delta = |sent, code| PredictionDelta::between(sent, code);
+        assert_eq!(delta("", "let"), Some(PredictionDelta::Replace("let".into())));
+        assert_eq!(delta("let", "let x"), Some(PredictionDelta::Append(" x".into())));
+        assert_eq!(delta("let x", "let x"), None);
+        assert_eq!(delta("let x", "let y"), Some(PredictionDelta::Replace("let y".into())));
+    }
 }
@@ -1,16 +1,25 @@
+use crate::config::GenerationOptions;
+use crate::models::backend::ModelBackend;
 use anyhow::Result;
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::FutureExt;
 use futures_util::StreamExt;
-use futures_util::{Stream, TryStreamExt};
-use reqwest::Client;
+use futures_util::TryStreamExt;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-const OLLAMA_BASE_URL: &str = "http://localhost:11434/api";
+const OLLAMA_PORT: u16 = 11434;
 
 #[derive(Serialize)]
 struct GenerateRequest {
     model: String,
     prompt: String,
     stream: bool,
+    // skips the model's prompt template, for fill-in-the-middle prompts
+    raw: bool,
+    options: GenerationOptions,
 }
 
 #[derive(Deserialize)]
@@ -18,34 +27,180 @@ struct GenerateResponse {
     response: String,
 }
 
+#[derive(Deserialize)]
+struct TagsResponse {
+    models: Vec<Tag>,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct ShowRequest {
+    model: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ShowResponse {
+    parameters: String,
+    template: String,
+    details: ModelDetails,
+    model_info: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ModelDetails {
+    parameter_size: String,
+    quantization_level: String,
+}
+
+impl ShowResponse {
+    fn into_info(self) -> ModelInfo {
+        // model_info keys are prefixed with the architecture, e.g. qwen2.context_length
+        let context_length = self
+            .model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64());
+        let num_ctx = self.parameters.lines().find_map(|line| {
+            let (name, value) = line.split_once(char::is_whitespace)?;
+            (name == "num_ctx").then(|| value.trim().parse().ok())?
+        });
+        ModelInfo {
+            parameter_size: self.details.parameter_size,
+            quantization_level: self.details.quantization_level,
+            context_length,
+            num_ctx,
+            template: self.template,
+        }
+    }
+}
+
+/// What `/api/show` reports about a model
+#[derive(Debug, Default)]
+pub struct ModelInfo {
+    pub parameter_size: String,
+    pub quantization_level: String,
+    /// The longest context the model was trained for
+    pub context_length: Option<u64>,
+    /// `num_ctx` from the modelfile, the window ollama actually runs it with
+    pub num_ctx: Option<u64>,
+    pub template: String,
+}
+
+/// The server URL for a host given the way `OLLAMA_HOST` takes it, where the
+/// scheme and port can be left out: `gpu-box` is `http://gpu-box:11434`. As
+/// with ollama, a URL with a scheme but no port uses the scheme's port.
+pub fn host_url(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.contains("://") {
+        return host.to_string();
+    }
+    // the port is whatever follows the last colon, unless that is inside an ipv6 address
+    let has_port = host
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| !port.contains(']') && port.parse::<u16>().is_ok());
+    if has_port {
+        format!("http://{}", host)
+    } else {
+        format!("http://{}:{}", host, OLLAMA_PORT)
+    }
+}
+
 #[derive(Clone)]
 pub struct OllamaClient {
     client: Client,
+    base_url: String,
+    // sent as a bearer token, for servers behind an authenticating proxy
+    api_key: Option<String>,
 }
 
 impl OllamaClient {
-    pub fn new() -> Self {
+    /// Client for an ollama server other than the local one, e.g. `http://gpu-box:11434`
+    pub fn with_url(url: &str) -> Self {
         Self {
             client: Client::new(),
+            base_url: format!("{}/api", url.trim_end_matches('/')),
+            api_key: None,
+        }
+    }
+
+    /// The server this client talks to, e.g. `http://localhost:11434`
+    pub fn url(&self) -> &str {
+        self.base_url.trim_end_matches("/api")
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.authorized(self.client.get(format!("{}/{}", self.base_url, path)))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.authorized(self.client.post(format!("{}/{}", self.base_url, path)))
+    }
+
+    fn authorized(&self, builder: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
         }
     }
+
+    /// Names of the models pulled on the server, e.g. `qwen2.5-coder:7b`
+    pub async fn models(&self) -> Result<Vec<String>> {
+        let response: TagsResponse = self
+            .get("tags")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.models.into_iter().map(|tag| tag.name).collect())
+    }
+
+    pub async fn show(&self, model: &str) -> Result<ModelInfo> {
+        let response: ShowResponse = self
+            .post("show")
+            .json(&ShowRequest {
+                model: model.to_string(),
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.into_info())
+    }
+
     pub async fn stream_generate(
         &self,
         model: &str,
         prompt: &str,
-    ) -> Result<impl Stream<Item = Result<String>>> {
+        raw: bool,
+        options: &GenerationOptions,
+    ) -> Result<BoxStream<'static, Result<String>>> {
         let request = GenerateRequest {
             model: model.to_string(),
             prompt: prompt.to_string(),
             stream: true,
+            raw,
+            options: options.clone(),
         };
 
         let response = self
-            .client
-            .post(format!("{}/generate", OLLAMA_BASE_URL))
+            .post("generate")
             .json(&request)
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(response
             .bytes_stream()
@@ -54,6 +209,104 @@ impl OllamaClient {
                 let bytes = chunk?;
                 let response: GenerateResponse = serde_json::from_slice(&bytes)?;
                 Ok(response.response)
-            }))
+            })
+            .boxed())
+    }
+}
+
+impl ModelBackend for OllamaClient {
+    fn stream_generate<'a>(
+        &'a self,
+        model: &'a str,
+        prompt: &'a str,
+        raw: bool,
+        options: &'a GenerationOptions,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        self.stream_generate(model, prompt, raw, options).boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        self.models().boxed()
+    }
+
+    // the server root answers "Ollama is running" without touching any model
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        async {
+            let url = self.url().to_string();
+            self.authorized(self.client.get(url))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn takes_raw_prompts(&self) -> bool {
+        true
+    }
+
+    fn model_info<'a>(&'a self, model: &'a str) -> BoxFuture<'a, Result<ModelInfo>> {
+        self.show(model).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{host_url, GenerateRequest, ShowResponse};
+    use crate::config::GenerationOptions;
+
+    #[test]
+    fn test_host_url_fills_in_scheme_and_port() {
+        assert_eq!(host_url("gpu-box"), "http://gpu-box:11434");
+        assert_eq!(host_url("gpu-box:8080"), "http://gpu-box:8080");
+        assert_eq!(
+            host_url("https://ollama.example.com/"),
+            "https://ollama.example.com"
+        );
+        assert_eq!(host_url("[::1]"), "http://[::1]:11434");
+        assert_eq!(
+            host_url("https://example.com/ollama"),
+            "https://example.com/ollama"
+        );
+    }
+
+    #[test]
+    fn test_only_set_options_are_sent() {
+        let request = GenerateRequest {
+            model: "m".to_string(),
+            prompt: "p".to_string(),
+            stream: true,
+            raw: false,
+            options: GenerationOptions {
+                seed: Some(7),
+                ..GenerationOptions::default()
+            },
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["options"], serde_json::json!({ "seed": 7 }));
+    }
+
+    #[test]
+    fn test_show_reads_the_window_sizes() {
+        let info = |json| {
+            serde_json::from_value::<ShowResponse>(json)
+                .unwrap()
+                .into_info()
+        };
+        let full = info(serde_json::json!({
+            "parameters": "stop \"<|im_end|>\"\nnum_ctx                        8192\ntemperature 0.2",
+            "details": { "parameter_size": "7.6B", "quantization_level": "Q4_K_M" },
+            "model_info": { "qwen2.context_length": 32768, "qwen2.block_count": 28 }
+        }));
+        assert_eq!(full.num_ctx, Some(8192));
+        assert_eq!(full.context_length, Some(32768));
+        assert_eq!(full.parameter_size, "7.6B");
+        // missing entirely, or not a number
+        let bare = info(serde_json::json!({ "parameters": "temperature 0.2" }));
+        assert_eq!(bare.num_ctx, None);
+        assert_eq!(bare.context_length, None);
+        let malformed = info(serde_json::json!({ "parameters": "num_ctx lots\nnum_ctx" }));
+        assert_eq!(malformed.num_ctx, None);
     }
 }
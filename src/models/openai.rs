@@ -0,0 +1,189 @@
+use crate::config::GenerationOptions;
+use crate::models::backend::ModelBackend;
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::{FutureExt, StreamExt, TryStreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: &'static str,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    delta: Delta,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Delta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// Client for servers speaking the OpenAI chat completions API (llama.cpp,
+/// vLLM, LM Studio, OpenAI itself)
+#[derive(Clone)]
+pub struct OpenAiClient {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiClient {
+    /// `url` is the API root, e.g. `https://api.openai.com/v1`
+    pub fn new(url: &str, api_key: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    /// Ids of the models the server offers
+    pub async fn models(&self) -> Result<Vec<String>> {
+        let mut builder = self.client.get(format!("{}/models", self.base_url));
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        let list: ModelList = builder.send().await?.error_for_status()?.json().await?;
+        Ok(list.data.into_iter().map(|model| model.id).collect())
+    }
+
+    pub async fn stream_generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<BoxStream<'static, Result<String>>> {
+        // there is no repeat penalty, frequency_penalty works differently
+        let request = ChatRequest {
+            model: model.to_string(),
+            messages: vec![Message {
+                role: "user",
+                content: prompt.to_string(),
+            }],
+            stream: true,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            max_tokens: options.num_predict,
+            seed: options.seed,
+        };
+        let mut builder = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&request);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        let response = builder.send().await?.error_for_status()?;
+
+        let mut buffer = Vec::new();
+        Ok(response
+            .bytes_stream()
+            .map_err(|e| anyhow::anyhow!("Stream error: {}", e))
+            .map(move |chunk| -> Result<String> {
+                buffer.extend_from_slice(&chunk?);
+                take_events(&mut buffer)
+            })
+            .boxed())
+    }
+}
+
+// the text in the whole event lines at the start of `buffer`, which are taken
+// out of it. Server sent events can be split across chunks anywhere, so a
+// partial line is left for the next chunk to finish.
+fn take_events(buffer: &mut Vec<u8>) -> Result<String> {
+    let mut text = String::new();
+    while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            continue;
+        };
+        if data.trim() == "[DONE]" {
+            continue;
+        }
+        let chunk: ChatChunk = serde_json::from_str(data.trim())?;
+        text.extend(chunk.choices.into_iter().filter_map(|c| c.delta.content));
+    }
+    Ok(text)
+}
+
+impl ModelBackend for OpenAiClient {
+    fn stream_generate<'a>(
+        &'a self,
+        model: &'a str,
+        prompt: &'a str,
+        _raw: bool,
+        options: &'a GenerationOptions,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        self.stream_generate(model, prompt, options).boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        self.models().boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take_events;
+
+    #[test]
+    fn test_takes_the_text_of_whole_events() {
+        let mut buffer = b": keep-alive\ndata: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"let x\"}}]}\n".to_vec();
+        assert_eq!(take_events(&mut buffer).unwrap(), "let x");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_keeps_a_partial_event_for_the_next_chunk() {
+        let mut buffer =
+            b"data: {\"choices\":[{\"delta\":{\"content\":\" = \"}}]}\ndata: {\"choi".to_vec();
+        assert_eq!(take_events(&mut buffer).unwrap(), " = ");
+        assert_eq!(buffer, b"data: {\"choi");
+        buffer.extend_from_slice(b"ces\":[{\"delta\":{\"content\":\"1;\"}}]}\r\ndata: [DONE]\n");
+        assert_eq!(take_events(&mut buffer).unwrap(), "1;");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_fails_on_a_malformed_event() {
+        let mut buffer = b"data: {not json}\n".to_vec();
+        assert!(take_events(&mut buffer).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+/// Special tokens a code model was trained to fill in the middle with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FimTokens {
+    prefix: &'static str,
+    suffix: &'static str,
+    middle: &'static str,
+}
+
+const QWEN: FimTokens = FimTokens {
+    prefix: "<|fim_prefix|>",
+    suffix: "<|fim_suffix|>",
+    middle: "<|fim_middle|>",
+};
+
+const STARCODER: FimTokens = FimTokens {
+    prefix: "<fim_prefix>",
+    suffix: "<fim_suffix>",
+    middle: "<fim_middle>",
+};
+
+const CODELLAMA: FimTokens = FimTokens {
+    prefix: "<PRE> ",
+    suffix: " <SUF>",
+    middle: " <MID>",
+};
+
+const DEEPSEEK: FimTokens = FimTokens {
+    prefix: "<｜fim▁begin｜>",
+    suffix: "<｜fim▁hole｜>",
+    middle: "<｜fim▁end｜>",
+};
+
+impl FimTokens {
+    /// Tokens for `model` going by its family, e.g. `qwen2.5-coder:7b`. None
+    /// for models that weren't trained to fill in the middle.
+    pub fn for_model(model: &str) -> Option<Self> {
+        let model = model.to_lowercase();
+        let families = [
+            ("qwen2.5-coder", QWEN),
+            ("codegemma", QWEN),
+            ("starcoder", STARCODER),
+            ("codellama", CODELLAMA),
+            ("deepseek-coder", DEEPSEEK),
+        ];
+        families
+            .into_iter()
+            .find(|(family, _)| model.contains(family))
+            .map(|(_, tokens)| tokens)
+    }
+
+    /// The raw prompt asking for what goes between `prefix` and `suffix`
+    pub fn fill(&self, prefix: &str, suffix: &str) -> String {
+        format!(
+            "{}{}{}{}{}",
+            self.prefix, prefix, self.suffix, suffix, self.middle
+        )
+    }
+}
+
+/// What a backend is asked to generate
+#[derive(Clone, Debug)]
+pub struct ModelPrompt {
+    /// Natural language prompt, for chat models and models without FIM tokens
+    pub instruction: String,
+    /// The code before and after the cursor, sent with FIM tokens instead of
+    /// the instruction to models that have them
+    pub fill: Option<(String, String)>,
+}
+
+impl ModelPrompt {
+    pub fn instruction(instruction: String) -> Self {
+        Self {
+            instruction,
+            fill: None,
+        }
+    }
+
+    /// The fill-in-the-middle prompt for `model`, if this prompt has code to
+    /// fill in and the model knows how
+    pub fn fim_for(&self, model: &str) -> Option<String> {
+        let (prefix, suffix) = self.fill.as_ref()?;
+        Some(FimTokens::for_model(model)?.fill(prefix, suffix))
+    }
+}
+
+/// A prompt written in the config for a language, replacing the built-in
+/// instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct PromptTemplate {
+    pub text: String,
+    pub language: String,
+    pub filename: String,
+}
+
+impl PromptTemplate {
+    /// The template with `{language}`, `{filename}`, `{goal}`, `{line}`,
+    /// `{prefix}`, `{suffix}` and `{related}` filled in. It is done in one
+    /// pass, so braces in the code itself are left alone.
+    pub fn render(
+        &self,
+        goal: &str,
+        line: &str,
+        prefix: &str,
+        suffix: &str,
+        related: &str,
+    ) -> String {
+        let mut out = String::with_capacity(self.text.len() + prefix.len() + suffix.len());
+        let mut rest = self.text.as_str();
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            rest = &rest[open..];
+            let value = rest.find('}').and_then(|close| {
+                let value = match &rest[1..close] {
+                    "language" => &self.language,
+                    "filename" => &self.filename,
+                    "goal" => goal,
+                    "line" => line,
+                    "prefix" => prefix,
+                    "suffix" => suffix,
+                    "related" => related,
+                    _ => return None,
+                };
+                Some((value, close))
+            });
+            match value {
+                Some((value, close)) => {
+                    out.push_str(value);
+                    rest = &rest[close + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fim_prompt_only_for_models_with_fim_tokens() {
+        let prompt = ModelPrompt {
+            instruction: "Complete the code".to_string(),
+            fill: Some(("let x = ".to_string(), ";\n".to_string())),
+        };
+        assert_eq!(
+            prompt.fim_for("qwen2.5-coder:7b").unwrap(),
+            "<|fim_prefix|>let x = <|fim_suffix|>;\n<|fim_middle|>"
+        );
+        assert_eq!(
+            prompt.fim_for("codellama:13b-code").unwrap(),
+            "<PRE> let x =  <SUF>;\n <MID>"
+        );
+        assert!(prompt.fim_for("llama3.1:8b").is_none());
+        assert!(ModelPrompt::instruction("hi".to_string())
+            .fim_for("qwen2.5-coder:7b")
+            .is_none());
+    }
+
+    #[test]
+    fn test_template_placeholders_are_filled_once() {
+        let template = PromptTemplate {
+            text: "Write {language} for {filename}, {goal}: {prefix}<here>{suffix} {unknown}"
+                .to_string(),
+            language: "zig".to_string(),
+            filename: "main.zig".to_string(),
+        };
+        assert_eq!(
+            template.render(
+                "finishing the line",
+                "const x",
+                "const x",
+                " = {suffix};",
+                ""
+            ),
+            "Write zig for main.zig, finishing the line: const x<here> = {suffix}; {unknown}"
+        );
+    }
+}
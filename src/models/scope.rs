@@ -0,0 +1,267 @@
+use crate::models::prompt::{ModelPrompt, PromptTemplate};
+
+/// How much code a prediction should write
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PredictionScope {
+    Line,
+    Block,
+    Function,
+    File,
+    /// As many lines as it takes to lead into the code after the cursor
+    Multiline,
+}
+
+impl PredictionScope {
+    /// What the scope is called in `prompts` tables in the config
+    pub fn key(&self) -> &'static str {
+        match self {
+            PredictionScope::Multiline => "multiline",
+            _ => self.name(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PredictionScope::Line => "line",
+            PredictionScope::Block => "block",
+            PredictionScope::Function => "function",
+            PredictionScope::File => "file",
+            PredictionScope::Multiline => "multi-line block",
+        }
+    }
+
+    fn goal(&self) -> &'static str {
+        match self {
+            PredictionScope::Line => "finishing only the last line",
+            PredictionScope::Block => "finishing only the block the last line is in",
+            PredictionScope::Function => "writing the rest of the function the last line is in",
+            PredictionScope::File => "writing the rest of the file",
+            PredictionScope::Multiline => "writing the lines that lead into the code after it",
+        }
+    }
+}
+
+/// Everything a prediction needs to be prompted for and cut off at the right place
+#[derive(Clone, Debug)]
+pub struct PredictionRequest {
+    pub scope: PredictionScope,
+    /// The line the cursor is on, predictions always start with it
+    pub line: String,
+    /// Byte offset of the cursor in `line`
+    pub column: usize,
+    /// Code up to the end of `line`, as much of the file as fits the budget
+    pub context: String,
+    /// Code after `line`, for multi-line predictions
+    pub suffix: String,
+    /// Blocks that have to close before a function prediction is done
+    pub depth: usize,
+    /// Code before and after the cursor, for models that fill in the middle
+    pub fill: Option<(String, String)>,
+    /// Prompt from the config to use instead of the built-in one
+    pub template: Option<PromptTemplate>,
+    /// Definitions from other files of the types the cursor line uses
+    pub related: String,
+}
+
+impl PredictionRequest {
+    /// The rest of `line` with no code around it
+    #[cfg(test)]
+    pub fn line(line: String) -> Self {
+        Self {
+            scope: PredictionScope::Line,
+            column: line.len(),
+            line,
+            context: String::new(),
+            suffix: String::new(),
+            depth: 0,
+            fill: None,
+            template: None,
+            related: String::new(),
+        }
+    }
+
+    /// The instruction for the scope, with the code around the cursor for
+    /// models that can fill in the middle instead
+    pub fn model_prompt(&self) -> ModelPrompt {
+        ModelPrompt {
+            instruction: self.prompt(),
+            fill: self.fill.clone(),
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        if let Some(template) = &self.template {
+            let prefix = if self.context.is_empty() {
+                &self.line
+            } else {
+                &self.context
+            };
+            return template.render(
+                self.scope.goal(),
+                &self.line,
+                prefix,
+                &self.suffix,
+                &self.related,
+            );
+        }
+        let prompt = match self.scope {
+            PredictionScope::Line if !self.context.is_empty() => format!("Complete the last line of the following code, returning only that line as raw code without any formatting, comments, or extra text. Example input ending in: 'let x = '  Example output: 'let x = Some(42);'. Here is the code:\n{}", self.context),
+            PredictionScope::Line => format!("Complete the code on this line, returning only the raw code without any formatting, comments, or extra text. Example input: 'let x = '  Example output: 'let x = Some(42);'. Here is the code {}", self.line),
+            PredictionScope::Multiline => format!("Continue the code before the cursor, {}. Return the last line before the cursor in full followed by the new code, as raw code without any formatting or explanation, and stop before repeating the code after the cursor. Here is the code before the cursor:\n{}\n\nHere is the code after the cursor:\n{}", self.scope.goal(), self.context, self.suffix),
+            _ => format!("Continue the following code, {}. Return the last line in full followed by the new code, as raw code without any formatting or explanation. Here is the code:\n{}", self.scope.goal(), self.context),
+        };
+        if self.related.is_empty() {
+            return prompt;
+        }
+        format!(
+            "These definitions from other files in the project are used in the code:\n{}{}",
+            self.related, prompt
+        )
+    }
+
+    /// Makes `code` start with the cursor line and cuts it where the scope is
+    /// finished, returning whether the model can stop generating
+    pub fn finish(&self, code: &str) -> (String, bool) {
+        self.cut(anchor_to_line(&self.line, code))
+    }
+
+    /// Like `finish` for what a model filled in at the cursor, which goes
+    /// between the two halves of the cursor line
+    pub fn finish_fill(&self, middle: &str) -> (String, bool) {
+        let (typed, after) = self.line.split_at(self.column.min(self.line.len()));
+        self.cut(format!("{}{}{}", typed, middle, after))
+    }
+
+    fn cut(&self, code: String) -> (String, bool) {
+        let rest_start = code.find('\n').map_or(code.len(), |p| p + 1);
+        let stop = match self.scope {
+            PredictionScope::Line => (rest_start < code.len()).then_some(rest_start - 1),
+            PredictionScope::Block => closing_line_end(&code, rest_start, 1),
+            PredictionScope::Function => closing_line_end(&code, rest_start, self.depth.max(1)),
+            PredictionScope::File => None,
+            PredictionScope::Multiline => repeated_suffix_start(&code, rest_start, &self.suffix),
+        };
+        match stop {
+            Some(end) => (code[..end].to_string(), true),
+            None => (code, false),
+        }
+    }
+}
+
+// models often drop the indentation of the line or only return what comes after it
+fn anchor_to_line(line: &str, code: &str) -> String {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    if code.starts_with(line) {
+        code.to_string()
+    } else if !trimmed.is_empty() && code.trim_start().starts_with(trimmed) {
+        format!("{}{}", indent, code.trim_start())
+    } else {
+        format!("{}{}", line, code)
+    }
+}
+
+// end of the line before the one where the model starts writing out the code
+// that already follows the cursor. Indentation has to match too, so a `}`
+// closing a block the model opened doesn't count as the one after the cursor.
+fn repeated_suffix_start(code: &str, from: usize, suffix: &str) -> Option<usize> {
+    let next = suffix
+        .lines()
+        .find(|line| !line.trim().is_empty())?
+        .trim_end();
+    let mut pos = from;
+    for line in code[from..].split_inclusive('\n') {
+        if line.trim_end() == next {
+            return Some(pos - 1);
+        }
+        pos += line.len();
+    }
+    None
+}
+
+// end of the line on which `depth` more blocks have closed than opened,
+// counting from `from`
+fn closing_line_end(code: &str, from: usize, depth: usize) -> Option<usize> {
+    let mut balance = 0;
+    for (offset, c) in code[from..].char_indices() {
+        match c {
+            '{' => balance += 1,
+            '}' => balance -= 1,
+            _ => continue,
+        }
+        if balance <= -(depth as i64) {
+            let pos = from + offset;
+            return Some(code[pos..].find('\n').map_or(code.len(), |p| pos + p));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(scope: PredictionScope, line: &str, depth: usize) -> PredictionRequest {
+        PredictionRequest {
+            scope,
+            line: line.to_string(),
+            column: line.len(),
+            context: String::new(),
+            suffix: String::new(),
+            depth,
+            fill: None,
+            template: None,
+            related: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_line_scope_stops_at_newline() {
+        let (code, done) = request(PredictionScope::Line, "    let x = ", 0)
+            .finish("let x = Some(42);\nlet y = 1;");
+        assert_eq!(code, "    let x = Some(42);");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_block_scope_stops_when_block_closes() {
+        let code = "    if x {\n        y();\n    }\n    z();\n}";
+        let (code, done) = request(PredictionScope::Block, "    if x {", 0).finish(code);
+        assert_eq!(code, "    if x {\n        y();\n    }");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_multiline_scope_stops_before_the_code_after_the_cursor() {
+        let mut request = request(PredictionScope::Multiline, "    let x = [", 0);
+        request.suffix = "\n    ];\n    x.len()\n".to_string();
+        let (code, done) =
+            request.finish("    let x = [\n        1,\n        2,\n    ];\n    x.len()");
+        assert_eq!(code, "    let x = [\n        1,\n        2,");
+        assert!(done);
+        let (code, done) = request.finish("    let x = [\n        1,");
+        assert_eq!(code, "    let x = [\n        1,");
+        assert!(!done);
+    }
+
+    #[test]
+    fn test_filled_in_code_goes_between_the_halves_of_the_line() {
+        let mut request = request(PredictionScope::Line, "    foo()", 0);
+        request.column = 8;
+        let (code, done) = request.finish_fill("1, 2");
+        assert_eq!(code, "    foo(1, 2)");
+        assert!(!done);
+        request.column = 9;
+        let (code, done) = request.finish_fill("?;\n    bar();");
+        assert_eq!(code, "    foo()?;");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_function_scope_waits_for_outer_block() {
+        let code = "        y();\n    }\n    z();\n}\n\nfn other() {}";
+        let (code, done) = request(PredictionScope::Function, "        y();", 2).finish(code);
+        assert_eq!(code, "        y();\n    }\n    z();\n}");
+        assert!(done);
+    }
+}
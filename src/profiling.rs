@@ -0,0 +1,90 @@
+//! Opt-in render loop instrumentation, enabled with `--features profiling`.
+//! Each session writes a chrome trace that can be opened in chrome://tracing
+//! or https://ui.perfetto.dev to find hot spots. `--profile-startup` needs no
+//! feature and times each startup step.
+
+use std::time::{Duration, Instant};
+
+/// Times the rest of the enclosing block as a named span. Compiles to nothing
+/// unless the `profiling` feature is enabled.
+#[cfg(feature = "profiling")]
+macro_rules! profile_span {
+    ($name:expr) => {
+        let _span = tracing::info_span!($name).entered();
+    };
+}
+
+#[cfg(not(feature = "profiling"))]
+macro_rules! profile_span {
+    ($name:expr) => {};
+}
+
+pub(crate) use profile_span;
+
+/// Keeps the trace file open until dropped at the end of the session
+#[cfg(feature = "profiling")]
+pub type ProfileGuard = tracing_chrome::FlushGuard;
+
+#[cfg(not(feature = "profiling"))]
+pub struct ProfileGuard;
+
+/// Starts writing `nars-trace-<timestamp>.json`, or the path in `NARS_TRACE_FILE`
+#[cfg(feature = "profiling")]
+pub fn init() -> ProfileGuard {
+    use tracing_subscriber::prelude::*;
+
+    let path = std::env::var("NARS_TRACE_FILE").unwrap_or_else(|_| {
+        format!(
+            "nars-trace-{}.json",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )
+    });
+    let (chrome_layer, guard) = tracing_chrome::ChromeLayerBuilder::new().file(path).build();
+    tracing_subscriber::registry().with(chrome_layer).init();
+    guard
+}
+
+#[cfg(not(feature = "profiling"))]
+pub fn init() -> ProfileGuard {
+    ProfileGuard
+}
+
+/// Time spent on each step of starting the editor, printed on exit when
+/// `--profile-startup` is passed
+pub struct StartupProfile {
+    enabled: bool,
+    last: Instant,
+    marks: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+    pub fn new(enabled: bool, start: Instant) -> Self {
+        Self {
+            enabled,
+            last: start,
+            marks: Vec::new(),
+        }
+    }
+
+    /// Ends the step `name`, which began at the previous mark. Only the first
+    /// mark with a name counts, later file opens don't add to startup.
+    pub fn mark(&mut self, name: &'static str) {
+        if !self.enabled || self.marks.iter().any(|(mark, _)| *mark == name) {
+            return;
+        }
+        let now = Instant::now();
+        self.marks.push((name, now - self.last));
+        self.last = now;
+    }
+
+    pub fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+        let mut total = Duration::ZERO;
+        for (name, took) in &self.marks {
+            total += *took;
+            eprintln!("{:<12} {:>10.2?} {:>10.2?}", name, took, total);
+        }
+    }
+}
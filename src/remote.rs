@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A file opened as `user@host:/path`, edited through a local copy
+pub struct RemoteFile {
+    pub host: String,
+    pub path: String,
+    pub local: PathBuf,
+}
+
+impl RemoteFile {
+    /// `target` as an scp target. Without a user in it, a local file of that
+    /// name wins, so `notes:todo.md` in the working directory opens as is.
+    pub fn parse(target: &str) -> Option<Self> {
+        let (host, path) = target.split_once(':')?;
+        if host.is_empty() || path.is_empty() || host.contains('/') {
+            return None;
+        }
+        if !host.contains('@') && Path::new(target).exists() {
+            return None;
+        }
+        let name = path.rsplit('/').next().unwrap_or("remote");
+        let local = env::temp_dir().join(format!("nars-{}-{}", std::process::id(), name));
+        Some(Self {
+            host: host.to_string(),
+            path: path.to_string(),
+            local,
+        })
+    }
+
+    pub fn target(&self) -> String {
+        format!("{}:{}", self.host, self.path)
+    }
+
+    pub fn fetch(&self) -> Result<()> {
+        scp(&self.target(), &self.local.to_string_lossy())
+    }
+
+    pub fn push(&self) -> Result<()> {
+        scp(&self.local.to_string_lossy(), &self.target())
+    }
+}
+
+fn scp(from: &str, to: &str) -> Result<()> {
+    let output = Command::new("scp")
+        .args(["-q", "-o", "BatchMode=yes", from, to])
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "scp {} failed: {}",
+            from,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+impl Drop for RemoteFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.local);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_scp_targets() {
+        let remote = RemoteFile::parse("me@box:/etc/nginx/nginx.conf").unwrap();
+        assert_eq!(remote.host, "me@box");
+        assert_eq!(remote.path, "/etc/nginx/nginx.conf");
+        assert_eq!(remote.target(), "me@box:/etc/nginx/nginx.conf");
+        assert!(remote.local.to_string_lossy().ends_with("-nginx.conf"));
+        for target in ["src/main.rs", "./a:b", "me@box:", ":/etc/hosts"] {
+            assert!(RemoteFile::parse(target).is_none(), "{}", target);
+        }
+    }
+
+    #[test]
+    fn test_local_files_with_a_colon_stay_local() {
+        let name = format!("nars-{}:todo.md", std::process::id());
+        std::fs::write(&name, "").unwrap();
+        let parsed = RemoteFile::parse(&name).is_some();
+        std::fs::remove_file(&name).unwrap();
+        assert!(!parsed);
+        // with no such file, or with a user, it is a host
+        assert_eq!(RemoteFile::parse("C:file").unwrap().host, "C");
+        assert_eq!(
+            RemoteFile::parse(&format!("me@{}", name)).unwrap().path,
+            "todo.md"
+        );
+    }
+}